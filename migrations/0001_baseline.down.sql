@@ -0,0 +1,33 @@
+-- Drops everything the baseline creates; all data is lost.
+
+DROP TABLE IF EXISTS operation_switches;
+DROP TABLE IF EXISTS auth_failures;
+DROP TABLE IF EXISTS anomaly_rule_state;
+DROP TABLE IF EXISTS anomaly_rules;
+DROP TABLE IF EXISTS alerts;
+DROP TABLE IF EXISTS email_deliveries;
+DROP TABLE IF EXISTS ms_signer_contacts;
+DROP TABLE IF EXISTS ms_approvals;
+DROP TABLE IF EXISTS ms_proposals;
+DROP TABLE IF EXISTS authorized_programs;
+DROP TABLE IF EXISTS vault_delegates;
+DROP TABLE IF EXISTS protocol_apy;
+DROP TABLE IF EXISTS yield_events;
+DROP TABLE IF EXISTS audit_trail;
+DROP TABLE IF EXISTS balance_snapshots;
+DROP TABLE IF EXISTS reconciliation_logs;
+DROP TABLE IF EXISTS webhook_fanout;
+DROP TABLE IF EXISTS webhook_dead_letters;
+DROP TABLE IF EXISTS webhook_deliveries;
+DROP TABLE IF EXISTS webhook_endpoints;
+DROP TABLE IF EXISTS event_outbox;
+DROP TABLE IF EXISTS twofa_resets;
+DROP TABLE IF EXISTS webauthn_credentials;
+DROP TABLE IF EXISTS twofa_policies;
+DROP TABLE IF EXISTS twofa_recovery_codes;
+DROP TABLE IF EXISTS twofa;
+DROP TABLE IF EXISTS withdraw_whitelist;
+DROP TABLE IF EXISTS timelocks;
+DROP TABLE IF EXISTS vaults;
+DROP TABLE IF EXISTS transactions;
+DROP TABLE IF EXISTS nonces;
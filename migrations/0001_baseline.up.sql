@@ -0,0 +1,363 @@
+-- Baseline: the schema previously created by db::init at startup. Every statement is
+-- idempotent, so existing deployments record this version without schema changes.
+
+-- Minimal tables to support nonces, tx history, and admin authorized programs
+CREATE TABLE IF NOT EXISTS nonces (
+    nonce TEXT PRIMARY KEY,
+    owner TEXT NOT NULL,
+    issued_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    used BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+-- Nonces are bound to a single action and expire after a TTL
+ALTER TABLE nonces ADD COLUMN IF NOT EXISTS purpose TEXT;
+ALTER TABLE nonces ADD COLUMN IF NOT EXISTS expires_at TIMESTAMPTZ;
+CREATE INDEX IF NOT EXISTS idx_nonces_expires_at ON nonces(expires_at);
+CREATE TABLE IF NOT EXISTS transactions (
+    id BIGSERIAL PRIMARY KEY,
+    owner TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    amount BIGINT,
+    kind TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    retry_count INT NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Idempotency on signature
+CREATE UNIQUE INDEX IF NOT EXISTS transactions_signature_key ON transactions(signature);
+
+-- Hot indexes for performance
+CREATE INDEX IF NOT EXISTS idx_transactions_owner_created_at ON transactions(owner, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_transactions_status ON transactions(status);
+
+-- Add status column if missing (for existing databases)
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'pending';
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS retry_count INT NOT NULL DEFAULT 0;
+ALTER TABLE transactions ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+-- Vault snapshots
+CREATE TABLE IF NOT EXISTS vaults (
+    owner TEXT PRIMARY KEY,
+    token_account TEXT,
+    total_deposits BIGINT NOT NULL DEFAULT 0,
+    total_withdrawals BIGINT NOT NULL DEFAULT 0,
+    total_balance BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Timelock schedules per owner
+CREATE TABLE IF NOT EXISTS timelocks (
+    id BIGSERIAL PRIMARY KEY,
+    owner TEXT NOT NULL,
+    amount BIGINT NOT NULL,
+    unlock_at TIMESTAMPTZ NOT NULL,
+    status TEXT NOT NULL DEFAULT 'scheduled',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_timelocks_owner_unlock_at ON timelocks(owner, unlock_at);
+
+-- Withdrawal whitelist (off-chain mirror for audit)
+CREATE TABLE IF NOT EXISTS withdraw_whitelist (
+    owner TEXT NOT NULL,
+    address TEXT NOT NULL,
+    added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (owner, address)
+);
+
+-- Two-factor auth secrets per owner
+CREATE TABLE IF NOT EXISTS twofa (
+    owner TEXT PRIMARY KEY,
+    secret TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Server-generated secrets are stored envelope-encrypted in secret_enc; `secret` only holds
+-- legacy plaintext rows. last_used_step rejects replay of a code within its window.
+ALTER TABLE twofa ALTER COLUMN secret DROP NOT NULL;
+ALTER TABLE twofa ADD COLUMN IF NOT EXISTS secret_enc TEXT;
+ALTER TABLE twofa ADD COLUMN IF NOT EXISTS last_used_step BIGINT;
+
+-- Single-use 2FA recovery codes (SHA-256 hashes only)
+CREATE TABLE IF NOT EXISTS twofa_recovery_codes (
+    id BIGSERIAL PRIMARY KEY,
+    owner TEXT NOT NULL,
+    code_hash TEXT NOT NULL,
+    used_at TIMESTAMPTZ NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_twofa_recovery_codes_owner ON twofa_recovery_codes(owner);
+
+-- Per-owner list of operations that require a second factor (absent row = all)
+CREATE TABLE IF NOT EXISTS twofa_policies (
+    owner TEXT PRIMARY KEY,
+    operations TEXT[] NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- WebAuthn / passkey credentials (ES256, SEC1 public key)
+CREATE TABLE IF NOT EXISTS webauthn_credentials (
+    id BIGSERIAL PRIMARY KEY,
+    owner TEXT NOT NULL,
+    credential_id TEXT NOT NULL UNIQUE,
+    public_key BYTEA NOT NULL,
+    sign_count BIGINT NOT NULL DEFAULT 0,
+    label TEXT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_used_at TIMESTAMPTZ NULL
+);
+CREATE INDEX IF NOT EXISTS idx_webauthn_credentials_owner ON webauthn_credentials(owner);
+
+-- Admin-assisted 2FA resets; applied by a background task once effective_at passes
+CREATE TABLE IF NOT EXISTS twofa_resets (
+    id BIGSERIAL PRIMARY KEY,
+    owner TEXT NOT NULL,
+    requested_by TEXT NOT NULL,
+    reason TEXT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    effective_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_twofa_resets_status ON twofa_resets(status, effective_at);
+
+-- Durable copy of every Notifier event; `id` is the cursor clients resume from
+CREATE TABLE IF NOT EXISTS event_outbox (
+    id BIGSERIAL PRIMARY KEY,
+    event_id UUID NOT NULL UNIQUE,
+    topic TEXT NOT NULL,
+    owner TEXT NULL,
+    event_type TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_event_outbox_topic ON event_outbox(topic, id);
+CREATE INDEX IF NOT EXISTS idx_event_outbox_created_at ON event_outbox(created_at);
+
+-- Webhook endpoints; owner NULL = integrator endpoint receiving every owner's events
+CREATE TABLE IF NOT EXISTS webhook_endpoints (
+    id BIGSERIAL PRIMARY KEY,
+    owner TEXT NULL,
+    url TEXT NOT NULL,
+    secret_enc TEXT NOT NULL,
+    event_types TEXT[] NOT NULL DEFAULT '{}',
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_webhook_endpoints_owner ON webhook_endpoints(owner);
+
+-- Delivery queue; endpoint_id NULL = unsigned delivery to a multisig signer contact URL
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    endpoint_id BIGINT NULL REFERENCES webhook_endpoints(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    event_id UUID NULL,
+    event_type TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    delivered_at TIMESTAMPTZ NULL,
+    UNIQUE (endpoint_id, event_id)
+);
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_due ON webhook_deliveries(status, next_attempt_at);
+CREATE TABLE IF NOT EXISTS webhook_dead_letters (
+    id BIGSERIAL PRIMARY KEY,
+    delivery_id BIGINT NOT NULL,
+    endpoint_id BIGINT NULL,
+    url TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    attempts INT NOT NULL,
+    last_error TEXT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    replayed_at TIMESTAMPTZ NULL
+);
+
+-- Last outbox id fanned out to webhook deliveries (single row)
+CREATE TABLE IF NOT EXISTS webhook_fanout (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    cursor BIGINT NOT NULL
+);
+
+-- Add new columns if missing
+ALTER TABLE vaults ADD COLUMN IF NOT EXISTS locked_balance BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE vaults ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'active';
+
+-- Freeze metadata, set while status = 'frozen'
+ALTER TABLE vaults ADD COLUMN IF NOT EXISTS frozen_reason TEXT;
+ALTER TABLE vaults ADD COLUMN IF NOT EXISTS frozen_by TEXT;
+ALTER TABLE vaults ADD COLUMN IF NOT EXISTS frozen_at TIMESTAMPTZ;
+
+-- Reconciliation logs
+CREATE TABLE IF NOT EXISTS reconciliation_logs (
+    id BIGSERIAL PRIMARY KEY,
+    vault_owner TEXT NOT NULL,
+    token_account TEXT,
+    db_balance BIGINT NOT NULL,
+    chain_balance BIGINT NOT NULL,
+    discrepancy BIGINT NOT NULL,
+    threshold BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Balance snapshots (hourly/daily)
+CREATE TABLE IF NOT EXISTS balance_snapshots (
+    id BIGSERIAL PRIMARY KEY,
+    owner TEXT NOT NULL,
+    balance BIGINT NOT NULL,
+    locked_balance BIGINT NOT NULL,
+    granularity TEXT NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Audit trail
+CREATE TABLE IF NOT EXISTS audit_trail (
+    id BIGSERIAL PRIMARY KEY,
+    owner TEXT,
+    action TEXT NOT NULL,
+    details JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Yield events/history
+CREATE TABLE IF NOT EXISTS yield_events (
+    id BIGSERIAL PRIMARY KEY,
+    owner TEXT NOT NULL,
+    protocol TEXT NOT NULL,
+    amount BIGINT NOT NULL,
+    kind TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Protocol APY snapshots (for analytics)
+CREATE TABLE IF NOT EXISTS protocol_apy (
+    id BIGSERIAL PRIMARY KEY,
+    protocol TEXT NOT NULL,
+    apy DOUBLE PRECISION NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Vault delegates (off-chain allowlist for UI and prechecks)
+CREATE TABLE IF NOT EXISTS vault_delegates (
+    owner TEXT NOT NULL,
+    delegate TEXT NOT NULL,
+    added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (owner, delegate)
+);
+CREATE TABLE IF NOT EXISTS authorized_programs (
+    program_id TEXT PRIMARY KEY,
+    added_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Multisig proposal tables
+CREATE TABLE IF NOT EXISTS ms_proposals (
+    id UUID PRIMARY KEY,
+    owner TEXT NOT NULL,
+    amount BIGINT NOT NULL,
+    threshold INT NOT NULL,
+    signers JSONB NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE TABLE IF NOT EXISTS ms_approvals (
+    id BIGSERIAL PRIMARY KEY,
+    proposal_id UUID NOT NULL REFERENCES ms_proposals(id) ON DELETE CASCADE,
+    signer TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (proposal_id, signer)
+);
+
+-- Optional contacts for signers to deliver notifications
+CREATE TABLE IF NOT EXISTS ms_signer_contacts (
+    pubkey TEXT PRIMARY KEY,
+    email TEXT,
+    webhook TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+ALTER TABLE ms_signer_contacts ADD COLUMN IF NOT EXISTS email_disabled TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE ms_signer_contacts ADD COLUMN IF NOT EXISTS unsubscribe_token TEXT UNIQUE;
+ALTER TABLE ms_signer_contacts ADD COLUMN IF NOT EXISTS unsubscribed_at TIMESTAMPTZ;
+
+-- Outgoing email queue; dedup_key stops repeated events from mailing twice
+CREATE TABLE IF NOT EXISTS email_deliveries (
+    id BIGSERIAL PRIMARY KEY,
+    dedup_key TEXT NOT NULL UNIQUE,
+    pubkey TEXT NOT NULL,
+    recipient TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    body TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    sent_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS idx_email_deliveries_due ON email_deliveries(status, next_attempt_at);
+CREATE INDEX IF NOT EXISTS idx_email_deliveries_recipient ON email_deliveries(recipient, created_at);
+
+-- Operational alerts; at most one open alert per (kind, owner)
+CREATE TABLE IF NOT EXISTS alerts (
+    id BIGSERIAL PRIMARY KEY,
+    kind TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    severity TEXT NOT NULL,
+    details JSONB NOT NULL DEFAULT '{}',
+    status TEXT NOT NULL DEFAULT 'open',
+    notify_count INT NOT NULL DEFAULT 1,
+    opened_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_notified_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    acknowledged_by TEXT,
+    acknowledged_at TIMESTAMPTZ,
+    resolved_at TIMESTAMPTZ
+);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_alerts_open ON alerts(kind, owner) WHERE status = 'open';
+
+-- Anomaly rules (seeded from defaults and ANOMALY_RULES) and their per-owner state
+CREATE TABLE IF NOT EXISTS anomaly_rules (
+    id TEXT PRIMARY KEY,
+    kind TEXT NOT NULL,
+    params JSONB NOT NULL DEFAULT '{}',
+    severity TEXT NOT NULL DEFAULT 'warning',
+    actions TEXT[] NOT NULL DEFAULT '{alert}',
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE TABLE IF NOT EXISTS anomaly_rule_state (
+    rule_id TEXT NOT NULL,
+    owner TEXT NOT NULL,
+    active BOOLEAN NOT NULL DEFAULT TRUE,
+    trigger_count INT NOT NULL DEFAULT 1,
+    details JSONB NOT NULL DEFAULT '{}',
+    last_triggered_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    cleared_at TIMESTAMPTZ,
+    PRIMARY KEY (rule_id, owner)
+);
+
+-- Rejected wallet signatures and nonces, input to the auth_failures rule
+CREATE TABLE IF NOT EXISTS auth_failures (
+    id BIGSERIAL PRIMARY KEY,
+    owner TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS idx_auth_failures_owner ON auth_failures(owner, created_at);
+
+-- Global kill switches; a missing row means the operation is enabled
+CREATE TABLE IF NOT EXISTS operation_switches (
+    operation TEXT PRIMARY KEY,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    reason TEXT,
+    updated_by TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
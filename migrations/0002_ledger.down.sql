@@ -0,0 +1,8 @@
+-- Drops the ledger; all journal entries are lost.
+
+DROP VIEW IF EXISTS ledger_vault_balances;
+DROP VIEW IF EXISTS ledger_balances;
+DROP TABLE IF EXISTS ledger_postings;
+DROP TABLE IF EXISTS ledger_entries;
+DROP FUNCTION IF EXISTS ledger_check_balanced();
+DROP FUNCTION IF EXISTS ledger_reject_change();
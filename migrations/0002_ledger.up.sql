@@ -0,0 +1,82 @@
+-- Append-only double-entry ledger for vault balance movements. Every entry is tied to a
+-- transaction signature and its postings sum to zero; `external` is the contra account for
+-- value entering or leaving the vaults.
+
+CREATE TABLE ledger_entries (
+    id BIGSERIAL PRIMARY KEY,
+    signature TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (signature, kind)
+);
+
+CREATE TABLE ledger_postings (
+    id BIGSERIAL PRIMARY KEY,
+    entry_id BIGINT NOT NULL REFERENCES ledger_entries(id),
+    owner TEXT NOT NULL,
+    account TEXT NOT NULL CHECK (account IN
+        ('available', 'locked', 'yield_deposited', 'yield_accrued', 'in_transit', 'external')),
+    amount BIGINT NOT NULL CHECK (amount <> 0)
+);
+CREATE INDEX idx_ledger_postings_owner_account ON ledger_postings(owner, account);
+CREATE INDEX idx_ledger_postings_entry ON ledger_postings(entry_id);
+
+CREATE FUNCTION ledger_reject_change() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'ledger is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER ledger_entries_append_only BEFORE UPDATE OR DELETE ON ledger_entries
+    FOR EACH ROW EXECUTE FUNCTION ledger_reject_change();
+CREATE TRIGGER ledger_postings_append_only BEFORE UPDATE OR DELETE ON ledger_postings
+    FOR EACH ROW EXECUTE FUNCTION ledger_reject_change();
+
+-- Checked at commit, once all postings of the entry are inserted
+CREATE FUNCTION ledger_check_balanced() RETURNS trigger AS $$
+BEGIN
+    IF (SELECT SUM(amount) FROM ledger_postings WHERE entry_id = NEW.entry_id) <> 0 THEN
+        RAISE EXCEPTION 'ledger entry % does not balance', NEW.entry_id;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE CONSTRAINT TRIGGER ledger_postings_balanced AFTER INSERT ON ledger_postings
+    DEFERRABLE INITIALLY DEFERRED
+    FOR EACH ROW EXECUTE FUNCTION ledger_check_balanced();
+
+CREATE VIEW ledger_balances AS
+    SELECT owner, account, SUM(amount)::BIGINT AS balance
+    FROM ledger_postings
+    GROUP BY owner, account;
+
+CREATE VIEW ledger_vault_balances AS
+    SELECT owner,
+        COALESCE(SUM(amount) FILTER (WHERE account = 'available'), 0)::BIGINT AS available,
+        COALESCE(SUM(amount) FILTER (WHERE account = 'locked'), 0)::BIGINT AS locked,
+        COALESCE(SUM(amount) FILTER (WHERE account = 'yield_deposited'), 0)::BIGINT AS yield_deposited,
+        COALESCE(SUM(amount) FILTER (WHERE account = 'yield_accrued'), 0)::BIGINT AS yield_accrued,
+        COALESCE(SUM(amount) FILTER (WHERE account = 'in_transit'), 0)::BIGINT AS in_transit
+    FROM ledger_postings
+    WHERE account <> 'external'
+    GROUP BY owner;
+
+-- Opening balances from the existing vault counters
+WITH opened AS (
+    INSERT INTO ledger_entries (signature, kind)
+    SELECT 'opening:' || owner, 'opening'
+    FROM vaults
+    WHERE total_balance <> 0 OR locked_balance <> 0
+    RETURNING id, signature
+)
+INSERT INTO ledger_postings (entry_id, owner, account, amount)
+SELECT o.id, v.owner, p.account, p.amount
+FROM opened o
+JOIN vaults v ON o.signature = 'opening:' || v.owner
+CROSS JOIN LATERAL (VALUES
+    ('available', v.total_balance - v.locked_balance),
+    ('locked', v.locked_balance),
+    ('external', -v.total_balance)
+) AS p(account, amount)
+WHERE p.amount <> 0;
@@ -0,0 +1,8 @@
+-- Restores the unkeyed snapshot table; snapshot rows are kept.
+
+DROP INDEX IF EXISTS idx_balance_snapshots_period;
+ALTER TABLE balance_snapshots
+    DROP COLUMN IF EXISTS period_start,
+    DROP COLUMN IF EXISTS yield_accrued,
+    DROP COLUMN IF EXISTS yield_deposited,
+    DROP COLUMN IF EXISTS available;
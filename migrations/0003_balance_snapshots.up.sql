@@ -0,0 +1,25 @@
+-- Balance snapshots keyed by period: one row per (owner, granularity, period_start) holding the
+-- balances at the end of the period, derived from the ledger.
+
+ALTER TABLE balance_snapshots
+    ADD COLUMN available BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN yield_deposited BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN yield_accrued BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN period_start TIMESTAMPTZ;
+
+UPDATE balance_snapshots
+SET available = balance - locked_balance,
+    period_start = date_trunc(CASE WHEN granularity = 'daily' THEN 'day' ELSE 'hour' END,
+                              recorded_at AT TIME ZONE 'UTC') AT TIME ZONE 'UTC';
+
+-- Keep the latest row per period
+DELETE FROM balance_snapshots s
+USING balance_snapshots newer
+WHERE newer.owner = s.owner
+  AND newer.granularity = s.granularity
+  AND newer.period_start = s.period_start
+  AND newer.id > s.id;
+
+ALTER TABLE balance_snapshots ALTER COLUMN period_start SET NOT NULL;
+CREATE UNIQUE INDEX idx_balance_snapshots_period
+    ON balance_snapshots(owner, granularity, period_start);
@@ -0,0 +1,3 @@
+-- Drops the TVL series index.
+
+DROP INDEX IF EXISTS idx_balance_snapshots_granularity_period;
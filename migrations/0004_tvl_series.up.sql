@@ -0,0 +1,4 @@
+-- Index for summing every vault's snapshot of one period (TVL series).
+
+CREATE INDEX idx_balance_snapshots_granularity_period
+    ON balance_snapshots(granularity, period_start);
@@ -0,0 +1,6 @@
+-- Drops reconciliation cases and the yield balances of the vault snapshot.
+
+DROP TABLE IF EXISTS reconciliation_cases;
+ALTER TABLE vaults
+    DROP COLUMN IF EXISTS yield_accrued_balance,
+    DROP COLUMN IF EXISTS yield_deposited_balance;
@@ -0,0 +1,34 @@
+-- Yield balances on the vault snapshot, so it mirrors every balance of the on-chain vault
+-- account, and reconciliation cases: one per drift found between the two. A vault has at
+-- most one escalated case at a time; later runs update it.
+
+ALTER TABLE vaults
+    ADD COLUMN yield_deposited_balance BIGINT NOT NULL DEFAULT 0,
+    ADD COLUMN yield_accrued_balance BIGINT NOT NULL DEFAULT 0;
+
+UPDATE vaults v
+SET yield_deposited_balance = b.yield_deposited,
+    yield_accrued_balance = b.yield_accrued
+FROM ledger_vault_balances b
+WHERE b.owner = v.owner;
+
+CREATE TABLE reconciliation_cases (
+    id BIGSERIAL PRIMARY KEY,
+    owner TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('healed', 'escalated', 'resolved')),
+    db_state JSONB NOT NULL,
+    chain_state JSONB NOT NULL,
+    -- Chain minus DB, per differing balance
+    discrepancies JSONB NOT NULL,
+    max_discrepancy BIGINT NOT NULL,
+    occurrences INTEGER NOT NULL DEFAULT 1,
+    opened_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    resolution TEXT,
+    resolved_by TEXT,
+    resolved_at TIMESTAMPTZ
+);
+CREATE UNIQUE INDEX idx_reconciliation_cases_escalated
+    ON reconciliation_cases(owner) WHERE status = 'escalated';
+CREATE INDEX idx_reconciliation_cases_status_opened
+    ON reconciliation_cases(status, opened_at DESC);
@@ -0,0 +1,9 @@
+-- Drops the reserve snapshots and the reconciled vault state.
+
+DROP TABLE IF EXISTS reserve_leaves;
+DROP TABLE IF EXISTS reserve_snapshots;
+ALTER TABLE vaults
+    DROP COLUMN IF EXISTS reconciled_at,
+    DROP COLUMN IF EXISTS reconciled_slot,
+    DROP COLUMN IF EXISTS reconciled_locked_balance,
+    DROP COLUMN IF EXISTS reconciled_balance;
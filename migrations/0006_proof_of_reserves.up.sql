@@ -0,0 +1,31 @@
+-- Proof of reserves: the vault state last confirmed on chain by reconciliation, and the
+-- Merkle snapshots built from it. A snapshot keeps every level of its tree (hex hashes, leaves
+-- first) so inclusion proofs can be served without rebuilding it.
+
+ALTER TABLE vaults
+    ADD COLUMN reconciled_balance BIGINT,
+    ADD COLUMN reconciled_locked_balance BIGINT,
+    ADD COLUMN reconciled_slot BIGINT,
+    ADD COLUMN reconciled_at TIMESTAMPTZ;
+
+CREATE TABLE reserve_snapshots (
+    id BIGSERIAL PRIMARY KEY,
+    root TEXT NOT NULL,
+    leaf_count INTEGER NOT NULL,
+    total_balance BIGINT NOT NULL,
+    total_locked BIGINT NOT NULL,
+    tree JSONB NOT NULL,
+    anchor_signature TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TABLE reserve_leaves (
+    snapshot_id BIGINT NOT NULL REFERENCES reserve_snapshots(id) ON DELETE CASCADE,
+    leaf_index INTEGER NOT NULL,
+    owner TEXT NOT NULL,
+    balance BIGINT NOT NULL,
+    locked BIGINT NOT NULL,
+    slot BIGINT NOT NULL,
+    PRIMARY KEY (snapshot_id, leaf_index)
+);
+CREATE INDEX idx_reserve_leaves_owner ON reserve_leaves(owner, snapshot_id DESC);
@@ -0,0 +1,3 @@
+-- Drops the one-pending-reset-per-owner index.
+
+DROP INDEX IF EXISTS idx_twofa_resets_pending_owner;
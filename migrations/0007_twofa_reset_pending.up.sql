@@ -0,0 +1,9 @@
+-- At most one pending 2FA reset per owner. Older duplicates left by concurrent requests are
+-- cancelled first, keeping the earliest.
+
+UPDATE twofa_resets SET status = 'cancelled', updated_at = NOW()
+WHERE status = 'pending'
+  AND id NOT IN (SELECT MIN(id) FROM twofa_resets WHERE status = 'pending' GROUP BY owner);
+
+CREATE UNIQUE INDEX idx_twofa_resets_pending_owner ON twofa_resets(owner)
+    WHERE status = 'pending';
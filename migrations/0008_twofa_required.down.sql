@@ -0,0 +1,3 @@
+-- Drops the required-2FA flags.
+
+DROP TABLE IF EXISTS twofa_required;
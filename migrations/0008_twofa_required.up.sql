@@ -0,0 +1,8 @@
+-- Owners that must enroll a second factor before any sensitive operation, set by the
+-- `require_2fa` anomaly action.
+
+CREATE TABLE twofa_required (
+    owner TEXT PRIMARY KEY,
+    reason TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
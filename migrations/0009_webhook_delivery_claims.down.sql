@@ -0,0 +1,4 @@
+-- Returns claimed deliveries to the queue and drops the lease column.
+
+UPDATE webhook_deliveries SET status = 'pending' WHERE status = 'in_flight';
+ALTER TABLE webhook_deliveries DROP COLUMN IF EXISTS locked_until;
@@ -0,0 +1,4 @@
+-- Lets dispatchers claim deliveries: a claimed row is `in_flight` until `locked_until`, after
+-- which another dispatcher may take it over.
+
+ALTER TABLE webhook_deliveries ADD COLUMN locked_until TIMESTAMPTZ NULL;
@@ -0,0 +1,4 @@
+-- Allows endpoint-less deliveries again and restores the (empty) contact URL column.
+
+ALTER TABLE webhook_deliveries ALTER COLUMN endpoint_id DROP NOT NULL;
+ALTER TABLE ms_signer_contacts ADD COLUMN IF NOT EXISTS webhook TEXT;
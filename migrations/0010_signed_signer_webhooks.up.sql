@@ -0,0 +1,6 @@
+-- Signer notifications go to webhook endpoints the signer registered, so every delivery is
+-- signed. Drops the unsigned contact URL and any deliveries still queued for one.
+
+DELETE FROM webhook_deliveries WHERE endpoint_id IS NULL;
+ALTER TABLE webhook_deliveries ALTER COLUMN endpoint_id SET NOT NULL;
+ALTER TABLE ms_signer_contacts DROP COLUMN IF EXISTS webhook;
@@ -0,0 +1,312 @@
+//! Shared upstream Solana `accountSubscribe` streams.
+//!
+//! Each watched pubkey has exactly one upstream subscription, no matter how many sockets
+//! follow it, and all of them are multiplexed over a single pubsub connection. Subscribers
+//! are reference-counted: the upstream is opened by the first [`AccountHub::subscribe`] and
+//! closed when the last [`AccountSubscription`] is dropped. The connection is opened while
+//! any pubkey is watched; when it drops it is re-established with backoff and every watched
+//! pubkey is subscribed again.
+
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
+use futures::future::{self, BoxFuture};
+use futures::stream::{self, AbortHandle, BoxStream, SelectAll};
+use futures::StreamExt;
+use solana_account_decoder::{UiAccount, UiAccountData};
+use solana_client::nonblocking::pubsub_client::PubsubClient;
+use solana_client::rpc_config::RpcAccountInfoConfig;
+use solana_client::rpc_response::Response;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tracing::warn;
+
+/// Updates buffered per pubkey. Only the latest account state matters, so a lagging
+/// subscriber simply skips ahead.
+const UPDATE_BUFFER: usize = 16;
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountUpdate {
+    pub pubkey: Pubkey,
+    pub slot: u64,
+    pub data_len: usize,
+}
+
+struct Upstream {
+    tx: broadcast::Sender<AccountUpdate>,
+    subscribers: usize,
+}
+
+/// Instructions for the connection task.
+#[derive(Debug, Clone, Copy)]
+enum Command {
+    Subscribe(Pubkey),
+    Unsubscribe(Pubkey),
+}
+
+pub struct AccountHub {
+    ws_url: String,
+    upstreams: Mutex<HashMap<Pubkey, Upstream>>,
+    /// Started by the first subscription.
+    connection: Mutex<Option<mpsc::UnboundedSender<Command>>>,
+}
+
+impl AccountHub {
+    /// `ws_url` is the RPC pubsub endpoint (`ws://` or `wss://`).
+    pub fn new(ws_url: impl Into<String>) -> Arc<Self> {
+        Arc::new(Self {
+            ws_url: ws_url.into(),
+            upstreams: Mutex::new(HashMap::new()),
+            connection: Mutex::new(None),
+        })
+    }
+
+    /// Hub for the pubsub endpoint that matches an HTTP RPC URL.
+    pub fn for_rpc_url(rpc_url: &str) -> Arc<Self> {
+        Self::new(
+            rpc_url
+                .replace("https://", "wss://")
+                .replace("http://", "ws://"),
+        )
+    }
+
+    pub fn subscribe(self: &Arc<Self>, pubkey: Pubkey) -> AccountSubscription {
+        let mut upstreams = self.upstreams.lock().unwrap();
+        let upstream = upstreams.entry(pubkey).or_insert_with(|| {
+            self.command(Command::Subscribe(pubkey));
+            Upstream {
+                tx: broadcast::channel(UPDATE_BUFFER).0,
+                subscribers: 0,
+            }
+        });
+        upstream.subscribers += 1;
+        AccountSubscription {
+            hub: self.clone(),
+            pubkey,
+            rx: upstream.tx.subscribe(),
+        }
+    }
+
+    /// Number of upstream subscriptions currently open.
+    pub fn upstream_count(&self) -> usize {
+        self.upstreams.lock().unwrap().len()
+    }
+
+    pub fn subscriber_count(&self, pubkey: &Pubkey) -> usize {
+        self.upstreams
+            .lock()
+            .unwrap()
+            .get(pubkey)
+            .map_or(0, |upstream| upstream.subscribers)
+    }
+
+    fn release(&self, pubkey: &Pubkey) {
+        let mut upstreams = self.upstreams.lock().unwrap();
+        if let Some(upstream) = upstreams.get_mut(pubkey) {
+            upstream.subscribers -= 1;
+            if upstream.subscribers == 0 {
+                upstreams.remove(pubkey);
+                if let Some(connection) = self.connection.lock().unwrap().as_ref() {
+                    let _ = connection.send(Command::Unsubscribe(*pubkey));
+                }
+            }
+        }
+    }
+
+    fn command(self: &Arc<Self>, command: Command) {
+        let mut connection = self.connection.lock().unwrap();
+        let connection = connection.get_or_insert_with(|| {
+            let (tx, rx) = mpsc::unbounded_channel();
+            tokio::spawn(run_connection(
+                self.ws_url.clone(),
+                Arc::downgrade(self),
+                rx,
+            ));
+            tx
+        });
+        let _ = connection.send(command);
+    }
+
+    fn watched(&self) -> Vec<Pubkey> {
+        self.upstreams.lock().unwrap().keys().copied().collect()
+    }
+
+    fn publish(&self, update: AccountUpdate) {
+        if let Some(upstream) = self.upstreams.lock().unwrap().get(&update.pubkey) {
+            let _ = upstream.tx.send(update);
+        }
+    }
+}
+
+/// One socket's interest in a pubkey. Dropping it releases the upstream reference.
+pub struct AccountSubscription {
+    hub: Arc<AccountHub>,
+    pubkey: Pubkey,
+    rx: broadcast::Receiver<AccountUpdate>,
+}
+
+impl AccountSubscription {
+    pub async fn next(&mut self) -> Option<AccountUpdate> {
+        loop {
+            match self.rx.recv().await {
+                Ok(update) => return Some(update),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+impl Drop for AccountSubscription {
+    fn drop(&mut self) {
+        self.hub.release(&self.pubkey);
+    }
+}
+
+/// Keeps the shared connection open while any pubkey is watched, until the hub is dropped.
+async fn run_connection(
+    ws_url: String,
+    hub: Weak<AccountHub>,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+) {
+    let mut delay = Duration::from_secs(1);
+    loop {
+        let Some(watched) = hub.upgrade().map(|hub| hub.watched()) else {
+            return;
+        };
+        if watched.is_empty() {
+            // Idle: connect again on the next subscription
+            match commands.recv().await {
+                Some(_) => continue,
+                None => return,
+            }
+        }
+        match PubsubClient::new(&ws_url).await {
+            Ok(client) => {
+                delay = Duration::from_secs(1);
+                match multiplex(&client, &hub, &mut commands).await {
+                    Ok(()) => continue,
+                    Err(e) => warn!("account pubsub connection lost: {e}"),
+                }
+            }
+            Err(e) => warn!("account pubsub connection failed: {e}"),
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+    }
+}
+
+type Unsubscribe = Box<dyn FnOnce() -> BoxFuture<'static, ()> + Send>;
+
+/// Subscription id and pubkey of an update; `None` once that subscription's stream ends.
+type Tagged = (u64, Pubkey, Option<AccountUpdate>);
+
+/// Subscriptions open on one connection.
+struct Subscriptions<'a> {
+    client: &'a PubsubClient,
+    updates: SelectAll<BoxStream<'a, Tagged>>,
+    active: HashMap<Pubkey, (u64, AbortHandle, Unsubscribe)>,
+    next_id: u64,
+}
+
+impl<'a> Subscriptions<'a> {
+    async fn add(&mut self, pubkey: Pubkey) -> Result<(), String> {
+        if self.active.contains_key(&pubkey) {
+            return Ok(());
+        }
+        let (updates, unsubscribe) = self
+            .client
+            .account_subscribe(&pubkey, Some(RpcAccountInfoConfig::default()))
+            .await
+            .map_err(|e| e.to_string())?;
+        let (updates, abort) = stream::abortable(updates);
+        let id = self.next_id;
+        self.next_id += 1;
+        self.updates.push(
+            updates
+                .map(move |update| Some(account_update(pubkey, update)))
+                .chain(stream::once(future::ready(None)))
+                .map(move |update| (id, pubkey, update))
+                .boxed(),
+        );
+        self.active.insert(pubkey, (id, abort, unsubscribe));
+        Ok(())
+    }
+
+    /// Whether subscription `id` is still the one serving `pubkey`.
+    fn is_current(&self, pubkey: &Pubkey, id: u64) -> bool {
+        self.active
+            .get(pubkey)
+            .is_some_and(|(active, ..)| *active == id)
+    }
+
+    async fn remove(&mut self, pubkey: &Pubkey) {
+        if let Some((_, abort, unsubscribe)) = self.active.remove(pubkey) {
+            abort.abort();
+            unsubscribe().await;
+        }
+    }
+}
+
+/// Serves every watched pubkey over `client`. Returns `Ok` once nothing is watched or the
+/// hub is gone, and an error if the connection drops.
+async fn multiplex(
+    client: &PubsubClient,
+    hub: &Weak<AccountHub>,
+    commands: &mut mpsc::UnboundedReceiver<Command>,
+) -> Result<(), String> {
+    let mut subscriptions = Subscriptions {
+        client,
+        updates: SelectAll::new(),
+        active: HashMap::new(),
+        next_id: 0,
+    };
+    let Some(watched) = hub.upgrade().map(|hub| hub.watched()) else {
+        return Ok(());
+    };
+    for pubkey in watched {
+        subscriptions.add(pubkey).await?;
+    }
+    loop {
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(Command::Subscribe(pubkey)) => subscriptions.add(pubkey).await?,
+                Some(Command::Unsubscribe(pubkey)) => {
+                    subscriptions.remove(&pubkey).await;
+                    if hub.upgrade().map_or(0, |hub| hub.upstream_count()) == 0 {
+                        return Ok(());
+                    }
+                }
+                None => return Ok(()),
+            },
+            Some((id, pubkey, update)) = subscriptions.updates.next() => match update {
+                Some(update) => match hub.upgrade() {
+                    Some(hub) => hub.publish(update),
+                    None => return Ok(()),
+                },
+                // Streams end together when the connection drops
+                None if subscriptions.is_current(&pubkey, id) => {
+                    return Err(format!("subscription for {pubkey} ended"));
+                }
+                None => {}
+            },
+        }
+    }
+}
+
+fn account_update(pubkey: Pubkey, update: Response<UiAccount>) -> AccountUpdate {
+    let data_len = match &update.value.data {
+        UiAccountData::Binary(data, _) | UiAccountData::LegacyBinary(data) => BASE64_STANDARD
+            .decode(data)
+            .map(|bytes| bytes.len())
+            .unwrap_or_else(|_| data.len()),
+        _ => 0,
+    };
+    AccountUpdate {
+        pubkey,
+        slot: update.context.slot,
+        data_len,
+    }
+}
@@ -0,0 +1,208 @@
+//! Alert lifecycle for conditions the monitors evaluate every cycle (`low_balance`,
+//! `unusual_activity`).
+//!
+//! The manager keeps at most one open alert per (kind, owner) in `alerts` and publishes on
+//! transitions only: a `security_alert` when the alert opens, again when it is still open
+//! and unacknowledged after `ALERT_RENOTIFY_SECONDS`, and an `alert_resolved` event once
+//! the condition clears.
+
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::sync::Arc;
+use time::OffsetDateTime;
+use tracing::warn;
+
+use crate::db;
+use crate::events::{Severity, VaultEvent};
+use crate::notify::Notifier;
+
+/// `security_alert.kind` published when an open alert resolves.
+pub const RESOLVED_KIND: &str = "alert_resolved";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Opened,
+    Renotified,
+    /// Still open; nothing published.
+    Suppressed,
+}
+
+/// Transition for an active condition, given the open alert's last notification time and
+/// acknowledgement. Acknowledged alerts are never re-notified.
+pub fn next_transition(
+    open: Option<(OffsetDateTime, bool)>,
+    now: OffsetDateTime,
+    renotify_seconds: i64,
+) -> Transition {
+    match open {
+        None => Transition::Opened,
+        Some((_, true)) => Transition::Suppressed,
+        Some(_) if renotify_seconds <= 0 => Transition::Suppressed,
+        Some((last_notified_at, false)) => {
+            if (now - last_notified_at).whole_seconds() >= renotify_seconds {
+                Transition::Renotified
+            } else {
+                Transition::Suppressed
+            }
+        }
+    }
+}
+
+fn with_alert_id(details: Value, id: i64, renotify: bool) -> Value {
+    match details {
+        Value::Object(mut map) => {
+            map.insert("alert_id".to_string(), id.into());
+            map.insert("renotify".to_string(), renotify.into());
+            Value::Object(map)
+        }
+        other => serde_json::json!({ "alert_id": id, "renotify": renotify, "value": other }),
+    }
+}
+
+pub struct AlertManager {
+    pool: PgPool,
+    notifier: Arc<Notifier>,
+    renotify_seconds: i64,
+}
+
+impl AlertManager {
+    pub fn new(pool: PgPool, notifier: Arc<Notifier>, renotify_seconds: i64) -> Self {
+        Self {
+            pool,
+            notifier,
+            renotify_seconds,
+        }
+    }
+
+    /// Reports that `kind` holds for `owner`.
+    pub async fn raise(
+        &self,
+        kind: &str,
+        owner: &str,
+        severity: Severity,
+        details: Value,
+    ) -> Result<Transition, sqlx::Error> {
+        let open = db::alert_open_get(&self.pool, kind, owner).await?;
+        let transition = next_transition(
+            open.map(|(_, last_notified_at, acknowledged)| (last_notified_at, acknowledged)),
+            OffsetDateTime::now_utc(),
+            self.renotify_seconds,
+        );
+        let id = match open {
+            None => {
+                match db::alert_open(&self.pool, kind, owner, severity.as_str(), &details).await? {
+                    Some(id) => id,
+                    // Opened concurrently by another monitor
+                    None => return Ok(Transition::Suppressed),
+                }
+            }
+            Some((id, _, _)) => {
+                let renotify = transition == Transition::Renotified;
+                db::alert_touch(&self.pool, id, &details, renotify).await?;
+                if !renotify {
+                    return Ok(transition);
+                }
+                id
+            }
+        };
+        self.notifier.publish_for(
+            owner,
+            VaultEvent::security(
+                kind,
+                severity,
+                with_alert_id(details, id, transition == Transition::Renotified),
+            ),
+        );
+        Ok(transition)
+    }
+
+    /// Reports that `kind` no longer holds for `owner`. Returns true if an open alert resolved.
+    pub async fn resolve(&self, kind: &str, owner: &str) -> Result<bool, sqlx::Error> {
+        let Some(id) = db::alert_resolve(&self.pool, kind, owner).await? else {
+            return Ok(false);
+        };
+        self.notifier.publish_for(
+            owner,
+            VaultEvent::security(
+                RESOLVED_KIND,
+                Severity::Info,
+                serde_json::json!({ "alert_id": id, "kind": kind }),
+            ),
+        );
+        Ok(true)
+    }
+
+    /// Raises `kind` for every owner in `active` and resolves it for every other owner
+    /// with an open alert. For monitors that evaluate all owners in one pass. An owner that
+    /// fails is logged and skipped; returns how many failed.
+    pub async fn reconcile(
+        &self,
+        kind: &str,
+        severity: Severity,
+        active: Vec<(String, Value)>,
+    ) -> Result<usize, sqlx::Error> {
+        let mut failed = 0;
+        let mut seen = HashSet::new();
+        for (owner, details) in active {
+            if let Err(e) = self.raise(kind, &owner, severity, details).await {
+                warn!("raising {kind} alert for {owner}: {e}");
+                failed += 1;
+            }
+            // Still active, so never resolved below even if the raise failed
+            seen.insert(owner);
+        }
+        for owner in db::alerts_open_owners(&self.pool, kind).await? {
+            if seen.contains(&owner) {
+                continue;
+            }
+            if let Err(e) = self.resolve(kind, &owner).await {
+                warn!("resolving {kind} alert for {owner}: {e}");
+                failed += 1;
+            }
+        }
+        Ok(failed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Duration;
+
+    #[test]
+    fn test_next_transition() {
+        let now = OffsetDateTime::now_utc();
+        let recent = now - Duration::seconds(10);
+        let stale = now - Duration::seconds(3600);
+
+        assert_eq!(next_transition(None, now, 3600), Transition::Opened);
+        assert_eq!(
+            next_transition(Some((recent, false)), now, 3600),
+            Transition::Suppressed
+        );
+        assert_eq!(
+            next_transition(Some((stale, false)), now, 3600),
+            Transition::Renotified
+        );
+        // Acknowledged or re-notify disabled
+        assert_eq!(
+            next_transition(Some((stale, true)), now, 3600),
+            Transition::Suppressed
+        );
+        assert_eq!(
+            next_transition(Some((stale, false)), now, 0),
+            Transition::Suppressed
+        );
+    }
+
+    #[test]
+    fn test_with_alert_id() {
+        let details = with_alert_id(serde_json::json!({ "available": 5 }), 7, false);
+        assert_eq!(
+            details,
+            serde_json::json!({ "available": 5, "alert_id": 7, "renotify": false })
+        );
+        assert_eq!(with_alert_id(Value::Null, 7, true)["renotify"], true);
+    }
+}
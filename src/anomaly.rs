@@ -0,0 +1,495 @@
+//! Configurable anomaly-detection rules evaluated by the security monitor.
+//!
+//! Rules live in `anomaly_rules`: the built-in [`default_rules`] are seeded once, and rules
+//! from `ANOMALY_RULES` (a JSON array of [`Rule`]) replace stored rules with the same id.
+//! Each cycle every enabled rule yields the owners it currently matches. Actions run when a
+//! rule starts matching an owner (tracked in `anomaly_rule_state`); the `alert` action keeps
+//! an alert of kind `<rule id>` open through the [`AlertManager`] until the rule stops
+//! matching.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::alerts::AlertManager;
+use crate::circuit_breaker;
+use crate::db;
+use crate::events::Severity;
+use crate::notify::Notifier;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleKind {
+    /// More than `max_count` transactions within `window_seconds`.
+    TxRate,
+    /// Withdrawals of at least `percent` of the balance within `window_seconds`.
+    WithdrawalRatio,
+    /// A withdrawal less than `window_seconds` after adding a whitelist address.
+    NewWhitelistWithdrawal,
+    /// More than `max_changes` delegate adds/removes within `window_seconds`.
+    DelegateChurn,
+    /// More than `max_failures` rejected signatures or nonces within `window_seconds`.
+    AuthFailures,
+    /// Any emergency withdrawal within `window_seconds`.
+    EmergencyWithdraw,
+}
+
+impl RuleKind {
+    pub const ALL: [RuleKind; 6] = [
+        RuleKind::TxRate,
+        RuleKind::WithdrawalRatio,
+        RuleKind::NewWhitelistWithdrawal,
+        RuleKind::DelegateChurn,
+        RuleKind::AuthFailures,
+        RuleKind::EmergencyWithdraw,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleKind::TxRate => "tx_rate",
+            RuleKind::WithdrawalRatio => "withdrawal_ratio",
+            RuleKind::NewWhitelistWithdrawal => "new_whitelist_withdrawal",
+            RuleKind::DelegateChurn => "delegate_churn",
+            RuleKind::AuthFailures => "auth_failures",
+            RuleKind::EmergencyWithdraw => "emergency_withdraw",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<RuleKind> {
+        RuleKind::ALL.into_iter().find(|k| k.as_str() == s)
+    }
+
+    /// Every parameter the kind reads, with its default.
+    pub fn default_params(&self) -> Value {
+        match self {
+            RuleKind::TxRate => serde_json::json!({ "max_count": 10, "window_seconds": 60 }),
+            RuleKind::WithdrawalRatio => {
+                serde_json::json!({ "percent": 50, "window_seconds": 3600 })
+            }
+            RuleKind::NewWhitelistWithdrawal => serde_json::json!({ "window_seconds": 86400 }),
+            RuleKind::DelegateChurn => {
+                serde_json::json!({ "max_changes": 5, "window_seconds": 3600 })
+            }
+            RuleKind::AuthFailures => {
+                serde_json::json!({ "max_failures": 10, "window_seconds": 900 })
+            }
+            RuleKind::EmergencyWithdraw => serde_json::json!({ "window_seconds": 3600 }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RuleAction {
+    /// Keep an alert open while the rule matches.
+    #[serde(rename = "alert")]
+    Alert,
+    /// Freeze the vault through [`crate::circuit_breaker::freeze`].
+    #[serde(rename = "freeze")]
+    Freeze,
+    /// Drop the owner's 2FA policy so every sensitive operation needs the second factor.
+    #[serde(rename = "require_2fa")]
+    Require2fa,
+}
+
+impl RuleAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleAction::Alert => "alert",
+            RuleAction::Freeze => "freeze",
+            RuleAction::Require2fa => "require_2fa",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<RuleAction> {
+        [
+            RuleAction::Alert,
+            RuleAction::Freeze,
+            RuleAction::Require2fa,
+        ]
+        .into_iter()
+        .find(|a| a.as_str() == s)
+    }
+}
+
+fn default_severity() -> Severity {
+    Severity::Warning
+}
+
+fn default_actions() -> Vec<RuleAction> {
+    vec![RuleAction::Alert]
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub id: String,
+    pub kind: RuleKind,
+    /// Overrides for [`RuleKind::default_params`].
+    #[serde(default)]
+    pub params: Value,
+    #[serde(default = "default_severity")]
+    pub severity: Severity,
+    #[serde(default = "default_actions")]
+    pub actions: Vec<RuleAction>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+impl Rule {
+    fn new(id: &str, kind: RuleKind, severity: Severity) -> Self {
+        Self {
+            id: id.to_string(),
+            kind,
+            params: serde_json::json!({}),
+            severity,
+            actions: default_actions(),
+            enabled: true,
+        }
+    }
+
+    /// The parameter from `params`, or the kind's default.
+    pub fn param(&self, key: &str) -> i64 {
+        self.params
+            .get(key)
+            .and_then(Value::as_i64)
+            .or_else(|| self.kind.default_params()[key].as_i64())
+            .unwrap_or(0)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.id.is_empty()
+            || self.id.len() > 64
+            || !self
+                .id
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            return Err(format!(
+                "rule id {:?} must be 1-64 chars of a-z, 0-9 and _",
+                self.id
+            ));
+        }
+        let params = match &self.params {
+            Value::Null => return Ok(()),
+            Value::Object(params) => params,
+            _ => return Err(format!("rule {}: params must be an object", self.id)),
+        };
+        let defaults = self.kind.default_params();
+        for (key, value) in params {
+            if defaults.get(key).is_none() {
+                return Err(format!(
+                    "rule {}: unknown param {key} for {}",
+                    self.id,
+                    self.kind.as_str()
+                ));
+            }
+            match value.as_i64() {
+                Some(v) if v > 0 => {}
+                _ => {
+                    return Err(format!(
+                        "rule {}: {key} must be a positive integer",
+                        self.id
+                    ))
+                }
+            }
+        }
+        if self.param("percent") > 100 {
+            return Err(format!("rule {}: percent must be at most 100", self.id));
+        }
+        Ok(())
+    }
+
+    pub fn to_row(&self) -> db::AnomalyRuleRow {
+        (
+            self.id.clone(),
+            self.kind.as_str().to_string(),
+            if self.params.is_null() {
+                serde_json::json!({})
+            } else {
+                self.params.clone()
+            },
+            self.severity.as_str().to_string(),
+            self.actions
+                .iter()
+                .map(|a| a.as_str().to_string())
+                .collect(),
+            self.enabled,
+        )
+    }
+
+    pub fn from_row(row: db::AnomalyRuleRow) -> Result<Self, String> {
+        let (id, kind, params, severity, actions, enabled) = row;
+        Ok(Self {
+            kind: RuleKind::parse(&kind)
+                .ok_or_else(|| format!("rule {id}: unknown kind {kind}"))?,
+            severity: Severity::parse(&severity)
+                .ok_or_else(|| format!("rule {id}: unknown severity {severity}"))?,
+            actions: actions
+                .iter()
+                .map(|a| {
+                    RuleAction::parse(a).ok_or_else(|| format!("rule {id}: unknown action {a}"))
+                })
+                .collect::<Result<_, _>>()?,
+            id,
+            params,
+            enabled,
+        })
+    }
+}
+
+/// Rules seeded on first start. They only alert; freezing and 2FA enforcement are opt-in.
+pub fn default_rules() -> Vec<Rule> {
+    vec![
+        Rule::new("unusual_activity", RuleKind::TxRate, Severity::Warning),
+        Rule::new(
+            "large_withdrawal",
+            RuleKind::WithdrawalRatio,
+            Severity::Warning,
+        ),
+        Rule::new(
+            "new_whitelist_withdrawal",
+            RuleKind::NewWhitelistWithdrawal,
+            Severity::Warning,
+        ),
+        Rule::new("delegate_churn", RuleKind::DelegateChurn, Severity::Warning),
+        Rule::new("auth_failures", RuleKind::AuthFailures, Severity::Warning),
+        Rule::new(
+            "emergency_withdraw_used",
+            RuleKind::EmergencyWithdraw,
+            Severity::Critical,
+        ),
+    ]
+}
+
+/// Parses and validates `ANOMALY_RULES`.
+pub fn configured_rules(json: &str) -> Result<Vec<Rule>, String> {
+    if json.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let rules: Vec<Rule> =
+        serde_json::from_str(json).map_err(|e| format!("invalid ANOMALY_RULES: {e}"))?;
+    for rule in &rules {
+        rule.validate()?;
+    }
+    Ok(rules)
+}
+
+/// Inserts missing default rules, then stores `configured` over any rule with the same id.
+pub async fn seed_rules(pool: &PgPool, configured: &[Rule]) -> Result<(), sqlx::Error> {
+    for rule in default_rules() {
+        db::anomaly_rule_upsert(pool, &rule.to_row(), false).await?;
+    }
+    for rule in configured {
+        db::anomaly_rule_upsert(pool, &rule.to_row(), true).await?;
+    }
+    Ok(())
+}
+
+/// Owners `rule` currently matches, with alert details.
+pub async fn evaluate(pool: &PgPool, rule: &Rule) -> Result<Vec<(String, Value)>, sqlx::Error> {
+    let window = rule.param("window_seconds");
+    let matches = match rule.kind {
+        RuleKind::TxRate => db::anomaly_tx_rate(pool, window, rule.param("max_count"))
+            .await?
+            .into_iter()
+            .map(|(owner, count)| (owner, serde_json::json!({ "count": count })))
+            .collect::<Vec<_>>(),
+        RuleKind::WithdrawalRatio => {
+            db::anomaly_withdrawal_ratio(pool, window, rule.param("percent"))
+                .await?
+                .into_iter()
+                .map(|(owner, withdrawn, balance)| {
+                    (
+                        owner,
+                        serde_json::json!({ "withdrawn": withdrawn, "balance": balance }),
+                    )
+                })
+                .collect()
+        }
+        RuleKind::NewWhitelistWithdrawal => db::anomaly_new_whitelist_withdrawals(pool, window)
+            .await?
+            .into_iter()
+            .map(|(owner, addresses, withdrawals)| {
+                (
+                    owner,
+                    serde_json::json!({ "addresses": addresses, "withdrawals": withdrawals }),
+                )
+            })
+            .collect(),
+        RuleKind::DelegateChurn => {
+            db::anomaly_delegate_churn(pool, window, rule.param("max_changes"))
+                .await?
+                .into_iter()
+                .map(|(owner, changes)| (owner, serde_json::json!({ "changes": changes })))
+                .collect()
+        }
+        RuleKind::AuthFailures => {
+            db::anomaly_auth_failures(pool, window, rule.param("max_failures"))
+                .await?
+                .into_iter()
+                .map(|(owner, failures)| (owner, serde_json::json!({ "failures": failures })))
+                .collect()
+        }
+        RuleKind::EmergencyWithdraw => db::anomaly_emergency_withdrawals(pool, window)
+            .await?
+            .into_iter()
+            .map(|(owner, count)| (owner, serde_json::json!({ "count": count })))
+            .collect(),
+    };
+    Ok(matches
+        .into_iter()
+        .map(|(owner, mut details)| {
+            details["rule"] = rule.id.clone().into();
+            details["window_seconds"] = window.into();
+            (owner, details)
+        })
+        .collect())
+}
+
+pub struct RulesEngine {
+    pool: PgPool,
+    notifier: Arc<Notifier>,
+    alerts: AlertManager,
+}
+
+impl RulesEngine {
+    pub fn new(pool: PgPool, notifier: Arc<Notifier>, renotify_seconds: i64) -> Self {
+        let alerts = AlertManager::new(pool.clone(), notifier.clone(), renotify_seconds);
+        Self {
+            pool,
+            notifier,
+            alerts,
+        }
+    }
+
+    /// Evaluates every stored rule once. A failing rule is logged and skipped.
+    pub async fn run_once(&self) -> Result<(), sqlx::Error> {
+        for row in db::anomaly_rules_list(&self.pool).await? {
+            let rule = match Rule::from_row(row) {
+                Ok(rule) => rule,
+                Err(e) => {
+                    warn!("skipping anomaly rule: {e}");
+                    continue;
+                }
+            };
+            if let Err(e) = self.run_rule(&rule).await {
+                warn!(rule = %rule.id, "anomaly rule failed: {e}");
+            }
+        }
+        Ok(())
+    }
+
+    async fn run_rule(&self, rule: &Rule) -> Result<(), sqlx::Error> {
+        // A disabled rule matches nobody, which clears its state and resolves its alerts
+        let matches = if rule.enabled {
+            evaluate(&self.pool, rule).await?
+        } else {
+            Vec::new()
+        };
+        for (owner, details) in &matches {
+            if db::anomaly_state_trigger(&self.pool, &rule.id, owner, details).await? {
+                self.apply_actions(rule, owner).await?;
+            }
+        }
+        let owners: Vec<String> = matches.iter().map(|(owner, _)| owner.clone()).collect();
+        db::anomaly_state_clear_except(&self.pool, &rule.id, &owners).await?;
+
+        let alerting = if rule.actions.contains(&RuleAction::Alert) {
+            matches
+        } else {
+            Vec::new()
+        };
+        self.alerts
+            .reconcile(&rule.id, rule.severity, alerting)
+            .await?;
+        Ok(())
+    }
+
+    async fn apply_actions(&self, rule: &Rule, owner: &str) -> Result<(), sqlx::Error> {
+        for action in &rule.actions {
+            match action {
+                // Handled by the alert manager in `run_rule`
+                RuleAction::Alert => {}
+                RuleAction::Freeze => {
+                    circuit_breaker::freeze(
+                        &self.pool,
+                        &self.notifier,
+                        owner,
+                        &format!("anomaly rule {} triggered", rule.id),
+                        &format!("rule:{}", rule.id),
+                    )
+                    .await?;
+                }
+                RuleAction::Require2fa => {
+                    db::twofa_policy_reset(&self.pool, owner).await?;
+                    db::twofa_required_set(&self.pool, owner, &format!("rule:{}", rule.id)).await?;
+                    db::insert_audit_log(
+                        &self.pool,
+                        Some(owner),
+                        "twofa_policy_enforced",
+                        serde_json::json!({ "rule": rule.id }),
+                    )
+                    .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kinds_and_actions_roundtrip() {
+        for kind in RuleKind::ALL {
+            assert_eq!(RuleKind::parse(kind.as_str()), Some(kind));
+        }
+        for action in [
+            RuleAction::Alert,
+            RuleAction::Freeze,
+            RuleAction::Require2fa,
+        ] {
+            assert_eq!(RuleAction::parse(action.as_str()), Some(action));
+            assert_eq!(
+                serde_json::to_value(action).unwrap(),
+                Value::from(action.as_str())
+            );
+        }
+        for rule in default_rules() {
+            assert!(rule.validate().is_ok());
+            assert_eq!(Rule::from_row(rule.to_row()).unwrap(), rule);
+        }
+    }
+
+    #[test]
+    fn test_configured_rules() {
+        let rules = configured_rules(
+            r#"[{ "id": "big_exit", "kind": "withdrawal_ratio", "params": { "percent": 80 },
+                  "severity": "critical", "actions": ["alert", "freeze", "require_2fa"] }]"#,
+        )
+        .unwrap();
+        assert_eq!(rules[0].param("percent"), 80);
+        assert_eq!(rules[0].param("window_seconds"), 3600);
+        assert_eq!(rules[0].severity, Severity::Critical);
+        assert_eq!(rules[0].actions.len(), 3);
+        assert!(configured_rules("").unwrap().is_empty());
+
+        let invalid = [
+            r#"[{ "id": "Bad Id", "kind": "tx_rate" }]"#,
+            r#"[{ "id": "r", "kind": "tx_rate", "params": { "percent": 5 } }]"#,
+            r#"[{ "id": "r", "kind": "tx_rate", "params": { "max_count": -1 } }]"#,
+            r#"[{ "id": "r", "kind": "withdrawal_ratio", "params": { "percent": 150 } }]"#,
+            r#"[{ "id": "r", "kind": "unknown" }]"#,
+        ];
+        for json in invalid {
+            assert!(configured_rules(json).is_err(), "{json}");
+        }
+    }
+}
@@ -6,19 +6,23 @@ use sqlx::PgPool;
 use std::sync::Arc;
 use tower_governor::{governor::GovernorConfigBuilder, GovernorLayer};
 
-use crate::{cache::Cache, config::AppConfig, metrics::Metrics, notify::Notifier, ops::RateLimiter, solana_client::SolanaClient};
+use crate::{account_hub::AccountHub, cache::Cache, config::AppConfig, metrics::Metrics, notify::Notifier, ops::RateLimiter, repo::Repos, solana_client::SolanaClient};
 
 mod routes;
+mod sse;
+mod twofa;
 mod ws;
 
 #[derive(Clone)]
 pub struct AppState {
     pub pool: PgPool,
+    pub repos: Repos,
     pub cfg: AppConfig,
     pub sol: SolanaClient,
     pub notifier: std::sync::Arc<Notifier>,
+    pub account_hub: std::sync::Arc<AccountHub>,
     pub rate_limiter: std::sync::Arc<RateLimiter>,
-    pub cache: Option<std::sync::Arc<Cache>>,
+    pub cache: std::sync::Arc<Cache>,
     pub metrics: std::sync::Arc<Metrics>,
 }
 
@@ -64,11 +68,17 @@ pub fn router(state: AppState) -> Router {
         )
         .route("/vault/proposal/:id", get(routes::vault_proposal_status))
         .route("/vault/balance/:owner", get(routes::vault_balance))
+        .route(
+            "/vault/balance-history/:owner",
+            get(routes::vault_balance_history),
+        )
         .route(
             "/vault/transactions/:owner",
             get(routes::vault_transactions),
         )
         .route("/vault/tvl", get(routes::vault_tvl))
+        .route("/vault/ledger/:owner", get(routes::vault_ledger))
+        .route("/proof/:owner", get(routes::proof_of_reserves_proof))
         .route(
             "/vault/yield-status/:owner",
             get(routes::vault_yield_status),
@@ -96,6 +106,59 @@ pub fn router(state: AppState) -> Router {
         // 2FA endpoints
         .route("/2fa/setup", post(routes::twofa_setup))
         .route("/2fa/verify", post(routes::twofa_verify))
+        .route("/2fa/disable", post(routes::twofa_disable))
+        .route("/2fa/policy", post(routes::twofa_policy_set))
+        .route("/2fa/policy/:owner", get(routes::twofa_policy_get))
+        .route("/2fa/webauthn/register", post(routes::webauthn_register))
+        .route("/2fa/webauthn/remove", post(routes::webauthn_remove))
+        .route(
+            "/2fa/webauthn/credentials/:owner",
+            get(routes::webauthn_credentials),
+        )
+        .route("/2fa/reset/cancel", post(routes::twofa_reset_cancel))
+        .route("/admin/2fa/reset", post(routes::admin_twofa_reset))
+        .route("/webhooks/register", post(routes::webhook_register))
+        .route("/webhooks/remove", post(routes::webhook_remove))
+        .route("/webhooks/:owner", get(routes::webhooks_list))
+        .route("/notifications/contact", post(routes::notification_contact))
+        .route("/notifications/preferences", post(routes::notification_preferences))
+        .route("/notifications/unsubscribe", get(routes::notification_unsubscribe))
+        .route("/notifications/:owner", get(routes::notification_settings))
+        .route("/admin/webhooks", post(routes::admin_webhook_register))
+        .route(
+            "/admin/webhooks/dead-letters",
+            get(routes::admin_webhook_dead_letters),
+        )
+        .route(
+            "/admin/webhooks/dead-letters/replay",
+            post(routes::admin_webhook_replay),
+        )
+        .route("/admin/alerts", get(routes::admin_alerts_list))
+        .route("/admin/alerts/:id/ack", post(routes::admin_alert_ack))
+        .route(
+            "/admin/anomaly-rules",
+            get(routes::admin_anomaly_rules_list).post(routes::admin_anomaly_rule_upsert),
+        )
+        // Circuit breaker
+        .route(
+            "/admin/circuit-breaker",
+            get(routes::admin_circuit_breaker_status),
+        )
+        .route(
+            "/admin/circuit-breaker/operation",
+            post(routes::admin_operation_switch_set),
+        )
+        .route("/admin/vault/freeze", post(routes::admin_vault_freeze))
+        .route("/admin/vault/unfreeze", post(routes::admin_vault_unfreeze))
+        .route("/admin/ledger/check", get(routes::admin_ledger_check))
+        .route(
+            "/admin/reconciliation/cases",
+            get(routes::admin_reconciliation_cases),
+        )
+        .route(
+            "/admin/reconciliation/cases/:id/resolve",
+            post(routes::admin_reconciliation_resolve),
+        )
         // Limits & analytics
         .route("/vault/limits/:owner", get(routes::vault_limits))
         .route("/analytics/tvl-series", get(routes::analytics_tvl_series))
@@ -143,6 +206,9 @@ pub fn router(state: AppState) -> Router {
             "/internal/transfer-collateral",
             post(routes::internal_transfer_collateral),
         )
+        .route("/events", get(routes::events_list))
+        .route("/events/session", post(sse::events_session))
+        .route("/events/stream", get(sse::events_stream))
         .route("/ws", get(ws::ws_handler))
         .route("/metrics", get(routes::metrics))
         .with_state(state)
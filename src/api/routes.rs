@@ -13,11 +13,27 @@ use serde::{Deserialize, Serialize};
 use solana_sdk::{pubkey::Pubkey, signature::Signer};
 use std::str::FromStr;
 
-use super::AppState;
+use super::{
+    sse,
+    twofa::{relying_party, verify_twofa_code, SecondFactor, SensitiveOp},
+    AppState,
+};
 use crate::cpi::CPIManager;
 use crate::{
-    auth::{verify_admin_jwt, verify_wallet_signature},
+    anomaly,
+    auth::{verify_admin_jwt, verify_wallet_signature, NoncePurpose},
+    cache::KeyClass,
+    circuit_breaker::{self, Blocked, Operation},
     db,
+    email::{self, EmailKind},
+    events::{
+        BalanceUpdate, EventEnvelope, Severity, TimelockEvent, TimelockStatus, Topic, TvlUpdate,
+        TxEvent, VaultEvent,
+    },
+    ledger::{self, EntryKind},
+    proof_of_reserves,
+    reconciliation::{self, CaseStatus},
+    snapshots::Granularity,
     solana_client::{
         build_compute_budget_instructions, build_instruction_add_withdraw_whitelist,
         build_instruction_add_yield_program, build_instruction_compound_yield,
@@ -34,6 +50,7 @@ use crate::{
         TransferCollateralParams, WithdrawMultisigParams, WithdrawParams, YieldDepositParams,
         YieldWithdrawParams,
     },
+    tvl::SeriesGranularity,
 };
 
 pub async fn health() -> Json<serde_json::Value> {
@@ -131,27 +148,22 @@ pub async fn vault_deposit(
     };
     // Verify wallet signature on message: deposit:{owner}:{amount}:{nonce}
     let message = format!("deposit:{}:{}:{}", req.owner, req.amount, req.nonce);
-    if let Err(e) = verify_wallet_signature(&req.owner, message.as_bytes(), &req.signature) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        );
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::Deposit,
+    )
+    .await
+    {
+        return rejection;
     }
-    // Consume nonce
-    match db::consume_nonce(&state.pool, &req.nonce, &req.owner).await {
-        Ok(true) => {}
-        Ok(false) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({ "error": "invalid or used nonce" })),
-            )
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            )
-        }
+    if let Err(rejection) =
+        ensure_operation_allowed(&state, &[&req.owner], Operation::Deposits).await
+    {
+        return rejection;
     }
 
     let owner = match Pubkey::from_str(&req.owner) {
@@ -209,16 +221,22 @@ pub struct WithdrawRequest {
 
 pub async fn vault_withdraw(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    second_factor: SecondFactor,
     Json(req): Json<WithdrawRequest>,
 ) -> impl IntoResponse {
     // Verify wallet signature on message: withdraw:{owner}:{amount}:{nonce}
     let message = format!("withdraw:{}:{}:{}", req.owner, req.amount, req.nonce);
-    if let Err(e) = verify_wallet_signature(&req.owner, message.as_bytes(), &req.signature) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        );
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::Withdraw,
+    )
+    .await
+    {
+        return rejection;
     }
     // Rate limiting per owner
     if !state.rate_limiter.check_and_record(&req.owner).await {
@@ -227,40 +245,17 @@ pub async fn vault_withdraw(
             Json(serde_json::json!({ "error": "rate limited" })),
         );
     }
-    match db::consume_nonce(&state.pool, &req.nonce, &req.owner).await {
-        Ok(true) => {}
-        Ok(false) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({ "error": "invalid or used nonce" })),
-            )
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            )
-        }
+    if let Err(rejection) =
+        ensure_operation_allowed(&state, &[&req.owner], Operation::Withdrawals).await
+    {
+        return rejection;
     }
 
-    // Enforce 2FA if enabled for owner
-    if let Ok(Some((secret, enabled))) = db::twofa_get(&state.pool, &req.owner).await {
-        if enabled {
-            // Expect client to include header X-2FA-CODE
-            if let Some(val) = headers.get("x-2fa-code").and_then(|v| v.to_str().ok()) {
-                if !crate::security::verify_totp(&secret, val) {
-                    return (
-                        StatusCode::UNAUTHORIZED,
-                        Json(serde_json::json!({ "error": "invalid 2fa code" })),
-                    );
-                }
-            } else {
-                return (
-                    StatusCode::UNAUTHORIZED,
-                    Json(serde_json::json!({ "error": "2fa code required" })),
-                );
-            }
-        }
+    if let Err(rejection) = second_factor
+        .require(&state, &req.owner, SensitiveOp::Withdraw)
+        .await
+    {
+        return rejection;
     }
 
     let program_id = match Pubkey::from_str(&state.cfg.program_id) {
@@ -332,43 +327,59 @@ pub async fn vault_withdraw(
     state.metrics.vault_operations.inc();
     state.metrics.transaction_submissions.inc();
     
-    let _ = db::insert_transaction_with_status(
+    let _ = state
+        .repos
+        .txs
+        .insert(
+            &req.owner,
+            &sig.to_string(),
+            Some(req.amount as i64),
+            "withdraw",
+            "pending",
+        )
+        .await;
+    let _ = ledger::record(
         &state.pool,
-        &req.owner,
+        EntryKind::WithdrawSubmitted,
         &sig.to_string(),
-        Some(req.amount as i64),
-        "withdraw",
-        "pending",
+        &req.owner,
+        req.amount as i64,
     )
     .await;
+    state.cache.invalidate_balance(&req.owner).await;
+    state.cache.invalidate_tvl().await;
     // best-effort snapshot update via on-chain balance using owner as token account
     if let Ok(owner_pk) = Pubkey::from_str(&req.owner) {
-        let _ = db::upsert_vault_token_account(&state.pool, &req.owner, &req.owner).await;
+        let _ = state
+            .repos
+            .vaults
+            .upsert_token_account(&req.owner, &req.owner)
+            .await;
         if let Ok(chain_bal) = crate::solana_client::get_token_balance(&state.sol, &owner_pk).await
         {
-            // Invalidate cache
-            if let Some(ref cache) = state.cache {
-                cache.invalidate_balance(&req.owner).await;
-            }
-            
-            let _ = db::update_vault_snapshot(
-                &state.pool,
+            let _ = state
+                .repos
+                .vaults
+                .update_snapshot(&req.owner, chain_bal as i64, 0, req.amount as i64)
+                .await;
+            state.notifier.publish_for(
                 &req.owner,
-                chain_bal as i64,
-                0,
-                req.amount as i64,
-            )
-            .await;
-            let _ = state.notifier.vault_balance_tx.send(
-                serde_json::json!({
-                    "owner": req.owner,
-                    "balance": chain_bal,
-                })
-                .to_string(),
+                VaultEvent::BalanceUpdate(BalanceUpdate {
+                    balance: chain_bal,
+                    previous_balance: None,
+                    delta: None,
+                }),
             );
         }
     }
-    let _ = state.notifier.withdraw_tx.send(serde_json::json!({ "owner": req.owner, "amount": req.amount, "signature": sig.to_string() }).to_string());
+    state.notifier.publish(
+        EventEnvelope::new(VaultEvent::Withdraw(TxEvent {
+            amount: Some(req.amount),
+            signature: sig.to_string(),
+        }))
+        .with_owner(&req.owner)
+        .with_correlation_id(sig.to_string()),
+    );
     let _ = db::insert_audit_log(
         &state.pool,
         Some(&req.owner),
@@ -389,61 +400,100 @@ pub async fn vault_balance(
     let start = std::time::Instant::now();
     state.metrics.vault_balance_queries.inc();
     
-    // Try cache first
-    if let Some(ref cache) = state.cache {
-        if let Some(cached_balance) = cache.get_balance(&owner).await {
-            state.metrics.balance_query_duration.observe(start.elapsed().as_secs_f64());
-            return (StatusCode::OK, Json(serde_json::json!({ "balance": cached_balance, "cached": true })));
-        }
-    }
-    
-    // Resolve owner -> token account via DB first; fallback to interpreting as token account
-    if let Ok(Some((token_acc_opt, _))) = db::get_vault(&state.pool, &owner).await {
-        if let Some(token_acc) = token_acc_opt {
-            if let Ok(pk) = Pubkey::from_str(&token_acc) {
-                match crate::solana_client::get_token_balance(&state.sol, &pk).await {
-                    Ok(bal) => {
-                        // Cache the result
-                        if let Some(ref cache) = state.cache {
-                            cache.set_balance(&owner, bal).await;
-                        }
-                        state.metrics.balance_query_duration.observe(start.elapsed().as_secs_f64());
-                        return (StatusCode::OK, Json(serde_json::json!({ "balance": bal, "cached": false })))
-                    }
-                    Err(e) => {
-                        state.metrics.balance_query_duration.observe(start.elapsed().as_secs_f64());
-                        return (
-                            StatusCode::BAD_GATEWAY,
-                            Json(serde_json::json!({ "error": e.to_string() })),
-                        )
-                    }
+    let vaults = state.repos.vaults.clone();
+    let sol = state.sol.clone();
+    let load_owner = owner.clone();
+    let loaded = state
+        .cache
+        .get_or_load(KeyClass::Balance, &owner, move || async move {
+            // Resolve owner -> token account via DB first; fallback to interpreting as token account
+            let token_acc = match vaults.get(&load_owner).await {
+                Ok(Some(vault)) => vault
+                    .token_account
+                    .and_then(|t| Pubkey::from_str(&t).ok()),
+                _ => None,
+            };
+            let token_acc = match token_acc.or_else(|| Pubkey::from_str(&load_owner).ok()) {
+                Some(pk) => pk,
+                None => {
+                    return Err((
+                        StatusCode::BAD_REQUEST,
+                        "invalid owner or token account".to_string(),
+                    ))
                 }
-            }
-        }
+            };
+            crate::solana_client::get_token_balance(&sol, &token_acc)
+                .await
+                .map_err(|e| (StatusCode::BAD_GATEWAY, e.to_string()))
+        })
+        .await;
+    state.metrics.balance_query_duration.observe(start.elapsed().as_secs_f64());
+    match loaded {
+        Ok((bal, cached)) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "balance": bal, "cached": cached })),
+        ),
+        Err((status, error)) => (status, Json(serde_json::json!({ "error": error }))),
     }
-    match Pubkey::from_str(&owner) {
-        Ok(token_acc) => {
-            match crate::solana_client::get_token_balance(&state.sol, &token_acc).await {
-                Ok(bal) => {
-                    // Cache the result
-                    if let Some(ref cache) = state.cache {
-                        cache.set_balance(&owner, bal).await;
-                    }
-                    state.metrics.balance_query_duration.observe(start.elapsed().as_secs_f64());
-                    (StatusCode::OK, Json(serde_json::json!({ "balance": bal, "cached": false })))
-                }
-                Err(e) => {
-                    state.metrics.balance_query_duration.observe(start.elapsed().as_secs_f64());
-                    (
-                        StatusCode::BAD_GATEWAY,
-                        Json(serde_json::json!({ "error": e.to_string() })),
-                    )
-                }
-            }
+}
+
+/// Range of a chart series (balance history, TVL).
+#[derive(Deserialize)]
+pub struct SeriesQuery {
+    pub granularity: Option<String>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub from: Option<time::OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    pub to: Option<time::OffsetDateTime>,
+}
+
+/// Balance snapshots for charts. `from`/`to` are RFC 3339; the default range is the last day
+/// of hourly or the last 30 days of daily snapshots.
+pub async fn vault_balance_history(
+    State(state): State<AppState>,
+    Path(owner): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<SeriesQuery>,
+) -> impl IntoResponse {
+    let granularity = match Granularity::parse(params.granularity.as_deref().unwrap_or("hourly")) {
+        Some(g) => g,
+        None => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "granularity must be hourly or daily" })),
+            )
         }
-        Err(_) => (
+    };
+    let to = params.to.unwrap_or_else(time::OffsetDateTime::now_utc);
+    let from = params.from.unwrap_or_else(|| match granularity {
+        Granularity::Hourly => to - time::Duration::DAY,
+        Granularity::Daily => to - time::Duration::days(30),
+    });
+    if from > to {
+        return (
             StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({ "error": "invalid owner or token account" })),
+            Json(serde_json::json!({ "error": "from must not be after to" })),
+        );
+    }
+    let rfc3339 = |t: time::OffsetDateTime| {
+        t.format(&time::format_description::well_known::Rfc3339)
+            .unwrap_or_default()
+    };
+    match db::balance_snapshots_list(&state.pool, &owner, granularity.as_str(), from, to, 1000)
+        .await
+    {
+        Ok(points) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "owner": owner,
+                "granularity": granularity,
+                "from": rfc3339(from),
+                "to": rfc3339(to),
+                "points": points,
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
         ),
     }
 }
@@ -479,17 +529,17 @@ pub async fn vault_transactions(
     let limit = params.limit.unwrap_or(50).min(100); // Max 100 per page
     let offset = params.offset.unwrap_or(0);
     
-    match db::list_transactions(&state.pool, &owner, limit, offset).await {
+    match state.repos.txs.list_for_owner(&owner, limit, offset).await {
         Ok(rows) => {
             let items: Vec<_> = rows
                 .into_iter()
-                .map(|(id, signature, amount, kind, created_at)| {
+                .map(|tx| {
                     serde_json::json!({
-                        "id": id,
-                        "signature": signature,
-                        "amount": amount,
-                        "kind": kind,
-                        "created_at": created_at,
+                        "id": tx.id,
+                        "signature": tx.signature,
+                        "amount": tx.amount,
+                        "kind": tx.kind,
+                        "created_at": tx.created_at,
                     })
                 })
                 .collect();
@@ -517,6 +567,57 @@ pub async fn vault_transactions(
     }
 }
 
+#[derive(Deserialize)]
+pub struct EventsQueryParams {
+    pub topic: String,
+    pub owner: Option<String>,
+    pub cursor: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// Pages through persisted events after `cursor`. Poll with the returned `next_cursor`.
+/// Owner-scoped topics need the same credentials as `/events/stream`.
+pub async fn events_list(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    axum::extract::Query(params): axum::extract::Query<EventsQueryParams>,
+) -> impl IntoResponse {
+    let Some(topic) = Topic::parse(&params.topic) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "unknown topic" })),
+        );
+    };
+    let session = match sse::http_session(&state, &headers) {
+        Ok(session) => session,
+        Err(rejection) => return rejection,
+    };
+    let owner = match sse::owner_filter(&session, &[topic], params.owner.as_deref()) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection,
+    };
+    let cursor = params.cursor.unwrap_or(0);
+    let limit = params.limit.unwrap_or(100).clamp(1, 500);
+
+    match state
+        .notifier
+        .replay(&[topic], owner.as_deref(), cursor, limit)
+        .await
+    {
+        Ok(events) => {
+            let next_cursor = events.last().and_then(|e| e.cursor).unwrap_or(cursor);
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "events": events, "next_cursor": next_cursor })),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
 #[derive(Deserialize)]
 pub struct ScheduleWithdrawRequest {
     pub owner: String,
@@ -528,39 +629,42 @@ pub struct ScheduleWithdrawRequest {
 
 pub async fn vault_schedule_withdraw(
     State(state): State<AppState>,
+    second_factor: SecondFactor,
     Json(req): Json<ScheduleWithdrawRequest>,
 ) -> impl IntoResponse {
+    if req.duration_seconds < 0 {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "invalid duration" })),
+        );
+    }
     // Verify signature on message: schedule:{owner}:{amount}:{duration}:{nonce}
     let message = format!(
         "schedule:{}:{}:{}:{}",
         req.owner, req.amount, req.duration_seconds, req.nonce
     );
-    if let Err(e) = verify_wallet_signature(&req.owner, message.as_bytes(), &req.signature) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        );
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::Schedule,
+    )
+    .await
+    {
+        return rejection;
     }
-    if req.duration_seconds < 0 {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({ "error": "invalid duration" })),
-        );
+    if let Err(rejection) =
+        ensure_operation_allowed(&state, &[&req.owner], Operation::Withdrawals).await
+    {
+        return rejection;
     }
-    match db::consume_nonce(&state.pool, &req.nonce, &req.owner).await {
-        Ok(true) => {}
-        Ok(false) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({ "error": "invalid or used nonce" })),
-            )
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            )
-        }
+    if let Err(rejection) = second_factor
+        .require(&state, &req.owner, SensitiveOp::ScheduleWithdraw)
+        .await
+    {
+        return rejection;
     }
     let program_id = match Pubkey::from_str(&state.cfg.program_id) {
         Ok(p) => p,
@@ -623,7 +727,11 @@ pub async fn vault_schedule_withdraw(
 
     // Insert timelock row for UI and cron
     let unlock_at = time::OffsetDateTime::now_utc() + time::Duration::seconds(req.duration_seconds);
-    let _ = db::timelock_insert(&state.pool, &req.owner, req.amount as i64, unlock_at).await;
+    let _ = state
+        .repos
+        .timelocks
+        .insert(&req.owner, req.amount as i64, unlock_at)
+        .await;
     let _ = db::insert_audit_log(
         &state.pool,
         Some(&req.owner),
@@ -631,7 +739,16 @@ pub async fn vault_schedule_withdraw(
         serde_json::json!({ "amount": req.amount, "unlock_at": unlock_at }),
     )
     .await;
-    let _ = state.notifier.timelock_tx.send(serde_json::json!({ "owner": req.owner, "amount": req.amount, "unlock_at": unlock_at, "signature": sig.to_string() }).to_string());
+    state.notifier.publish(
+        EventEnvelope::new(VaultEvent::Timelock(TimelockEvent {
+            status: TimelockStatus::Scheduled,
+            amount: req.amount as i64,
+            unlock_at,
+            signature: Some(sig.to_string()),
+        }))
+        .with_owner(&req.owner)
+        .with_correlation_id(sig.to_string()),
+    );
     (
         StatusCode::OK,
         Json(serde_json::json!({ "signature": sig.to_string(), "unlock_at": unlock_at })),
@@ -642,12 +759,12 @@ pub async fn vault_list_timelocks(
     State(state): State<AppState>,
     Path(owner): Path<String>,
 ) -> impl IntoResponse {
-    match db::timelock_list(&state.pool, &owner).await {
+    match state.repos.timelocks.list_for_owner(&owner).await {
         Ok(rows) => {
             let now = time::OffsetDateTime::now_utc();
-            let items: Vec<_> = rows.into_iter().map(|(id, amount, unlock_at, status)| {
-                let remaining = (unlock_at - now).whole_seconds();
-                serde_json::json!({ "id": id, "amount": amount, "unlock_at": unlock_at, "status": status, "remaining_seconds": remaining.max(0) })
+            let items: Vec<_> = rows.into_iter().map(|t| {
+                let remaining = (t.unlock_at - now).whole_seconds();
+                serde_json::json!({ "id": t.id, "amount": t.amount, "unlock_at": t.unlock_at, "status": t.status, "remaining_seconds": remaining.max(0) })
             }).collect();
             (StatusCode::OK, Json(serde_json::json!({ "items": items })))
         }
@@ -659,30 +776,30 @@ pub async fn vault_list_timelocks(
 }
 
 pub async fn vault_tvl(State(state): State<AppState>) -> impl IntoResponse {
-    // Try cache first
-    if let Some(ref cache) = state.cache {
-        if let Some(cached_tvl) = cache.get_tvl().await {
-            state.metrics.total_value_locked.set(cached_tvl as f64);
-            return (StatusCode::OK, Json(serde_json::json!({ "tvl": cached_tvl, "cached": true })));
-        }
-    }
-    
-    let row = sqlx::query_scalar::<_, i64>(
-        "SELECT COALESCE(SUM(CASE WHEN kind = 'deposit' THEN amount ELSE -amount END), 0) AS tvl FROM transactions"
-    );
-    let row = row.fetch_one(&state.pool).await;
-    match row {
-        Ok(tvl) => {
-            // Cache the result
-            if let Some(ref cache) = state.cache {
-                cache.set_tvl(tvl).await;
+    let ledger = state.repos.ledger.clone();
+    let loaded = state
+        .cache
+        .get_or_load(KeyClass::Tvl, "", move || async move { ledger.tvl().await })
+        .await;
+    match loaded {
+        Ok((tvl, cached)) => {
+            let total = tvl.total();
+            state.metrics.total_value_locked.set(total as f64);
+            if !cached {
+                state
+                    .notifier
+                    .publish(EventEnvelope::new(VaultEvent::Tvl(TvlUpdate { tvl: total })));
             }
-            state.metrics.total_value_locked.set(tvl as f64);
-            let _ = state
-                .notifier
-                .tvl_tx
-                .send(serde_json::json!({ "tvl": tvl }).to_string());
-            (StatusCode::OK, Json(serde_json::json!({ "tvl": tvl, "cached": false })))
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "tvl": total,
+                    "available": tvl.available,
+                    "locked": tvl.locked,
+                    "in_yield": tvl.in_yield,
+                    "cached": cached,
+                })),
+            )
         }
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -718,6 +835,9 @@ pub async fn vault_emergency_withdraw(
         );
     }
 
+    // Not subject to the circuit breaker: this is how governance moves funds out of a frozen
+    // vault or while withdrawals are switched off.
+
     // Build instruction with governance authority (deployer keypair assumed to be governance signer)
     let program_id = match Pubkey::from_str(&state.cfg.program_id) {
         Ok(p) => p,
@@ -805,14 +925,27 @@ pub async fn vault_emergency_withdraw(
         }
     };
 
-    let _ = db::insert_transaction(
+    let _ = state
+        .repos
+        .txs
+        .insert(
+            &req.owner,
+            &sig.to_string(),
+            Some(req.amount as i64),
+            "emergency_withdraw",
+            "pending",
+        )
+        .await;
+    let _ = ledger::record(
         &state.pool,
-        &req.owner,
+        EntryKind::WithdrawSubmitted,
         &sig.to_string(),
-        Some(req.amount as i64),
-        "emergency_withdraw",
+        &req.owner,
+        req.amount as i64,
     )
     .await;
+    state.cache.invalidate_balance(&req.owner).await;
+    state.cache.invalidate_tvl().await;
     let _ = db::insert_audit_log(
         &state.pool,
         Some(&req.owner),
@@ -820,7 +953,15 @@ pub async fn vault_emergency_withdraw(
         serde_json::json!({ "amount": req.amount, "reason": req.reason }),
     )
     .await;
-    let _ = state.notifier.security_tx.send(serde_json::json!({ "kind": "emergency_withdraw", "owner": req.owner, "amount": req.amount, "signature": sig.to_string() }).to_string());
+    state.notifier.publish(
+        EventEnvelope::new(VaultEvent::security(
+            "emergency_withdraw",
+            Severity::Critical,
+            serde_json::json!({ "amount": req.amount, "reason": req.reason, "signature": sig.to_string() }),
+        ))
+        .with_owner(&req.owner)
+        .with_correlation_id(sig.to_string()),
+    );
     (
         StatusCode::OK,
         Json(serde_json::json!({ "signature": sig.to_string() })),
@@ -888,6 +1029,7 @@ pub struct ProposeWithdrawRequest {
 
 pub async fn vault_propose_withdraw(
     State(state): State<AppState>,
+    second_factor: SecondFactor,
     Json(req): Json<ProposeWithdrawRequest>,
 ) -> impl IntoResponse {
     // Verify initiator signature on message: propose_withdraw:{owner}:{amount}:{threshold}:{nonce}
@@ -895,26 +1037,28 @@ pub async fn vault_propose_withdraw(
         "propose_withdraw:{}:{}:{}:{}",
         req.owner, req.amount, req.threshold, req.nonce
     );
-    if let Err(e) = verify_wallet_signature(&req.owner, message.as_bytes(), &req.signature) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        );
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::ProposeWithdraw,
+    )
+    .await
+    {
+        return rejection;
     }
-    match db::consume_nonce(&state.pool, &req.nonce, &req.owner).await {
-        Ok(true) => {}
-        Ok(false) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({ "error": "invalid or used nonce" })),
-            )
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            )
-        }
+    if let Err(rejection) =
+        ensure_operation_allowed(&state, &[&req.owner], Operation::Withdrawals).await
+    {
+        return rejection;
+    }
+    if let Err(rejection) = second_factor
+        .require(&state, &req.owner, SensitiveOp::ProposeWithdraw)
+        .await
+    {
+        return rejection;
     }
 
     let (threshold, signers) = if req.signers.is_empty() || req.threshold == 0 {
@@ -960,15 +1104,11 @@ pub async fn vault_propose_withdraw(
 
     let id = uuid::Uuid::new_v4().to_string();
     let signers_json = serde_json::json!(signers);
-    if let Err(e) = db::ms_create_proposal(
-        &state.pool,
-        &id,
-        &req.owner,
-        req.amount as i64,
-        threshold as i32,
-        &signers_json,
-    )
-    .await
+    if let Err(e) = state
+        .repos
+        .proposals
+        .create(&id, &req.owner, req.amount as i64, threshold as i32, &signers)
+        .await
     {
         return (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -976,13 +1116,22 @@ pub async fn vault_propose_withdraw(
         );
     }
     let _ = db::insert_audit_log(&state.pool, Some(&req.owner), "ms_propose_withdraw", serde_json::json!({ "proposal_id": id, "amount": req.amount, "threshold": req.threshold, "signers": signers_json })).await;
-    let _ = state.notifier.security_tx.send(serde_json::json!({ "kind": "ms_proposal", "proposal_id": id, "owner": req.owner, "amount": req.amount }).to_string());
-    // Notify signers via webhook if available
+    state.notifier.publish(
+        EventEnvelope::new(VaultEvent::security(
+            "ms_proposal",
+            Severity::Info,
+            serde_json::json!({ "proposal_id": id, "amount": req.amount }),
+        ))
+        .with_owner(&req.owner)
+        .with_correlation_id(format!("ms_proposal:{id}")),
+    );
+    // Notify signers via their webhook endpoints and email if available; both are queued and retried by their dispatchers
+    let payload_for = |pk: &str| serde_json::json!({ "action": "request_signature", "proposal_id": id, "owner": req.owner, "amount": req.amount, "signer": pk });
+    queue_signer_webhooks(&state, &signers, "ms_request_signature", payload_for).await;
     if let Ok(contacts) = db::ms_get_contacts_for(&state.pool, &signers).await {
-        for (pk, _email, webhook) in contacts.into_iter() {
-            if let Some(url) = webhook {
-                let payload = serde_json::json!({ "action": "request_signature", "proposal_id": id, "owner": req.owner, "amount": req.amount, "signer": pk });
-                let _ = send_webhook(&url, &payload).await;
+        for (pk, email) in contacts.into_iter() {
+            if email.is_some() {
+                let _ = email::queue(&state.pool, &state.cfg, &pk, EmailKind::MsRequestSignature, &payload_for(&pk), &format!("{id}:{pk}")).await;
             }
         }
     }
@@ -1006,29 +1155,20 @@ pub async fn vault_approve_withdraw(
 ) -> impl IntoResponse {
     // Verify signer signature on message: approve_withdraw:{proposal_id}:{nonce}
     let message = format!("approve_withdraw:{}:{}", req.proposal_id, req.nonce);
-    if let Err(e) = verify_wallet_signature(&req.signer, message.as_bytes(), &req.signature) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        );
-    }
-    match db::consume_nonce(&state.pool, &req.nonce, &req.signer).await {
-        Ok(true) => {}
-        Ok(false) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({ "error": "invalid or used nonce" })),
-            )
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            )
-        }
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.signer,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::ApproveWithdraw,
+    )
+    .await
+    {
+        return rejection;
     }
 
-    let prop = match db::ms_get_proposal(&state.pool, &req.proposal_id).await {
+    let prop = match state.repos.proposals.get(&req.proposal_id).await {
         Ok(Some(p)) => p,
         Ok(None) => {
             return (
@@ -1043,25 +1183,31 @@ pub async fn vault_approve_withdraw(
             )
         }
     };
-    let (owner, amount, threshold, signers_json, status) = prop;
-    if status != "pending" {
+    let (owner, amount, threshold) = (prop.owner, prop.amount, prop.threshold);
+    if let Err(rejection) =
+        ensure_operation_allowed(&state, &[&owner], Operation::Withdrawals).await
+    {
+        return rejection;
+    }
+    if prop.status != "pending" {
         return (
             StatusCode::BAD_REQUEST,
             Json(serde_json::json!({ "error": "proposal not pending" })),
         );
     }
     // signer must be in allowed list
-    let allowed: Vec<String> = match serde_json::from_value(signers_json.clone()) {
-        Ok(v) => v,
-        Err(_) => vec![],
-    };
-    if !allowed.iter().any(|s| s == &req.signer) {
+    if !prop.signers.iter().any(|s| s == &req.signer) {
         return (
             StatusCode::BAD_REQUEST,
             Json(serde_json::json!({ "error": "signer not allowed" })),
         );
     }
-    match db::ms_insert_approval(&state.pool, &req.proposal_id, &req.signer, &req.signature).await {
+    match state
+        .repos
+        .proposals
+        .add_approval(&req.proposal_id, &req.signer, &req.signature)
+        .await
+    {
         Ok(true) => {}
         Ok(false) => {
             return (
@@ -1076,7 +1222,7 @@ pub async fn vault_approve_withdraw(
             )
         }
     }
-    let count = match db::ms_count_approvals(&state.pool, &req.proposal_id).await {
+    let count = match state.repos.proposals.approval_count(&req.proposal_id).await {
         Ok(c) => c,
         Err(e) => {
             return (
@@ -1086,7 +1232,14 @@ pub async fn vault_approve_withdraw(
         }
     };
 
-    let _ = state.notifier.security_tx.send(serde_json::json!({ "kind": "ms_approval", "proposal_id": req.proposal_id, "signer": req.signer }).to_string());
+    state.notifier.publish(
+        EventEnvelope::new(VaultEvent::security(
+            "ms_approval",
+            Severity::Info,
+            serde_json::json!({ "proposal_id": req.proposal_id, "signer": req.signer }),
+        ))
+        .with_correlation_id(format!("ms_proposal:{}", req.proposal_id)),
+    );
 
     if count as i32 >= threshold {
         // Threshold reached. Build a partially signed transaction for co-signing by approvers.
@@ -1099,7 +1252,7 @@ pub async fn vault_approve_withdraw(
             Err(_) => Pubkey::default(),
         };
         // Collect current approvals as signer set
-        let approvals = match db::ms_list_approvals(&state.pool, &req.proposal_id).await {
+        let approvals = match state.repos.proposals.approvers(&req.proposal_id).await {
             Ok(v) => v,
             Err(_) => vec![],
         };
@@ -1136,17 +1289,22 @@ pub async fn vault_approve_withdraw(
             }
         };
         let tx_b64 = STANDARD.encode(&raw);
-        let _ = db::ms_set_status(&state.pool, &req.proposal_id, "approved").await;
+        let _ = state
+            .repos
+            .proposals
+            .set_status(&req.proposal_id, "approved")
+            .await;
 
-        // Notify signers (including authority and others) via webhook with the partial tx
+        // Notify signers (including authority and others) via webhook and email with the partial tx
         let signer_strings: Vec<String> = std::iter::once(req.signer.clone())
             .chain(approvals.into_iter().filter(|s| s != &req.signer))
             .collect();
+        let payload = serde_json::json!({ "action": "partial_tx_ready", "proposal_id": req.proposal_id, "owner": owner, "amount": amount, "tx_base64": tx_b64.clone(), "authority": req.signer });
+        queue_signer_webhooks(&state, &signer_strings, "ms_partial_tx_ready", |_| payload.clone()).await;
         if let Ok(contacts) = db::ms_get_contacts_for(&state.pool, &signer_strings).await {
-            for (_pk, _email, webhook) in contacts.into_iter() {
-                if let Some(url) = webhook {
-                    let payload = serde_json::json!({ "action": "partial_tx_ready", "proposal_id": req.proposal_id, "owner": owner, "amount": amount, "tx_base64": tx_b64.clone(), "authority": req.signer });
-                    let _ = send_webhook(&url, &payload).await;
+            for (pk, email) in contacts.into_iter() {
+                if email.is_some() {
+                    let _ = email::queue(&state.pool, &state.cfg, &pk, EmailKind::MsPartialTxReady, &payload, &format!("{}:{pk}", req.proposal_id)).await;
                 }
             }
         }
@@ -1168,7 +1326,7 @@ pub async fn vault_proposal_status(
     State(state): State<AppState>,
     Path(id): Path<String>,
 ) -> impl IntoResponse {
-    let prop = match db::ms_get_proposal(&state.pool, &id).await {
+    let prop = match state.repos.proposals.get(&id).await {
         Ok(Some(p)) => p,
         Ok(None) => {
             return (
@@ -1183,15 +1341,11 @@ pub async fn vault_proposal_status(
             )
         }
     };
-    let (_owner, _amount, threshold, _signers, status) = prop;
-    let approvals = match db::ms_count_approvals(&state.pool, &id).await {
-        Ok(c) => c,
-        Err(_) => 0,
-    };
+    let approvals = state.repos.proposals.approval_count(&id).await.unwrap_or(0);
     (
         StatusCode::OK,
         Json(
-            serde_json::json!({ "status": status, "approvals": approvals, "threshold": threshold }),
+            serde_json::json!({ "status": prop.status, "approvals": approvals, "threshold": prop.threshold }),
         ),
     )
 }
@@ -1199,19 +1353,6 @@ pub async fn vault_proposal_status(
 // ---------------
 // Webhook utility
 // ---------------
-async fn send_webhook(url: &str, payload: &serde_json::Value) -> Result<(), String> {
-    let client = reqwest::Client::new();
-    client
-        .post(url)
-        .json(payload)
-        .send()
-        .await
-        .map_err(|e| e.to_string())?
-        .error_for_status()
-        .map_err(|e| e.to_string())?;
-    Ok(())
-}
-
 // -----------------
 // Delegation routes
 // -----------------
@@ -1225,6 +1366,7 @@ pub struct DelegateAddRequest {
 
 pub async fn vault_delegate_add(
     State(state): State<AppState>,
+    second_factor: SecondFactor,
     Json(req): Json<DelegateAddRequest>,
 ) -> impl IntoResponse {
     if Pubkey::from_str(&req.owner).is_err() || Pubkey::from_str(&req.delegate).is_err() {
@@ -1235,26 +1377,23 @@ pub async fn vault_delegate_add(
     }
     // Require owner signature
     let message = format!("delegate_add:{}:{}:{}", req.owner, req.delegate, req.nonce);
-    if let Err(e) = verify_wallet_signature(&req.owner, message.as_bytes(), &req.signature) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        );
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::DelegateAdd,
+    )
+    .await
+    {
+        return rejection;
     }
-    match db::consume_nonce(&state.pool, &req.nonce, &req.owner).await {
-        Ok(true) => {}
-        Ok(false) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({ "error": "invalid or used nonce" })),
-            )
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            )
-        }
+    if let Err(rejection) = second_factor
+        .require(&state, &req.owner, SensitiveOp::DelegateAdd)
+        .await
+    {
+        return rejection;
     }
     match db::delegate_add(&state.pool, &req.owner, &req.delegate).await {
         Ok(true) => {
@@ -1301,26 +1440,17 @@ pub async fn vault_delegate_remove(
         "delegate_remove:{}:{}:{}",
         req.owner, req.delegate, req.nonce
     );
-    if let Err(e) = verify_wallet_signature(&req.owner, message.as_bytes(), &req.signature) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        );
-    }
-    match db::consume_nonce(&state.pool, &req.nonce, &req.owner).await {
-        Ok(true) => {}
-        Ok(false) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({ "error": "invalid or used nonce" })),
-            )
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            )
-        }
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::DelegateRemove,
+    )
+    .await
+    {
+        return rejection;
     }
     match db::delegate_remove(&state.pool, &req.owner, &req.delegate).await {
         Ok(true) => {
@@ -1347,20 +1477,45 @@ pub async fn vault_delegate_remove(
 #[derive(Deserialize)]
 pub struct NonceRequest {
     pub owner: String,
+    pub purpose: NoncePurpose,
 }
 
 #[derive(Serialize)]
 pub struct NonceResponse {
     pub nonce: String,
+    pub purpose: NoncePurpose,
+    pub expires_at: time::OffsetDateTime,
+    pub message_template: &'static str,
 }
 
 pub async fn issue_nonce(
     State(state): State<AppState>,
     Json(req): Json<NonceRequest>,
 ) -> impl IntoResponse {
+    if Pubkey::from_str(&req.owner).is_err() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "invalid owner" })),
+        )
+            .into_response();
+    }
     let nonce = uuid::Uuid::new_v4().to_string();
-    match db::insert_nonce(&state.pool, &nonce, &req.owner).await {
-        Ok(_) => Json(NonceResponse { nonce }).into_response(),
+    match db::insert_nonce(
+        &state.pool,
+        &nonce,
+        &req.owner,
+        req.purpose.as_str(),
+        state.cfg.nonce_ttl_seconds,
+    )
+    .await
+    {
+        Ok(expires_at) => Json(NonceResponse {
+            nonce,
+            purpose: req.purpose,
+            expires_at,
+            message_template: req.purpose.message_template(),
+        })
+        .into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({ "error": e.to_string() })),
@@ -1369,6 +1524,38 @@ pub async fn issue_nonce(
     }
 }
 
+/// Verifies `owner`'s wallet signature over `message` and consumes the nonce, which must have
+/// been issued to `owner` for `purpose`. Every purpose-bound signed request goes through here
+/// before rate limits and circuit-breaker checks, so a refused request cannot be replayed once
+/// they lift.
+pub(super) async fn verify_signed_request(
+    state: &AppState,
+    owner: &str,
+    message: &str,
+    nonce: &str,
+    signature: &str,
+    purpose: NoncePurpose,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    if let Err(e) = verify_wallet_signature(owner, message.as_bytes(), signature) {
+        let _ = db::auth_failure_insert(&state.pool, owner, "signature").await;
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ));
+    }
+    match db::consume_nonce(&state.pool, nonce, owner, purpose.as_str()).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "invalid or used nonce" })),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )),
+    }
+}
+
 #[derive(Deserialize)]
 pub struct AdminAddProgramRequest {
     pub program_id: String,
@@ -1437,8 +1624,11 @@ pub async fn admin_set_vault_token_account(
             Json(serde_json::json!({ "error": "invalid pubkey(s)" })),
         );
     }
-    if let Err(e) =
-        db::upsert_vault_token_account(&state.pool, &req.owner, &req.token_account).await
+    if let Err(e) = state
+        .repos
+        .vaults
+        .upsert_token_account(&req.owner, &req.token_account)
+        .await
     {
         return (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -1448,11 +1638,20 @@ pub async fn admin_set_vault_token_account(
     // backfill snapshot from chain
     if let Ok(pk) = Pubkey::from_str(&req.token_account) {
         if let Ok(bal) = crate::solana_client::get_token_balance(&state.sol, &pk).await {
-            let _ = db::update_vault_snapshot(&state.pool, &req.owner, bal as i64, 0, 0).await;
             let _ = state
-                .notifier
-                .vault_balance_tx
-                .send(serde_json::json!({ "owner": req.owner, "balance": bal }).to_string());
+                .repos
+                .vaults
+                .update_snapshot(&req.owner, bal as i64, 0, 0)
+                .await;
+            state.cache.invalidate_balance(&req.owner).await;
+            state.notifier.publish_for(
+                &req.owner,
+                VaultEvent::BalanceUpdate(BalanceUpdate {
+                    balance: bal,
+                    previous_balance: None,
+                    delta: None,
+                }),
+            );
         }
     }
     (StatusCode::OK, Json(serde_json::json!({ "ok": true })))
@@ -1471,26 +1670,21 @@ pub async fn pm_lock(
     Json(req): Json<PmLockRequest>,
 ) -> impl IntoResponse {
     let message = format!("pm_lock:{}:{}:{}", req.owner, req.amount, req.nonce);
-    if let Err(e) = verify_wallet_signature(&req.owner, message.as_bytes(), &req.signature) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        );
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::PmLock,
+    )
+    .await
+    {
+        return rejection;
     }
-    match db::consume_nonce(&state.pool, &req.nonce, &req.owner).await {
-        Ok(true) => {}
-        Ok(false) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({ "error": "invalid or used nonce" })),
-            )
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            )
-        }
+    if let Err(rejection) = ensure_operation_allowed(&state, &[&req.owner], Operation::Locks).await
+    {
+        return rejection;
     }
     let owner = match Pubkey::from_str(&req.owner) {
         Ok(p) => p,
@@ -1503,15 +1697,19 @@ pub async fn pm_lock(
             state.metrics.vault_operations.inc();
             state.metrics.transaction_submissions.inc();
             
-            let _ = db::insert_transaction_with_status(
-                &state.pool,
-                &req.owner,
-                &sig,
-                Some(req.amount as i64),
-                "lock",
-                "pending",
-            )
-            .await;
+            let _ = state
+                .repos
+                .txs
+                .insert(
+                    &req.owner,
+                    &sig,
+                    Some(req.amount as i64),
+                    "lock",
+                    "pending",
+                )
+                .await;
+            state.cache.invalidate_balance(&req.owner).await;
+            state.cache.invalidate_tvl().await;
             let _ = db::insert_audit_log(
                 &state.pool,
                 Some(&req.owner),
@@ -1519,9 +1717,13 @@ pub async fn pm_lock(
                 serde_json::json!({ "amount": req.amount, "signature": sig }),
             )
             .await;
-            let _ = state.notifier.lock_tx.send(
-                serde_json::json!({"owner": req.owner, "amount": req.amount, "signature": sig})
-                    .to_string(),
+            state.notifier.publish(
+                EventEnvelope::new(VaultEvent::Lock(TxEvent {
+                    amount: Some(req.amount),
+                    signature: sig.clone(),
+                }))
+                .with_owner(&req.owner)
+                .with_correlation_id(sig.clone()),
             );
             (StatusCode::OK, Json(serde_json::json!({"signature": sig})))
         }
@@ -1542,29 +1744,31 @@ pub struct PmUnlockRequest {
 
 pub async fn pm_unlock(
     State(state): State<AppState>,
+    second_factor: SecondFactor,
     Json(req): Json<PmUnlockRequest>,
 ) -> impl IntoResponse {
     let message = format!("pm_unlock:{}:{}:{}", req.owner, req.amount, req.nonce);
-    if let Err(e) = verify_wallet_signature(&req.owner, message.as_bytes(), &req.signature) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        );
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::PmUnlock,
+    )
+    .await
+    {
+        return rejection;
     }
-    match db::consume_nonce(&state.pool, &req.nonce, &req.owner).await {
-        Ok(true) => {}
-        Ok(false) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({ "error": "invalid or used nonce" })),
-            )
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            )
-        }
+    if let Err(rejection) = ensure_operation_allowed(&state, &[&req.owner], Operation::Locks).await
+    {
+        return rejection;
+    }
+    if let Err(rejection) = second_factor
+        .require(&state, &req.owner, SensitiveOp::PmUnlock)
+        .await
+    {
+        return rejection;
     }
     let owner = match Pubkey::from_str(&req.owner) {
         Ok(p) => p,
@@ -1577,15 +1781,19 @@ pub async fn pm_unlock(
             state.metrics.vault_operations.inc();
             state.metrics.transaction_submissions.inc();
             
-            let _ = db::insert_transaction_with_status(
-                &state.pool,
-                &req.owner,
-                &sig,
-                Some(req.amount as i64),
-                "unlock",
-                "pending",
-            )
-            .await;
+            let _ = state
+                .repos
+                .txs
+                .insert(
+                    &req.owner,
+                    &sig,
+                    Some(req.amount as i64),
+                    "unlock",
+                    "pending",
+                )
+                .await;
+            state.cache.invalidate_balance(&req.owner).await;
+            state.cache.invalidate_tvl().await;
             let _ = db::insert_audit_log(
                 &state.pool,
                 Some(&req.owner),
@@ -1593,9 +1801,13 @@ pub async fn pm_unlock(
                 serde_json::json!({ "amount": req.amount, "signature": sig }),
             )
             .await;
-            let _ = state.notifier.unlock_tx.send(
-                serde_json::json!({"owner": req.owner, "amount": req.amount, "signature": sig})
-                    .to_string(),
+            state.notifier.publish(
+                EventEnvelope::new(VaultEvent::Unlock(TxEvent {
+                    amount: Some(req.amount),
+                    signature: sig.clone(),
+                }))
+                .with_owner(&req.owner)
+                .with_correlation_id(sig.clone()),
             );
             (StatusCode::OK, Json(serde_json::json!({"signature": sig})))
         }
@@ -1626,26 +1838,21 @@ pub async fn vault_yield_deposit(
         "yield_deposit:{}:{}:{}:{}",
         req.owner, req.amount, req.yield_program, req.nonce
     );
-    if let Err(e) = verify_wallet_signature(&req.owner, message.as_bytes(), &req.signature) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        );
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::YieldDeposit,
+    )
+    .await
+    {
+        return rejection;
     }
-    match db::consume_nonce(&state.pool, &req.nonce, &req.owner).await {
-        Ok(true) => {}
-        Ok(false) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({ "error": "invalid or used nonce" })),
-            )
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            )
-        }
+    if let Err(rejection) = ensure_operation_allowed(&state, &[&req.owner], Operation::Yield).await
+    {
+        return rejection;
     }
     let program_id = match Pubkey::from_str(&state.cfg.program_id) {
         Ok(p) => p,
@@ -1694,32 +1901,34 @@ pub async fn vault_yield_deposit(
 
 pub async fn vault_yield_withdraw(
     State(state): State<AppState>,
+    second_factor: SecondFactor,
     Json(req): Json<YieldOpRequest>,
 ) -> impl IntoResponse {
     let message = format!(
         "yield_withdraw:{}:{}:{}:{}",
         req.owner, req.amount, req.yield_program, req.nonce
     );
-    if let Err(e) = verify_wallet_signature(&req.owner, message.as_bytes(), &req.signature) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        );
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::YieldWithdraw,
+    )
+    .await
+    {
+        return rejection;
     }
-    match db::consume_nonce(&state.pool, &req.nonce, &req.owner).await {
-        Ok(true) => {}
-        Ok(false) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({ "error": "invalid or used nonce" })),
-            )
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            )
-        }
+    if let Err(rejection) = ensure_operation_allowed(&state, &[&req.owner], Operation::Yield).await
+    {
+        return rejection;
+    }
+    if let Err(rejection) = second_factor
+        .require(&state, &req.owner, SensitiveOp::YieldWithdraw)
+        .await
+    {
+        return rejection;
     }
     let program_id = match Pubkey::from_str(&state.cfg.program_id) {
         Ok(p) => p,
@@ -1783,26 +1992,21 @@ pub async fn vault_compound_yield(
         "compound_yield:{}:{}:{}:{}",
         req.owner, req.compounded_amount, req.yield_program, req.nonce
     );
-    if let Err(e) = verify_wallet_signature(&req.owner, message.as_bytes(), &req.signature) {
-        return (
-            StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        );
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::CompoundYield,
+    )
+    .await
+    {
+        return rejection;
     }
-    match db::consume_nonce(&state.pool, &req.nonce, &req.owner).await {
-        Ok(true) => {}
-        Ok(false) => {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({ "error": "invalid or used nonce" })),
-            )
-        }
-        Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({ "error": e.to_string() })),
-            )
-        }
+    if let Err(rejection) = ensure_operation_allowed(&state, &[&req.owner], Operation::Yield).await
+    {
+        return rejection;
     }
     let program_id = match Pubkey::from_str(&state.cfg.program_id) {
         Ok(p) => p,
@@ -1880,6 +2084,15 @@ pub async fn internal_transfer_collateral(
         );
     }
 
+    if let Err(rejection) = ensure_operation_allowed(
+        &state,
+        &[&req.from_owner, &req.to_owner],
+        Operation::Transfers,
+    )
+    .await
+    {
+        return rejection;
+    }
     let program_id = match Pubkey::from_str(&state.cfg.program_id) {
         Ok(p) => p,
         Err(_) => {
@@ -1986,6 +2199,17 @@ pub async fn internal_transfer_collateral(
         }
     };
 
+    let _ = ledger::record_transfer(
+        &state.pool,
+        &sig.to_string(),
+        &req.from_owner,
+        &req.to_owner,
+        req.amount as i64,
+    )
+    .await;
+    state.cache.invalidate_balance(&req.from_owner).await;
+    state.cache.invalidate_balance(&req.to_owner).await;
+    state.cache.invalidate_tvl().await;
     let _ = db::insert_audit_log(&state.pool, None, "transfer_collateral", serde_json::json!({ "from_owner": req.from_owner, "to_owner": req.to_owner, "amount": req.amount, "signature": sig.to_string() })).await;
     (
         StatusCode::OK,
@@ -2373,7 +2597,14 @@ pub async fn admin_yield_program_add(
         serde_json::json!({ "yield_program": req.yield_program, "signature": signature }),
     )
     .await;
-    let _ = state.notifier.security_tx.send(serde_json::json!({ "kind": "yield_program_add", "program": req.yield_program, "signature": signature }).to_string());
+    state.notifier.publish(
+        EventEnvelope::new(VaultEvent::security(
+            "yield_program_add",
+            Severity::Warning,
+            serde_json::json!({ "program": req.yield_program, "signature": signature }),
+        ))
+        .with_correlation_id(signature.clone()),
+    );
     (
         StatusCode::OK,
         Json(serde_json::json!({ "signature": signature })),
@@ -2482,7 +2713,14 @@ pub async fn admin_yield_program_remove(
         serde_json::json!({ "yield_program": req.yield_program, "signature": signature }),
     )
     .await;
-    let _ = state.notifier.security_tx.send(serde_json::json!({ "kind": "yield_program_remove", "program": req.yield_program, "signature": signature }).to_string());
+    state.notifier.publish(
+        EventEnvelope::new(VaultEvent::security(
+            "yield_program_remove",
+            Severity::Warning,
+            serde_json::json!({ "program": req.yield_program, "signature": signature }),
+        ))
+        .with_correlation_id(signature.clone()),
+    );
     (
         StatusCode::OK,
         Json(serde_json::json!({ "signature": signature })),
@@ -2587,7 +2825,14 @@ pub async fn admin_risk_level_set(
         serde_json::json!({ "risk_level": req.risk_level, "signature": signature }),
     )
     .await;
-    let _ = state.notifier.security_tx.send(serde_json::json!({ "kind": "risk_level_set", "risk_level": req.risk_level, "signature": signature }).to_string());
+    state.notifier.publish(
+        EventEnvelope::new(VaultEvent::security(
+            "risk_level_set",
+            Severity::Warning,
+            serde_json::json!({ "risk_level": req.risk_level, "signature": signature }),
+        ))
+        .with_correlation_id(signature.clone()),
+    );
     (
         StatusCode::OK,
         Json(serde_json::json!({ "signature": signature })),
@@ -2600,7 +2845,8 @@ pub async fn admin_risk_level_set(
 #[derive(Deserialize)]
 pub struct TwoFASetupReq {
     pub owner: String,
-    pub secret: Option<String>,
+    pub nonce: String,
+    pub signature: String,
 }
 
 #[derive(Deserialize)]
@@ -2609,6 +2855,71 @@ pub struct TwoFAVerifyReq {
     pub code: String,
 }
 
+#[derive(Deserialize)]
+pub struct TwoFADisableReq {
+    pub owner: String,
+    pub nonce: String,
+    pub signature: String,
+    pub code: Option<String>,
+    pub recovery_code: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TwoFAResetCancelReq {
+    pub owner: String,
+    pub nonce: String,
+    pub signature: String,
+}
+
+#[derive(Deserialize)]
+pub struct TwoFAPolicyReq {
+    pub owner: String,
+    pub operations: Vec<SensitiveOp>,
+    pub nonce: String,
+    pub signature: String,
+}
+
+#[derive(Deserialize)]
+pub struct WebAuthnRegisterReq {
+    pub owner: String,
+    pub nonce: String,
+    pub signature: String,
+    /// base64url `clientDataJSON` from `navigator.credentials.create`
+    pub client_data_json: String,
+    /// base64url `attestationObject` from `navigator.credentials.create`
+    pub attestation_object: String,
+    pub label: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct WebAuthnRemoveReq {
+    pub owner: String,
+    pub credential_id: String,
+    pub nonce: String,
+    pub signature: String,
+}
+
+#[derive(Deserialize)]
+pub struct AdminTwoFAResetReq {
+    pub owner: String,
+    pub reason: Option<String>,
+}
+
+const TWOFA_RECOVERY_CODE_COUNT: usize = 10;
+
+/// Records a 2FA lifecycle step in the audit trail and broadcasts it as a security alert.
+async fn record_twofa_event(
+    state: &AppState,
+    owner: &str,
+    action: &str,
+    details: serde_json::Value,
+) {
+    let _ = db::insert_audit_log(&state.pool, Some(owner), action, details.clone()).await;
+    state
+        .notifier
+        .publish_for(owner, VaultEvent::security(action, Severity::Warning, details));
+}
+
 pub async fn twofa_setup(
     State(state): State<AppState>,
     Json(req): Json<TwoFASetupReq>,
@@ -2619,21 +2930,103 @@ pub async fn twofa_setup(
             Json(serde_json::json!({ "error": "invalid owner" })),
         );
     }
-    let secret = req.secret.unwrap_or_else(|| "".to_string());
-    if secret.is_empty() {
-        return (
-            StatusCode::BAD_REQUEST,
-            Json(serde_json::json!({ "error": "missing secret" })),
-        );
-    }
-    if let Err(e) = db::twofa_upsert(&state.pool, &req.owner, &secret, false).await {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": e.to_string() })),
-        );
+    let message = format!("twofa_setup:{}:{}", req.owner, req.nonce);
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::TwofaSetup,
+    )
+    .await
+    {
+        return rejection;
     }
-    (StatusCode::OK, Json(serde_json::json!({ "ok": true })))
-}
+    // An active second factor must be disabled before a new secret can be enrolled
+    match db::twofa_get(&state.pool, &req.owner).await {
+        Ok(Some((_, true))) => {
+            return (
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({ "error": "2fa already enabled" })),
+            )
+        }
+        Ok(_) => {}
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        }
+    }
+    let enrollment =
+        match crate::security::generate_totp_enrollment(&state.cfg.twofa_issuer, &req.owner) {
+            Ok(e) => e,
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({ "error": e.to_string() })),
+                )
+            }
+        };
+    let sealed = match crate::security::seal_secret(
+        &state.cfg.twofa_encryption_key,
+        &enrollment.secret_base32,
+    ) {
+        Ok(s) => s,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        }
+    };
+    if let Err(e) = db::twofa_upsert(&state.pool, &req.owner, &sealed, false).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        );
+    }
+    let recovery_codes = match issue_recovery_codes(&state, &req.owner).await {
+        Ok(codes) => codes,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        }
+    };
+    record_twofa_event(
+        &state,
+        &req.owner,
+        "twofa_setup",
+        serde_json::json!({
+            "issuer": state.cfg.twofa_issuer,
+            "recovery_codes": recovery_codes.len(),
+        }),
+    )
+    .await;
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "otpauth_url": enrollment.otpauth_url,
+            "secret": enrollment.secret_base32,
+            "recovery_codes": recovery_codes,
+        })),
+    )
+}
+
+/// Replaces the owner's recovery codes with a fresh set. The codes are shown once; only their
+/// hashes are stored.
+async fn issue_recovery_codes(state: &AppState, owner: &str) -> Result<Vec<String>, sqlx::Error> {
+    let recovery_codes = crate::security::generate_recovery_codes(TWOFA_RECOVERY_CODE_COUNT);
+    let hashes: Vec<String> = recovery_codes
+        .iter()
+        .map(|c| crate::security::hash_recovery_code(c))
+        .collect();
+    db::twofa_replace_recovery_codes(&state.pool, owner, &hashes).await?;
+    Ok(recovery_codes)
+}
 
 pub async fn twofa_verify(
     State(state): State<AppState>,
@@ -2654,7 +3047,7 @@ pub async fn twofa_verify(
             )
         }
     };
-    let (secret, _enabled) = match row {
+    let (secret, enabled) = match row {
         Some(r) => r,
         None => {
             return (
@@ -2663,108 +3056,180 @@ pub async fn twofa_verify(
             )
         }
     };
-    // Lightweight TOTP validation using totp-rs (base32 secret assumed)
-    match crate::security::verify_totp(&secret, &req.code) {
-        true => {
-            let _ = db::twofa_upsert(&state.pool, &req.owner, &secret, true).await;
+    match verify_twofa_code(&state, &req.owner, &secret, &req.code).await {
+        Ok(true) => {
+            let _ = db::twofa_set_enabled(&state.pool, &req.owner, true).await;
+            if !enabled {
+                record_twofa_event(&state, &req.owner, "twofa_enabled", serde_json::json!({}))
+                    .await;
+            }
             (StatusCode::OK, Json(serde_json::json!({ "ok": true })))
         }
-        false => (
+        Ok(false) => (
             StatusCode::UNAUTHORIZED,
             Json(serde_json::json!({ "error": "invalid code" })),
         ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
     }
 }
 
-// -----------------
-// Limits & analytics
-// -----------------
-pub async fn vault_limits(
+/// Disables 2FA after a wallet signature plus either a current TOTP code or an unused
+/// recovery code. Re-enrolling afterwards via `/2fa/setup` rotates the secret.
+pub async fn twofa_disable(
     State(state): State<AppState>,
-    Path(owner): Path<String>,
+    Json(req): Json<TwoFADisableReq>,
 ) -> impl IntoResponse {
-    // Derive current usage from recent transactions (last 24h) as a mirror
-    let since = time::OffsetDateTime::now_utc() - time::Duration::hours(24);
-    let rows = sqlx::query_as::<_, (i64, String, Option<i64>, String, time::OffsetDateTime)>(
-        "SELECT id, signature, amount, kind, created_at FROM transactions WHERE owner = $1 AND kind = 'withdraw' AND created_at >= $2"
+    if Pubkey::from_str(&req.owner).is_err() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "invalid owner" })),
+        );
+    }
+    let message = format!("twofa_disable:{}:{}", req.owner, req.nonce);
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::TwofaDisable,
     )
-    .bind(&owner)
-    .bind(since)
-    .fetch_all(&state.pool)
     .await
-    .unwrap_or_default();
-    let used: i64 = rows.iter().map(|(_, _, amt, _, _)| amt.unwrap_or(0)).sum();
-    (
-        StatusCode::OK,
-        Json(serde_json::json!({ "window_seconds": 86400, "used": used, "limit": null })),
-    )
-}
-
-#[derive(sqlx::FromRow)]
-struct AnalyticsTvlRow {
-    day: Option<time::OffsetDateTime>,
-    delta: Option<i64>,
-}
-
-#[derive(sqlx::FromRow)]
-struct VaultTotalRow {
-    total_balance: Option<i64>,
-}
-
-#[derive(sqlx::FromRow)]
-struct VaultUtilizationRow {
-    total_balance: Option<i64>,
-    locked_balance: Option<i64>,
-}
-
-pub async fn analytics_tvl_series(State(state): State<AppState>) -> impl IntoResponse {
-    // Aggregate transactions by day to build series
-    let rows = sqlx::query_as::<_, AnalyticsTvlRow>(
-        "SELECT date_trunc('day', created_at) AS day, SUM(CASE WHEN kind = 'deposit' THEN amount ELSE -amount END) AS delta FROM transactions GROUP BY 1 ORDER BY 1 ASC"
-    )
-    .fetch_all(&state.pool)
-    .await;
-    match rows {
-        Ok(rs) => {
-            let mut tvl = 0i64;
-            let series: Vec<_> = rs
-                .into_iter()
-                .map(|r| {
-                    let d = r.day.unwrap();
-                    let delta = r.delta.unwrap_or(0);
-                    tvl += delta;
-                    serde_json::json!({ "t": d, "tvl": tvl })
-                })
-                .collect();
-            (
-                StatusCode::OK,
-                Json(serde_json::json!({ "series": series })),
+    {
+        return rejection;
+    }
+    // Passkey-only owners have no TOTP secret but can disable with a recovery code
+    let secret = match db::twofa_get(&state.pool, &req.owner).await {
+        Ok(row) => row.map(|(secret, _)| secret),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
             )
         }
-        Err(e) => (
+    };
+    match db::twofa_any_factor(&state.pool, &req.owner).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "2fa not set up" })),
+            )
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        }
+    }
+    let (verified, method) = match (&req.code, &req.recovery_code) {
+        (Some(code), None) => (
+            match &secret {
+                Some(secret) => verify_twofa_code(&state, &req.owner, secret, code).await,
+                None => Ok(false),
+            },
+            "totp",
+        ),
+        (None, Some(recovery)) => (
+            db::twofa_consume_recovery_code(
+                &state.pool,
+                &req.owner,
+                &crate::security::hash_recovery_code(recovery),
+            )
+            .await
+            .map_err(Into::into),
+            "recovery_code",
+        ),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "provide exactly one of code or recovery_code" })),
+            )
+        }
+    };
+    match verified {
+        Ok(true) => {}
+        Ok(false) => {
+            record_twofa_event(
+                &state,
+                &req.owner,
+                "twofa_disable_failed",
+                serde_json::json!({ "method": method }),
+            )
+            .await;
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "error": "invalid code" })),
+            );
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        }
+    }
+    if let Err(e) = db::twofa_delete(&state.pool, &req.owner).await {
+        return (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({ "error": e.to_string() })),
-        ),
+        );
     }
+    // A self-service disable supersedes any pending admin reset
+    let _ = db::twofa_reset_cancel(&state.pool, &req.owner).await;
+    record_twofa_event(
+        &state,
+        &req.owner,
+        "twofa_disabled",
+        serde_json::json!({ "method": method }),
+    )
+    .await;
+    (StatusCode::OK, Json(serde_json::json!({ "ok": true })))
 }
 
-pub async fn analytics_distribution(State(state): State<AppState>) -> impl IntoResponse {
-    let rows = sqlx::query_as::<_, VaultTotalRow>("SELECT total_balance FROM vaults")
-        .fetch_all(&state.pool)
-        .await;
-    match rows {
-        Ok(rs) => {
-            let mut buckets = vec![0u64; 10];
-            for r in rs.into_iter() {
-                let v = r.total_balance.unwrap_or(0) as u64;
-                let idx = std::cmp::min(9, (v as f64).log10().floor().max(0.0) as usize);
-                buckets[idx] += 1;
-            }
-            (
-                StatusCode::OK,
-                Json(serde_json::json!({ "buckets": buckets })),
+/// Lets the wallet owner abort an admin-initiated reset during its cooling-off period.
+pub async fn twofa_reset_cancel(
+    State(state): State<AppState>,
+    Json(req): Json<TwoFAResetCancelReq>,
+) -> impl IntoResponse {
+    if Pubkey::from_str(&req.owner).is_err() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "invalid owner" })),
+        );
+    }
+    let message = format!("twofa_reset_cancel:{}:{}", req.owner, req.nonce);
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::TwofaResetCancel,
+    )
+    .await
+    {
+        return rejection;
+    }
+    match db::twofa_reset_cancel(&state.pool, &req.owner).await {
+        Ok(Some(id)) => {
+            record_twofa_event(
+                &state,
+                &req.owner,
+                "twofa_reset_cancelled",
+                serde_json::json!({ "reset_id": id }),
             )
+            .await;
+            (StatusCode::OK, Json(serde_json::json!({ "ok": true, "reset_id": id })))
         }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "no pending reset" })),
+        ),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({ "error": e.to_string() })),
@@ -2772,28 +3237,23 @@ pub async fn analytics_distribution(State(state): State<AppState>) -> impl IntoR
     }
 }
 
-pub async fn analytics_utilization(State(state): State<AppState>) -> impl IntoResponse {
-    let rows = sqlx::query_as::<_, VaultUtilizationRow>(
-        "SELECT total_balance, locked_balance FROM vaults",
-    )
-    .fetch_all(&state.pool)
-    .await;
-    match rows {
-        Ok(rs) => {
-            let mut total: i128 = 0;
-            let mut locked: i128 = 0;
-            for r in rs.into_iter() {
-                total += r.total_balance.unwrap_or(0) as i128;
-                locked += r.locked_balance.unwrap_or(0) as i128;
-            }
-            let utilization = if total > 0 {
-                (locked as f64) / (total as f64)
-            } else {
-                0.0
+pub async fn twofa_policy_get(
+    State(state): State<AppState>,
+    Path(owner): Path<String>,
+) -> impl IntoResponse {
+    match db::twofa_policy_get(&state.pool, &owner).await {
+        Ok(stored) => {
+            let operations: Vec<SensitiveOp> = match &stored {
+                Some(ops) => ops.iter().filter_map(|s| SensitiveOp::parse(s)).collect(),
+                None => SensitiveOp::ALL.to_vec(),
             };
             (
                 StatusCode::OK,
-                Json(serde_json::json!({ "utilization": utilization })),
+                Json(serde_json::json!({
+                    "owner": owner,
+                    "operations": operations,
+                    "is_default": stored.is_none(),
+                })),
             )
         }
         Err(e) => (
@@ -2803,37 +3263,135 @@ pub async fn analytics_utilization(State(state): State<AppState>) -> impl IntoRe
     }
 }
 
-// -----------------
-// Min-delay withdraw request (on-chain request flow)
-// -----------------
-#[derive(Deserialize)]
-pub struct RequestWithdrawReq {
-    pub owner: String,
-    pub amount: u64,
-    pub nonce: String,
-    pub signature: String,
-}
-
-pub async fn vault_request_withdraw(
+/// Replaces the owner's 2FA policy. The wallet signs
+/// `twofa_policy:{owner}:{operations joined by ','}:{nonce}`; when 2FA is enabled a valid
+/// `X-2FA-CODE` is also required so a stolen wallet key alone cannot relax the policy.
+pub async fn twofa_policy_set(
     State(state): State<AppState>,
-    Json(req): Json<RequestWithdrawReq>,
+    second_factor: SecondFactor,
+    Json(req): Json<TwoFAPolicyReq>,
 ) -> impl IntoResponse {
+    if Pubkey::from_str(&req.owner).is_err() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "invalid owner" })),
+        );
+    }
+    let mut operations: Vec<String> = req
+        .operations
+        .iter()
+        .map(|op| op.as_str().to_string())
+        .collect();
+    operations.sort();
+    operations.dedup();
     let message = format!(
-        "request_withdraw:{}:{}:{}",
-        req.owner, req.amount, req.nonce
+        "twofa_policy:{}:{}:{}",
+        req.owner,
+        req.operations
+            .iter()
+            .map(|op| op.as_str())
+            .collect::<Vec<_>>()
+            .join(","),
+        req.nonce
     );
-    if let Err(e) = verify_wallet_signature(&req.owner, message.as_bytes(), &req.signature) {
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::TwofaPolicy,
+    )
+    .await
+    {
+        return rejection;
+    }
+    if let Err(rejection) = second_factor.require_if_enabled(&state, &req.owner).await {
+        return rejection;
+    }
+    if let Err(e) = db::twofa_policy_set(&state.pool, &req.owner, &operations).await {
         return (
-            StatusCode::UNAUTHORIZED,
+            StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({ "error": e.to_string() })),
         );
     }
-    match db::consume_nonce(&state.pool, &req.nonce, &req.owner).await {
+    record_twofa_event(
+        &state,
+        &req.owner,
+        "twofa_policy_updated",
+        serde_json::json!({ "operations": operations }),
+    )
+    .await;
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({ "owner": req.owner, "operations": operations })),
+    )
+}
+
+/// Registers a passkey. The client requests a `webauthn_register` nonce, passes it (UTF-8) as
+/// the `navigator.credentials.create` challenge and signs `webauthn_register:{owner}:{nonce}`
+/// with its wallet. Owners that already have a factor must present it as well.
+pub async fn webauthn_register(
+    State(state): State<AppState>,
+    second_factor: SecondFactor,
+    Json(req): Json<WebAuthnRegisterReq>,
+) -> impl IntoResponse {
+    if Pubkey::from_str(&req.owner).is_err() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "invalid owner" })),
+        );
+    }
+    let message = format!("webauthn_register:{}:{}", req.owner, req.nonce);
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::WebauthnRegister,
+    )
+    .await
+    {
+        return rejection;
+    }
+    if let Err(rejection) = second_factor.require_if_enabled(&state, &req.owner).await {
+        return rejection;
+    }
+    let credential = match crate::webauthn::b64url_decode(&req.client_data_json).and_then(
+        |client_data| {
+            let attestation = crate::webauthn::b64url_decode(&req.attestation_object)?;
+            crate::webauthn::verify_registration(
+                &relying_party(&state),
+                &req.nonce,
+                &client_data,
+                &attestation,
+            )
+        },
+    ) {
+        Ok(c) => c,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        }
+    };
+    match db::webauthn_credential_insert(
+        &state.pool,
+        &req.owner,
+        &credential.credential_id,
+        &credential.public_key,
+        credential.sign_count as i64,
+        req.label.as_deref(),
+    )
+    .await
+    {
         Ok(true) => {}
         Ok(false) => {
             return (
-                StatusCode::BAD_REQUEST,
-                Json(serde_json::json!({ "error": "invalid or used nonce" })),
+                StatusCode::CONFLICT,
+                Json(serde_json::json!({ "error": "credential already registered" })),
             )
         }
         Err(e) => {
@@ -2843,6 +3401,396 @@ pub async fn vault_request_withdraw(
             )
         }
     }
+    // The first passkey of an owner without TOTP gets recovery codes, as TOTP enrollment does
+    let first_factor = match (
+        db::twofa_get(&state.pool, &req.owner).await,
+        db::webauthn_credential_count(&state.pool, &req.owner).await,
+    ) {
+        (Ok(totp), Ok(passkeys)) => totp.is_none() && passkeys == 1,
+        (Err(e), _) | (_, Err(e)) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        }
+    };
+    let recovery_codes = if first_factor {
+        match issue_recovery_codes(&state, &req.owner).await {
+            Ok(codes) => Some(codes),
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({ "error": e.to_string() })),
+                )
+            }
+        }
+    } else {
+        None
+    };
+    record_twofa_event(
+        &state,
+        &req.owner,
+        "webauthn_registered",
+        serde_json::json!({
+            "credential_id": credential.credential_id,
+            "label": req.label,
+            "recovery_codes": recovery_codes.as_ref().map(Vec::len),
+        }),
+    )
+    .await;
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "credential_id": credential.credential_id,
+            "recovery_codes": recovery_codes,
+        })),
+    )
+}
+
+pub async fn webauthn_credentials(
+    State(state): State<AppState>,
+    Path(owner): Path<String>,
+) -> impl IntoResponse {
+    match db::webauthn_credentials_list(&state.pool, &owner).await {
+        Ok(rows) => {
+            let credentials: Vec<_> = rows
+                .into_iter()
+                .map(|(credential_id, label, sign_count, created_at, last_used_at)| {
+                    serde_json::json!({
+                        "credential_id": credential_id,
+                        "label": label,
+                        "sign_count": sign_count,
+                        "created_at": created_at,
+                        "last_used_at": last_used_at,
+                    })
+                })
+                .collect();
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "owner": owner, "credentials": credentials })),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// Removes a passkey; signs `webauthn_remove:{owner}:{credential_id}:{nonce}` and requires a
+/// second factor (which may be the passkey being removed).
+pub async fn webauthn_remove(
+    State(state): State<AppState>,
+    second_factor: SecondFactor,
+    Json(req): Json<WebAuthnRemoveReq>,
+) -> impl IntoResponse {
+    let message = format!(
+        "webauthn_remove:{}:{}:{}",
+        req.owner, req.credential_id, req.nonce
+    );
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::WebauthnRemove,
+    )
+    .await
+    {
+        return rejection;
+    }
+    if let Err(rejection) = second_factor.require_if_enabled(&state, &req.owner).await {
+        return rejection;
+    }
+    match db::webauthn_credential_delete(&state.pool, &req.owner, &req.credential_id).await {
+        Ok(true) => {
+            record_twofa_event(
+                &state,
+                &req.owner,
+                "webauthn_removed",
+                serde_json::json!({ "credential_id": req.credential_id }),
+            )
+            .await;
+            (StatusCode::OK, Json(serde_json::json!({ "ok": true })))
+        }
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "credential not found" })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// Admin-assisted recovery for an owner who lost both authenticator and recovery codes.
+/// The reset only takes effect after `twofa_reset_cooldown_seconds`, giving the real owner
+/// time to see the alert and cancel it.
+pub async fn admin_twofa_reset(
+    State(state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Json(req): Json<AdminTwoFAResetReq>,
+) -> impl IntoResponse {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "admin secret not configured" })),
+        );
+    }
+    let claims = match verify_admin_jwt(auth.token(), &state.cfg.admin_jwt_secret) {
+        Ok(c) => c,
+        Err(_) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "error": "unauthorized" })),
+            )
+        }
+    };
+    if Pubkey::from_str(&req.owner).is_err() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "invalid owner" })),
+        );
+    }
+    match db::twofa_any_factor(&state.pool, &req.owner).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "2fa not set up" })),
+            )
+        }
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        }
+    }
+    let (id, effective_at, created) = match db::twofa_reset_request(
+        &state.pool,
+        &req.owner,
+        &claims.sub,
+        req.reason.as_deref(),
+        state.cfg.twofa_reset_cooldown_seconds,
+    )
+    .await
+    {
+        Ok(r) => r,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        }
+    };
+    if created {
+        record_twofa_event(
+            &state,
+            &req.owner,
+            "twofa_reset_requested",
+            serde_json::json!({
+                "reset_id": id,
+                "requested_by": claims.sub,
+                "reason": req.reason,
+                "effective_at": effective_at,
+            }),
+        )
+        .await;
+    }
+    (
+        StatusCode::ACCEPTED,
+        Json(serde_json::json!({
+            "reset_id": id,
+            "effective_at": effective_at,
+            "already_pending": !created,
+        })),
+    )
+}
+
+// -----------------
+// Limits & analytics
+// -----------------
+pub async fn vault_limits(
+    State(state): State<AppState>,
+    Path(owner): Path<String>,
+) -> impl IntoResponse {
+    // Derive current usage from recent transactions (last 24h) as a mirror
+    let since = time::OffsetDateTime::now_utc() - time::Duration::hours(24);
+    let used = state
+        .repos
+        .txs
+        .withdrawn_since(&owner, since)
+        .await
+        .unwrap_or(0);
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({ "window_seconds": 86400, "used": used, "limit": null })),
+    )
+}
+
+/// TVL per period from the balance snapshots. `granularity` is hourly, daily (default),
+/// weekly or monthly; `from`/`to` are RFC 3339.
+pub async fn analytics_tvl_series(
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<SeriesQuery>,
+) -> impl IntoResponse {
+    let granularity =
+        match SeriesGranularity::parse(params.granularity.as_deref().unwrap_or("daily")) {
+            Some(g) => g,
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({
+                        "error": "granularity must be hourly, daily, weekly or monthly"
+                    })),
+                )
+            }
+        };
+    let to = params.to.unwrap_or_else(time::OffsetDateTime::now_utc);
+    let from = params.from.unwrap_or(to - granularity.default_range());
+    if from > to {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "from must not be after to" })),
+        );
+    }
+    let rfc3339 = |t: time::OffsetDateTime| {
+        t.format(&time::format_description::well_known::Rfc3339)
+            .unwrap_or_default()
+    };
+    match db::tvl_series(
+        &state.pool,
+        granularity.source().as_str(),
+        granularity.unit(),
+        from,
+        to,
+        1000,
+    )
+    .await
+    {
+        Ok(points) => {
+            let series: Vec<_> = points
+                .into_iter()
+                .map(|p| {
+                    serde_json::json!({
+                        "t": rfc3339(p.period_start),
+                        "tvl": p.tvl.total(),
+                        "available": p.tvl.available,
+                        "locked": p.tvl.locked,
+                        "in_yield": p.tvl.in_yield,
+                    })
+                })
+                .collect();
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "granularity": granularity,
+                    "from": rfc3339(from),
+                    "to": rfc3339(to),
+                    "series": series,
+                })),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+pub async fn analytics_distribution(State(state): State<AppState>) -> impl IntoResponse {
+    match state.repos.vaults.list().await {
+        Ok(vaults) => {
+            let mut buckets = vec![0u64; 10];
+            for vault in vaults {
+                let v = vault.total_balance as u64;
+                let idx = std::cmp::min(9, (v as f64).log10().floor().max(0.0) as usize);
+                buckets[idx] += 1;
+            }
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "buckets": buckets })),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+pub async fn analytics_utilization(State(state): State<AppState>) -> impl IntoResponse {
+    match state.repos.vaults.list().await {
+        Ok(vaults) => {
+            let mut total: i128 = 0;
+            let mut locked: i128 = 0;
+            for vault in vaults {
+                total += vault.total_balance as i128;
+                locked += vault.locked_balance as i128;
+            }
+            let utilization = if total > 0 {
+                (locked as f64) / (total as f64)
+            } else {
+                0.0
+            };
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "utilization": utilization })),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+// -----------------
+// Min-delay withdraw request (on-chain request flow)
+// -----------------
+#[derive(Deserialize)]
+pub struct RequestWithdrawReq {
+    pub owner: String,
+    pub amount: u64,
+    pub nonce: String,
+    pub signature: String,
+}
+
+pub async fn vault_request_withdraw(
+    State(state): State<AppState>,
+    second_factor: SecondFactor,
+    Json(req): Json<RequestWithdrawReq>,
+) -> impl IntoResponse {
+    let message = format!(
+        "request_withdraw:{}:{}:{}",
+        req.owner, req.amount, req.nonce
+    );
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::RequestWithdraw,
+    )
+    .await
+    {
+        return rejection;
+    }
+    if let Err(rejection) =
+        ensure_operation_allowed(&state, &[&req.owner], Operation::Withdrawals).await
+    {
+        return rejection;
+    }
+    if let Err(rejection) = second_factor
+        .require(&state, &req.owner, SensitiveOp::RequestWithdraw)
+        .await
+    {
+        return rejection;
+    }
     let program_id = match Pubkey::from_str(&state.cfg.program_id) {
         Ok(p) => p,
         Err(_) => {
@@ -2879,3 +3827,1226 @@ pub async fn vault_request_withdraw(
         Json(serde_json::json!({ "instruction": payload })),
     )
 }
+
+// -----------------
+// Webhooks
+// -----------------
+#[derive(Deserialize)]
+pub struct WebhookRegisterReq {
+    pub owner: String,
+    pub url: String,
+    #[serde(default)]
+    pub event_types: Vec<String>,
+    pub nonce: String,
+    pub signature: String,
+}
+
+#[derive(Deserialize)]
+pub struct WebhookRemoveReq {
+    pub owner: String,
+    pub id: i64,
+    pub nonce: String,
+    pub signature: String,
+}
+
+#[derive(Deserialize)]
+pub struct AdminWebhookRegisterReq {
+    pub url: String,
+    #[serde(default)]
+    pub event_types: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AdminDeadLetterQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct AdminDeadLetterReplayReq {
+    pub ids: Vec<i64>,
+}
+
+/// Generates and seals a signing secret, then stores the endpoint. Returns (id, secret).
+/// Queues a signed delivery of `payload_for(signer)` to every active endpoint a signer
+/// registered for `event_type`. Signers without an endpoint are skipped.
+async fn queue_signer_webhooks(
+    state: &AppState,
+    signers: &[String],
+    event_type: &str,
+    payload_for: impl Fn(&str) -> serde_json::Value,
+) {
+    let Ok(endpoints) = db::webhook_endpoints_for_owners(&state.pool, signers, event_type).await
+    else {
+        return;
+    };
+    for (endpoint_id, signer, url) in endpoints {
+        let _ = db::webhook_delivery_enqueue(
+            &state.pool,
+            endpoint_id,
+            &url,
+            Some(uuid::Uuid::new_v4()),
+            event_type,
+            &payload_for(&signer),
+        )
+        .await;
+    }
+}
+
+async fn create_webhook_endpoint(
+    state: &AppState,
+    owner: Option<&str>,
+    url: &str,
+    event_types: &[String],
+    created_by: &str,
+) -> Result<(i64, String), (StatusCode, Json<serde_json::Value>)> {
+    if let Err(e) = crate::webhooks::validate_endpoint(url, event_types, owner.is_some()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": e })),
+        ));
+    }
+    let internal = |e: String| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e })),
+        )
+    };
+    let secret = crate::webhooks::generate_secret();
+    let sealed = crate::security::seal_secret(&state.cfg.twofa_encryption_key, &secret)
+        .map_err(|e| internal(e.to_string()))?;
+    let id = db::webhook_endpoint_insert(&state.pool, owner, url, &sealed, event_types, created_by)
+        .await
+        .map_err(|e| internal(e.to_string()))?;
+    Ok((id, secret))
+}
+
+/// Registers an endpoint for the owner's own events; signs
+/// `webhook_register:{owner}:{url}:{nonce}`. The signing secret is only returned here.
+pub async fn webhook_register(
+    State(state): State<AppState>,
+    Json(req): Json<WebhookRegisterReq>,
+) -> impl IntoResponse {
+    let message = format!("webhook_register:{}:{}:{}", req.owner, req.url, req.nonce);
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::WebhookRegister,
+    )
+    .await
+    {
+        return rejection;
+    }
+    let (id, secret) = match create_webhook_endpoint(
+        &state,
+        Some(&req.owner),
+        &req.url,
+        &req.event_types,
+        &req.owner,
+    )
+    .await
+    {
+        Ok(r) => r,
+        Err(rejection) => return rejection,
+    };
+    let _ = db::insert_audit_log(
+        &state.pool,
+        Some(&req.owner),
+        "webhook_registered",
+        serde_json::json!({ "id": id, "url": req.url, "event_types": req.event_types }),
+    )
+    .await;
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({ "id": id, "secret": secret })),
+    )
+}
+
+pub async fn webhooks_list(
+    State(state): State<AppState>,
+    Path(owner): Path<String>,
+) -> impl IntoResponse {
+    match db::webhook_endpoints_for_owner(&state.pool, &owner).await {
+        Ok(rows) => {
+            let endpoints: Vec<_> = rows
+                .into_iter()
+                .map(|(id, url, event_types, active, created_at)| {
+                    serde_json::json!({
+                        "id": id,
+                        "url": url,
+                        "event_types": event_types,
+                        "active": active,
+                        "created_at": created_at,
+                    })
+                })
+                .collect();
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "owner": owner, "endpoints": endpoints })),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// Deletes an endpoint and its pending deliveries; signs `webhook_remove:{owner}:{id}:{nonce}`.
+pub async fn webhook_remove(
+    State(state): State<AppState>,
+    Json(req): Json<WebhookRemoveReq>,
+) -> impl IntoResponse {
+    let message = format!("webhook_remove:{}:{}:{}", req.owner, req.id, req.nonce);
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::WebhookRemove,
+    )
+    .await
+    {
+        return rejection;
+    }
+    match db::webhook_endpoint_delete(&state.pool, &req.owner, req.id).await {
+        Ok(true) => {
+            let _ = db::insert_audit_log(
+                &state.pool,
+                Some(&req.owner),
+                "webhook_removed",
+                serde_json::json!({ "id": req.id }),
+            )
+            .await;
+            (StatusCode::OK, Json(serde_json::json!({ "ok": true })))
+        }
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "webhook not found" })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// Registers an integrator endpoint that receives events for every owner.
+pub async fn admin_webhook_register(
+    State(state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Json(req): Json<AdminWebhookRegisterReq>,
+) -> impl IntoResponse {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "admin secret not configured" })),
+        );
+    }
+    let claims = match verify_admin_jwt(auth.token(), &state.cfg.admin_jwt_secret) {
+        Ok(c) => c,
+        Err(_) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "error": "unauthorized" })),
+            )
+        }
+    };
+    let (id, secret) =
+        match create_webhook_endpoint(&state, None, &req.url, &req.event_types, &claims.sub).await
+        {
+            Ok(r) => r,
+            Err(rejection) => return rejection,
+        };
+    let _ = db::insert_audit_log(
+        &state.pool,
+        None,
+        "admin_webhook_registered",
+        serde_json::json!({ "id": id, "url": req.url, "event_types": req.event_types, "admin": claims.sub }),
+    )
+    .await;
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({ "id": id, "secret": secret })),
+    )
+}
+
+pub async fn admin_webhook_dead_letters(
+    State(state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    axum::extract::Query(params): axum::extract::Query<AdminDeadLetterQuery>,
+) -> impl IntoResponse {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "admin secret not configured" })),
+        );
+    }
+    if verify_admin_jwt(auth.token(), &state.cfg.admin_jwt_secret).is_err() {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({ "error": "unauthorized" })),
+        );
+    }
+    let limit = params.limit.unwrap_or(100).clamp(1, 500);
+    match db::webhook_dead_letters_list(&state.pool, limit).await {
+        Ok(rows) => {
+            let items: Vec<_> = rows
+                .into_iter()
+                .map(
+                    |(id, delivery_id, endpoint_id, url, event_type, attempts, last_error, created_at)| {
+                        serde_json::json!({
+                            "id": id,
+                            "delivery_id": delivery_id,
+                            "endpoint_id": endpoint_id,
+                            "url": url,
+                            "event_type": event_type,
+                            "attempts": attempts,
+                            "last_error": last_error,
+                            "created_at": created_at,
+                        })
+                    },
+                )
+                .collect();
+            (StatusCode::OK, Json(serde_json::json!({ "items": items })))
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// Re-queues dead-lettered deliveries with a fresh attempt budget.
+pub async fn admin_webhook_replay(
+    State(state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Json(req): Json<AdminDeadLetterReplayReq>,
+) -> impl IntoResponse {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "admin secret not configured" })),
+        );
+    }
+    let claims = match verify_admin_jwt(auth.token(), &state.cfg.admin_jwt_secret) {
+        Ok(c) => c,
+        Err(_) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "error": "unauthorized" })),
+            )
+        }
+    };
+    let mut replayed = Vec::new();
+    let mut skipped = Vec::new();
+    for id in req.ids {
+        match db::webhook_dead_letter_replay(&state.pool, id).await {
+            Ok(true) => replayed.push(id),
+            Ok(false) => skipped.push(id),
+            Err(e) => {
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(serde_json::json!({ "error": e.to_string(), "replayed": replayed })),
+                )
+            }
+        }
+    }
+    let _ = db::insert_audit_log(
+        &state.pool,
+        None,
+        "admin_webhook_replay",
+        serde_json::json!({ "replayed": replayed, "admin": claims.sub }),
+    )
+    .await;
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({ "replayed": replayed, "skipped": skipped })),
+    )
+}
+
+// -----------------
+// Email notifications
+// -----------------
+#[derive(Deserialize)]
+pub struct NotificationContactReq {
+    pub owner: String,
+    /// `None` removes the address.
+    pub email: Option<String>,
+    pub nonce: String,
+    pub signature: String,
+}
+
+#[derive(Deserialize)]
+pub struct NotificationPreferencesReq {
+    pub owner: String,
+    /// Email kinds the owner does not want, e.g. `["timelock_due_soon"]`.
+    #[serde(default)]
+    pub disabled: Vec<String>,
+    pub nonce: String,
+    pub signature: String,
+}
+
+#[derive(Deserialize)]
+pub struct UnsubscribeQuery {
+    pub token: String,
+}
+
+/// Sets or clears the owner's notification email; signs
+/// `notification_contact:{owner}:{email}:{nonce}` (empty `email` when clearing).
+pub async fn notification_contact(
+    State(state): State<AppState>,
+    Json(req): Json<NotificationContactReq>,
+) -> impl IntoResponse {
+    let email = req.email.as_deref().map(str::trim).filter(|e| !e.is_empty());
+    if let Some(email) = email {
+        if !email::is_valid_address(email) {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "invalid email" })),
+            );
+        }
+    }
+    let message = format!(
+        "notification_contact:{}:{}:{}",
+        req.owner,
+        email.unwrap_or_default(),
+        req.nonce
+    );
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::NotificationContact,
+    )
+    .await
+    {
+        return rejection;
+    }
+    let token = email::generate_unsubscribe_token();
+    if let Err(e) = db::notification_contact_set_email(&state.pool, &req.owner, email, &token).await
+    {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        );
+    }
+    let _ = db::insert_audit_log(
+        &state.pool,
+        Some(&req.owner),
+        "notification_contact_updated",
+        serde_json::json!({ "email_set": email.is_some() }),
+    )
+    .await;
+    (StatusCode::OK, Json(serde_json::json!({ "ok": true })))
+}
+
+/// Replaces the owner's disabled email kinds; signs
+/// `notification_preferences:{owner}:{comma-separated disabled kinds}:{nonce}`.
+pub async fn notification_preferences(
+    State(state): State<AppState>,
+    Json(req): Json<NotificationPreferencesReq>,
+) -> impl IntoResponse {
+    if let Some(unknown) = req.disabled.iter().find(|k| EmailKind::parse(k).is_none()) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": format!("unknown notification kind: {unknown}") })),
+        );
+    }
+    let message = format!(
+        "notification_preferences:{}:{}:{}",
+        req.owner,
+        req.disabled.join(","),
+        req.nonce
+    );
+    if let Err(rejection) = verify_signed_request(
+        &state,
+        &req.owner,
+        &message,
+        &req.nonce,
+        &req.signature,
+        NoncePurpose::NotificationPreferences,
+    )
+    .await
+    {
+        return rejection;
+    }
+    match db::notification_contact_set_disabled(&state.pool, &req.owner, &req.disabled).await {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "disabled": req.disabled })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// Notification settings with the address masked, since this read endpoint is public.
+pub async fn notification_settings(
+    State(state): State<AppState>,
+    Path(owner): Path<String>,
+) -> impl IntoResponse {
+    match db::notification_contact_get(&state.pool, &owner).await {
+        Ok(contact) => {
+            let (email, disabled, unsubscribed, _) =
+                contact.unwrap_or((None, Vec::new(), false, None));
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "email": email.as_deref().map(email::mask_address),
+                    "disabled": disabled,
+                    "unsubscribed": unsubscribed,
+                    "kinds": EmailKind::ALL.iter().map(EmailKind::as_str).collect::<Vec<_>>(),
+                })),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// Target of the link in every email. Stops all email until a new address is set.
+pub async fn notification_unsubscribe(
+    State(state): State<AppState>,
+    axum::extract::Query(params): axum::extract::Query<UnsubscribeQuery>,
+) -> impl IntoResponse {
+    match db::notification_unsubscribe(&state.pool, &params.token).await {
+        Ok(true) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "unsubscribed": true })),
+        ),
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "unknown token" })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+// -----------------
+// Alerts
+// -----------------
+#[derive(Deserialize)]
+pub struct AdminAlertsQuery {
+    /// `open` (default), `resolved` or `all`.
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
+
+pub async fn admin_alerts_list(
+    State(state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    axum::extract::Query(params): axum::extract::Query<AdminAlertsQuery>,
+) -> impl IntoResponse {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "admin secret not configured" })),
+        );
+    }
+    if verify_admin_jwt(auth.token(), &state.cfg.admin_jwt_secret).is_err() {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({ "error": "unauthorized" })),
+        );
+    }
+    let status = match params.status.as_deref().unwrap_or("open") {
+        "all" => None,
+        s @ ("open" | "resolved") => Some(s),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": "status must be open, resolved or all" })),
+            )
+        }
+    };
+    let limit = params.limit.unwrap_or(100).clamp(1, 500);
+    match db::alerts_list(&state.pool, status, limit).await {
+        Ok(rows) => {
+            let items: Vec<_> = rows
+                .into_iter()
+                .map(
+                    |(
+                        id,
+                        kind,
+                        owner,
+                        severity,
+                        details,
+                        status,
+                        notify_count,
+                        opened_at,
+                        last_seen_at,
+                        acknowledged_by,
+                        acknowledged_at,
+                        resolved_at,
+                    )| {
+                        serde_json::json!({
+                            "id": id,
+                            "kind": kind,
+                            "owner": owner,
+                            "severity": severity,
+                            "details": details,
+                            "status": status,
+                            "notify_count": notify_count,
+                            "opened_at": opened_at,
+                            "last_seen_at": last_seen_at,
+                            "acknowledged_by": acknowledged_by,
+                            "acknowledged_at": acknowledged_at,
+                            "resolved_at": resolved_at,
+                        })
+                    },
+                )
+                .collect();
+            (StatusCode::OK, Json(serde_json::json!({ "items": items })))
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// Acknowledges an open alert, which stops its re-notifications until it resolves.
+pub async fn admin_alert_ack(
+    State(state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "admin secret not configured" })),
+        );
+    }
+    let claims = match verify_admin_jwt(auth.token(), &state.cfg.admin_jwt_secret) {
+        Ok(c) => c,
+        Err(_) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "error": "unauthorized" })),
+            )
+        }
+    };
+    match db::alert_acknowledge(&state.pool, id, &claims.sub).await {
+        Ok(true) => {
+            let _ = db::insert_audit_log(
+                &state.pool,
+                None,
+                "admin_alert_acknowledged",
+                serde_json::json!({ "id": id, "admin": claims.sub }),
+            )
+            .await;
+            (StatusCode::OK, Json(serde_json::json!({ "acknowledged": id })))
+        }
+        Ok(false) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "no open unacknowledged alert with this id" })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+// -----------------
+// Anomaly rules
+// -----------------
+/// Rules with their active (rule, owner) states.
+pub async fn admin_anomaly_rules_list(
+    State(state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> impl IntoResponse {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "admin secret not configured" })),
+        );
+    }
+    if verify_admin_jwt(auth.token(), &state.cfg.admin_jwt_secret).is_err() {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({ "error": "unauthorized" })),
+        );
+    }
+    let rules = match db::anomaly_rules_list(&state.pool).await {
+        Ok(rows) => rows
+            .into_iter()
+            .filter_map(|row| anomaly::Rule::from_row(row).ok())
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        }
+    };
+    match db::anomaly_states_active(&state.pool).await {
+        Ok(rows) => {
+            let active: Vec<_> = rows
+                .into_iter()
+                .map(|(rule_id, owner, trigger_count, details, last_triggered_at)| {
+                    serde_json::json!({
+                        "rule": rule_id,
+                        "owner": owner,
+                        "trigger_count": trigger_count,
+                        "details": details,
+                        "last_triggered_at": last_triggered_at,
+                    })
+                })
+                .collect();
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "rules": rules, "active": active })),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// Creates or replaces a rule; takes effect on the next monitor cycle. A rule also set in
+/// `ANOMALY_RULES` is overwritten again on restart.
+pub async fn admin_anomaly_rule_upsert(
+    State(state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Json(rule): Json<anomaly::Rule>,
+) -> impl IntoResponse {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "admin secret not configured" })),
+        );
+    }
+    let claims = match verify_admin_jwt(auth.token(), &state.cfg.admin_jwt_secret) {
+        Ok(c) => c,
+        Err(_) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "error": "unauthorized" })),
+            )
+        }
+    };
+    if let Err(e) = rule.validate() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": e })),
+        );
+    }
+    if let Err(e) = db::anomaly_rule_upsert(&state.pool, &rule.to_row(), true).await {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        );
+    }
+    let _ = db::insert_audit_log(
+        &state.pool,
+        None,
+        "anomaly_rule_updated",
+        serde_json::json!({ "rule": rule, "admin": claims.sub }),
+    )
+    .await;
+    (StatusCode::OK, Json(serde_json::json!({ "rule": rule })))
+}
+
+// -----------------
+// Circuit breaker
+// -----------------
+/// Refuses the request when `operation` is switched off (503) or any of `owners`' vaults is
+/// frozen (423). Called before building or submitting a transaction.
+async fn ensure_operation_allowed(
+    state: &AppState,
+    owners: &[&str],
+    operation: Operation,
+) -> Result<(), (StatusCode, Json<serde_json::Value>)> {
+    match circuit_breaker::check(&state.pool, owners, operation).await {
+        Ok(None) => Ok(()),
+        Ok(Some(blocked)) => {
+            let status = match blocked {
+                Blocked::VaultFrozen { .. } => StatusCode::LOCKED,
+                Blocked::OperationDisabled { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            };
+            Err((
+                status,
+                Json(serde_json::json!({
+                    "error": blocked.message(),
+                    "reason": blocked.reason(),
+                    "operation": operation.as_str(),
+                })),
+            ))
+        }
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        )),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AdminFreezeReq {
+    pub owner: String,
+    pub reason: String,
+}
+
+#[derive(Deserialize)]
+pub struct AdminUnfreezeReq {
+    pub owner: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AdminOperationSwitchReq {
+    pub operation: Operation,
+    pub enabled: bool,
+    /// Required when disabling.
+    pub reason: Option<String>,
+}
+
+/// Kill switch state for every operation and the currently frozen vaults.
+pub async fn admin_circuit_breaker_status(
+    State(state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> impl IntoResponse {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "admin secret not configured" })),
+        );
+    }
+    if verify_admin_jwt(auth.token(), &state.cfg.admin_jwt_secret).is_err() {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({ "error": "unauthorized" })),
+        );
+    }
+    let switches = match db::operation_switches_list(&state.pool).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        }
+    };
+    // Operations without a row have never been switched off
+    let operations: Vec<_> = Operation::ALL
+        .iter()
+        .map(|op| match switches.iter().find(|s| s.0 == op.as_str()) {
+            Some((_, enabled, reason, updated_by, updated_at)) => serde_json::json!({
+                "operation": op.as_str(),
+                "enabled": enabled,
+                "reason": reason,
+                "updated_by": updated_by,
+                "updated_at": updated_at,
+            }),
+            None => serde_json::json!({ "operation": op.as_str(), "enabled": true }),
+        })
+        .collect();
+    match db::vaults_frozen_list(&state.pool).await {
+        Ok(rows) => {
+            let frozen: Vec<_> = rows
+                .into_iter()
+                .map(|(owner, reason, frozen_by, frozen_at)| {
+                    serde_json::json!({
+                        "owner": owner,
+                        "reason": reason,
+                        "frozen_by": frozen_by,
+                        "frozen_at": frozen_at,
+                    })
+                })
+                .collect();
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({ "operations": operations, "frozen_vaults": frozen })),
+            )
+        }
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+pub async fn admin_vault_freeze(
+    State(state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Json(req): Json<AdminFreezeReq>,
+) -> impl IntoResponse {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "admin secret not configured" })),
+        );
+    }
+    let claims = match verify_admin_jwt(auth.token(), &state.cfg.admin_jwt_secret) {
+        Ok(c) => c,
+        Err(_) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "error": "unauthorized" })),
+            )
+        }
+    };
+    let reason = req.reason.trim();
+    if reason.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "reason is required" })),
+        );
+    }
+    match circuit_breaker::freeze(
+        &state.pool,
+        &state.notifier,
+        &req.owner,
+        reason,
+        &claims.sub,
+    )
+    .await
+    {
+        Ok(true) => (StatusCode::OK, Json(serde_json::json!({ "frozen": true }))),
+        Ok(false) => (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({ "error": "vault not found or already frozen" })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+pub async fn admin_vault_unfreeze(
+    State(state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Json(req): Json<AdminUnfreezeReq>,
+) -> impl IntoResponse {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "admin secret not configured" })),
+        );
+    }
+    let claims = match verify_admin_jwt(auth.token(), &state.cfg.admin_jwt_secret) {
+        Ok(c) => c,
+        Err(_) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "error": "unauthorized" })),
+            )
+        }
+    };
+    match circuit_breaker::unfreeze(
+        &state.pool,
+        &state.notifier,
+        &req.owner,
+        req.reason.as_deref(),
+        &claims.sub,
+    )
+    .await
+    {
+        Ok(true) => (StatusCode::OK, Json(serde_json::json!({ "frozen": false }))),
+        Ok(false) => (
+            StatusCode::CONFLICT,
+            Json(serde_json::json!({ "error": "vault is not frozen" })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+/// Switches an operation type on or off for every vault.
+pub async fn admin_operation_switch_set(
+    State(state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Json(req): Json<AdminOperationSwitchReq>,
+) -> impl IntoResponse {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "admin secret not configured" })),
+        );
+    }
+    let claims = match verify_admin_jwt(auth.token(), &state.cfg.admin_jwt_secret) {
+        Ok(c) => c,
+        Err(_) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "error": "unauthorized" })),
+            )
+        }
+    };
+    let reason = req
+        .reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|r| !r.is_empty());
+    if !req.enabled && reason.is_none() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "reason is required" })),
+        );
+    }
+    match circuit_breaker::set_operation(
+        &state.pool,
+        &state.notifier,
+        req.operation,
+        req.enabled,
+        reason,
+        &claims.sub,
+    )
+    .await
+    {
+        Ok(changed) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "operation": req.operation.as_str(),
+                "enabled": req.enabled,
+                "changed": changed,
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+// -----------------
+// Ledger
+// -----------------
+#[derive(Deserialize)]
+pub struct LedgerQuery {
+    pub limit: Option<i64>,
+}
+
+/// Balances derived from the ledger and `owner`'s most recent entries, newest first.
+pub async fn vault_ledger(
+    State(state): State<AppState>,
+    Path(owner): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<LedgerQuery>,
+) -> impl IntoResponse {
+    let limit = params.limit.unwrap_or(50).clamp(1, 200);
+    let balances = match ledger::balances(&state.pool, &owner).await {
+        Ok(b) => b,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        }
+    };
+    let postings = match db::ledger_postings_for(&state.pool, &owner, limit).await {
+        Ok(p) => p,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+        }
+    };
+    let mut entries: Vec<serde_json::Value> = Vec::new();
+    let mut current: Option<i64> = None;
+    for p in postings {
+        if current != Some(p.entry_id) {
+            current = Some(p.entry_id);
+            entries.push(serde_json::json!({
+                "id": p.entry_id,
+                "signature": p.signature,
+                "kind": p.kind,
+                "created_at": p.created_at,
+                "postings": [],
+            }));
+        }
+        if let Some(list) = entries
+            .last_mut()
+            .and_then(|e| e["postings"].as_array_mut())
+        {
+            list.push(serde_json::json!({ "account": p.account, "amount": p.amount }));
+        }
+    }
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({
+            "owner": owner,
+            "balances": balances,
+            "custody": balances.custody(),
+            "entries": entries,
+        })),
+    )
+}
+
+/// Runs the ledger checker now and returns the violations it found.
+pub async fn admin_ledger_check(
+    State(state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+) -> impl IntoResponse {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "admin secret not configured" })),
+        );
+    }
+    if verify_admin_jwt(auth.token(), &state.cfg.admin_jwt_secret).is_err() {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({ "error": "unauthorized" })),
+        );
+    }
+    match crate::tasks::ledger::check_once(&state).await {
+        Ok(violations) => (
+            StatusCode::OK,
+            Json(serde_json::json!({
+                "ok": violations.is_empty(),
+                "violations": violations,
+            })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+// -----------------
+// Reconciliation
+// -----------------
+#[derive(Deserialize)]
+pub struct AdminReconciliationQuery {
+    /// `healed`, `escalated`, `resolved` or `all` (default).
+    pub status: Option<String>,
+    pub owner: Option<String>,
+    pub limit: Option<i64>,
+}
+
+pub async fn admin_reconciliation_cases(
+    State(state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    axum::extract::Query(params): axum::extract::Query<AdminReconciliationQuery>,
+) -> impl IntoResponse {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "admin secret not configured" })),
+        );
+    }
+    if verify_admin_jwt(auth.token(), &state.cfg.admin_jwt_secret).is_err() {
+        return (
+            StatusCode::UNAUTHORIZED,
+            Json(serde_json::json!({ "error": "unauthorized" })),
+        );
+    }
+    let status = match params.status.as_deref().unwrap_or("all") {
+        "all" => None,
+        s => match CaseStatus::parse(s) {
+            Some(status) => Some(status.as_str()),
+            None => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(serde_json::json!({
+                        "error": "status must be healed, escalated, resolved or all"
+                    })),
+                )
+            }
+        },
+    };
+    let limit = params.limit.unwrap_or(100).clamp(1, 500);
+    match db::reconciliation_cases_list(&state.pool, status, params.owner.as_deref(), limit).await {
+        Ok(items) => (StatusCode::OK, Json(serde_json::json!({ "items": items }))),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AdminReconciliationResolveReq {
+    pub resolution: String,
+    /// Rewrite the vault snapshot from the case's last seen chain state first.
+    #[serde(default)]
+    pub apply_chain: bool,
+}
+
+/// Resolves an escalated reconciliation case.
+pub async fn admin_reconciliation_resolve(
+    State(state): State<AppState>,
+    TypedHeader(auth): TypedHeader<Authorization<Bearer>>,
+    Path(id): Path<i64>,
+    Json(req): Json<AdminReconciliationResolveReq>,
+) -> impl IntoResponse {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": "admin secret not configured" })),
+        );
+    }
+    let claims = match verify_admin_jwt(auth.token(), &state.cfg.admin_jwt_secret) {
+        Ok(c) => c,
+        Err(_) => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(serde_json::json!({ "error": "unauthorized" })),
+            )
+        }
+    };
+    let resolution = req.resolution.trim();
+    if resolution.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "resolution is required" })),
+        );
+    }
+    match reconciliation::resolve(&state.pool, id, resolution, &claims.sub, req.apply_chain).await {
+        Ok(Some(case)) => {
+            if req.apply_chain {
+                state.cache.invalidate_balance(&case.owner).await;
+            }
+            (StatusCode::OK, Json(serde_json::json!({ "case": case })))
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "no escalated case with this id" })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
+
+// -----------------
+// Proof of reserves
+// -----------------
+/// Inclusion proof of `owner`'s reconciled balance in the newest reserve snapshot that has it.
+pub async fn proof_of_reserves_proof(
+    State(state): State<AppState>,
+    Path(owner): Path<String>,
+) -> impl IntoResponse {
+    match proof_of_reserves::proof_for(&state.pool, &owner).await {
+        Ok(Some(proof)) => (StatusCode::OK, Json(serde_json::json!(proof))),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": "owner is not in any reserve snapshot" })),
+        ),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() })),
+        ),
+    }
+}
@@ -0,0 +1,210 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
+};
+use futures::stream;
+use serde::Deserialize;
+use std::{convert::Infallible, time::Duration};
+
+use super::ws::{self, RpcError, Session};
+use super::AppState;
+use crate::auth::{issue_event_session, verify_event_session};
+use crate::events::Topic;
+
+const HEARTBEAT_SECONDS: u64 = 15;
+/// Lifetime of an event session token.
+const SESSION_TTL_SECONDS: i64 = 3600;
+const SESSION_COOKIE: &str = "events_session";
+
+#[derive(Deserialize)]
+pub struct StreamParams {
+    /// Comma-separated topic names, same as the WebSocket `topic` field.
+    pub topics: String,
+    pub owner: Option<String>,
+    /// Resume point for clients that cannot set `Last-Event-ID`.
+    pub cursor: Option<i64>,
+}
+
+/// Wallet signature exchanged for an event session, see [`events_session`].
+#[derive(Deserialize)]
+pub struct SessionRequest {
+    pub owner: String,
+    pub nonce: String,
+    pub signature: String,
+}
+
+pub(super) type Rejection = (StatusCode, Json<serde_json::Value>);
+
+fn rejection(e: RpcError) -> Rejection {
+    let status = match e.code {
+        ws::UNAUTHORIZED => StatusCode::UNAUTHORIZED,
+        ws::INTERNAL_ERROR => StatusCode::INTERNAL_SERVER_ERROR,
+        _ => StatusCode::BAD_REQUEST,
+    };
+    (status, Json(serde_json::json!({ "error": e.message })))
+}
+
+/// Session of an HTTP event request: an admin JWT or an event session token (see
+/// [`events_session`]) in `Authorization: Bearer`, or an event session in the
+/// [`SESSION_COOKIE`] cookie. Anonymous otherwise.
+pub(super) fn http_session(state: &AppState, headers: &HeaderMap) -> Result<Session, Rejection> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim);
+    let secret = &state.cfg.events_session_secret;
+    match (bearer, session_cookie(headers)) {
+        (Some(token), _) => match verify_event_session(token, secret) {
+            Ok(owner) => Ok(Session::Owner(owner)),
+            Err(_) => ws::admin_session(state, token).map_err(rejection),
+        },
+        (None, Some(token)) => verify_event_session(token, secret)
+            .map(Session::Owner)
+            .map_err(|_| {
+                (
+                    StatusCode::UNAUTHORIZED,
+                    Json(serde_json::json!({ "error": "invalid or expired session" })),
+                )
+            }),
+        (None, None) => Ok(Session::Anonymous),
+    }
+}
+
+fn session_cookie(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|pair| pair.trim().strip_prefix(SESSION_COOKIE)?.strip_prefix('='))
+}
+
+/// Exchanges a wallet signature of `ws_auth:{owner}:{nonce}` (a `ws_auth` nonce, consumed) for
+/// a session token valid for [`SESSION_TTL_SECONDS`]. The token is returned in the body and set
+/// as an `HttpOnly` cookie scoped to `/events`, so a reconnecting `EventSource` presents it
+/// again without a fresh signature.
+pub async fn events_session(
+    State(state): State<AppState>,
+    Json(req): Json<SessionRequest>,
+) -> Response {
+    let owner = match ws::wallet_owner(&state, &req.owner, &req.nonce, &req.signature).await {
+        Ok(owner) => owner,
+        Err(e) => return rejection(e).into_response(),
+    };
+    let token = match issue_event_session(
+        &owner,
+        &state.cfg.events_session_secret,
+        SESSION_TTL_SECONDS,
+    ) {
+        Ok(token) => token,
+        Err(e) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() })),
+            )
+                .into_response()
+        }
+    };
+    let cookie = format!(
+        "{SESSION_COOKIE}={token}; Path=/events; Max-Age={SESSION_TTL_SECONDS}; \
+         HttpOnly; Secure; SameSite=Strict"
+    );
+    (
+        [(header::SET_COOKIE, cookie)],
+        Json(serde_json::json!({
+            "token": token,
+            "owner": owner,
+            "expires_in": SESSION_TTL_SECONDS,
+        })),
+    )
+        .into_response()
+}
+
+/// Owner filter for `topics` under the WebSocket subscription rules: owner-scoped topics are
+/// refused to anonymous sessions and pinned to the owner of a wallet session. The filter only
+/// applies to the owner-scoped topics; global ones are delivered unfiltered.
+pub(super) fn owner_filter(
+    session: &Session,
+    topics: &[Topic],
+    requested: Option<&str>,
+) -> Result<Option<String>, Rejection> {
+    let mut owner = None;
+    for topic in topics {
+        let resolved = ws::resolve_owner(session, *topic, requested).map_err(rejection)?;
+        if topic.is_owner_scoped() {
+            owner = resolved;
+        }
+    }
+    Ok(owner)
+}
+
+/// One-way event feed over Server-Sent Events on the same topics and owner rules as `/ws`.
+///
+/// Each event's SSE `id` is its outbox cursor, so a reconnecting `EventSource` resumes via
+/// `Last-Event-ID` and receives the missed events before live ones.
+pub async fn events_stream(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<StreamParams>,
+) -> Response {
+    let bad_request = |msg: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": msg })),
+        )
+            .into_response()
+    };
+    let mut topics = Vec::new();
+    for name in params
+        .topics
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+    {
+        match Topic::parse(name) {
+            Some(topic) if !topics.contains(&topic) => topics.push(topic),
+            Some(_) => {}
+            None => return bad_request(format!("unknown topic: {name}")),
+        }
+    }
+    if topics.is_empty() {
+        return bad_request("at least one topic is required".to_string());
+    }
+    let session = match http_session(&state, &headers) {
+        Ok(session) => session,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let owner = match owner_filter(&session, &topics, params.owner.as_deref()) {
+        Ok(owner) => owner,
+        Err(rejection) => return rejection.into_response(),
+    };
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<i64>().ok());
+    let cursor = last_event_id.or(params.cursor);
+
+    let subscription = state.notifier.subscribe_from(&topics, owner, cursor);
+    let events = stream::unfold(subscription, |mut subscription| async move {
+        let envelope = subscription.next().await?;
+        let mut event = Event::default().data(envelope.to_json());
+        if let Some(cursor) = envelope.cursor {
+            event = event.id(cursor.to_string());
+        }
+        Some((Ok::<_, Infallible>(event), subscription))
+    });
+
+    Sse::new(events)
+        .keep_alive(
+            KeepAlive::new()
+                .interval(Duration::from_secs(HEARTBEAT_SECONDS))
+                .text("heartbeat"),
+        )
+        .into_response()
+}
@@ -0,0 +1,266 @@
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+
+use super::AppState;
+use crate::{
+    auth::NoncePurpose,
+    db,
+    error::AppError,
+    security::{SecondFactorProof, SecondFactorVerifier, TotpVerifier, WebAuthnVerifier},
+    webauthn::{self, Assertion, RelyingParty},
+};
+
+pub const TWOFA_CODE_HEADER: &str = "x-2fa-code";
+/// base64url-encoded JSON [`Assertion`] from `navigator.credentials.get`.
+pub const TWOFA_WEBAUTHN_HEADER: &str = "x-2fa-webauthn";
+
+/// Operations an owner can require a second factor for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SensitiveOp {
+    Withdraw,
+    ScheduleWithdraw,
+    RequestWithdraw,
+    ProposeWithdraw,
+    DelegateAdd,
+    YieldWithdraw,
+    PmUnlock,
+}
+
+impl SensitiveOp {
+    pub const ALL: [SensitiveOp; 7] = [
+        SensitiveOp::Withdraw,
+        SensitiveOp::ScheduleWithdraw,
+        SensitiveOp::RequestWithdraw,
+        SensitiveOp::ProposeWithdraw,
+        SensitiveOp::DelegateAdd,
+        SensitiveOp::YieldWithdraw,
+        SensitiveOp::PmUnlock,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SensitiveOp::Withdraw => "withdraw",
+            SensitiveOp::ScheduleWithdraw => "schedule_withdraw",
+            SensitiveOp::RequestWithdraw => "request_withdraw",
+            SensitiveOp::ProposeWithdraw => "propose_withdraw",
+            SensitiveOp::DelegateAdd => "delegate_add",
+            SensitiveOp::YieldWithdraw => "yield_withdraw",
+            SensitiveOp::PmUnlock => "pm_unlock",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<SensitiveOp> {
+        SensitiveOp::ALL.into_iter().find(|op| op.as_str() == s)
+    }
+}
+
+/// Operations that need a second factor for `owner`. Owners without a stored policy get
+/// every sensitive operation.
+pub async fn policy_for(state: &AppState, owner: &str) -> Result<Vec<SensitiveOp>, sqlx::Error> {
+    Ok(match db::twofa_policy_get(&state.pool, owner).await? {
+        Some(ops) => ops.iter().filter_map(|s| SensitiveOp::parse(s)).collect(),
+        None => SensitiveOp::ALL.to_vec(),
+    })
+}
+
+pub type GuardRejection = (StatusCode, Json<serde_json::Value>);
+
+fn reject(status: StatusCode, msg: impl Into<String>) -> GuardRejection {
+    (status, Json(serde_json::json!({ "error": msg.into() })))
+}
+
+/// Extracts the optional `X-2FA-CODE` / `X-2FA-WEBAUTHN` headers. Handlers call
+/// [`SecondFactor::require`] once the wallet signature and nonce are verified, so codes cannot
+/// be brute-forced without a signature.
+pub struct SecondFactor {
+    code: Option<String>,
+    webauthn: Option<String>,
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for SecondFactor {
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        Ok(SecondFactor {
+            code: header(TWOFA_CODE_HEADER),
+            webauthn: header(TWOFA_WEBAUTHN_HEADER),
+        })
+    }
+}
+
+impl SecondFactor {
+    /// Enforces the owner's policy for `op`: a no-op unless 2FA is enabled and `op` is listed.
+    /// Owners flagged by the `require_2fa` anomaly action are refused every sensitive
+    /// operation until they enroll a factor.
+    pub async fn require(
+        &self,
+        state: &AppState,
+        owner: &str,
+        op: SensitiveOp,
+    ) -> Result<(), GuardRejection> {
+        let internal = |e: sqlx::Error| reject(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+        if db::twofa_required_get(&state.pool, owner)
+            .await
+            .map_err(internal)?
+            && !db::twofa_any_enabled(&state.pool, owner)
+                .await
+                .map_err(internal)?
+        {
+            return Err(reject(StatusCode::FORBIDDEN, "2fa enrollment required"));
+        }
+        let policy = policy_for(state, owner).await.map_err(internal)?;
+        if !policy.contains(&op) {
+            return Ok(());
+        }
+        self.require_if_enabled(state, owner).await
+    }
+
+    /// Requires a valid second factor whenever the owner has one enrolled (an enabled TOTP
+    /// secret or at least one passkey), regardless of policy. Either factor is accepted.
+    pub async fn require_if_enabled(
+        &self,
+        state: &AppState,
+        owner: &str,
+    ) -> Result<(), GuardRejection> {
+        let internal = |e: sqlx::Error| reject(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+        let totp_secret = match db::twofa_get(&state.pool, owner).await.map_err(internal)? {
+            Some((secret, true)) => Some(secret),
+            _ => None,
+        };
+        let passkeys = db::webauthn_credential_count(&state.pool, owner)
+            .await
+            .map_err(internal)?;
+        if totp_secret.is_none() && passkeys == 0 {
+            return Ok(());
+        }
+        let proof = self
+            .proof()?
+            .ok_or_else(|| reject(StatusCode::UNAUTHORIZED, "2fa code required"))?;
+        match verify_second_factor(state, owner, totp_secret.as_deref(), &proof).await {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(reject(StatusCode::UNAUTHORIZED, "invalid 2fa code")),
+            Err(AppError::BadRequest(msg)) => Err(reject(StatusCode::BAD_REQUEST, msg)),
+            Err(e) => Err(reject(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        }
+    }
+
+    /// A WebAuthn assertion takes precedence over a TOTP code when both headers are sent.
+    fn proof(&self) -> Result<Option<SecondFactorProof>, GuardRejection> {
+        if let Some(raw) = &self.webauthn {
+            let assertion: Assertion = webauthn::b64url_decode(raw)
+                .ok()
+                .and_then(|json| serde_json::from_slice(&json).ok())
+                .ok_or_else(|| reject(StatusCode::BAD_REQUEST, "malformed webauthn assertion"))?;
+            return Ok(Some(SecondFactorProof::WebAuthn(assertion)));
+        }
+        Ok(self.code.clone().map(SecondFactorProof::Totp))
+    }
+}
+
+pub(crate) fn relying_party(state: &AppState) -> RelyingParty {
+    RelyingParty {
+        id: state.cfg.webauthn_rp_id.clone(),
+        origin: state.cfg.webauthn_origin.clone(),
+    }
+}
+
+/// Verifies `proof` against the matching enrolled factor and persists its replay counter.
+async fn verify_second_factor(
+    state: &AppState,
+    owner: &str,
+    totp_secret: Option<&str>,
+    proof: &SecondFactorProof,
+) -> Result<bool, AppError> {
+    match proof {
+        SecondFactorProof::Totp(code) => match totp_secret {
+            Some(secret) => verify_twofa_code(state, owner, secret, code).await,
+            None => Ok(false),
+        },
+        SecondFactorProof::WebAuthn(assertion) => {
+            let Some((public_key, _)) =
+                db::webauthn_credential_get(&state.pool, owner, &assertion.credential_id).await?
+            else {
+                return Ok(false);
+            };
+            // The challenge is a `webauthn_assert` nonce; consuming it makes the assertion single-use
+            let client_data = webauthn::b64url_decode(&assertion.client_data_json)?;
+            let challenge = webauthn::client_data_challenge(&client_data)?;
+            if !db::consume_nonce(
+                &state.pool,
+                &challenge,
+                owner,
+                NoncePurpose::WebauthnAssert.as_str(),
+            )
+            .await?
+            {
+                return Ok(false);
+            }
+            let rp = relying_party(state);
+            let verifier = WebAuthnVerifier {
+                rp: &rp,
+                challenge: &challenge,
+                public_key: &public_key,
+            };
+            match verifier.verify(proof) {
+                Some(count) => Ok(db::webauthn_credential_touch(
+                    &state.pool,
+                    owner,
+                    &assertion.credential_id,
+                    count as i64,
+                )
+                .await?),
+                None => Ok(false),
+            }
+        }
+    }
+}
+
+/// Checks a TOTP code against the stored secret and burns its time step so the same code
+/// cannot be replayed within its validity window.
+pub(crate) async fn verify_twofa_code(
+    state: &AppState,
+    owner: &str,
+    stored_secret: &str,
+    code: &str,
+) -> Result<bool, AppError> {
+    let verifier = TotpVerifier {
+        secret_base32: crate::security::reveal_totp_secret(
+            &state.cfg.twofa_encryption_key,
+            stored_secret,
+        )?,
+    };
+    match verifier.verify(&SecondFactorProof::Totp(code.to_string())) {
+        Some(step) => Ok(db::twofa_mark_step_used(&state.pool, owner, step as i64).await?),
+        None => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sensitive_op_roundtrip() {
+        for op in SensitiveOp::ALL {
+            assert_eq!(SensitiveOp::parse(op.as_str()), Some(op));
+            let json = serde_json::to_value(op).unwrap();
+            assert_eq!(json, serde_json::json!(op.as_str()));
+        }
+        assert_eq!(SensitiveOp::parse("deposit"), None);
+    }
+}
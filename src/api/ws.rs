@@ -1,123 +1,468 @@
+//! WebSocket feed with a JSON-RPC-style protocol (docs/FLOW.md §7).
+//!
+//! Clients send `{"id", "method", "params"}` and get back `{"id", "result"}` or
+//! `{"id", "error": {"code", "message"}}`. Pushes have no `id`:
+//! `{"method": "event" | "account", "params": {"subscription", ...}}`.
+
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::extract::State;
 use axum::response::IntoResponse;
-use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
 use futures::{stream::SplitSink, SinkExt, StreamExt};
 use serde::Deserialize;
-use solana_account_decoder::UiAccountData;
-use solana_client::nonblocking::pubsub_client::PubsubClient;
-use solana_client::rpc_config::RpcAccountInfoConfig;
+use serde_json::{json, Value};
 use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
 use std::str::FromStr;
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{mpsc, Notify};
+use tokio::task::AbortHandle;
 
+use super::routes::verify_signed_request;
 use super::AppState;
+use crate::auth::{verify_admin_jwt, NoncePurpose};
+use crate::config::SlowConsumerPolicy;
+use crate::events::Topic;
+
+pub const PROTOCOL_VERSION: u32 = 2;
+
+pub const PARSE_ERROR: i32 = -32700;
+pub const METHOD_NOT_FOUND: i32 = -32601;
+pub const INVALID_PARAMS: i32 = -32602;
+pub const INTERNAL_ERROR: i32 = -32603;
+pub const UNAUTHORIZED: i32 = 4001;
+pub const UNKNOWN_SUBSCRIPTION: i32 = 4004;
+pub const TOO_MANY_SUBSCRIPTIONS: i32 = 4029;
+
+/// A frame that cannot be written within this long counts as a dead connection.
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub async fn ws_handler(State(state): State<AppState>, ws: WebSocketUpgrade) -> impl IntoResponse {
     ws.on_upgrade(move |socket| handle_socket(state, socket))
 }
 
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) struct RpcError {
+    pub(super) code: i32,
+    pub(super) message: String,
+}
+
+impl RpcError {
+    fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
 #[derive(Deserialize)]
-struct SubscribeMsg {
-    subscribe: String,
-    topic: Option<String>,
+#[serde(untagged)]
+enum AuthParams {
+    Wallet {
+        owner: String,
+        nonce: String,
+        signature: String,
+    },
+    Admin {
+        token: String,
+    },
+}
+
+#[derive(Deserialize)]
+struct SubscribeParams {
+    topic: String,
     owner: Option<String>,
+    /// Resume after this outbox cursor; missed events are replayed before live ones.
+    cursor: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct UnsubscribeParams {
+    subscription: String,
+}
+
+#[derive(Deserialize)]
+struct AccountSubscribeParams {
+    pubkey: String,
+}
+
+/// Who the connection has authenticated as.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Session {
+    Anonymous,
+    Owner(String),
+    Admin,
+}
+
+/// Write side of a connection: a bounded queue drained by a single writer task.
+#[derive(Clone)]
+struct Outbound {
+    tx: mpsc::Sender<String>,
+    policy: SlowConsumerPolicy,
+    overflow: Arc<Notify>,
+}
+
+impl Outbound {
+    /// Queues a frame. Returns false once the connection should stop producing.
+    async fn send(&self, text: String) -> bool {
+        match self.policy {
+            SlowConsumerPolicy::Wait => self.tx.send(text).await.is_ok(),
+            SlowConsumerPolicy::Disconnect => match self.tx.try_send(text) {
+                Ok(()) => true,
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    self.overflow.notify_one();
+                    false
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => false,
+            },
+        }
+    }
+
+    async fn reply(&self, id: &Value, result: Result<Value, RpcError>) -> bool {
+        let frame = match result {
+            Ok(result) => json!({ "id": id, "result": result }),
+            Err(e) => json!({ "id": id, "error": { "code": e.code, "message": e.message } }),
+        };
+        self.send(frame.to_string()).await
+    }
+}
+
+struct Connection {
+    state: AppState,
+    outbound: Outbound,
+    session: Session,
+    subscriptions: HashMap<String, AbortHandle>,
+    next_subscription: u64,
 }
 
 async fn handle_socket(state: AppState, socket: WebSocket) {
-    let (sender, mut receiver) = socket.split();
-    let sender = Arc::new(Mutex::new(sender));
-    let _ = send_text(&sender, "connected".into()).await;
-    while let Some(Ok(msg)) = receiver.next().await {
-        match msg {
-            Message::Text(_t) => {
-                if let Ok(req) = serde_json::from_str::<SubscribeMsg>(&_t) {
-                    // Topic-based subscription via notifier
-                    if let Some(topic) = req.topic.as_ref() {
-                        let mut rx = match topic.as_str() {
-                            "deposit_event" => state.notifier.deposit_tx.subscribe(),
-                            "withdraw_event" => state.notifier.withdraw_tx.subscribe(),
-                            "lock_event" => state.notifier.lock_tx.subscribe(),
-                            "unlock_event" => state.notifier.unlock_tx.subscribe(),
-                            "timelock_event" => state.notifier.timelock_tx.subscribe(),
-                            "vault_balance_update" => state.notifier.vault_balance_tx.subscribe(),
-                            "tvl_update" => state.notifier.tvl_tx.subscribe(),
-                            "security_alert" => state.notifier.security_tx.subscribe(),
-                            "analytics_update" => state.notifier.analytics_tx.subscribe(),
-                            _ => {
-                                let _ = send_text(&sender, "unknown topic".into()).await;
-                                continue;
-                            }
-                        };
-                        let ws_sender = sender.clone();
-                        let owner_filter = req.owner.clone();
-                        tokio::spawn(async move {
-                            while let Ok(msg) = rx.recv().await {
-                                if let Some(ref owner) = owner_filter {
-                                    if !msg.contains(owner) {
-                                        continue;
-                                    }
-                                }
-                                let _ = send_text(&ws_sender, msg).await;
-                            }
-                        });
-                        let _ = send_text(&sender, "subscribed".into()).await;
-                        continue;
-                    }
-                    if let Ok(pk) = Pubkey::from_str(&req.subscribe) {
-                        let ws_url = state
-                            .cfg
-                            .solana_rpc_url
-                            .replace("https://", "wss://")
-                            .replace("http://", "ws://");
-                        let ws_sender = sender.clone();
-                        tokio::spawn(async move {
-                            if let Ok(client) = PubsubClient::new(&ws_url).await {
-                                if let Ok((mut stream, subscription)) = client
-                                    .account_subscribe(&pk, Some(RpcAccountInfoConfig::default()))
-                                    .await
-                                {
-                                    while let Some(update) = stream.next().await {
-                                        let data_len = match &update.value.data {
-                                            UiAccountData::Binary(data, _)
-                                            | UiAccountData::LegacyBinary(data) => BASE64_STANDARD
-                                                .decode(data)
-                                                .map(|bytes| bytes.len())
-                                                .unwrap_or_else(|_| data.len()),
-                                            _ => 0,
-                                        };
-                                        let _ = send_text(
-                                            &ws_sender,
-                                            serde_json::json!({
-                                                "pubkey": pk.to_string(),
-                                                "slot": update.context.slot,
-                                                "data_len": data_len,
-                                            })
-                                            .to_string(),
-                                        )
-                                        .await;
-                                    }
-                                    let _ = subscription().await;
-                                }
-                            }
-                        });
-                        let _ = send_text(&sender, "subscribed".into()).await;
-                        continue;
+    let (sink, mut receiver) = socket.split();
+    let (tx, rx) = mpsc::channel(state.cfg.ws_send_queue_capacity.max(1));
+    let mut writer = tokio::spawn(run_writer(sink, rx));
+    let overflow = Arc::new(Notify::new());
+    let mut conn = Connection {
+        outbound: Outbound {
+            tx,
+            policy: state.cfg.ws_slow_consumer_policy,
+            overflow: overflow.clone(),
+        },
+        state,
+        session: Session::Anonymous,
+        subscriptions: HashMap::new(),
+        next_subscription: 1,
+    };
+    conn.outbound
+        .send(
+            json!({ "method": "connected", "params": { "protocol": PROTOCOL_VERSION } })
+                .to_string(),
+        )
+        .await;
+
+    loop {
+        tokio::select! {
+            msg = receiver.next() => match msg {
+                Some(Ok(Message::Text(text))) => {
+                    let (id, result) = match parse_request(&text) {
+                        Ok(req) => {
+                            let result = conn.dispatch(req.method.as_str(), req.params).await;
+                            (req.id, result)
+                        }
+                        Err(e) => (Value::Null, Err(e)),
+                    };
+                    if !conn.outbound.reply(&id, result).await {
+                        break;
                     }
                 }
-                let _ = send_text(&sender, "pong".into()).await;
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            _ = overflow.notified() => {
+                tracing::info!("closing slow websocket consumer");
+                break;
             }
-            Message::Close(_) => break,
-            _ => {}
+            _ = &mut writer => break,
         }
     }
+
+    for (_, handle) in conn.subscriptions.drain() {
+        handle.abort();
+    }
+    drop(conn);
+    writer.abort();
 }
 
-async fn send_text(
-    sender: &Arc<Mutex<SplitSink<WebSocket, Message>>>,
-    text: String,
-) -> Result<(), ()> {
-    let mut guard = sender.lock().await;
-    guard.send(Message::Text(text)).await.map_err(|_| ())
+async fn run_writer(mut sink: SplitSink<WebSocket, Message>, mut rx: mpsc::Receiver<String>) {
+    while let Some(text) = rx.recv().await {
+        match tokio::time::timeout(SEND_TIMEOUT, sink.send(Message::Text(text))).await {
+            Ok(Ok(())) => {}
+            _ => break,
+        }
+    }
+    let _ = sink.close().await;
+}
+
+fn parse_request(text: &str) -> Result<Request, RpcError> {
+    serde_json::from_str(text).map_err(|e| RpcError::new(PARSE_ERROR, e.to_string()))
+}
+
+fn params<T: serde::de::DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn parse_pubkey(s: &str, field: &str) -> Result<Pubkey, RpcError> {
+    Pubkey::from_str(s).map_err(|_| RpcError::new(INVALID_PARAMS, format!("invalid {field}")))
+}
+
+/// Wallet owner proven by `signature` over `ws_auth:{owner}:{nonce}`; the nonce is consumed.
+pub(super) async fn wallet_owner(
+    state: &AppState,
+    owner: &str,
+    nonce: &str,
+    signature: &str,
+) -> Result<String, RpcError> {
+    let owner = parse_pubkey(owner, "owner")?.to_string();
+    let message = format!("ws_auth:{}:{}", owner, nonce);
+    let purpose = NoncePurpose::WsAuth;
+    verify_signed_request(state, &owner, &message, nonce, signature, purpose)
+        .await
+        .map_err(|(status, body)| {
+            let code = if status.is_server_error() {
+                INTERNAL_ERROR
+            } else {
+                UNAUTHORIZED
+            };
+            RpcError::new(code, body["error"].as_str().unwrap_or_default())
+        })?;
+    Ok(owner)
+}
+
+pub(super) fn admin_session(state: &AppState, token: &str) -> Result<Session, RpcError> {
+    if state.cfg.admin_jwt_secret.is_empty() {
+        return Err(RpcError::new(UNAUTHORIZED, "admin auth disabled"));
+    }
+    verify_admin_jwt(token, &state.cfg.admin_jwt_secret)
+        .map_err(|e| RpcError::new(UNAUTHORIZED, e.to_string()))?;
+    Ok(Session::Admin)
+}
+
+/// Owner filter for a subscription. Owner-scoped topics need an authenticated session: an
+/// owner session is pinned to its own events, an admin may filter by any owner or none.
+pub(super) fn resolve_owner(
+    session: &Session,
+    topic: Topic,
+    requested: Option<&str>,
+) -> Result<Option<String>, RpcError> {
+    let requested = requested
+        .map(|owner| parse_pubkey(owner, "owner").map(|pk| pk.to_string()))
+        .transpose()?;
+    if !topic.is_owner_scoped() {
+        return Ok(requested);
+    }
+    match session {
+        Session::Admin => Ok(requested),
+        Session::Owner(owner) => match requested {
+            Some(requested) if &requested != owner => Err(RpcError::new(
+                UNAUTHORIZED,
+                "session is not authorized for this owner",
+            )),
+            _ => Ok(Some(owner.clone())),
+        },
+        Session::Anonymous => Err(RpcError::new(
+            UNAUTHORIZED,
+            format!("{} requires an authenticated session", topic.as_str()),
+        )),
+    }
+}
+
+impl Connection {
+    async fn dispatch(&mut self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "auth" => self.auth(params).await,
+            "subscribe" => self.subscribe(params),
+            "unsubscribe" => self.unsubscribe(params),
+            "account_subscribe" => self.account_subscribe(params),
+            "ping" => Ok(json!("pong")),
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method: {method}"),
+            )),
+        }
+    }
+
+    /// Wallet sessions sign `ws_auth:{owner}:{nonce}`; admin sessions present an admin JWT.
+    async fn auth(&mut self, params: Value) -> Result<Value, RpcError> {
+        self.session = match self::params::<AuthParams>(params)? {
+            AuthParams::Wallet {
+                owner,
+                nonce,
+                signature,
+            } => Session::Owner(wallet_owner(&self.state, &owner, &nonce, &signature).await?),
+            AuthParams::Admin { token } => admin_session(&self.state, &token)?,
+        };
+        match &self.session {
+            Session::Owner(owner) => Ok(json!({ "owner": owner })),
+            _ => Ok(json!({ "admin": true })),
+        }
+    }
+
+    fn subscribe(&mut self, params: Value) -> Result<Value, RpcError> {
+        let req: SubscribeParams = self::params(params)?;
+        let topic = Topic::parse(&req.topic).ok_or_else(|| {
+            RpcError::new(INVALID_PARAMS, format!("unknown topic: {}", req.topic))
+        })?;
+        let owner = resolve_owner(&self.session, topic, req.owner.as_deref())?;
+
+        let id = self.subscription_id()?;
+        let mut subscription = self
+            .state
+            .notifier
+            .subscribe_from(&[topic], owner, req.cursor);
+        let outbound = self.outbound.clone();
+        let sub_id = id.clone();
+        let task = tokio::spawn(async move {
+            while let Some(envelope) = subscription.next().await {
+                let frame = json!({
+                    "method": "event",
+                    "params": { "subscription": sub_id, "event": envelope },
+                });
+                if !outbound.send(frame.to_string()).await {
+                    break;
+                }
+            }
+        });
+        self.subscriptions.insert(id.clone(), task.abort_handle());
+        Ok(json!({ "subscription": id }))
+    }
+
+    fn unsubscribe(&mut self, params: Value) -> Result<Value, RpcError> {
+        let req: UnsubscribeParams = self::params(params)?;
+        match self.subscriptions.remove(&req.subscription) {
+            Some(handle) => {
+                handle.abort();
+                Ok(json!(true))
+            }
+            None => Err(RpcError::new(
+                UNKNOWN_SUBSCRIPTION,
+                format!("unknown subscription: {}", req.subscription),
+            )),
+        }
+    }
+
+    /// Follows account updates through the shared [`crate::account_hub::AccountHub`], so
+    /// any number of sockets watching one pubkey share a single upstream subscription.
+    fn account_subscribe(&mut self, params: Value) -> Result<Value, RpcError> {
+        let req: AccountSubscribeParams = self::params(params)?;
+        let pk = parse_pubkey(&req.pubkey, "pubkey")?;
+
+        let id = self.subscription_id()?;
+        let mut updates = self.state.account_hub.subscribe(pk);
+        let outbound = self.outbound.clone();
+        let sub_id = id.clone();
+        let task = tokio::spawn(async move {
+            while let Some(update) = updates.next().await {
+                let frame = json!({
+                    "method": "account",
+                    "params": {
+                        "subscription": sub_id,
+                        "pubkey": update.pubkey.to_string(),
+                        "slot": update.slot,
+                        "data_len": update.data_len,
+                    },
+                });
+                if !outbound.send(frame.to_string()).await {
+                    break;
+                }
+            }
+        });
+        self.subscriptions.insert(id.clone(), task.abort_handle());
+        Ok(json!({ "subscription": id }))
+    }
+
+    /// Allocates the next subscription id, refusing once the connection holds
+    /// `WS_MAX_SUBSCRIPTIONS` subscriptions.
+    fn subscription_id(&mut self) -> Result<String, RpcError> {
+        let max = self.state.cfg.ws_max_subscriptions;
+        if self.subscriptions.len() >= max {
+            return Err(RpcError::new(
+                TOO_MANY_SUBSCRIPTIONS,
+                format!("subscription limit of {max} reached"),
+            ));
+        }
+        let id = format!("s{}", self.next_subscription);
+        self.next_subscription += 1;
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "11111111111111111111111111111112";
+    const BOB: &str = "So11111111111111111111111111111111111111112";
+
+    #[test]
+    fn test_parse_request() {
+        let req = parse_request(r#"{"id":7,"method":"subscribe","params":{"topic":"tvl_update"}}"#)
+            .unwrap();
+        assert_eq!(req.id, json!(7));
+        assert_eq!(req.method, "subscribe");
+        assert_eq!(req.params["topic"], "tvl_update");
+
+        assert_eq!(parse_request("ping").unwrap_err().code, PARSE_ERROR);
+        assert_eq!(parse_request(r#"{"id":1}"#).unwrap_err().code, PARSE_ERROR);
+    }
+
+    #[test]
+    fn test_resolve_owner() {
+        let alice = Session::Owner(ALICE.to_string());
+
+        // Global topics are open and keep the optional filter
+        assert_eq!(
+            resolve_owner(&Session::Anonymous, Topic::TvlUpdate, None),
+            Ok(None)
+        );
+        assert_eq!(
+            resolve_owner(&Session::Anonymous, Topic::TvlUpdate, Some("nope"))
+                .unwrap_err()
+                .code,
+            INVALID_PARAMS
+        );
+
+        // Owner-scoped topics are pinned to the session owner
+        assert_eq!(
+            resolve_owner(&Session::Anonymous, Topic::DepositEvent, Some(ALICE))
+                .unwrap_err()
+                .code,
+            UNAUTHORIZED
+        );
+        assert_eq!(
+            resolve_owner(&alice, Topic::DepositEvent, None),
+            Ok(Some(ALICE.to_string()))
+        );
+        assert_eq!(
+            resolve_owner(&alice, Topic::DepositEvent, Some(BOB))
+                .unwrap_err()
+                .code,
+            UNAUTHORIZED
+        );
+        assert_eq!(
+            resolve_owner(&Session::Admin, Topic::SecurityAlert, Some(BOB)),
+            Ok(Some(BOB.to_string()))
+        );
+        assert_eq!(
+            resolve_owner(&Session::Admin, Topic::SecurityAlert, None),
+            Ok(None)
+        );
+    }
 }
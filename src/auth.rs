@@ -1,6 +1,6 @@
 use crate::error::{AppError, AppResult};
 use ed25519_dalek::{PublicKey, Signature, Verifier};
-use jsonwebtoken::{decode, Algorithm, DecodingKey, Validation};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 
 pub fn verify_wallet_signature(
@@ -23,6 +23,109 @@ pub fn verify_wallet_signature(
         .map_err(|_| AppError::Unauthorized)
 }
 
+/// Action a nonce is issued for. A nonce can only be consumed by the action it was issued for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoncePurpose {
+    Deposit,
+    Withdraw,
+    Schedule,
+    RequestWithdraw,
+    ProposeWithdraw,
+    ApproveWithdraw,
+    DelegateAdd,
+    DelegateRemove,
+    PmLock,
+    PmUnlock,
+    YieldDeposit,
+    YieldWithdraw,
+    CompoundYield,
+    TwofaSetup,
+    TwofaDisable,
+    TwofaResetCancel,
+    TwofaPolicy,
+    WebauthnRegister,
+    WebauthnRemove,
+    WebauthnAssert,
+    WebhookRegister,
+    WebhookRemove,
+    WsAuth,
+    NotificationContact,
+    NotificationPreferences,
+}
+
+impl NoncePurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NoncePurpose::Deposit => "deposit",
+            NoncePurpose::Withdraw => "withdraw",
+            NoncePurpose::Schedule => "schedule",
+            NoncePurpose::RequestWithdraw => "request_withdraw",
+            NoncePurpose::ProposeWithdraw => "propose_withdraw",
+            NoncePurpose::ApproveWithdraw => "approve_withdraw",
+            NoncePurpose::DelegateAdd => "delegate_add",
+            NoncePurpose::DelegateRemove => "delegate_remove",
+            NoncePurpose::PmLock => "pm_lock",
+            NoncePurpose::PmUnlock => "pm_unlock",
+            NoncePurpose::YieldDeposit => "yield_deposit",
+            NoncePurpose::YieldWithdraw => "yield_withdraw",
+            NoncePurpose::CompoundYield => "compound_yield",
+            NoncePurpose::TwofaSetup => "twofa_setup",
+            NoncePurpose::TwofaDisable => "twofa_disable",
+            NoncePurpose::TwofaResetCancel => "twofa_reset_cancel",
+            NoncePurpose::TwofaPolicy => "twofa_policy",
+            NoncePurpose::WebauthnRegister => "webauthn_register",
+            NoncePurpose::WebauthnRemove => "webauthn_remove",
+            NoncePurpose::WebauthnAssert => "webauthn_assert",
+            NoncePurpose::WebhookRegister => "webhook_register",
+            NoncePurpose::WebhookRemove => "webhook_remove",
+            NoncePurpose::WsAuth => "ws_auth",
+            NoncePurpose::NotificationContact => "notification_contact",
+            NoncePurpose::NotificationPreferences => "notification_preferences",
+        }
+    }
+
+    /// Message the client must sign with its wallet, with `{placeholders}` for request fields.
+    pub fn message_template(&self) -> &'static str {
+        match self {
+            NoncePurpose::Deposit => "deposit:{owner}:{amount}:{nonce}",
+            NoncePurpose::Withdraw => "withdraw:{owner}:{amount}:{nonce}",
+            NoncePurpose::Schedule => "schedule:{owner}:{amount}:{duration_seconds}:{nonce}",
+            NoncePurpose::RequestWithdraw => "request_withdraw:{owner}:{amount}:{nonce}",
+            NoncePurpose::ProposeWithdraw => {
+                "propose_withdraw:{owner}:{amount}:{threshold}:{nonce}"
+            }
+            NoncePurpose::ApproveWithdraw => "approve_withdraw:{proposal_id}:{nonce}",
+            NoncePurpose::DelegateAdd => "delegate_add:{owner}:{delegate}:{nonce}",
+            NoncePurpose::DelegateRemove => "delegate_remove:{owner}:{delegate}:{nonce}",
+            NoncePurpose::PmLock => "pm_lock:{owner}:{amount}:{nonce}",
+            NoncePurpose::PmUnlock => "pm_unlock:{owner}:{amount}:{nonce}",
+            NoncePurpose::YieldDeposit => "yield_deposit:{owner}:{amount}:{yield_program}:{nonce}",
+            NoncePurpose::YieldWithdraw => {
+                "yield_withdraw:{owner}:{amount}:{yield_program}:{nonce}"
+            }
+            NoncePurpose::CompoundYield => {
+                "compound_yield:{owner}:{compounded_amount}:{yield_program}:{nonce}"
+            }
+            NoncePurpose::TwofaSetup => "twofa_setup:{owner}:{nonce}",
+            NoncePurpose::TwofaDisable => "twofa_disable:{owner}:{nonce}",
+            NoncePurpose::TwofaResetCancel => "twofa_reset_cancel:{owner}:{nonce}",
+            NoncePurpose::TwofaPolicy => "twofa_policy:{owner}:{operations}:{nonce}",
+            NoncePurpose::WebauthnRegister => "webauthn_register:{owner}:{nonce}",
+            NoncePurpose::WebauthnRemove => "webauthn_remove:{owner}:{credential_id}:{nonce}",
+            // Not wallet-signed: the nonce is used as the WebAuthn assertion challenge
+            NoncePurpose::WebauthnAssert => "webauthn_assert:{owner}:{nonce}",
+            NoncePurpose::WebhookRegister => "webhook_register:{owner}:{url}:{nonce}",
+            NoncePurpose::WebhookRemove => "webhook_remove:{owner}:{id}:{nonce}",
+            NoncePurpose::WsAuth => "ws_auth:{owner}:{nonce}",
+            NoncePurpose::NotificationContact => "notification_contact:{owner}:{email}:{nonce}",
+            NoncePurpose::NotificationPreferences => {
+                "notification_preferences:{owner}:{disabled}:{nonce}"
+            }
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AdminClaims {
     pub sub: String,
@@ -45,6 +148,49 @@ pub fn verify_admin_jwt(token: &str, secret: &str) -> AppResult<AdminClaims> {
     Ok(token_data.claims)
 }
 
+/// Claims of a wallet session for the HTTP event feeds, see [`issue_event_session`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EventSessionClaims {
+    pub sub: String,
+    pub role: String,
+    pub exp: usize,
+}
+
+const EVENT_SESSION_ROLE: &str = "events";
+
+/// Signs a session token for `owner` that expires after `ttl_seconds`. Unlike the `ws_auth`
+/// nonce it was exchanged for, the token can be presented again until it expires, so an
+/// `EventSource` can reconnect with it.
+pub fn issue_event_session(owner: &str, secret: &str, ttl_seconds: i64) -> AppResult<String> {
+    let exp = time::OffsetDateTime::now_utc().unix_timestamp() + ttl_seconds;
+    let claims = EventSessionClaims {
+        sub: owner.to_string(),
+        role: EVENT_SESSION_ROLE.to_string(),
+        exp: exp as usize,
+    };
+    encode(
+        &Header::new(Algorithm::HS256),
+        &claims,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )
+    .map_err(|e| AppError::Internal(e.to_string()))
+}
+
+/// Owner of a valid, unexpired event session token.
+pub fn verify_event_session(token: &str, secret: &str) -> AppResult<String> {
+    let validation = Validation::new(Algorithm::HS256);
+    let token_data = decode::<EventSessionClaims>(
+        token,
+        &DecodingKey::from_secret(secret.as_bytes()),
+        &validation,
+    )
+    .map_err(|_| AppError::Unauthorized)?;
+    if token_data.claims.role != EVENT_SESSION_ROLE {
+        return Err(AppError::Unauthorized);
+    }
+    Ok(token_data.claims.sub)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -61,4 +207,39 @@ mod tests {
         let signature = bs58::encode(sig.to_bytes()).into_string();
         assert!(verify_wallet_signature(&owner, message, &signature).is_ok());
     }
+
+    #[test]
+    fn test_nonce_purpose_templates_start_with_action() {
+        for purpose in [NoncePurpose::Deposit, NoncePurpose::ApproveWithdraw, NoncePurpose::PmUnlock] {
+            let parsed: NoncePurpose =
+                serde_json::from_value(serde_json::json!(purpose.as_str())).unwrap();
+            assert_eq!(parsed, purpose);
+            assert!(purpose.message_template().starts_with(&format!("{}:", purpose.as_str())));
+            assert!(purpose.message_template().ends_with(":{nonce}"));
+        }
+    }
+
+    #[test]
+    fn test_event_session_roundtrip() {
+        let token = issue_event_session("alice", "secret", 60).unwrap();
+        assert_eq!(verify_event_session(&token, "secret").unwrap(), "alice");
+        assert!(verify_event_session(&token, "other-secret").is_err());
+
+        let expired = issue_event_session("alice", "secret", -120).unwrap();
+        assert!(verify_event_session(&expired, "secret").is_err());
+
+        // Admin tokens signed with the same secret are not event sessions
+        let admin = AdminClaims {
+            sub: "admin".to_string(),
+            role: "admin".to_string(),
+            exp: (time::OffsetDateTime::now_utc().unix_timestamp() + 60) as usize,
+        };
+        let admin = encode(
+            &Header::default(),
+            &admin,
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+        assert!(verify_event_session(&admin, "secret").is_err());
+    }
 }
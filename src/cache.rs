@@ -1,132 +1,745 @@
+//! Two-tier cache: an in-process LRU in front of an optional Redis shared through one
+//! multiplexed [`ConnectionManager`].
+//!
+//! Values are stored as JSON under `{namespace}:{class}:v{version}:{id}`. The namespace
+//! (`CACHE_NAMESPACE`) separates deployments sharing a Redis; the per-class version is bumped
+//! whenever the cached type changes, so entries written by an older build are never decoded.
+//! TTLs are per key class (`CACHE_TTLS`, falling back to `CACHE_TTL_SECONDS`).
+//!
+//! [`Cache::get_or_load`] protects the loader (usually RPC): concurrent misses for one key
+//! wait for a single load, and a local entry up to `CACHE_STALE_SECONDS` past its TTL is
+//! served while one background refresh runs. Without Redis (`REDIS_URL` empty or
+//! unreachable at startup) the local tier works alone. Every lookup is counted in the
+//! `cache_*_total{class}` metrics.
+//!
+//! [`Cache::invalidate`] evicts both tiers and publishes an [`Invalidation`] on
+//! `{namespace}:invalidations`; every replica runs `tasks::cache_invalidation` to evict the
+//! key from its own local tier. A load that was in flight when any key was invalidated is
+//! returned but not cached, so it cannot put an older value back.
+
+use hashlink::LruCache;
+use redis::aio::ConnectionManager;
 use redis::{AsyncCommands, Client};
-use std::sync::Arc;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tracing::warn;
 
+use crate::config::AppConfig;
+use crate::metrics::Metrics;
+
+/// Kind of cached value; sets the key prefix, schema version and TTL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyClass {
+    /// Vault balance per owner (`u64`).
+    Balance,
+    /// Total value locked ([`crate::tvl::Tvl`]), single key.
+    Tvl,
+}
+
+impl KeyClass {
+    pub const ALL: [KeyClass; 2] = [KeyClass::Balance, KeyClass::Tvl];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyClass::Balance => "balance",
+            KeyClass::Tvl => "tvl",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<KeyClass> {
+        KeyClass::ALL.into_iter().find(|c| c.as_str() == s)
+    }
+
+    /// Schema version of the cached value; bump when its type changes.
+    pub fn version(&self) -> u32 {
+        match self {
+            KeyClass::Balance => 1,
+            KeyClass::Tvl => 2,
+        }
+    }
+}
+
+/// TTL per key class.
+#[derive(Debug, Clone)]
+pub struct CacheTtls {
+    default_seconds: u64,
+    overrides: HashMap<KeyClass, u64>,
+}
+
+impl CacheTtls {
+    pub fn uniform(default_seconds: u64) -> Self {
+        Self {
+            default_seconds,
+            overrides: HashMap::new(),
+        }
+    }
+
+    /// Parses `CACHE_TTLS`, e.g. `balance=30,tvl=120`; classes not listed use `default_seconds`.
+    pub fn parse(default_seconds: u64, spec: &str) -> Result<Self, String> {
+        let mut ttls = Self::uniform(default_seconds);
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (class, seconds) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("expected class=seconds, got '{entry}'"))?;
+            let class = KeyClass::parse(class.trim())
+                .ok_or_else(|| format!("unknown cache key class '{}'", class.trim()))?;
+            let seconds = seconds
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| format!("invalid TTL for {}: '{}'", class.as_str(), seconds.trim()))?;
+            ttls.overrides.insert(class, seconds);
+        }
+        Ok(ttls)
+    }
+
+    pub fn get(&self, class: KeyClass) -> u64 {
+        self.overrides
+            .get(&class)
+            .copied()
+            .unwrap_or(self.default_seconds)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheSettings {
+    pub namespace: String,
+    pub ttls: CacheTtls,
+    /// How long past its TTL a local entry may be served while it refreshes.
+    pub stale_seconds: u64,
+    /// Maximum number of entries in the in-process tier.
+    pub local_capacity: usize,
+}
+
+impl CacheSettings {
+    /// An invalid `CACHE_TTLS` is logged and ignored.
+    pub fn from_config(cfg: &AppConfig) -> Self {
+        let ttls = CacheTtls::parse(cfg.cache_ttl_seconds, &cfg.cache_ttls).unwrap_or_else(|e| {
+            warn!("invalid CACHE_TTLS ({e}), using CACHE_TTL_SECONDS for every key class");
+            CacheTtls::uniform(cfg.cache_ttl_seconds)
+        });
+        Self {
+            namespace: cfg.cache_namespace.clone(),
+            ttls,
+            stale_seconds: cfg.cache_stale_seconds,
+            local_capacity: cfg.cache_local_capacity,
+        }
+    }
+}
+
+/// `{namespace}:{class}:v{version}`, plus `:{id}` unless `id` is empty.
+pub fn cache_key(namespace: &str, class: KeyClass, id: &str) -> String {
+    let base = format!("{}:{}:v{}", namespace, class.as_str(), class.version());
+    if id.is_empty() {
+        base
+    } else {
+        format!("{base}:{id}")
+    }
+}
+
+/// Redis channel carrying [`Invalidation`] messages.
+pub fn invalidation_channel(namespace: &str) -> String {
+    format!("{namespace}:invalidations")
+}
+
+/// A key evicted by one replica, published so the others drop their local copy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Invalidation {
+    /// Instance that published it; it has already evicted its own tiers.
+    pub origin: String,
+    pub class: KeyClass,
+    pub id: String,
+}
+
+/// Connects to Redis once; the manager reconnects on its own after a dropped connection.
+pub async fn connect_redis(redis_url: &str) -> Result<ConnectionManager, redis::RedisError> {
+    let client = Client::open(redis_url)?;
+    // One retry: an unreachable Redis should not hold up startup, the cache works without it
+    ConnectionManager::new_with_backoff(client, 2, 100, 1).await
+}
+
+struct LocalEntry {
+    raw: String,
+    fresh_until: Instant,
+    stale_until: Instant,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Local {
+    Fresh(String),
+    Stale(String),
+    Missing,
+}
+
+fn classify(entry: Option<&LocalEntry>, now: Instant) -> Local {
+    match entry {
+        Some(e) if now < e.fresh_until => Local::Fresh(e.raw.clone()),
+        Some(e) if now < e.stale_until => Local::Stale(e.raw.clone()),
+        _ => Local::Missing,
+    }
+}
+
 #[derive(Clone)]
 pub struct Cache {
-    client: Arc<Client>,
-    ttl_seconds: u64,
+    local: Arc<Mutex<LruCache<String, LocalEntry>>>,
+    /// One lock per key with a load in progress; waiters queue on it instead of loading.
+    loads: Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>,
+    redis: Option<ConnectionManager>,
+    settings: Arc<CacheSettings>,
+    metrics: Arc<Metrics>,
+    /// Identifies this replica's messages on the invalidation channel.
+    instance_id: Arc<str>,
+    /// Bumped on every eviction; loads started before a bump are not cached.
+    evictions: Arc<AtomicU64>,
 }
 
 impl Cache {
-    pub fn new(redis_url: &str, ttl_seconds: u64) -> Result<Self, redis::RedisError> {
-        let client = Client::open(redis_url)?;
-        Ok(Self {
-            client: Arc::new(client),
-            ttl_seconds,
-        })
+    pub fn new(
+        settings: CacheSettings,
+        redis: Option<ConnectionManager>,
+        metrics: Arc<Metrics>,
+    ) -> Self {
+        Self {
+            local: Arc::new(Mutex::new(LruCache::new(settings.local_capacity.max(1)))),
+            loads: Arc::new(Mutex::new(HashMap::new())),
+            redis,
+            settings: Arc::new(settings),
+            metrics,
+            instance_id: uuid::Uuid::new_v4().to_string().into(),
+            evictions: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    pub fn has_redis(&self) -> bool {
+        self.redis.is_some()
+    }
+
+    pub fn key(&self, class: KeyClass, id: &str) -> String {
+        cache_key(&self.settings.namespace, class, id)
+    }
+
+    pub fn namespace(&self) -> &str {
+        &self.settings.namespace
+    }
+
+    fn local_get(&self, key: &str) -> Local {
+        let mut local = self.local.lock().unwrap();
+        let state = classify(local.get(key), Instant::now());
+        if state == Local::Missing {
+            local.remove(key);
+        }
+        state
+    }
+
+    fn local_set(&self, class: KeyClass, key: String, raw: String) {
+        let fresh_until = Instant::now() + Duration::from_secs(self.settings.ttls.get(class));
+        let entry = LocalEntry {
+            raw,
+            fresh_until,
+            stale_until: fresh_until + Duration::from_secs(self.settings.stale_seconds),
+        };
+        self.local.lock().unwrap().insert(key, entry);
     }
 
-    pub async fn get_balance(&self, key: &str) -> Option<u64> {
-        let mut conn = match self.client.get_async_connection().await {
-            Ok(conn) => conn,
+    fn evict_local(&self, key: &str) {
+        self.local.lock().unwrap().remove(key);
+        self.evictions.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn decode<T: DeserializeOwned>(&self, class: KeyClass, raw: &str) -> Option<T> {
+        match serde_json::from_str(raw) {
+            Ok(value) => Some(value),
             Err(e) => {
-                warn!("Redis connection error: {}", e);
-                return None;
+                warn!(class = class.as_str(), "undecodable cache entry: {}", e);
+                self.count_error(class);
+                None
             }
-        };
-        match conn.get::<_, String>(format!("balance:{}", key)).await {
-            Ok(val) => val.parse().ok(),
+        }
+    }
+
+    fn count_hit(&self, class: KeyClass, tier: &str) {
+        self.metrics
+            .cache_hits
+            .with_label_values(&[class.as_str(), tier])
+            .inc();
+    }
+
+    fn count_error(&self, class: KeyClass) {
+        self.metrics
+            .cache_errors
+            .with_label_values(&[class.as_str()])
+            .inc();
+    }
+
+    async fn redis_get(&self, class: KeyClass, key: &str) -> Option<String> {
+        let mut conn = self.redis.clone()?;
+        match conn.get::<_, Option<String>>(key).await {
+            Ok(raw) => raw,
             Err(e) => {
-                if e.kind() == redis::ErrorKind::TypeError {
-                    None
-                } else {
-                    warn!("Redis get error: {}", e);
-                    None
-                }
+                warn!("Redis get error: {}", e);
+                self.count_error(class);
+                None
             }
         }
     }
 
-    pub async fn set_balance(&self, key: &str, value: u64) {
-        let mut conn = match self.client.get_async_connection().await {
-            Ok(conn) => conn,
+    /// Fresh value from either tier, promoting Redis hits into the local tier.
+    async fn lookup<T: DeserializeOwned>(&self, class: KeyClass, key: &str) -> Option<T> {
+        if let Local::Fresh(raw) = self.local_get(key) {
+            if let Some(value) = self.decode(class, &raw) {
+                self.count_hit(class, "local");
+                return Some(value);
+            }
+        }
+        let raw = self.redis_get(class, key).await?;
+        let value = self.decode(class, &raw)?;
+        self.count_hit(class, "redis");
+        self.local_set(class, key.to_string(), raw);
+        Some(value)
+    }
+
+    pub async fn get_json<T: DeserializeOwned>(&self, class: KeyClass, id: &str) -> Option<T> {
+        let value = self.lookup(class, &self.key(class, id)).await;
+        if value.is_none() {
+            self.metrics
+                .cache_misses
+                .with_label_values(&[class.as_str()])
+                .inc();
+        }
+        value
+    }
+
+    pub async fn set_json<T: Serialize>(&self, class: KeyClass, id: &str, value: &T) {
+        let raw = match serde_json::to_string(value) {
+            Ok(raw) => raw,
             Err(e) => {
-                warn!("Redis connection error: {}", e);
+                warn!(class = class.as_str(), "cache encode error: {}", e);
                 return;
             }
         };
-        if let Err(e) = conn
-            .set_ex::<_, _, ()>(
-                format!("balance:{}", key),
-                value.to_string(),
-                self.ttl_seconds,
-            )
-            .await
-        {
-            warn!("Redis set error: {}", e);
+        let key = self.key(class, id);
+        self.local_set(class, key.clone(), raw.clone());
+        if let Some(mut conn) = self.redis.clone() {
+            let ttl = self.settings.ttls.get(class).max(1);
+            if let Err(e) = conn.set_ex::<_, _, ()>(key, raw, ttl).await {
+                warn!("Redis set error: {}", e);
+                self.count_error(class);
+            }
         }
     }
 
-    pub async fn invalidate_balance(&self, key: &str) {
-        let mut conn = match self.client.get_async_connection().await {
-            Ok(conn) => conn,
+    /// Evicts `(class, id)` from both tiers and tells the other replicas to do the same.
+    pub async fn invalidate(&self, class: KeyClass, id: &str) {
+        let key = self.key(class, id);
+        self.evict_local(&key);
+        self.count_invalidation(class, "local");
+        let Some(mut conn) = self.redis.clone() else {
+            return;
+        };
+        let message = Invalidation {
+            origin: self.instance_id.to_string(),
+            class,
+            id: id.to_string(),
+        };
+        let payload = match serde_json::to_string(&message) {
+            Ok(payload) => payload,
             Err(e) => {
-                warn!("Redis connection error: {}", e);
+                warn!(class = class.as_str(), "invalidation encode error: {}", e);
                 return;
             }
         };
-        if let Err(e) = conn.del::<_, ()>(format!("balance:{}", key)).await {
-            warn!("Redis delete error: {}", e);
+        let result: redis::RedisResult<()> = redis::pipe()
+            .del(&key)
+            .ignore()
+            .publish(invalidation_channel(&self.settings.namespace), payload)
+            .ignore()
+            .query_async(&mut conn)
+            .await;
+        if let Err(e) = result {
+            warn!("Redis invalidate error: {}", e);
+            self.count_error(class);
         }
     }
 
-    pub async fn get_tvl(&self) -> Option<i64> {
-        let mut conn = match self.client.get_async_connection().await {
-            Ok(conn) => conn,
-            Err(e) => {
-                warn!("Redis connection error: {}", e);
-                return None;
+    /// Applies a message from the invalidation channel. Returns false for this replica's own.
+    pub fn apply_invalidation(&self, message: &Invalidation) -> bool {
+        if *message.origin == *self.instance_id {
+            return false;
+        }
+        self.evict_local(&self.key(message.class, &message.id));
+        self.count_invalidation(message.class, "remote");
+        true
+    }
+
+    /// Empties the local tier, e.g. after invalidations may have been missed.
+    pub fn clear_local(&self) {
+        self.local.lock().unwrap().clear();
+        self.evictions.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn count_invalidation(&self, class: KeyClass, origin: &str) {
+        self.metrics
+            .cache_invalidations
+            .with_label_values(&[class.as_str(), origin])
+            .inc();
+    }
+
+    /// Caches a loaded value unless something was evicted since `epoch`, when the load may
+    /// have read data that is already outdated.
+    async fn store_loaded<T: Serialize>(&self, class: KeyClass, id: &str, value: &T, epoch: u64) {
+        if self.evictions.load(Ordering::SeqCst) == epoch {
+            self.set_json(class, id, value).await;
+        }
+    }
+
+    fn load_gate(&self, key: &str) -> Arc<tokio::sync::Mutex<()>> {
+        self.loads
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Drops the key's gate once nobody else holds or waits on it. `gate` must be unlocked.
+    fn release_gate(&self, key: &str, gate: Arc<tokio::sync::Mutex<()>>) {
+        let mut loads = self.loads.lock().unwrap();
+        // The map's reference and ours
+        if Arc::strong_count(&gate) <= 2 {
+            loads.remove(key);
+        }
+    }
+
+    /// Cached value for `(class, id)`, or the result of `load`, which is cached on success.
+    /// Returns the value and whether it came from the cache. Concurrent callers for one key
+    /// share a single `load`; a stale local value is returned at once while `load` refreshes
+    /// it in the background. Errors are not cached.
+    pub async fn get_or_load<T, E, F, Fut>(
+        &self,
+        class: KeyClass,
+        id: &str,
+        load: F,
+    ) -> Result<(T, bool), E>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync + 'static,
+        E: Send + 'static,
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+    {
+        let key = self.key(class, id);
+        if let Local::Stale(raw) = self.local_get(&key) {
+            if let Some(value) = self.decode(class, &raw) {
+                self.metrics
+                    .cache_stale_hits
+                    .with_label_values(&[class.as_str()])
+                    .inc();
+                self.refresh_in_background(class, id, load);
+                return Ok((value, true));
             }
-        };
-        match conn.get::<_, String>("tvl".to_string()).await {
-            Ok(val) => val.parse().ok(),
-            Err(e) => {
-                if e.kind() == redis::ErrorKind::TypeError {
-                    None
-                } else {
-                    warn!("Redis get error: {}", e);
-                    None
-                }
+        }
+        if let Some(value) = self.lookup(class, &key).await {
+            return Ok((value, true));
+        }
+
+        let gate = self.load_gate(&key);
+        let guard = gate.lock().await;
+        // Filled by the load we waited for
+        if let Local::Fresh(raw) = self.local_get(&key) {
+            if let Some(value) = self.decode(class, &raw) {
+                self.metrics
+                    .cache_coalesced
+                    .with_label_values(&[class.as_str()])
+                    .inc();
+                drop(guard);
+                self.release_gate(&key, gate);
+                return Ok((value, true));
             }
         }
+        self.metrics
+            .cache_misses
+            .with_label_values(&[class.as_str()])
+            .inc();
+        let epoch = self.evictions.load(Ordering::SeqCst);
+        let result = load().await;
+        if let Ok(value) = &result {
+            self.store_loaded(class, id, value, epoch).await;
+        }
+        drop(guard);
+        self.release_gate(&key, gate);
+        result.map(|value| (value, false))
     }
 
-    pub async fn set_tvl(&self, value: i64) {
-        let mut conn = match self.client.get_async_connection().await {
-            Ok(conn) => conn,
-            Err(e) => {
-                warn!("Redis connection error: {}", e);
+    /// Runs `load` in the background unless a load for the key is already in progress.
+    fn refresh_in_background<T, E, F, Fut>(&self, class: KeyClass, id: &str, load: F)
+    where
+        T: Serialize + Send + Sync + 'static,
+        E: Send + 'static,
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, E>> + Send + 'static,
+    {
+        let key = self.key(class, id);
+        let gate = self.load_gate(&key);
+        let guard = match gate.clone().try_lock_owned() {
+            Ok(guard) => guard,
+            Err(_) => {
+                self.release_gate(&key, gate);
                 return;
             }
         };
-        if let Err(e) = conn
-            .set_ex::<_, _, ()>("tvl".to_string(), value.to_string(), 60)
-            .await
-        {
-            warn!("Redis set error: {}", e);
-        }
+        let cache = self.clone();
+        let id = id.to_string();
+        let epoch = self.evictions.load(Ordering::SeqCst);
+        tokio::spawn(async move {
+            if let Ok(value) = load().await {
+                cache.store_loaded(class, &id, &value, epoch).await;
+            }
+            drop(guard);
+            cache.release_gate(&key, gate);
+        });
+    }
+
+    pub async fn invalidate_balance(&self, owner: &str) {
+        self.invalidate(KeyClass::Balance, owner).await
+    }
+
+    pub async fn invalidate_tvl(&self) {
+        self.invalidate(KeyClass::Tvl, "").await
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::OnceLock;
+
+    /// Metrics register globally, so tests share one instance.
+    fn metrics() -> Arc<Metrics> {
+        static METRICS: OnceLock<Arc<Metrics>> = OnceLock::new();
+        METRICS.get_or_init(|| Metrics::new().unwrap()).clone()
+    }
+
+    fn local_cache(ttl_seconds: u64, stale_seconds: u64) -> Cache {
+        let settings = CacheSettings {
+            namespace: "cvms-test".to_string(),
+            ttls: CacheTtls::uniform(ttl_seconds),
+            stale_seconds,
+            local_capacity: 16,
+        };
+        Cache::new(settings, None, metrics())
+    }
+
+    #[test]
+    fn test_cache_key() {
+        assert_eq!(
+            cache_key("cvms", KeyClass::Balance, "owner1"),
+            "cvms:balance:v1:owner1"
+        );
+        assert_eq!(cache_key("cvms", KeyClass::Tvl, ""), "cvms:tvl:v2");
+    }
+
+    #[test]
+    fn test_ttls_parse() {
+        let ttls = CacheTtls::parse(60, " tvl=120 ,").unwrap();
+        assert_eq!(ttls.get(KeyClass::Tvl), 120);
+        assert_eq!(ttls.get(KeyClass::Balance), 60);
+        assert!(CacheTtls::parse(60, "positions=5").is_err());
+        assert!(CacheTtls::parse(60, "balance").is_err());
+        assert!(CacheTtls::parse(60, "balance=soon").is_err());
+    }
+
+    #[test]
+    fn test_classify() {
+        let now = Instant::now();
+        let entry = LocalEntry {
+            raw: "1".to_string(),
+            fresh_until: now + Duration::from_secs(10),
+            stale_until: now + Duration::from_secs(20),
+        };
+        assert_eq!(classify(Some(&entry), now), Local::Fresh("1".to_string()));
+        assert_eq!(
+            classify(Some(&entry), now + Duration::from_secs(15)),
+            Local::Stale("1".to_string())
+        );
+        assert_eq!(
+            classify(Some(&entry), now + Duration::from_secs(20)),
+            Local::Missing
+        );
+        assert_eq!(classify(None, now), Local::Missing);
+    }
+
+    #[test]
+    fn test_local_tier_is_bounded() {
+        let cache = Cache::new(
+            CacheSettings {
+                namespace: "cvms-test".to_string(),
+                ttls: CacheTtls::uniform(60),
+                stale_seconds: 0,
+                local_capacity: 2,
+            },
+            None,
+            metrics(),
+        );
+        for owner in ["a", "b", "c"] {
+            let key = cache.key(KeyClass::Balance, owner);
+            cache.local_set(KeyClass::Balance, key, "1".to_string());
+        }
+        assert_eq!(
+            cache.local_get(&cache.key(KeyClass::Balance, "a")),
+            Local::Missing
+        );
+        assert_eq!(
+            cache.local_get(&cache.key(KeyClass::Balance, "c")),
+            Local::Fresh("1".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_misses_share_one_load() {
+        let cache = local_cache(60, 0);
+        let loads = Arc::new(AtomicUsize::new(0));
+        let mut tasks = Vec::new();
+        for _ in 0..8 {
+            let cache = cache.clone();
+            let loads = loads.clone();
+            tasks.push(tokio::spawn(async move {
+                cache
+                    .get_or_load(KeyClass::Balance, "owner1", move || async move {
+                        loads.fetch_add(1, Ordering::SeqCst);
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        Ok::<u64, String>(42)
+                    })
+                    .await
+            }));
+        }
+        let mut from_cache = 0;
+        for task in tasks {
+            let (value, cached) = task.await.unwrap().unwrap();
+            assert_eq!(value, 42);
+            from_cache += cached as usize;
+        }
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+        assert_eq!(from_cache, 7);
+        assert!(cache.loads.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_failed_load_is_not_cached() {
+        let cache = local_cache(60, 0);
+        let failed = cache
+            .get_or_load(KeyClass::Tvl, "", || async { Err::<i64, _>("rpc down") })
+            .await;
+        assert_eq!(failed, Err("rpc down"));
+        let loaded = cache
+            .get_or_load(KeyClass::Tvl, "", || async { Ok::<i64, &str>(7) })
+            .await;
+        assert_eq!(loaded, Ok((7, false)));
+    }
+
+    #[tokio::test]
+    async fn test_stale_value_served_while_refreshing() {
+        // Zero TTL: every entry is stale as soon as it is written
+        let cache = local_cache(0, 60);
+        cache.set_json(KeyClass::Balance, "owner1", &1u64).await;
+
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let (value, cached) = cache
+            .get_or_load(KeyClass::Balance, "owner1", move || async move {
+                let _ = tx.send(());
+                Ok::<u64, String>(2)
+            })
+            .await
+            .unwrap();
+        assert_eq!((value, cached), (1, true));
+
+        // The background refresh ran and replaced the entry
+        rx.await.unwrap();
+        for _ in 0..100 {
+            if cache.loads.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let key = cache.key(KeyClass::Balance, "owner1");
+        assert_eq!(cache.local_get(&key), Local::Stale("2".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_remote_invalidation_evicts_local_tier() {
+        let cache = local_cache(60, 0);
+        cache.set_json(KeyClass::Balance, "owner1", &1u64).await;
+        let key = cache.key(KeyClass::Balance, "owner1");
+
+        let own = Invalidation {
+            origin: cache.instance_id.to_string(),
+            class: KeyClass::Balance,
+            id: "owner1".to_string(),
+        };
+        assert!(!cache.apply_invalidation(&own));
+        assert_eq!(cache.local_get(&key), Local::Fresh("1".to_string()));
+
+        let remote = Invalidation {
+            origin: "other-replica".to_string(),
+            ..own
+        };
+        let payload = serde_json::to_string(&remote).unwrap();
+        assert!(payload.contains(r#""class":"balance""#));
+        let decoded: Invalidation = serde_json::from_str(&payload).unwrap();
+        assert!(cache.apply_invalidation(&decoded));
+        assert_eq!(cache.local_get(&key), Local::Missing);
+    }
+
+    #[tokio::test]
+    async fn test_load_overtaken_by_invalidation_is_not_cached() {
+        let cache = local_cache(60, 0);
+        let (started_tx, started_rx) = tokio::sync::oneshot::channel();
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        let loader = cache.clone();
+        let task = tokio::spawn(async move {
+            loader
+                .get_or_load(KeyClass::Balance, "owner1", move || async move {
+                    let _ = started_tx.send(());
+                    let _ = release_rx.await;
+                    Ok::<u64, String>(1)
+                })
+                .await
+        });
+        started_rx.await.unwrap();
+        cache.invalidate_balance("owner1").await;
+        release_tx.send(()).unwrap();
+
+        assert_eq!(task.await.unwrap(), Ok((1, false)));
+        let key = cache.key(KeyClass::Balance, "owner1");
+        assert_eq!(cache.local_get(&key), Local::Missing);
+    }
 
     #[tokio::test]
     #[ignore] // Requires Redis server
     async fn test_cache_operations() {
-        let cache = Cache::new("redis://localhost:6379", 60).unwrap();
+        let redis = connect_redis("redis://localhost:6379").await.unwrap();
+        let settings = CacheSettings {
+            namespace: "cvms-test".to_string(),
+            ttls: CacheTtls::uniform(60),
+            stale_seconds: 0,
+            local_capacity: 16,
+        };
+        let cache = Cache::new(settings.clone(), Some(redis.clone()), metrics());
         let key = "test_owner";
-        
+
         // Test set and get
-        cache.set_balance(key, 1000).await;
-        let balance = cache.get_balance(key).await;
-        assert_eq!(balance, Some(1000));
-        
+        cache.set_json(KeyClass::Balance, key, &1000u64).await;
+        assert_eq!(cache.get_json(KeyClass::Balance, key).await, Some(1000u64));
+
+        // A second instance reads it through Redis
+        let other = Cache::new(settings, Some(redis), metrics());
+        assert_eq!(other.get_json(KeyClass::Balance, key).await, Some(1000u64));
+
         // Test invalidation
         cache.invalidate_balance(key).await;
-        let balance_after = cache.get_balance(key).await;
-        assert_eq!(balance_after, None);
+        assert_eq!(cache.get_json::<u64>(KeyClass::Balance, key).await, None);
     }
 }
@@ -0,0 +1,203 @@
+//! Vault freezes and global kill switches.
+//!
+//! A frozen vault (`vaults.status = 'frozen'`) rejects every operation that would build or
+//! submit a transaction for it until an admin unfreezes it. A switched-off operation in
+//! `operation_switches` is rejected for every vault. Freezes come from admins or from the
+//! anomaly rules engine; every change is written to the audit trail and published on
+//! `security_alert`.
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use crate::db;
+use crate::events::{EventEnvelope, Severity, VaultEvent};
+use crate::notify::Notifier;
+
+/// Operation types with a global kill switch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    Deposits,
+    /// Direct, scheduled, requested and multisig withdrawals.
+    Withdrawals,
+    /// Position manager collateral lock and unlock.
+    Locks,
+    /// Yield deposit, withdraw and compound.
+    Yield,
+    /// Collateral transfers between vaults.
+    Transfers,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 5] = [
+        Operation::Deposits,
+        Operation::Withdrawals,
+        Operation::Locks,
+        Operation::Yield,
+        Operation::Transfers,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Deposits => "deposits",
+            Operation::Withdrawals => "withdrawals",
+            Operation::Locks => "locks",
+            Operation::Yield => "yield",
+            Operation::Transfers => "transfers",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Operation> {
+        Operation::ALL.into_iter().find(|o| o.as_str() == s)
+    }
+}
+
+/// Why an operation was refused.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Blocked {
+    VaultFrozen {
+        owner: String,
+        reason: Option<String>,
+    },
+    OperationDisabled {
+        operation: Operation,
+        reason: Option<String>,
+    },
+}
+
+impl Blocked {
+    pub fn message(&self) -> String {
+        match self {
+            Blocked::VaultFrozen { owner, .. } => format!("vault {owner} is frozen"),
+            Blocked::OperationDisabled { operation, .. } => {
+                format!("operation {} is disabled", operation.as_str())
+            }
+        }
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            Blocked::VaultFrozen { reason, .. } | Blocked::OperationDisabled { reason, .. } => {
+                reason.as_deref()
+            }
+        }
+    }
+}
+
+/// Checks the kill switch for `operation`, then whether any of `owners`' vaults is frozen.
+pub async fn check(
+    pool: &PgPool,
+    owners: &[&str],
+    operation: Operation,
+) -> Result<Option<Blocked>, sqlx::Error> {
+    if let Some(reason) = db::operation_switch_disabled(pool, operation.as_str()).await? {
+        return Ok(Some(Blocked::OperationDisabled { operation, reason }));
+    }
+    let owners: Vec<String> = owners.iter().map(|o| o.to_string()).collect();
+    Ok(db::vault_frozen_any(pool, &owners)
+        .await?
+        .map(|(owner, reason, _, _)| Blocked::VaultFrozen { owner, reason }))
+}
+
+/// Freezes `owner`'s vault. Returns false if it does not exist or is already frozen.
+pub async fn freeze(
+    pool: &PgPool,
+    notifier: &Notifier,
+    owner: &str,
+    reason: &str,
+    frozen_by: &str,
+) -> Result<bool, sqlx::Error> {
+    if !db::vault_freeze(pool, owner, reason, frozen_by).await? {
+        return Ok(false);
+    }
+    let details = serde_json::json!({ "reason": reason, "by": frozen_by });
+    db::insert_audit_log(pool, Some(owner), "vault_frozen", details.clone()).await?;
+    notifier.publish_for(
+        owner,
+        VaultEvent::security("vault_frozen", Severity::Critical, details),
+    );
+    Ok(true)
+}
+
+/// Unfreezes `owner`'s vault. Returns false if it was not frozen.
+pub async fn unfreeze(
+    pool: &PgPool,
+    notifier: &Notifier,
+    owner: &str,
+    reason: Option<&str>,
+    unfrozen_by: &str,
+) -> Result<bool, sqlx::Error> {
+    if !db::vault_unfreeze(pool, owner).await? {
+        return Ok(false);
+    }
+    let details = serde_json::json!({ "reason": reason, "by": unfrozen_by });
+    db::insert_audit_log(pool, Some(owner), "vault_unfrozen", details.clone()).await?;
+    notifier.publish_for(
+        owner,
+        VaultEvent::security("vault_unfrozen", Severity::Warning, details),
+    );
+    Ok(true)
+}
+
+/// Switches `operation` on or off for every vault. Returns true if its state changed; only
+/// changes are published, every call is audited.
+pub async fn set_operation(
+    pool: &PgPool,
+    notifier: &Notifier,
+    operation: Operation,
+    enabled: bool,
+    reason: Option<&str>,
+    updated_by: &str,
+) -> Result<bool, sqlx::Error> {
+    let changed =
+        db::operation_switch_set(pool, operation.as_str(), enabled, reason, updated_by).await?;
+    let (kind, severity) = if enabled {
+        ("operation_enabled", Severity::Warning)
+    } else {
+        ("operation_disabled", Severity::Critical)
+    };
+    let details = serde_json::json!({
+        "operation": operation.as_str(),
+        "reason": reason,
+        "by": updated_by,
+    });
+    db::insert_audit_log(pool, None, kind, details.clone()).await?;
+    if changed {
+        notifier.publish(EventEnvelope::new(VaultEvent::security(
+            kind, severity, details,
+        )));
+    }
+    Ok(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operation_round_trip() {
+        for op in Operation::ALL {
+            assert_eq!(Operation::parse(op.as_str()), Some(op));
+        }
+        assert_eq!(Operation::parse("deposit"), None);
+        assert_eq!(
+            serde_json::to_value(Operation::Withdrawals).unwrap(),
+            "withdrawals"
+        );
+    }
+
+    #[test]
+    fn test_blocked_message() {
+        let frozen = Blocked::VaultFrozen {
+            owner: "abc".to_string(),
+            reason: Some("investigating".to_string()),
+        };
+        assert_eq!(frozen.message(), "vault abc is frozen");
+        assert_eq!(frozen.reason(), Some("investigating"));
+        let disabled = Blocked::OperationDisabled {
+            operation: Operation::Yield,
+            reason: None,
+        };
+        assert_eq!(disabled.message(), "operation yield is disabled");
+    }
+}
@@ -1,5 +1,29 @@
 use serde::Deserialize;
 
+/// Shortest cooling-off accepted for admin 2FA resets; lower settings are raised to it.
+pub const MIN_TWOFA_RESET_COOLDOWN_SECONDS: i64 = 3600;
+
+/// What a WebSocket connection does when its send queue is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlowConsumerPolicy {
+    /// Subscriptions wait for queue space; with the outbox, a lagging subscription catches up
+    /// from `event_outbox` instead of losing events.
+    Wait,
+    /// Close the connection; the client reconnects and resumes from its last cursor.
+    Disconnect,
+}
+
+impl SlowConsumerPolicy {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "wait" => Some(SlowConsumerPolicy::Wait),
+            "disconnect" => Some(SlowConsumerPolicy::Disconnect),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct AppConfig {
     pub host: String,
@@ -11,12 +35,64 @@ pub struct AppConfig {
     pub deployer_keypair_path: String,
     pub vault_authority_pubkey: String,
     pub admin_jwt_secret: String,
+    /// Signs event feed session tokens. A random per-process key when unset.
+    pub events_session_secret: String,
     pub position_manager_program_id: String,
     pub reconciliation_threshold: i64,
+    /// Largest drift reconciliation corrects by itself; larger drift is escalated. 0 disables.
+    pub reconciliation_auto_heal_max: i64,
     pub low_balance_threshold: i64,
     pub redis_url: String,
     pub cache_ttl_seconds: u64,
+    /// Per key class TTL overrides, e.g. `balance=30,tvl=120`.
+    pub cache_ttls: String,
+    /// Key prefix separating deployments that share a Redis.
+    pub cache_namespace: String,
+    /// Seconds past its TTL a locally cached value may be served while it refreshes.
+    pub cache_stale_seconds: u64,
+    /// Maximum entries in the in-process cache tier.
+    pub cache_local_capacity: usize,
     pub balance_monitor_interval_seconds: u64,
+    pub nonce_ttl_seconds: i64,
+    pub nonce_cleanup_interval_seconds: u64,
+    pub twofa_encryption_key: String,
+    pub twofa_issuer: String,
+    pub twofa_reset_cooldown_seconds: i64,
+    pub webauthn_rp_id: String,
+    pub webauthn_origin: String,
+    pub event_retention_hours: i64,
+    pub webhook_max_attempts: i32,
+    /// Lets webhook deliveries reach loopback and private addresses (local development only).
+    pub webhook_allow_private_targets: bool,
+    pub ws_send_queue_capacity: usize,
+    pub ws_slow_consumer_policy: SlowConsumerPolicy,
+    pub ws_max_subscriptions: usize,
+    /// Email is disabled while empty.
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    /// `starttls`, `tls` (implicit) or `none`.
+    pub smtp_tls: String,
+    pub smtp_username: String,
+    pub smtp_password: String,
+    pub smtp_from: String,
+    pub email_rate_limit_per_hour: i64,
+    /// Externally reachable base URL, used for unsubscribe links.
+    pub public_base_url: String,
+    /// Re-notify an unacknowledged open alert after this many seconds; 0 disables.
+    pub alert_renotify_seconds: i64,
+    /// JSON array of anomaly rules; overrides the stored rule with the same id.
+    pub anomaly_rules: String,
+    pub ledger_check_interval_seconds: u64,
+    /// Age after which a submitted withdrawal is settled from its finalized status.
+    pub ledger_settle_after_seconds: i64,
+    pub balance_snapshot_interval_seconds: u64,
+    /// Hourly snapshots older than this are dropped once rolled up into daily ones.
+    pub balance_snapshot_hourly_retention_days: i64,
+    /// How far back missing daily snapshots are backfilled.
+    pub balance_snapshot_backfill_days: i64,
+    pub proof_of_reserves_interval_seconds: u64,
+    /// Publish each proof-of-reserves root in a memo transaction paid by the deployer.
+    pub proof_of_reserves_anchor: bool,
 }
 
 impl AppConfig {
@@ -37,12 +113,26 @@ impl AppConfig {
             deployer_keypair_path: std::env::var("DEPLOYER_KEYPAIR_PATH").unwrap_or_default(),
             vault_authority_pubkey: std::env::var("VAULT_AUTHORITY_PUBKEY").unwrap_or_default(),
             admin_jwt_secret: std::env::var("ADMIN_JWT_SECRET").unwrap_or_default(),
+            events_session_secret: std::env::var("EVENTS_SESSION_SECRET")
+                .ok()
+                .filter(|v| !v.is_empty())
+                .unwrap_or_else(|| {
+                    format!(
+                        "{}{}",
+                        uuid::Uuid::new_v4().simple(),
+                        uuid::Uuid::new_v4().simple()
+                    )
+                }),
             position_manager_program_id: std::env::var("POSITION_MANAGER_PROGRAM_ID")
                 .unwrap_or_default(),
             reconciliation_threshold: std::env::var("RECONCILIATION_THRESHOLD")
                 .ok()
                 .and_then(|v| v.parse().ok())
                 .unwrap_or(0),
+            reconciliation_auto_heal_max: std::env::var("RECONCILIATION_AUTO_HEAL_MAX")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0),
             low_balance_threshold: std::env::var("LOW_BALANCE_THRESHOLD")
                 .ok()
                 .and_then(|v| v.parse().ok())
@@ -53,10 +143,115 @@ impl AppConfig {
                 .ok()
                 .and_then(|v| v.parse().ok())
                 .unwrap_or(60),
+            cache_ttls: std::env::var("CACHE_TTLS").unwrap_or_default(),
+            cache_namespace: std::env::var("CACHE_NAMESPACE")
+                .unwrap_or_else(|_| "cvms".to_string()),
+            cache_stale_seconds: std::env::var("CACHE_STALE_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            cache_local_capacity: std::env::var("CACHE_LOCAL_CAPACITY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10_000),
             balance_monitor_interval_seconds: std::env::var("BALANCE_MONITOR_INTERVAL_SECONDS")
                 .ok()
                 .and_then(|v| v.parse().ok())
                 .unwrap_or(30),
+            nonce_ttl_seconds: std::env::var("NONCE_TTL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300),
+            nonce_cleanup_interval_seconds: std::env::var("NONCE_CLEANUP_INTERVAL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(600),
+            twofa_encryption_key: std::env::var("TWOFA_ENCRYPTION_KEY").unwrap_or_default(),
+            twofa_issuer: std::env::var("TWOFA_ISSUER").unwrap_or_else(|_| "CVMS".to_string()),
+            twofa_reset_cooldown_seconds: std::env::var("TWOFA_RESET_COOLDOWN_SECONDS")
+                .ok()
+                .and_then(|v| v.parse::<i64>().ok())
+                .unwrap_or(72 * 3600)
+                .max(MIN_TWOFA_RESET_COOLDOWN_SECONDS),
+            webauthn_rp_id: std::env::var("WEBAUTHN_RP_ID")
+                .unwrap_or_else(|_| "localhost".to_string()),
+            webauthn_origin: std::env::var("WEBAUTHN_ORIGIN")
+                .unwrap_or_else(|_| "http://localhost:8080".to_string()),
+            event_retention_hours: std::env::var("EVENT_RETENTION_HOURS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(7 * 24),
+            webhook_max_attempts: std::env::var("WEBHOOK_MAX_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(8),
+            webhook_allow_private_targets: std::env::var("WEBHOOK_ALLOW_PRIVATE_TARGETS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(false),
+            ws_send_queue_capacity: std::env::var("WS_SEND_QUEUE_CAPACITY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(256),
+            ws_slow_consumer_policy: std::env::var("WS_SLOW_CONSUMER_POLICY")
+                .ok()
+                .and_then(|v| SlowConsumerPolicy::parse(&v))
+                .unwrap_or(SlowConsumerPolicy::Disconnect),
+            ws_max_subscriptions: std::env::var("WS_MAX_SUBSCRIPTIONS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(64),
+            smtp_host: std::env::var("SMTP_HOST").unwrap_or_default(),
+            smtp_port: std::env::var("SMTP_PORT")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(587),
+            smtp_tls: std::env::var("SMTP_TLS").unwrap_or_else(|_| "starttls".to_string()),
+            smtp_username: std::env::var("SMTP_USERNAME").unwrap_or_default(),
+            smtp_password: std::env::var("SMTP_PASSWORD").unwrap_or_default(),
+            smtp_from: std::env::var("SMTP_FROM")
+                .unwrap_or_else(|_| "CVMS <no-reply@localhost>".to_string()),
+            email_rate_limit_per_hour: std::env::var("EMAIL_RATE_LIMIT_PER_HOUR")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            public_base_url: std::env::var("PUBLIC_BASE_URL")
+                .unwrap_or_else(|_| "http://localhost:8080".to_string()),
+            alert_renotify_seconds: std::env::var("ALERT_RENOTIFY_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3600),
+            anomaly_rules: std::env::var("ANOMALY_RULES").unwrap_or_default(),
+            ledger_check_interval_seconds: std::env::var("LEDGER_CHECK_INTERVAL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300),
+            ledger_settle_after_seconds: std::env::var("LEDGER_SETTLE_AFTER_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(120),
+            balance_snapshot_interval_seconds: std::env::var("BALANCE_SNAPSHOT_INTERVAL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300),
+            balance_snapshot_hourly_retention_days: std::env::var(
+                "BALANCE_SNAPSHOT_HOURLY_RETENTION_DAYS",
+            )
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(7),
+            balance_snapshot_backfill_days: std::env::var("BALANCE_SNAPSHOT_BACKFILL_DAYS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(30),
+            proof_of_reserves_interval_seconds: std::env::var("PROOF_OF_RESERVES_INTERVAL_SECONDS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(3600),
+            proof_of_reserves_anchor: std::env::var("PROOF_OF_RESERVES_ANCHOR")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(false),
         }
     }
 }
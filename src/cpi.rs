@@ -1,7 +1,7 @@
 use crate::{
     api::AppState,
-    db,
     error::{AppError, AppResult},
+    ledger::{self, EntryKind},
     solana_client::{
         build_compute_budget_instructions, build_instruction_pm_lock, build_instruction_pm_unlock,
         load_deployer_keypair, send_transaction_with_retries,
@@ -42,8 +42,20 @@ impl CPIManager {
             recent_blockhash,
         );
         let sig = send_transaction_with_retries(&self.state.sol, &tx, 3).await?;
-        let _ =
-            db::increment_locked_balance(&self.state.pool, &owner.to_string(), amount as i64).await;
+        let _ = ledger::record(
+            &self.state.pool,
+            EntryKind::Lock,
+            &sig.to_string(),
+            &owner.to_string(),
+            amount as i64,
+        )
+        .await;
+        let _ = self
+            .state
+            .repos
+            .vaults
+            .add_locked(&owner.to_string(), amount as i64)
+            .await;
         Ok(sig.to_string())
     }
 
@@ -69,9 +81,20 @@ impl CPIManager {
             recent_blockhash,
         );
         let sig = send_transaction_with_retries(&self.state.sol, &tx, 3).await?;
-        let _ =
-            db::increment_locked_balance(&self.state.pool, &owner.to_string(), -(amount as i64))
-                .await;
+        let _ = ledger::record(
+            &self.state.pool,
+            EntryKind::Unlock,
+            &sig.to_string(),
+            &owner.to_string(),
+            amount as i64,
+        )
+        .await;
+        let _ = self
+            .state
+            .repos
+            .vaults
+            .add_locked(&owner.to_string(), -(amount as i64))
+            .await;
         Ok(sig.to_string())
     }
 }
@@ -1,4 +1,14 @@
-use sqlx::{postgres::PgPoolOptions, PgPool};
+use sqlx::{postgres::PgPoolOptions, PgExecutor, PgPool};
+
+use crate::ledger::{LedgerBalances, LedgerPosting};
+use crate::proof_of_reserves::{Leaf, ReserveSnapshot};
+use crate::reconciliation::{ReconciliationCase, SnapshotRow, VaultBalances};
+use crate::repo::{Proposal, Timelock, Transaction, Vault};
+use crate::snapshots::BalanceSnapshot;
+use crate::tvl::{Tvl, TvlPoint};
+
+/// Columns of [`Vault`]; `vaults` also carries freeze metadata that the struct leaves out.
+const VAULT_COLUMNS: &str = "owner, token_account, total_deposits, total_withdrawals, total_balance, locked_balance, status, updated_at";
 
 pub async fn connect(database_url: &str) -> Result<PgPool, sqlx::Error> {
     let max_connections = std::env::var("DB_MAX_CONNECTIONS")
@@ -15,296 +25,76 @@ pub async fn connect(database_url: &str) -> Result<PgPool, sqlx::Error> {
         .await
 }
 
-pub async fn init(pool: &PgPool) -> Result<(), sqlx::Error> {
-    // Minimal tables to support nonces, tx history, and admin authorized programs
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS nonces (
-			nonce TEXT PRIMARY KEY,
-			owner TEXT NOT NULL,
-			issued_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-			used BOOLEAN NOT NULL DEFAULT FALSE
-		)",
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS transactions (
-			id BIGSERIAL PRIMARY KEY,
-			owner TEXT NOT NULL,
-			signature TEXT NOT NULL,
-			amount BIGINT,
-			kind TEXT NOT NULL,
-			status TEXT NOT NULL DEFAULT 'pending',
-			retry_count INT NOT NULL DEFAULT 0,
-			created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-			updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-		)",
-    )
-    .execute(pool)
-    .await?;
-
-    // Idempotency on signature
-    sqlx::query(
-        "CREATE UNIQUE INDEX IF NOT EXISTS transactions_signature_key ON transactions(signature)",
-    )
-    .execute(pool)
-    .await?;
-
-    // Hot indexes for performance
-    sqlx::query(
-		"CREATE INDEX IF NOT EXISTS idx_transactions_owner_created_at ON transactions(owner, created_at DESC)"
-	)
-	.execute(pool)
-	.await?;
-
-    sqlx::query(
-		"CREATE INDEX IF NOT EXISTS idx_transactions_status ON transactions(status)"
-	)
-	.execute(pool)
-	.await?;
-
-    // Add status column if missing (for existing databases)
-    sqlx::query(
-        "ALTER TABLE transactions ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'pending'"
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query(
-        "ALTER TABLE transactions ADD COLUMN IF NOT EXISTS retry_count INT NOT NULL DEFAULT 0"
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query(
-        "ALTER TABLE transactions ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()"
-    )
-    .execute(pool)
-    .await?;
-
-    // Vault snapshots
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS vaults (
-			owner TEXT PRIMARY KEY,
-			token_account TEXT,
-			total_deposits BIGINT NOT NULL DEFAULT 0,
-			total_withdrawals BIGINT NOT NULL DEFAULT 0,
-			total_balance BIGINT NOT NULL DEFAULT 0,
-			updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-		)",
-    )
-    .execute(pool)
-    .await?;
-
-    // Timelock schedules per owner
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS timelocks (
-			id BIGSERIAL PRIMARY KEY,
-			owner TEXT NOT NULL,
-			amount BIGINT NOT NULL,
-			unlock_at TIMESTAMPTZ NOT NULL,
-			status TEXT NOT NULL DEFAULT 'scheduled',
-			created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-			updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-		)",
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query(
-        "CREATE INDEX IF NOT EXISTS idx_timelocks_owner_unlock_at ON timelocks(owner, unlock_at)",
-    )
-    .execute(pool)
-    .await?;
-
-    // Withdrawal whitelist (off-chain mirror for audit)
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS withdraw_whitelist (
-			owner TEXT NOT NULL,
-			address TEXT NOT NULL,
-			added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-			PRIMARY KEY (owner, address)
-		)",
-    )
-    .execute(pool)
-    .await?;
-
-    // Two-factor auth secrets per owner
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS twofa (
-			owner TEXT PRIMARY KEY,
-			secret TEXT NOT NULL,
-			enabled BOOLEAN NOT NULL DEFAULT FALSE,
-			updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-		)",
-    )
-    .execute(pool)
-    .await?;
-
-    // Add new columns if missing
-    sqlx::query(
-        "ALTER TABLE vaults ADD COLUMN IF NOT EXISTS locked_balance BIGINT NOT NULL DEFAULT 0",
-    )
-    .execute(pool)
-    .await?;
-    sqlx::query(
-        "ALTER TABLE vaults ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'active'",
-    )
-    .execute(pool)
-    .await?;
-
-    // Reconciliation logs
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS reconciliation_logs (
-			id BIGSERIAL PRIMARY KEY,
-			vault_owner TEXT NOT NULL,
-			token_account TEXT,
-			db_balance BIGINT NOT NULL,
-			chain_balance BIGINT NOT NULL,
-			discrepancy BIGINT NOT NULL,
-			threshold BIGINT NOT NULL,
-			created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-		)",
-    )
-    .execute(pool)
-    .await?;
-
-    // Balance snapshots (hourly/daily)
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS balance_snapshots (
-			id BIGSERIAL PRIMARY KEY,
-			owner TEXT NOT NULL,
-			balance BIGINT NOT NULL,
-			locked_balance BIGINT NOT NULL,
-			granularity TEXT NOT NULL,
-			recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-		)",
-    )
-    .execute(pool)
-    .await?;
-
-    // Audit trail
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS audit_trail (
-			id BIGSERIAL PRIMARY KEY,
-			owner TEXT,
-			action TEXT NOT NULL,
-			details JSONB,
-			created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-		)",
-    )
-    .execute(pool)
-    .await?;
-
-    // Yield events/history
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS yield_events (
-			id BIGSERIAL PRIMARY KEY,
-			owner TEXT NOT NULL,
-			protocol TEXT NOT NULL,
-			amount BIGINT NOT NULL,
-			kind TEXT NOT NULL,
-			created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-		)",
-    )
-    .execute(pool)
-    .await?;
-
-    // Protocol APY snapshots (for analytics)
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS protocol_apy (
-			id BIGSERIAL PRIMARY KEY,
-			protocol TEXT NOT NULL,
-			apy DOUBLE PRECISION NOT NULL,
-			recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-		)",
-    )
-    .execute(pool)
-    .await?;
-
-    // Vault delegates (off-chain allowlist for UI and prechecks)
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS vault_delegates (
-			owner TEXT NOT NULL,
-			delegate TEXT NOT NULL,
-			added_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-			PRIMARY KEY (owner, delegate)
-		)",
-    )
-    .execute(pool)
-    .await?;
-
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS authorized_programs (
-			program_id TEXT PRIMARY KEY,
-			added_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-		)",
-    )
-    .execute(pool)
-    .await?;
-
-    // Multisig proposal tables
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS ms_proposals (
-            id UUID PRIMARY KEY,
-            owner TEXT NOT NULL,
-            amount BIGINT NOT NULL,
-            threshold INT NOT NULL,
-            signers JSONB NOT NULL,
-            status TEXT NOT NULL DEFAULT 'pending',
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        )",
-    )
-    .execute(pool)
-    .await?;
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS ms_approvals (
-            id BIGSERIAL PRIMARY KEY,
-            proposal_id UUID NOT NULL REFERENCES ms_proposals(id) ON DELETE CASCADE,
-            signer TEXT NOT NULL,
-            signature TEXT NOT NULL,
-            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-            UNIQUE (proposal_id, signer)
-        )",
+pub async fn insert_nonce(
+    pool: &PgPool,
+    nonce: &str,
+    owner: &str,
+    purpose: &str,
+    ttl_seconds: i64,
+) -> Result<time::OffsetDateTime, sqlx::Error> {
+    let expires_at = sqlx::query_scalar::<_, time::OffsetDateTime>(
+        "INSERT INTO nonces (nonce, owner, purpose, expires_at)
+         VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))
+         RETURNING expires_at",
     )
-    .execute(pool)
+    .bind(nonce)
+    .bind(owner)
+    .bind(purpose)
+    .bind(ttl_seconds)
+    .fetch_one(pool)
     .await?;
+    Ok(expires_at)
+}
 
-    // Optional contacts for signers to deliver notifications
-    sqlx::query(
-        "CREATE TABLE IF NOT EXISTS ms_signer_contacts (
-            pubkey TEXT PRIMARY KEY,
-            email TEXT,
-            webhook TEXT,
-            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-        )",
+/// Atomically marks a nonce as used. Succeeds only if it belongs to `owner`, was issued for
+/// `purpose`, is unused and has not expired.
+pub async fn consume_nonce(
+    pool: &PgPool,
+    nonce: &str,
+    owner: &str,
+    purpose: &str,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE nonces SET used = TRUE
+         WHERE nonce = $1 AND owner = $2 AND purpose = $3 AND used = FALSE AND expires_at > NOW()",
     )
+    .bind(nonce)
+    .bind(owner)
+    .bind(purpose)
     .execute(pool)
     .await?;
-
-    Ok(())
+    if res.rows_affected() != 1 {
+        auth_failure_insert(pool, owner, "nonce").await?;
+        return Ok(false);
+    }
+    Ok(true)
 }
 
-pub async fn insert_nonce(pool: &PgPool, nonce: &str, owner: &str) -> Result<(), sqlx::Error> {
-    sqlx::query("INSERT INTO nonces (nonce, owner) VALUES ($1, $2) ON CONFLICT (nonce) DO NOTHING")
-        .bind(nonce)
+pub async fn auth_failure_insert(pool: &PgPool, owner: &str, kind: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO auth_failures (owner, kind) VALUES ($1, $2)")
         .bind(owner)
+        .bind(kind)
         .execute(pool)
         .await?;
     Ok(())
 }
 
-pub async fn consume_nonce(pool: &PgPool, nonce: &str, owner: &str) -> Result<bool, sqlx::Error> {
+/// Deletes auth failures older than a day; no rule window is longer.
+pub async fn prune_auth_failures(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM auth_failures WHERE created_at < NOW() - INTERVAL '1 day'")
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected())
+}
+
+/// Deletes used nonces and nonces that expired (including legacy rows without an expiry).
+pub async fn prune_nonces(pool: &PgPool) -> Result<u64, sqlx::Error> {
     let res = sqlx::query(
-        "UPDATE nonces SET used = TRUE WHERE nonce = $1 AND owner = $2 AND used = FALSE",
+        "DELETE FROM nonces WHERE used = TRUE OR expires_at IS NULL OR expires_at <= NOW()",
     )
-    .bind(nonce)
-    .bind(owner)
     .execute(pool)
     .await?;
-    Ok(res.rows_affected() == 1)
+    Ok(res.rows_affected())
 }
 
 pub async fn add_authorized_program(pool: &PgPool, program_id: &str) -> Result<(), sqlx::Error> {
@@ -371,9 +161,9 @@ pub async fn increment_transaction_retry(
 pub async fn get_pending_transactions(
     pool: &PgPool,
     limit: i64,
-) -> Result<Vec<(String, String, String, i32)>, sqlx::Error> {
-    let rows = sqlx::query_as::<_, (String, String, String, i32)>(
-        "SELECT owner, signature, kind, retry_count FROM transactions WHERE status = 'pending' ORDER BY created_at ASC LIMIT $1"
+) -> Result<Vec<Transaction>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, Transaction>(
+        "SELECT * FROM transactions WHERE status = 'pending' ORDER BY created_at ASC LIMIT $1",
     )
     .bind(limit)
     .fetch_all(pool)
@@ -386,28 +176,56 @@ pub async fn list_transactions(
     owner: &str,
     limit: i64,
     offset: i64,
-) -> Result<Vec<(i64, String, Option<i64>, String, time::OffsetDateTime)>, sqlx::Error> {
-    let rows = sqlx::query_as::<_, (i64, String, Option<i64>, String, time::OffsetDateTime)>(
-		"SELECT id, signature, amount, kind, created_at FROM transactions WHERE owner = $1 ORDER BY id DESC LIMIT $2 OFFSET $3"
-	)
-	.bind(owner)
-	.bind(limit)
-	.bind(offset)
-	.fetch_all(pool)
-	.await?;
+) -> Result<Vec<Transaction>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, Transaction>(
+        "SELECT * FROM transactions WHERE owner = $1 ORDER BY id DESC LIMIT $2 OFFSET $3",
+    )
+    .bind(owner)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await?;
     Ok(rows)
 }
 
-pub async fn get_vault(
+pub async fn sum_withdrawals_since(
     pool: &PgPool,
     owner: &str,
-) -> Result<Option<(Option<String>, i64)>, sqlx::Error> {
-    let row = sqlx::query_as::<_, (Option<String>, i64)>(
-        "SELECT token_account, total_balance FROM vaults WHERE owner = $1",
+    since: time::OffsetDateTime,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        "SELECT COALESCE(SUM(amount), 0)::BIGINT FROM transactions WHERE owner = $1 AND kind = 'withdraw' AND created_at >= $2",
     )
     .bind(owner)
-    .fetch_optional(pool)
-    .await?;
+    .bind(since)
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn count_transaction_owners(pool: &PgPool) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>("SELECT COUNT(DISTINCT owner)::BIGINT FROM transactions")
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn transaction_volume_since(
+    pool: &PgPool,
+    since: time::OffsetDateTime,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        "SELECT COALESCE(SUM(ABS(amount)), 0)::BIGINT FROM transactions WHERE created_at > $1",
+    )
+    .bind(since)
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn get_vault(pool: &PgPool, owner: &str) -> Result<Option<Vault>, sqlx::Error> {
+    let sql = format!("SELECT {VAULT_COLUMNS} FROM vaults WHERE owner = $1");
+    let row = sqlx::query_as::<_, Vault>(&sql)
+        .bind(owner)
+        .fetch_optional(pool)
+        .await?;
     Ok(row)
 }
 
@@ -477,6 +295,28 @@ pub async fn increment_locked_balance(
     Ok(())
 }
 
+/// Adds to the yield balances of an existing vault, never going below zero.
+pub async fn vault_add_yield(
+    pool: &PgPool,
+    owner: &str,
+    deposited_delta: i64,
+    accrued_delta: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE vaults SET
+             yield_deposited_balance = GREATEST(yield_deposited_balance + $2, 0),
+             yield_accrued_balance = GREATEST(yield_accrued_balance + $3, 0),
+             updated_at = NOW()
+         WHERE owner = $1",
+    )
+    .bind(owner)
+    .bind(deposited_delta)
+    .bind(accrued_delta)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
 pub async fn insert_balance_snapshot(
     pool: &PgPool,
     owner: &str,
@@ -484,18 +324,25 @@ pub async fn insert_balance_snapshot(
     locked: i64,
     granularity: &str,
 ) -> Result<(), sqlx::Error> {
-    sqlx::query("INSERT INTO balance_snapshots (owner, balance, locked_balance, granularity) VALUES ($1, $2, $3, $4)")
-		.bind(owner)
-		.bind(balance)
-		.bind(locked)
-		.bind(granularity)
-		.execute(pool)
-		.await?;
+    sqlx::query(
+        "INSERT INTO balance_snapshots (owner, balance, locked_balance, available, granularity, period_start)
+         VALUES ($1, $2, $3, $2 - $3, $4,
+                 date_trunc(CASE WHEN $4 = 'daily' THEN 'day' ELSE 'hour' END, NOW() AT TIME ZONE 'UTC') AT TIME ZONE 'UTC')
+         ON CONFLICT (owner, granularity, period_start) DO UPDATE
+         SET balance = EXCLUDED.balance, locked_balance = EXCLUDED.locked_balance,
+             available = EXCLUDED.available, recorded_at = NOW()",
+    )
+    .bind(owner)
+    .bind(balance)
+    .bind(locked)
+    .bind(granularity)
+    .execute(pool)
+    .await?;
     Ok(())
 }
 
 pub async fn insert_audit_log(
-    pool: &PgPool,
+    executor: impl PgExecutor<'_>,
     owner: Option<&str>,
     action: &str,
     details: serde_json::Value,
@@ -504,7 +351,7 @@ pub async fn insert_audit_log(
         .bind(owner)
         .bind(action)
         .bind(details)
-        .execute(pool)
+        .execute(executor)
         .await?;
     Ok(())
 }
@@ -552,266 +399,2245 @@ pub async fn whitelist_list(pool: &PgPool, owner: &str) -> Result<Vec<String>, s
 pub async fn twofa_upsert(
     pool: &PgPool,
     owner: &str,
-    secret: &str,
+    secret_enc: &str,
     enabled: bool,
 ) -> Result<(), sqlx::Error> {
     sqlx::query(
-        "INSERT INTO twofa (owner, secret, enabled) VALUES ($1, $2, $3)
-         ON CONFLICT (owner) DO UPDATE SET secret = EXCLUDED.secret, enabled = EXCLUDED.enabled, updated_at = NOW()"
+        "INSERT INTO twofa (owner, secret, secret_enc, enabled) VALUES ($1, NULL, $2, $3)
+         ON CONFLICT (owner) DO UPDATE SET secret = NULL, secret_enc = EXCLUDED.secret_enc,
+             enabled = EXCLUDED.enabled, last_used_step = NULL, updated_at = NOW()"
     )
     .bind(owner)
-    .bind(secret)
+    .bind(secret_enc)
     .bind(enabled)
     .execute(pool)
     .await?;
     Ok(())
 }
 
-pub async fn twofa_get(pool: &PgPool, owner: &str) -> Result<Option<(String, bool)>, sqlx::Error> {
-    let row =
-        sqlx::query_as::<_, (String, bool)>("SELECT secret, enabled FROM twofa WHERE owner = $1")
-            .bind(owner)
-            .fetch_optional(pool)
-            .await?;
-    Ok(row)
-}
-
-pub async fn insert_yield_event(
-    pool: &PgPool,
-    owner: &str,
-    protocol: &str,
-    amount: i64,
-    kind: &str,
-) -> Result<(), sqlx::Error> {
-    sqlx::query("INSERT INTO yield_events (owner, protocol, amount, kind) VALUES ($1, $2, $3, $4)")
+pub async fn twofa_set_enabled(pool: &PgPool, owner: &str, enabled: bool) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE twofa SET enabled = $2, updated_at = NOW() WHERE owner = $1")
         .bind(owner)
-        .bind(protocol)
-        .bind(amount)
-        .bind(kind)
+        .bind(enabled)
         .execute(pool)
         .await?;
     Ok(())
 }
 
-pub async fn insert_protocol_apy(
-    pool: &PgPool,
-    protocol: &str,
-    apy: f64,
-) -> Result<(), sqlx::Error> {
-    sqlx::query("INSERT INTO protocol_apy (protocol, apy) VALUES ($1, $2)")
-        .bind(protocol)
-        .bind(apy)
-        .execute(pool)
-        .await?;
-    Ok(())
+/// Returns (secret, enabled) where `secret` is either a sealed `v1:` envelope or a legacy
+/// plaintext base32 secret.
+pub async fn twofa_get(pool: &PgPool, owner: &str) -> Result<Option<(String, bool)>, sqlx::Error> {
+    let row = sqlx::query_as::<_, (String, bool)>(
+        "SELECT COALESCE(secret_enc, secret, ''), enabled FROM twofa WHERE owner = $1",
+    )
+    .bind(owner)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row)
 }
 
-pub async fn latest_protocol_apys(pool: &PgPool) -> Result<Vec<(String, f64)>, sqlx::Error> {
-    // Select latest APY per protocol
-    let rows = sqlx::query_as::<_, (String, f64)>(
-        "SELECT DISTINCT ON (protocol) protocol, apy FROM protocol_apy ORDER BY protocol, recorded_at DESC"
+/// Whether `owner` has any second factor stored: a TOTP secret (enabled or not) or a passkey.
+pub async fn twofa_any_factor(pool: &PgPool, owner: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM twofa WHERE owner = $1)
+             OR EXISTS (SELECT 1 FROM webauthn_credentials WHERE owner = $1)",
     )
-    .fetch_all(pool)
+    .bind(owner)
+    .fetch_one(pool)
+    .await
+}
+
+/// Whether `owner` has an enabled TOTP secret or a passkey, i.e. a usable second factor.
+pub async fn twofa_any_enabled(pool: &PgPool, owner: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM twofa WHERE owner = $1 AND enabled)
+             OR EXISTS (SELECT 1 FROM webauthn_credentials WHERE owner = $1)",
+    )
+    .bind(owner)
+    .fetch_one(pool)
+    .await
+}
+
+/// Records `step` as used. Returns false if the same or a later step was already accepted.
+pub async fn twofa_mark_step_used(pool: &PgPool, owner: &str, step: i64) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE twofa SET last_used_step = $2, updated_at = NOW()
+         WHERE owner = $1 AND (last_used_step IS NULL OR last_used_step < $2)",
+    )
+    .bind(owner)
+    .bind(step)
+    .execute(pool)
     .await?;
-    Ok(rows)
+    Ok(res.rows_affected() == 1)
 }
 
-// -----------------
-// Timelocks
-// -----------------
-pub async fn timelock_insert(
-    pool: &PgPool,
-    owner: &str,
-    amount: i64,
-    unlock_at: time::OffsetDateTime,
-) -> Result<(), sqlx::Error> {
-    sqlx::query("INSERT INTO timelocks (owner, amount, unlock_at) VALUES ($1, $2, $3)")
+/// Removes every second factor (TOTP, passkeys) and all recovery codes for `owner`.
+pub async fn twofa_delete(pool: &PgPool, owner: &str) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    twofa_delete_in(&mut tx, owner).await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn twofa_delete_in(conn: &mut sqlx::PgConnection, owner: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM twofa WHERE owner = $1")
         .bind(owner)
-        .bind(amount)
-        .bind(unlock_at)
-        .execute(pool)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM twofa_recovery_codes WHERE owner = $1")
+        .bind(owner)
+        .execute(&mut *conn)
+        .await?;
+    sqlx::query("DELETE FROM webauthn_credentials WHERE owner = $1")
+        .bind(owner)
+        .execute(&mut *conn)
         .await?;
     Ok(())
 }
 
-pub async fn timelock_list(
+/// Replaces every recovery code for `owner` with the given hashes.
+pub async fn twofa_replace_recovery_codes(
     pool: &PgPool,
     owner: &str,
-) -> Result<Vec<(i64, i64, time::OffsetDateTime, String)>, sqlx::Error> {
-    let rows = sqlx::query_as::<_, (i64, i64, time::OffsetDateTime, String)>(
-        "SELECT id, amount, unlock_at, status FROM timelocks WHERE owner = $1 ORDER BY unlock_at ASC"
+    code_hashes: &[String],
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM twofa_recovery_codes WHERE owner = $1")
+        .bind(owner)
+        .execute(&mut *tx)
+        .await?;
+    for hash in code_hashes {
+        sqlx::query("INSERT INTO twofa_recovery_codes (owner, code_hash) VALUES ($1, $2)")
+            .bind(owner)
+            .bind(hash)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Marks a recovery code as used. Returns false if it does not exist or was already used.
+pub async fn twofa_consume_recovery_code(
+    pool: &PgPool,
+    owner: &str,
+    code_hash: &str,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE twofa_recovery_codes SET used_at = NOW()
+         WHERE owner = $1 AND code_hash = $2 AND used_at IS NULL",
     )
     .bind(owner)
-    .fetch_all(pool)
+    .bind(code_hash)
+    .execute(pool)
     .await?;
-    Ok(rows)
+    Ok(res.rows_affected() == 1)
 }
 
-pub async fn timelock_mark_status(pool: &PgPool, id: i64, status: &str) -> Result<(), sqlx::Error> {
-    sqlx::query("UPDATE timelocks SET status = $2, updated_at = NOW() WHERE id = $1")
-        .bind(id)
-        .bind(status)
+/// Flags `owner` as having to enroll a second factor; the first reason is kept.
+pub async fn twofa_required_set(pool: &PgPool, owner: &str, reason: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO twofa_required (owner, reason) VALUES ($1, $2) ON CONFLICT (owner) DO NOTHING",
+    )
+    .bind(owner)
+    .bind(reason)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn twofa_required_get(pool: &PgPool, owner: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM twofa_required WHERE owner = $1)")
+        .bind(owner)
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn twofa_policy_get(pool: &PgPool, owner: &str) -> Result<Option<Vec<String>>, sqlx::Error> {
+    let row = sqlx::query_as::<_, (Vec<String>,)>(
+        "SELECT operations FROM twofa_policies WHERE owner = $1",
+    )
+    .bind(owner)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|(ops,)| ops))
+}
+
+pub async fn twofa_policy_set(
+    pool: &PgPool,
+    owner: &str,
+    operations: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO twofa_policies (owner, operations) VALUES ($1, $2)
+         ON CONFLICT (owner) DO UPDATE SET operations = EXCLUDED.operations, updated_at = NOW()",
+    )
+    .bind(owner)
+    .bind(operations)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Drops the owner's policy so every sensitive operation requires the second factor again.
+pub async fn twofa_policy_reset(pool: &PgPool, owner: &str) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM twofa_policies WHERE owner = $1")
+        .bind(owner)
         .execute(pool)
         .await?;
-    Ok(())
+    Ok(res.rows_affected() == 1)
 }
 
-pub async fn timelock_due_within(
+/// Schedules a reset `cooldown_seconds` from now. Returns (id, effective_at, created) where
+/// `created` is false if a reset was already pending for the owner.
+pub async fn twofa_reset_request(
     pool: &PgPool,
-    seconds: i64,
-) -> Result<Vec<(i64, String, i64, time::OffsetDateTime)>, sqlx::Error> {
-    let rows = sqlx::query_as::<_, (i64, String, i64, time::OffsetDateTime)>(
-        "SELECT id, owner, amount, unlock_at FROM timelocks WHERE status = 'scheduled' AND unlock_at <= NOW() + make_interval(secs => $1) ORDER BY unlock_at ASC"
+    owner: &str,
+    requested_by: &str,
+    reason: Option<&str>,
+    cooldown_seconds: i64,
+) -> Result<(i64, time::OffsetDateTime, bool), sqlx::Error> {
+    loop {
+        // The partial unique index allows one pending reset per owner
+        if let Some((id, effective_at)) = sqlx::query_as::<_, (i64, time::OffsetDateTime)>(
+            "INSERT INTO twofa_resets (owner, requested_by, reason, effective_at)
+             VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))
+             ON CONFLICT (owner) WHERE status = 'pending' DO NOTHING
+             RETURNING id, effective_at",
+        )
+        .bind(owner)
+        .bind(requested_by)
+        .bind(reason)
+        .bind(cooldown_seconds)
+        .fetch_optional(pool)
+        .await?
+        {
+            return Ok((id, effective_at, true));
+        }
+        // Retried if the conflicting reset was cancelled or applied in between
+        if let Some((id, effective_at)) = sqlx::query_as::<_, (i64, time::OffsetDateTime)>(
+            "SELECT id, effective_at FROM twofa_resets WHERE owner = $1 AND status = 'pending'",
+        )
+        .bind(owner)
+        .fetch_optional(pool)
+        .await?
+        {
+            return Ok((id, effective_at, false));
+        }
+    }
+}
+
+/// Cancels the pending reset for `owner`, returning its id if there was one.
+pub async fn twofa_reset_cancel(pool: &PgPool, owner: &str) -> Result<Option<i64>, sqlx::Error> {
+    let row = sqlx::query_as::<_, (i64,)>(
+        "UPDATE twofa_resets SET status = 'cancelled', updated_at = NOW()
+         WHERE owner = $1 AND status = 'pending' RETURNING id",
+    )
+    .bind(owner)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|(id,)| id))
+}
+
+/// Pending resets whose cooling-off period has elapsed: (id, owner, requested_by).
+pub async fn twofa_resets_due(pool: &PgPool) -> Result<Vec<(i64, String, String)>, sqlx::Error> {
+    sqlx::query_as::<_, (i64, String, String)>(
+        "SELECT id, owner, requested_by FROM twofa_resets
+         WHERE status = 'pending' AND effective_at <= NOW() ORDER BY effective_at",
     )
-    .bind(seconds)
     .fetch_all(pool)
+    .await
+}
+
+/// Marks a pending reset as applied and removes `owner`'s second factors in one transaction.
+/// Returns false, changing nothing, if the reset was cancelled in the meantime.
+pub async fn twofa_reset_apply(pool: &PgPool, id: i64, owner: &str) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let res = sqlx::query(
+        "UPDATE twofa_resets SET status = 'applied', updated_at = NOW()
+         WHERE id = $1 AND status = 'pending'",
+    )
+    .bind(id)
+    .execute(&mut *tx)
     .await?;
-    Ok(rows)
+    if res.rows_affected() != 1 {
+        return Ok(false);
+    }
+    twofa_delete_in(&mut tx, owner).await?;
+    tx.commit().await?;
+    Ok(true)
 }
 
 // -----------------
-// Delegates (off-chain)
+// WebAuthn helpers
 // -----------------
-pub async fn delegate_add(pool: &PgPool, owner: &str, delegate: &str) -> Result<bool, sqlx::Error> {
+pub async fn webauthn_credential_insert(
+    pool: &PgPool,
+    owner: &str,
+    credential_id: &str,
+    public_key: &[u8],
+    sign_count: i64,
+    label: Option<&str>,
+) -> Result<bool, sqlx::Error> {
     let res = sqlx::query(
-        "INSERT INTO vault_delegates (owner, delegate) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        "INSERT INTO webauthn_credentials (owner, credential_id, public_key, sign_count, label)
+         VALUES ($1, $2, $3, $4, $5) ON CONFLICT (credential_id) DO NOTHING",
     )
     .bind(owner)
-    .bind(delegate)
+    .bind(credential_id)
+    .bind(public_key)
+    .bind(sign_count)
+    .bind(label)
     .execute(pool)
     .await?;
     Ok(res.rows_affected() == 1)
 }
 
-pub async fn delegate_remove(
+/// Returns (public_key, sign_count) for an owner's credential.
+pub async fn webauthn_credential_get(
     pool: &PgPool,
     owner: &str,
-    delegate: &str,
-) -> Result<bool, sqlx::Error> {
-    let res = sqlx::query("DELETE FROM vault_delegates WHERE owner = $1 AND delegate = $2")
-        .bind(owner)
-        .bind(delegate)
-        .execute(pool)
-        .await?;
-    Ok(res.rows_affected() == 1)
+    credential_id: &str,
+) -> Result<Option<(Vec<u8>, i64)>, sqlx::Error> {
+    sqlx::query_as::<_, (Vec<u8>, i64)>(
+        "SELECT public_key, sign_count FROM webauthn_credentials WHERE owner = $1 AND credential_id = $2",
+    )
+    .bind(owner)
+    .bind(credential_id)
+    .fetch_optional(pool)
+    .await
 }
 
-pub async fn delegate_list(pool: &PgPool, owner: &str) -> Result<Vec<String>, sqlx::Error> {
-    let rows = sqlx::query_as::<_, (String,)>(
-        "SELECT delegate FROM vault_delegates WHERE owner = $1 ORDER BY added_at DESC",
+/// (credential_id, label, sign_count, created_at, last_used_at)
+pub async fn webauthn_credentials_list(
+    pool: &PgPool,
+    owner: &str,
+) -> Result<
+    Vec<(String, Option<String>, i64, time::OffsetDateTime, Option<time::OffsetDateTime>)>,
+    sqlx::Error,
+> {
+    sqlx::query_as(
+        "SELECT credential_id, label, sign_count, created_at, last_used_at
+         FROM webauthn_credentials WHERE owner = $1 ORDER BY created_at",
     )
     .bind(owner)
     .fetch_all(pool)
+    .await
+}
+
+pub async fn webauthn_credential_count(pool: &PgPool, owner: &str) -> Result<i64, sqlx::Error> {
+    let row = sqlx::query_as::<_, (i64,)>(
+        "SELECT COUNT(*) FROM webauthn_credentials WHERE owner = $1",
+    )
+    .bind(owner)
+    .fetch_one(pool)
     .await?;
-    Ok(rows.into_iter().map(|(d,)| d).collect())
+    Ok(row.0)
 }
 
-// -----------------
-// Multisig proposals
-// -----------------
-pub async fn ms_create_proposal(
+/// Stores a new sign count. Returns false if it did not advance, which indicates a cloned
+/// authenticator; authenticators that always report 0 (synced passkeys) are allowed.
+pub async fn webauthn_credential_touch(
     pool: &PgPool,
-    id: &str,
     owner: &str,
-    amount: i64,
-    threshold: i32,
-    signers_json: &serde_json::Value,
-) -> Result<(), sqlx::Error> {
-    sqlx::query(
-        "INSERT INTO ms_proposals (id, owner, amount, threshold, signers) VALUES ($1, $2, $3, $4, $5)"
+    credential_id: &str,
+    sign_count: i64,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE webauthn_credentials SET sign_count = $3, last_used_at = NOW()
+         WHERE owner = $1 AND credential_id = $2
+           AND (sign_count < $3 OR (sign_count = 0 AND $3 = 0))",
     )
-    .bind(id)
     .bind(owner)
-    .bind(amount)
-    .bind(threshold)
-    .bind(signers_json)
+    .bind(credential_id)
+    .bind(sign_count)
     .execute(pool)
     .await?;
-    Ok(())
+    Ok(res.rows_affected() == 1)
 }
 
-pub async fn ms_insert_approval(
+pub async fn webauthn_credential_delete(
     pool: &PgPool,
-    proposal_id: &str,
-    signer: &str,
-    signature: &str,
+    owner: &str,
+    credential_id: &str,
 ) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM webauthn_credentials WHERE owner = $1 AND credential_id = $2")
+        .bind(owner)
+        .bind(credential_id)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+// -----------------
+// Event outbox
+// -----------------
+/// Advisory lock held from id assignment to commit of an outbox row.
+const OUTBOX_ORDER_LOCK_KEY: i64 = 0x0063_766d_736f_6278;
+
+/// Appends an event and returns its id. Writers take the ordering lock before the id is drawn
+/// and keep it until commit, so ids become visible in order even with several processes
+/// writing: a reader that sees id N has already seen every id below it, and cursors that
+/// skip past N never skip a row committed later.
+pub async fn outbox_insert(
+    pool: &PgPool,
+    event_id: uuid::Uuid,
+    topic: &str,
+    owner: Option<&str>,
+    event_type: &str,
+    payload: &serde_json::Value,
+) -> Result<i64, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(OUTBOX_ORDER_LOCK_KEY)
+        .execute(&mut *tx)
+        .await?;
+    let (id,) = sqlx::query_as::<_, (i64,)>(
+        "INSERT INTO event_outbox (event_id, topic, owner, event_type, payload)
+         VALUES ($1, $2, $3, $4, $5) RETURNING id",
+    )
+    .bind(event_id)
+    .bind(topic)
+    .bind(owner)
+    .bind(event_type)
+    .bind(payload)
+    .fetch_one(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(id)
+}
+
+/// Events on any of `owner_topics` or `global_topics` with id > `after`, oldest first. `owner`
+/// restricts the owner topics to one owner's events; global topics carry no owner.
+pub async fn outbox_after(
+    pool: &PgPool,
+    owner_topics: &[&str],
+    global_topics: &[&str],
+    owner: Option<&str>,
+    after: i64,
+    limit: i64,
+) -> Result<Vec<(i64, serde_json::Value)>, sqlx::Error> {
+    sqlx::query_as::<_, (i64, serde_json::Value)>(
+        "SELECT id, payload FROM event_outbox
+         WHERE id > $3
+           AND ((topic = ANY($1) AND ($4::TEXT IS NULL OR owner = $4)) OR topic = ANY($2))
+         ORDER BY id ASC LIMIT $5",
+    )
+    .bind(owner_topics)
+    .bind(global_topics)
+    .bind(after)
+    .bind(owner)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Deletes outbox rows older than `retention_hours`.
+pub async fn outbox_prune(pool: &PgPool, retention_hours: i64) -> Result<u64, sqlx::Error> {
     let res = sqlx::query(
-        "INSERT INTO ms_approvals (proposal_id, signer, signature) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING"
+        "DELETE FROM event_outbox WHERE created_at < NOW() - make_interval(hours => $1::INT)",
     )
-    .bind(proposal_id)
-    .bind(signer)
-    .bind(signature)
+    .bind(retention_hours)
     .execute(pool)
     .await?;
-    Ok(res.rows_affected() == 1)
+    Ok(res.rows_affected())
 }
 
-pub async fn ms_get_proposal(
+pub async fn outbox_max_id(executor: impl PgExecutor<'_>) -> Result<i64, sqlx::Error> {
+    let (id,) = sqlx::query_as::<_, (i64,)>("SELECT COALESCE(MAX(id), 0) FROM event_outbox")
+        .fetch_one(executor)
+        .await?;
+    Ok(id)
+}
+
+/// (id, event_id, owner, event_type, payload)
+pub type OutboxRow = (i64, uuid::Uuid, Option<String>, String, serde_json::Value);
+
+/// Events on every topic with id > `after`, oldest first.
+pub async fn outbox_after_all(
+    executor: impl PgExecutor<'_>,
+    after: i64,
+    limit: i64,
+) -> Result<Vec<OutboxRow>, sqlx::Error> {
+    sqlx::query_as::<_, OutboxRow>(
+        "SELECT id, event_id, owner, event_type, payload FROM event_outbox
+         WHERE id > $1 ORDER BY id ASC LIMIT $2",
+    )
+    .bind(after)
+    .bind(limit)
+    .fetch_all(executor)
+    .await
+}
+
+// -----------------
+// Webhooks
+// -----------------
+pub async fn webhook_endpoint_insert(
     pool: &PgPool,
-    id: &str,
-) -> Result<Option<(String, i64, i32, serde_json::Value, String)>, sqlx::Error> {
-    let row = sqlx::query_as::<_, (String, i64, i32, serde_json::Value, String)>(
-        "SELECT owner, amount, threshold, signers, status FROM ms_proposals WHERE id = $1",
+    owner: Option<&str>,
+    url: &str,
+    secret_enc: &str,
+    event_types: &[String],
+    created_by: &str,
+) -> Result<i64, sqlx::Error> {
+    let (id,) = sqlx::query_as::<_, (i64,)>(
+        "INSERT INTO webhook_endpoints (owner, url, secret_enc, event_types, created_by)
+         VALUES ($1, $2, $3, $4, $5) RETURNING id",
     )
-    .bind(id)
-    .fetch_optional(pool)
+    .bind(owner)
+    .bind(url)
+    .bind(secret_enc)
+    .bind(event_types)
+    .bind(created_by)
+    .fetch_one(pool)
     .await?;
-    Ok(row)
+    Ok(id)
 }
 
-pub async fn ms_count_approvals(pool: &PgPool, id: &str) -> Result<i64, sqlx::Error> {
-    let count =
-        sqlx::query_scalar::<_, i64>("SELECT COUNT(1) FROM ms_approvals WHERE proposal_id = $1")
-            .bind(id)
-            .fetch_one(pool)
-            .await?;
-    Ok(count)
+/// Endpoints registered by `owner`: (id, url, event_types, active, created_at).
+pub async fn webhook_endpoints_for_owner(
+    pool: &PgPool,
+    owner: &str,
+) -> Result<Vec<(i64, String, Vec<String>, bool, time::OffsetDateTime)>, sqlx::Error> {
+    sqlx::query_as::<_, (i64, String, Vec<String>, bool, time::OffsetDateTime)>(
+        "SELECT id, url, event_types, active, created_at FROM webhook_endpoints
+         WHERE owner = $1 ORDER BY id",
+    )
+    .bind(owner)
+    .fetch_all(pool)
+    .await
 }
 
-pub async fn ms_set_status(pool: &PgPool, id: &str, status: &str) -> Result<(), sqlx::Error> {
-    sqlx::query("UPDATE ms_proposals SET status = $2, updated_at = NOW() WHERE id = $1")
+/// Active endpoints for fan-out: (id, owner, url, event_types).
+pub async fn webhook_endpoints_active(
+    executor: impl PgExecutor<'_>,
+) -> Result<Vec<(i64, Option<String>, String, Vec<String>)>, sqlx::Error> {
+    sqlx::query_as::<_, (i64, Option<String>, String, Vec<String>)>(
+        "SELECT id, owner, url, event_types FROM webhook_endpoints WHERE active = TRUE",
+    )
+    .fetch_all(executor)
+    .await
+}
+
+/// Active endpoints registered by any of `owners` whose filter accepts `event_type`:
+/// (id, owner, url).
+pub async fn webhook_endpoints_for_owners(
+    pool: &PgPool,
+    owners: &[String],
+    event_type: &str,
+) -> Result<Vec<(i64, String, String)>, sqlx::Error> {
+    sqlx::query_as::<_, (i64, String, String)>(
+        "SELECT id, owner, url FROM webhook_endpoints
+         WHERE active = TRUE AND owner = ANY($1)
+           AND (cardinality(event_types) = 0 OR $2 = ANY(event_types))
+         ORDER BY id",
+    )
+    .bind(owners)
+    .bind(event_type)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn webhook_endpoint_delete(
+    pool: &PgPool,
+    owner: &str,
+    id: i64,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM webhook_endpoints WHERE id = $1 AND owner = $2")
         .bind(id)
-        .bind(status)
+        .bind(owner)
         .execute(pool)
         .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+/// Advisory lock serializing webhook fan-out across processes.
+const WEBHOOK_FANOUT_LOCK_KEY: i64 = 0x0063_766d_7377_6866;
+
+/// Takes the fan-out lock for the rest of the transaction. Returns false if another process
+/// holds it, in which case that process advances the cursor and this one should skip.
+pub async fn webhook_fanout_try_lock(executor: impl PgExecutor<'_>) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT pg_try_advisory_xact_lock($1)")
+        .bind(WEBHOOK_FANOUT_LOCK_KEY)
+        .fetch_one(executor)
+        .await
+}
+
+pub async fn webhook_fanout_cursor(
+    executor: impl PgExecutor<'_>,
+) -> Result<Option<i64>, sqlx::Error> {
+    let row = sqlx::query_as::<_, (i64,)>("SELECT cursor FROM webhook_fanout")
+        .fetch_optional(executor)
+        .await?;
+    Ok(row.map(|(c,)| c))
+}
+
+pub async fn webhook_set_fanout_cursor(
+    executor: impl PgExecutor<'_>,
+    cursor: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO webhook_fanout (id, cursor) VALUES (TRUE, $1)
+         ON CONFLICT (id) DO UPDATE SET cursor = EXCLUDED.cursor",
+    )
+    .bind(cursor)
+    .execute(executor)
+    .await?;
     Ok(())
 }
 
-pub async fn ms_list_approvals(pool: &PgPool, id: &str) -> Result<Vec<String>, sqlx::Error> {
-    let rows = sqlx::query_as::<_, (String,)>(
-        "SELECT signer FROM ms_approvals WHERE proposal_id = $1 ORDER BY id ASC",
+/// Queues a delivery. Idempotent per (endpoint, event) so a re-run fan-out does not duplicate.
+pub async fn webhook_delivery_enqueue(
+    executor: impl PgExecutor<'_>,
+    endpoint_id: i64,
+    url: &str,
+    event_id: Option<uuid::Uuid>,
+    event_type: &str,
+    payload: &serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO webhook_deliveries (endpoint_id, url, event_id, event_type, payload)
+         VALUES ($1, $2, $3, $4, $5) ON CONFLICT (endpoint_id, event_id) DO NOTHING",
     )
-    .bind(id)
-    .fetch_all(pool)
+    .bind(endpoint_id)
+    .bind(url)
+    .bind(event_id)
+    .bind(event_type)
+    .bind(payload)
+    .execute(executor)
     .await?;
-    Ok(rows.into_iter().map(|(s,)| s).collect())
+    Ok(())
 }
 
-pub async fn ms_get_contacts_for(
+/// (id, url, endpoint secret_enc, event_id, payload, attempts)
+pub type WebhookDeliveryRow = (i64, String, String, Option<uuid::Uuid>, serde_json::Value, i32);
+
+/// Claims up to `limit` due deliveries for `lease_seconds`: pending rows whose next attempt is
+/// due, plus in-flight rows whose lease ran out (their worker died). Concurrent dispatchers
+/// skip each other's rows, so every claimed delivery is attempted by one worker at a time.
+pub async fn webhook_deliveries_claim(
     pool: &PgPool,
-    pubkeys: &[String],
-) -> Result<Vec<(String, Option<String>, Option<String>)>, sqlx::Error> {
-    if pubkeys.is_empty() {
-        return Ok(vec![]);
-    }
-    // Build simple IN clause
-    let query = String::from(
-        "SELECT pubkey, email, webhook FROM ms_signer_contacts WHERE pubkey = ANY($1)",
-    );
-    let rows = sqlx::query_as::<_, (String, Option<String>, Option<String>)>(&query)
-        .bind(pubkeys)
-        .fetch_all(pool)
-        .await?;
-    Ok(rows)
+    limit: i64,
+    lease_seconds: i64,
+) -> Result<Vec<WebhookDeliveryRow>, sqlx::Error> {
+    sqlx::query_as::<_, WebhookDeliveryRow>(
+        "WITH due AS (
+             SELECT id FROM webhook_deliveries
+             WHERE (status = 'pending' AND next_attempt_at <= NOW())
+                OR (status = 'in_flight' AND locked_until < NOW())
+             ORDER BY next_attempt_at LIMIT $1
+             FOR UPDATE SKIP LOCKED
+         ), claimed AS (
+             UPDATE webhook_deliveries d
+             SET status = 'in_flight',
+                 locked_until = NOW() + make_interval(secs => $2::DOUBLE PRECISION)
+             FROM due WHERE d.id = due.id
+             RETURNING d.id, d.endpoint_id, d.url, d.event_id, d.payload, d.attempts,
+                       d.next_attempt_at
+         )
+         SELECT c.id, c.url, e.secret_enc, c.event_id, c.payload, c.attempts
+         FROM claimed c JOIN webhook_endpoints e ON e.id = c.endpoint_id
+         ORDER BY c.next_attempt_at",
+    )
+    .bind(limit)
+    .bind(lease_seconds as f64)
+    .fetch_all(pool)
+    .await
 }
 
-pub async fn list_vaults(pool: &PgPool) -> Result<Vec<(String, Option<String>, i64)>, sqlx::Error> {
-    let rows = sqlx::query_as::<_, (String, Option<String>, i64)>(
-        "SELECT owner, token_account, total_balance FROM vaults",
+pub async fn webhook_delivery_succeeded(pool: &PgPool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE webhook_deliveries
+         SET status = 'delivered', attempts = attempts + 1, delivered_at = NOW(), last_error = NULL,
+             locked_until = NULL
+         WHERE id = $1",
     )
-    .fetch_all(pool)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn webhook_delivery_retry(
+    pool: &PgPool,
+    id: i64,
+    error: &str,
+    delay_seconds: i64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE webhook_deliveries
+         SET status = 'pending', attempts = attempts + 1, last_error = $2, locked_until = NULL,
+             next_attempt_at = NOW() + make_interval(secs => $3::DOUBLE PRECISION)
+         WHERE id = $1",
+    )
+    .bind(id)
+    .bind(error)
+    .bind(delay_seconds as f64)
+    .execute(pool)
     .await?;
+    Ok(())
+}
+
+/// Marks the delivery dead and copies it to `webhook_dead_letters`.
+pub async fn webhook_delivery_dead_letter(
+    pool: &PgPool,
+    id: i64,
+    error: &str,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query(
+        "UPDATE webhook_deliveries
+         SET status = 'dead', attempts = attempts + 1, last_error = $2, locked_until = NULL
+         WHERE id = $1",
+    )
+    .bind(id)
+    .bind(error)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO webhook_dead_letters (delivery_id, endpoint_id, url, event_type, attempts, last_error)
+         SELECT id, endpoint_id, url, event_type, attempts, last_error FROM webhook_deliveries WHERE id = $1",
+    )
+    .bind(id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await
+}
+
+/// (id, delivery_id, endpoint_id, url, event_type, attempts, last_error, created_at)
+pub type DeadLetterRow =
+    (i64, i64, Option<i64>, String, String, i32, Option<String>, time::OffsetDateTime);
+
+/// Dead letters not yet replayed, newest first.
+pub async fn webhook_dead_letters_list(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<DeadLetterRow>, sqlx::Error> {
+    sqlx::query_as::<_, DeadLetterRow>(
+        "SELECT id, delivery_id, endpoint_id, url, event_type, attempts, last_error, created_at
+         FROM webhook_dead_letters WHERE replayed_at IS NULL ORDER BY id DESC LIMIT $1",
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Puts a dead-lettered delivery back in the queue with a fresh attempt budget.
+/// Returns false if the dead letter does not exist or was already replayed.
+pub async fn webhook_dead_letter_replay(pool: &PgPool, id: i64) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let row = sqlx::query_as::<_, (i64,)>(
+        "UPDATE webhook_dead_letters SET replayed_at = NOW()
+         WHERE id = $1 AND replayed_at IS NULL RETURNING delivery_id",
+    )
+    .bind(id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some((delivery_id,)) = row else {
+        return Ok(false);
+    };
+    let res = sqlx::query(
+        "UPDATE webhook_deliveries
+         SET status = 'pending', attempts = 0, next_attempt_at = NOW(), last_error = NULL
+         WHERE id = $1 AND status = 'dead'",
+    )
+    .bind(delivery_id)
+    .execute(&mut *tx)
+    .await?;
+    if res.rows_affected() != 1 {
+        return Ok(false);
+    }
+    tx.commit().await?;
+    Ok(true)
+}
+
+pub async fn insert_yield_event(
+    pool: &PgPool,
+    owner: &str,
+    protocol: &str,
+    amount: i64,
+    kind: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO yield_events (owner, protocol, amount, kind) VALUES ($1, $2, $3, $4)")
+        .bind(owner)
+        .bind(protocol)
+        .bind(amount)
+        .bind(kind)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn insert_protocol_apy(
+    pool: &PgPool,
+    protocol: &str,
+    apy: f64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO protocol_apy (protocol, apy) VALUES ($1, $2)")
+        .bind(protocol)
+        .bind(apy)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn latest_protocol_apys(pool: &PgPool) -> Result<Vec<(String, f64)>, sqlx::Error> {
+    // Select latest APY per protocol
+    let rows = sqlx::query_as::<_, (String, f64)>(
+        "SELECT DISTINCT ON (protocol) protocol, apy FROM protocol_apy ORDER BY protocol, recorded_at DESC"
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn average_protocol_apy_since(
+    pool: &PgPool,
+    since: time::OffsetDateTime,
+) -> Result<f64, sqlx::Error> {
+    sqlx::query_scalar::<_, f64>(
+        "SELECT COALESCE(AVG(apy), 0.0) FROM protocol_apy WHERE recorded_at > $1",
+    )
+    .bind(since)
+    .fetch_one(pool)
+    .await
+}
+
+// -----------------
+// Alerts
+// -----------------
+/// (id, last_notified_at, acknowledged)
+pub type OpenAlertRow = (i64, time::OffsetDateTime, bool);
+
+pub async fn alert_open_get(
+    pool: &PgPool,
+    kind: &str,
+    owner: &str,
+) -> Result<Option<OpenAlertRow>, sqlx::Error> {
+    sqlx::query_as::<_, OpenAlertRow>(
+        "SELECT id, last_notified_at, acknowledged_at IS NOT NULL
+         FROM alerts WHERE kind = $1 AND owner = $2 AND status = 'open'",
+    )
+    .bind(kind)
+    .bind(owner)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Opens an alert. Returns `None` if one is already open for (kind, owner).
+pub async fn alert_open(
+    pool: &PgPool,
+    kind: &str,
+    owner: &str,
+    severity: &str,
+    details: &serde_json::Value,
+) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        "INSERT INTO alerts (kind, owner, severity, details) VALUES ($1, $2, $3, $4)
+         ON CONFLICT (kind, owner) WHERE status = 'open' DO NOTHING RETURNING id",
+    )
+    .bind(kind)
+    .bind(owner)
+    .bind(severity)
+    .bind(details)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Records that the condition is still active; `notified` also bumps the notification time.
+pub async fn alert_touch(
+    pool: &PgPool,
+    id: i64,
+    details: &serde_json::Value,
+    notified: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE alerts SET details = $2, last_seen_at = NOW(),
+             last_notified_at = CASE WHEN $3 THEN NOW() ELSE last_notified_at END,
+             notify_count = notify_count + CASE WHEN $3 THEN 1 ELSE 0 END
+         WHERE id = $1",
+    )
+    .bind(id)
+    .bind(details)
+    .bind(notified)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Resolves the open alert for (kind, owner), returning its id.
+pub async fn alert_resolve(
+    pool: &PgPool,
+    kind: &str,
+    owner: &str,
+) -> Result<Option<i64>, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        "UPDATE alerts SET status = 'resolved', resolved_at = NOW()
+         WHERE kind = $1 AND owner = $2 AND status = 'open' RETURNING id",
+    )
+    .bind(kind)
+    .bind(owner)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Owners with an open alert of `kind`.
+pub async fn alerts_open_owners(pool: &PgPool, kind: &str) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT owner FROM alerts WHERE kind = $1 AND status = 'open'",
+    )
+    .bind(kind)
+    .fetch_all(pool)
+    .await
+}
+
+/// (id, kind, owner, severity, details, status, notify_count, opened_at, last_seen_at,
+/// acknowledged_by, acknowledged_at, resolved_at)
+pub type AlertRow = (
+    i64,
+    String,
+    String,
+    String,
+    serde_json::Value,
+    String,
+    i32,
+    time::OffsetDateTime,
+    time::OffsetDateTime,
+    Option<String>,
+    Option<time::OffsetDateTime>,
+    Option<time::OffsetDateTime>,
+);
+
+/// Alerts with `status` (`open` or `resolved`), or all alerts when `None`; newest first.
+pub async fn alerts_list(
+    pool: &PgPool,
+    status: Option<&str>,
+    limit: i64,
+) -> Result<Vec<AlertRow>, sqlx::Error> {
+    sqlx::query_as::<_, AlertRow>(
+        "SELECT id, kind, owner, severity, details, status, notify_count, opened_at, last_seen_at,
+                acknowledged_by, acknowledged_at, resolved_at
+         FROM alerts WHERE $1::TEXT IS NULL OR status = $1 ORDER BY id DESC LIMIT $2",
+    )
+    .bind(status)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Acknowledges an open alert. Returns false if it does not exist, is resolved or was
+/// already acknowledged.
+pub async fn alert_acknowledge(pool: &PgPool, id: i64, admin: &str) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE alerts SET acknowledged_by = $2, acknowledged_at = NOW()
+         WHERE id = $1 AND status = 'open' AND acknowledged_at IS NULL",
+    )
+    .bind(id)
+    .bind(admin)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+// -----------------
+// Anomaly rules
+// -----------------
+/// (id, kind, params, severity, actions, enabled)
+pub type AnomalyRuleRow = (String, String, serde_json::Value, String, Vec<String>, bool);
+
+/// Stores a rule. With `overwrite` false an existing rule of the same id is left untouched.
+pub async fn anomaly_rule_upsert(
+    pool: &PgPool,
+    rule: &AnomalyRuleRow,
+    overwrite: bool,
+) -> Result<(), sqlx::Error> {
+    let (id, kind, params, severity, actions, enabled) = rule;
+    let sql = if overwrite {
+        "INSERT INTO anomaly_rules (id, kind, params, severity, actions, enabled) VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT (id) DO UPDATE SET kind = EXCLUDED.kind, params = EXCLUDED.params,
+             severity = EXCLUDED.severity, actions = EXCLUDED.actions, enabled = EXCLUDED.enabled,
+             updated_at = NOW()"
+    } else {
+        "INSERT INTO anomaly_rules (id, kind, params, severity, actions, enabled) VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT (id) DO NOTHING"
+    };
+    sqlx::query(sql)
+        .bind(id)
+        .bind(kind)
+        .bind(params)
+        .bind(severity)
+        .bind(actions)
+        .bind(enabled)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn anomaly_rules_list(pool: &PgPool) -> Result<Vec<AnomalyRuleRow>, sqlx::Error> {
+    sqlx::query_as::<_, AnomalyRuleRow>(
+        "SELECT id, kind, params, severity, actions, enabled FROM anomaly_rules ORDER BY id",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Marks the rule as triggered for `owner`. Returns true if it was not already active,
+/// i.e. the rule's actions should run.
+pub async fn anomaly_state_trigger(
+    pool: &PgPool,
+    rule_id: &str,
+    owner: &str,
+    details: &serde_json::Value,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "WITH prev AS (SELECT active FROM anomaly_rule_state WHERE rule_id = $1 AND owner = $2)
+         INSERT INTO anomaly_rule_state (rule_id, owner, details) VALUES ($1, $2, $3)
+         ON CONFLICT (rule_id, owner) DO UPDATE SET details = EXCLUDED.details,
+             last_triggered_at = NOW(), cleared_at = NULL, active = TRUE,
+             trigger_count = anomaly_rule_state.trigger_count
+                 + CASE WHEN anomaly_rule_state.active THEN 0 ELSE 1 END
+         RETURNING COALESCE((SELECT NOT active FROM prev), TRUE)",
+    )
+    .bind(rule_id)
+    .bind(owner)
+    .bind(details)
+    .fetch_one(pool)
+    .await
+}
+
+/// Clears the rule for every active owner not in `owners`.
+pub async fn anomaly_state_clear_except(
+    pool: &PgPool,
+    rule_id: &str,
+    owners: &[String],
+) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE anomaly_rule_state SET active = FALSE, cleared_at = NOW()
+         WHERE rule_id = $1 AND active AND NOT (owner = ANY($2))",
+    )
+    .bind(rule_id)
+    .bind(owners)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+/// (rule_id, owner, trigger_count, details, last_triggered_at)
+pub type AnomalyStateRow = (String, String, i32, serde_json::Value, time::OffsetDateTime);
+
+pub async fn anomaly_states_active(pool: &PgPool) -> Result<Vec<AnomalyStateRow>, sqlx::Error> {
+    sqlx::query_as::<_, AnomalyStateRow>(
+        "SELECT rule_id, owner, trigger_count, details, last_triggered_at
+         FROM anomaly_rule_state WHERE active ORDER BY last_triggered_at DESC",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// (owner, transactions) for owners with more than `max_count` transactions in the window.
+pub async fn anomaly_tx_rate(
+    pool: &PgPool,
+    window_seconds: i64,
+    max_count: i64,
+) -> Result<Vec<(String, i64)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, i64)>(
+        "SELECT owner, COUNT(*)::BIGINT FROM transactions
+         WHERE created_at > NOW() - make_interval(secs => $1::DOUBLE PRECISION)
+         GROUP BY owner HAVING COUNT(*) > $2",
+    )
+    .bind(window_seconds as f64)
+    .bind(max_count)
+    .fetch_all(pool)
+    .await
+}
+
+/// (owner, withdrawn, total_balance) for owners who withdrew at least `percent` of their
+/// balance at the start of the window.
+pub async fn anomaly_withdrawal_ratio(
+    pool: &PgPool,
+    window_seconds: i64,
+    percent: i64,
+) -> Result<Vec<(String, i64, i64)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, i64, i64)>(
+        "SELECT t.owner, SUM(t.amount)::BIGINT, v.total_balance FROM transactions t
+         JOIN vaults v ON v.owner = t.owner
+         WHERE t.kind = 'withdraw' AND t.amount > 0
+           AND t.created_at > NOW() - make_interval(secs => $1::DOUBLE PRECISION)
+         GROUP BY t.owner, v.total_balance
+         HAVING SUM(t.amount) * 100 >= $2 * (v.total_balance + SUM(t.amount))",
+    )
+    .bind(window_seconds as f64)
+    .bind(percent)
+    .fetch_all(pool)
+    .await
+}
+
+/// (owner, addresses, withdrawals) for owners who withdrew after adding a whitelist address
+/// less than `window_seconds` ago.
+pub async fn anomaly_new_whitelist_withdrawals(
+    pool: &PgPool,
+    window_seconds: i64,
+) -> Result<Vec<(String, Vec<String>, i64)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, Vec<String>, i64)>(
+        "SELECT w.owner, array_agg(DISTINCT w.address), COUNT(DISTINCT t.id)::BIGINT
+         FROM (
+             SELECT owner, address, added_at FROM withdraw_whitelist
+             UNION
+             SELECT owner, details->>'address', created_at FROM audit_trail
+             WHERE action = 'whitelist_add_requested' AND owner IS NOT NULL
+               AND details->>'address' IS NOT NULL
+         ) w
+         JOIN transactions t ON t.owner = w.owner AND t.kind = 'withdraw' AND t.created_at >= w.added_at
+         WHERE w.added_at > NOW() - make_interval(secs => $1::DOUBLE PRECISION)
+         GROUP BY w.owner",
+    )
+    .bind(window_seconds as f64)
+    .fetch_all(pool)
+    .await
+}
+
+/// (owner, changes) for owners with more than `max_changes` delegate adds/removes in the window.
+pub async fn anomaly_delegate_churn(
+    pool: &PgPool,
+    window_seconds: i64,
+    max_changes: i64,
+) -> Result<Vec<(String, i64)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, i64)>(
+        "SELECT owner, COUNT(*)::BIGINT FROM audit_trail
+         WHERE action IN ('delegate_add', 'delegate_remove') AND owner IS NOT NULL
+           AND created_at > NOW() - make_interval(secs => $1::DOUBLE PRECISION)
+         GROUP BY owner HAVING COUNT(*) > $2",
+    )
+    .bind(window_seconds as f64)
+    .bind(max_changes)
+    .fetch_all(pool)
+    .await
+}
+
+/// (owner, failures) for owners with more than `max_failures` rejected signatures or nonces.
+pub async fn anomaly_auth_failures(
+    pool: &PgPool,
+    window_seconds: i64,
+    max_failures: i64,
+) -> Result<Vec<(String, i64)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, i64)>(
+        "SELECT owner, COUNT(*)::BIGINT FROM auth_failures
+         WHERE created_at > NOW() - make_interval(secs => $1::DOUBLE PRECISION)
+         GROUP BY owner HAVING COUNT(*) > $2",
+    )
+    .bind(window_seconds as f64)
+    .bind(max_failures)
+    .fetch_all(pool)
+    .await
+}
+
+/// (owner, count) for owners with an emergency withdrawal in the window.
+pub async fn anomaly_emergency_withdrawals(
+    pool: &PgPool,
+    window_seconds: i64,
+) -> Result<Vec<(String, i64)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, i64)>(
+        "SELECT owner, COUNT(*)::BIGINT FROM transactions
+         WHERE kind = 'emergency_withdraw'
+           AND created_at > NOW() - make_interval(secs => $1::DOUBLE PRECISION)
+         GROUP BY owner",
+    )
+    .bind(window_seconds as f64)
+    .fetch_all(pool)
+    .await
+}
+
+// -----------------
+// Circuit breaker
+// -----------------
+/// Freezes an active vault. Returns false if the vault does not exist or is already frozen.
+pub async fn vault_freeze(
+    pool: &PgPool,
+    owner: &str,
+    reason: &str,
+    frozen_by: &str,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE vaults SET status = 'frozen', frozen_reason = $2, frozen_by = $3,
+                frozen_at = NOW(), updated_at = NOW()
+         WHERE owner = $1 AND status <> 'frozen'",
+    )
+    .bind(owner)
+    .bind(reason)
+    .bind(frozen_by)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+/// Returns false if the vault is not frozen.
+pub async fn vault_unfreeze(pool: &PgPool, owner: &str) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE vaults SET status = 'active', frozen_reason = NULL, frozen_by = NULL,
+                frozen_at = NULL, updated_at = NOW()
+         WHERE owner = $1 AND status = 'frozen'",
+    )
+    .bind(owner)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+/// (owner, reason, frozen_by, frozen_at)
+pub type FrozenVaultRow = (
+    String,
+    Option<String>,
+    Option<String>,
+    Option<time::OffsetDateTime>,
+);
+
+/// The first of `owners` whose vault is frozen.
+pub async fn vault_frozen_any(
+    pool: &PgPool,
+    owners: &[String],
+) -> Result<Option<FrozenVaultRow>, sqlx::Error> {
+    sqlx::query_as::<_, FrozenVaultRow>(
+        "SELECT owner, frozen_reason, frozen_by, frozen_at FROM vaults
+         WHERE owner = ANY($1) AND status = 'frozen' ORDER BY owner LIMIT 1",
+    )
+    .bind(owners)
+    .fetch_optional(pool)
+    .await
+}
+
+pub async fn vaults_frozen_list(pool: &PgPool) -> Result<Vec<FrozenVaultRow>, sqlx::Error> {
+    sqlx::query_as::<_, FrozenVaultRow>(
+        "SELECT owner, frozen_reason, frozen_by, frozen_at FROM vaults
+         WHERE status = 'frozen' ORDER BY frozen_at DESC NULLS LAST",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Sets a kill switch and its reason. Returns true if the operation changed state.
+pub async fn operation_switch_set(
+    pool: &PgPool,
+    operation: &str,
+    enabled: bool,
+    reason: Option<&str>,
+    updated_by: &str,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "WITH prev AS (SELECT enabled FROM operation_switches WHERE operation = $1)
+         INSERT INTO operation_switches (operation, enabled, reason, updated_by)
+         VALUES ($1, $2, $3, $4)
+         ON CONFLICT (operation) DO UPDATE SET enabled = EXCLUDED.enabled,
+             reason = EXCLUDED.reason, updated_by = EXCLUDED.updated_by, updated_at = NOW()
+         RETURNING COALESCE((SELECT enabled FROM prev), TRUE) <> $2",
+    )
+    .bind(operation)
+    .bind(enabled)
+    .bind(reason)
+    .bind(updated_by)
+    .fetch_one(pool)
+    .await
+}
+
+/// `Some(reason)` if the operation is switched off.
+pub async fn operation_switch_disabled(
+    pool: &PgPool,
+    operation: &str,
+) -> Result<Option<Option<String>>, sqlx::Error> {
+    sqlx::query_scalar::<_, Option<String>>(
+        "SELECT reason FROM operation_switches WHERE operation = $1 AND NOT enabled",
+    )
+    .bind(operation)
+    .fetch_optional(pool)
+    .await
+}
+
+/// (operation, enabled, reason, updated_by, updated_at)
+pub type OperationSwitchRow = (String, bool, Option<String>, String, time::OffsetDateTime);
+
+pub async fn operation_switches_list(
+    pool: &PgPool,
+) -> Result<Vec<OperationSwitchRow>, sqlx::Error> {
+    sqlx::query_as::<_, OperationSwitchRow>(
+        "SELECT operation, enabled, reason, updated_by, updated_at
+         FROM operation_switches ORDER BY operation",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+// -----------------
+// Timelocks
+// -----------------
+pub async fn timelock_insert(
+    pool: &PgPool,
+    owner: &str,
+    amount: i64,
+    unlock_at: time::OffsetDateTime,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO timelocks (owner, amount, unlock_at) VALUES ($1, $2, $3)")
+        .bind(owner)
+        .bind(amount)
+        .bind(unlock_at)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn timelock_list(
+    pool: &PgPool,
+    owner: &str,
+) -> Result<Vec<Timelock>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, Timelock>(
+        "SELECT id, owner, amount, unlock_at, status FROM timelocks WHERE owner = $1 ORDER BY unlock_at ASC"
+    )
+    .bind(owner)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+pub async fn timelock_mark_status(pool: &PgPool, id: i64, status: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE timelocks SET status = $2, updated_at = NOW() WHERE id = $1")
+        .bind(id)
+        .bind(status)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn timelock_due_within(
+    pool: &PgPool,
+    seconds: i64,
+) -> Result<Vec<Timelock>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, Timelock>(
+        "SELECT id, owner, amount, unlock_at, status FROM timelocks WHERE status = 'scheduled' AND unlock_at <= NOW() + make_interval(secs => $1) ORDER BY unlock_at ASC"
+    )
+    .bind(seconds)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows)
+}
+
+// -----------------
+// Delegates (off-chain)
+// -----------------
+pub async fn delegate_add(pool: &PgPool, owner: &str, delegate: &str) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "INSERT INTO vault_delegates (owner, delegate) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+    )
+    .bind(owner)
+    .bind(delegate)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+pub async fn delegate_remove(
+    pool: &PgPool,
+    owner: &str,
+    delegate: &str,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM vault_delegates WHERE owner = $1 AND delegate = $2")
+        .bind(owner)
+        .bind(delegate)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+pub async fn delegate_list(pool: &PgPool, owner: &str) -> Result<Vec<String>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (String,)>(
+        "SELECT delegate FROM vault_delegates WHERE owner = $1 ORDER BY added_at DESC",
+    )
+    .bind(owner)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|(d,)| d).collect())
+}
+
+// -----------------
+// Multisig proposals
+// -----------------
+pub async fn ms_create_proposal(
+    pool: &PgPool,
+    id: &str,
+    owner: &str,
+    amount: i64,
+    threshold: i32,
+    signers_json: &serde_json::Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO ms_proposals (id, owner, amount, threshold, signers) VALUES ($1, $2, $3, $4, $5)"
+    )
+    .bind(id)
+    .bind(owner)
+    .bind(amount)
+    .bind(threshold)
+    .bind(signers_json)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn ms_insert_approval(
+    pool: &PgPool,
+    proposal_id: &str,
+    signer: &str,
+    signature: &str,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "INSERT INTO ms_approvals (proposal_id, signer, signature) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING"
+    )
+    .bind(proposal_id)
+    .bind(signer)
+    .bind(signature)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+pub async fn ms_get_proposal(pool: &PgPool, id: &str) -> Result<Option<Proposal>, sqlx::Error> {
+    let row = sqlx::query_as::<_, Proposal>(
+        "SELECT id::text AS id, owner, amount, threshold, signers, status, created_at FROM ms_proposals WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(row)
+}
+
+pub async fn ms_count_approvals(pool: &PgPool, id: &str) -> Result<i64, sqlx::Error> {
+    let count =
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(1) FROM ms_approvals WHERE proposal_id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await?;
+    Ok(count)
+}
+
+pub async fn ms_set_status(pool: &PgPool, id: &str, status: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE ms_proposals SET status = $2, updated_at = NOW() WHERE id = $1")
+        .bind(id)
+        .bind(status)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn ms_list_approvals(pool: &PgPool, id: &str) -> Result<Vec<String>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (String,)>(
+        "SELECT signer FROM ms_approvals WHERE proposal_id = $1 ORDER BY id ASC",
+    )
+    .bind(id)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|(s,)| s).collect())
+}
+
+pub async fn ms_get_contacts_for(
+    pool: &PgPool,
+    pubkeys: &[String],
+) -> Result<Vec<(String, Option<String>)>, sqlx::Error> {
+    if pubkeys.is_empty() {
+        return Ok(vec![]);
+    }
+    // Build simple IN clause
+    let query = String::from("SELECT pubkey, email FROM ms_signer_contacts WHERE pubkey = ANY($1)");
+    let rows = sqlx::query_as::<_, (String, Option<String>)>(&query)
+        .bind(pubkeys)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+// -----------------
+// Email notifications
+// -----------------
+/// (email, disabled kinds, unsubscribed, unsubscribe_token)
+pub type NotificationContactRow = (Option<String>, Vec<String>, bool, Option<String>);
+
+pub async fn notification_contact_get(
+    pool: &PgPool,
+    pubkey: &str,
+) -> Result<Option<NotificationContactRow>, sqlx::Error> {
+    sqlx::query_as::<_, NotificationContactRow>(
+        "SELECT email, email_disabled, unsubscribed_at IS NOT NULL, unsubscribe_token
+         FROM ms_signer_contacts WHERE pubkey = $1",
+    )
+    .bind(pubkey)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Sets (or clears) the email address. A new address re-subscribes with a fresh token.
+pub async fn notification_contact_set_email(
+    pool: &PgPool,
+    pubkey: &str,
+    email: Option<&str>,
+    unsubscribe_token: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO ms_signer_contacts (pubkey, email, unsubscribe_token) VALUES ($1, $2, $3)
+         ON CONFLICT (pubkey) DO UPDATE
+         SET email = EXCLUDED.email, unsubscribe_token = EXCLUDED.unsubscribe_token,
+             unsubscribed_at = NULL, updated_at = NOW()",
+    )
+    .bind(pubkey)
+    .bind(email)
+    .bind(unsubscribe_token)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn notification_contact_set_disabled(
+    pool: &PgPool,
+    pubkey: &str,
+    disabled: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO ms_signer_contacts (pubkey, email_disabled) VALUES ($1, $2)
+         ON CONFLICT (pubkey) DO UPDATE SET email_disabled = EXCLUDED.email_disabled, updated_at = NOW()",
+    )
+    .bind(pubkey)
+    .bind(disabled)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Returns false if no contact has this token.
+pub async fn notification_unsubscribe(pool: &PgPool, token: &str) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE ms_signer_contacts SET unsubscribed_at = COALESCE(unsubscribed_at, NOW()), updated_at = NOW()
+         WHERE unsubscribe_token = $1",
+    )
+    .bind(token)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+/// Emails queued or sent to `recipient` in the last `seconds`, for rate limiting.
+pub async fn email_recent_count(
+    pool: &PgPool,
+    recipient: &str,
+    seconds: i64,
+) -> Result<i64, sqlx::Error> {
+    let (count,) = sqlx::query_as::<_, (i64,)>(
+        "SELECT COUNT(*) FROM email_deliveries
+         WHERE recipient = $1 AND status <> 'failed'
+           AND created_at > NOW() - make_interval(secs => $2::DOUBLE PRECISION)",
+    )
+    .bind(recipient)
+    .bind(seconds as f64)
+    .fetch_one(pool)
+    .await?;
+    Ok(count)
+}
+
+/// Returns false if an email with the same `dedup_key` was already queued.
+pub async fn email_enqueue(
+    pool: &PgPool,
+    dedup_key: &str,
+    pubkey: &str,
+    recipient: &str,
+    kind: &str,
+    subject: &str,
+    body: &str,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "INSERT INTO email_deliveries (dedup_key, pubkey, recipient, kind, subject, body)
+         VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (dedup_key) DO NOTHING",
+    )
+    .bind(dedup_key)
+    .bind(pubkey)
+    .bind(recipient)
+    .bind(kind)
+    .bind(subject)
+    .bind(body)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+/// (id, recipient, subject, body, attempts)
+pub type EmailDeliveryRow = (i64, String, String, String, i32);
+
+pub async fn email_deliveries_due(
+    pool: &PgPool,
+    limit: i64,
+) -> Result<Vec<EmailDeliveryRow>, sqlx::Error> {
+    sqlx::query_as::<_, EmailDeliveryRow>(
+        "SELECT id, recipient, subject, body, attempts FROM email_deliveries
+         WHERE status = 'pending' AND next_attempt_at <= NOW()
+         ORDER BY next_attempt_at LIMIT $1",
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn email_delivery_sent(pool: &PgPool, id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE email_deliveries
+         SET status = 'sent', attempts = attempts + 1, sent_at = NOW(), last_error = NULL
+         WHERE id = $1",
+    )
+    .bind(id)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Schedules another attempt, or marks the email failed when `delay_seconds` is `None`.
+pub async fn email_delivery_retry(
+    pool: &PgPool,
+    id: i64,
+    error: &str,
+    delay_seconds: Option<i64>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE email_deliveries
+         SET attempts = attempts + 1, last_error = $2,
+             status = CASE WHEN $3::DOUBLE PRECISION IS NULL THEN 'failed' ELSE status END,
+             next_attempt_at = NOW() + make_interval(secs => COALESCE($3::DOUBLE PRECISION, 0))
+         WHERE id = $1",
+    )
+    .bind(id)
+    .bind(error)
+    .bind(delay_seconds.map(|s| s as f64))
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// -----------------
+// Ledger
+// -----------------
+/// Appends an entry with its `(owner, account, amount)` postings. Returns false if an entry
+/// of this kind already exists for `signature`.
+pub async fn ledger_post(
+    pool: &PgPool,
+    signature: &str,
+    kind: &str,
+    postings: &[(String, String, i64)],
+) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let entry_id = sqlx::query_scalar::<_, i64>(
+        "INSERT INTO ledger_entries (signature, kind) VALUES ($1, $2)
+         ON CONFLICT (signature, kind) DO NOTHING
+         RETURNING id",
+    )
+    .bind(signature)
+    .bind(kind)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(entry_id) = entry_id else {
+        return Ok(false);
+    };
+    for (owner, account, amount) in postings {
+        sqlx::query(
+            "INSERT INTO ledger_postings (entry_id, owner, account, amount) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(entry_id)
+        .bind(owner)
+        .bind(account)
+        .bind(amount)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(true)
+}
+
+pub async fn ledger_entry_exists(
+    pool: &PgPool,
+    signature: &str,
+    kind: &str,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM ledger_entries WHERE signature = $1 AND kind = $2)",
+    )
+    .bind(signature)
+    .bind(kind)
+    .fetch_one(pool)
+    .await
+}
+
+/// Amount moved to `in_transit` by the `withdraw_submitted` entry for `signature`.
+pub async fn ledger_in_transit_amount(
+    pool: &PgPool,
+    signature: &str,
+) -> Result<Option<(String, i64)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, i64)>(
+        "SELECT p.owner, p.amount FROM ledger_postings p
+         JOIN ledger_entries e ON e.id = p.entry_id
+         WHERE e.signature = $1 AND e.kind = 'withdraw_submitted' AND p.account = 'in_transit'",
+    )
+    .bind(signature)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Time of the earliest ledger entry.
+pub async fn ledger_first_entry_at(
+    pool: &PgPool,
+) -> Result<Option<time::OffsetDateTime>, sqlx::Error> {
+    sqlx::query_scalar::<_, Option<time::OffsetDateTime>>("SELECT MIN(created_at) FROM ledger_entries")
+        .fetch_one(pool)
+        .await
+}
+
+pub async fn ledger_vault_balances(
+    pool: &PgPool,
+    owner: &str,
+) -> Result<Option<LedgerBalances>, sqlx::Error> {
+    sqlx::query_as::<_, LedgerBalances>("SELECT * FROM ledger_vault_balances WHERE owner = $1")
+        .bind(owner)
+        .fetch_optional(pool)
+        .await
+}
+
+pub async fn ledger_all_vault_balances(pool: &PgPool) -> Result<Vec<LedgerBalances>, sqlx::Error> {
+    sqlx::query_as::<_, LedgerBalances>("SELECT * FROM ledger_vault_balances ORDER BY owner")
+        .fetch_all(pool)
+        .await
+}
+
+/// `owner`'s postings in their `limit` most recent entries, newest first.
+pub async fn ledger_postings_for(
+    pool: &PgPool,
+    owner: &str,
+    limit: i64,
+) -> Result<Vec<LedgerPosting>, sqlx::Error> {
+    sqlx::query_as::<_, LedgerPosting>(
+        "SELECT e.id AS entry_id, e.signature, e.kind, p.owner, p.account, p.amount, e.created_at
+         FROM ledger_postings p
+         JOIN ledger_entries e ON e.id = p.entry_id
+         WHERE p.owner = $1 AND e.id IN (
+             SELECT DISTINCT entry_id FROM ledger_postings WHERE owner = $1
+             ORDER BY entry_id DESC LIMIT $2
+         )
+         ORDER BY e.id DESC, p.id ASC",
+    )
+    .bind(owner)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// (entry id, an owner it touches, sum of its postings) for entries that do not sum to zero.
+pub async fn ledger_unbalanced_entries(
+    pool: &PgPool,
+) -> Result<Vec<(i64, String, i64)>, sqlx::Error> {
+    sqlx::query_as::<_, (i64, String, i64)>(
+        "SELECT entry_id, MIN(owner), SUM(amount)::BIGINT FROM ledger_postings
+         GROUP BY entry_id HAVING SUM(amount) <> 0",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// (owner, account, balance) for vault accounts below zero.
+pub async fn ledger_negative_balances(
+    pool: &PgPool,
+) -> Result<Vec<(String, String, i64)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, String, i64)>(
+        "SELECT owner, account, balance FROM ledger_balances
+         WHERE account <> 'external' AND balance < 0
+         ORDER BY owner, account",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// (signature, owner, amount, created_at) of submitted withdrawals older than
+/// `older_than_secs` that are neither settled nor failed, oldest first.
+pub async fn ledger_unsettled_withdrawals(
+    pool: &PgPool,
+    older_than_secs: i64,
+) -> Result<Vec<(String, String, i64, time::OffsetDateTime)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, String, i64, time::OffsetDateTime)>(
+        "SELECT e.signature, p.owner, p.amount, e.created_at
+         FROM ledger_entries e
+         JOIN ledger_postings p ON p.entry_id = e.id AND p.account = 'in_transit'
+         WHERE e.kind = 'withdraw_submitted'
+           AND e.created_at < NOW() - make_interval(secs => $1::DOUBLE PRECISION)
+           AND NOT EXISTS (
+               SELECT 1 FROM ledger_entries s
+               WHERE s.signature = e.signature AND s.kind IN ('withdraw_settled', 'withdraw_failed')
+           )
+         ORDER BY e.created_at ASC",
+    )
+    .bind(older_than_secs as f64)
+    .fetch_all(pool)
+    .await
+}
+
+// -----------------
+// Balance snapshots
+// -----------------
+/// Latest recorded period of `granularity` across all vaults.
+pub async fn balance_snapshot_last_period(
+    pool: &PgPool,
+    granularity: &str,
+) -> Result<Option<time::OffsetDateTime>, sqlx::Error> {
+    sqlx::query_scalar::<_, Option<time::OffsetDateTime>>(
+        "SELECT MAX(period_start) FROM balance_snapshots WHERE granularity = $1",
+    )
+    .bind(granularity)
+    .fetch_one(pool)
+    .await
+}
+
+/// Snapshots every vault for the period starting at `period_start` with its ledger balances
+/// as of `period_end`. Existing snapshots are kept. Returns the rows written.
+pub async fn balance_snapshot_from_ledger(
+    pool: &PgPool,
+    granularity: &str,
+    period_start: time::OffsetDateTime,
+    period_end: time::OffsetDateTime,
+) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query(
+        "INSERT INTO balance_snapshots
+             (owner, balance, locked_balance, available, yield_deposited, yield_accrued,
+              granularity, period_start, recorded_at)
+         SELECT v.owner, b.available + b.locked + b.in_transit, b.locked, b.available,
+                b.yield_deposited, b.yield_accrued, $1, $2, NOW()
+         FROM vaults v
+         CROSS JOIN LATERAL (
+             SELECT
+                 COALESCE(SUM(p.amount) FILTER (WHERE p.account = 'available'), 0)::BIGINT AS available,
+                 COALESCE(SUM(p.amount) FILTER (WHERE p.account = 'locked'), 0)::BIGINT AS locked,
+                 COALESCE(SUM(p.amount) FILTER (WHERE p.account = 'in_transit'), 0)::BIGINT AS in_transit,
+                 COALESCE(SUM(p.amount) FILTER (WHERE p.account = 'yield_deposited'), 0)::BIGINT AS yield_deposited,
+                 COALESCE(SUM(p.amount) FILTER (WHERE p.account = 'yield_accrued'), 0)::BIGINT AS yield_accrued
+             FROM ledger_postings p
+             JOIN ledger_entries e ON e.id = p.entry_id
+             WHERE p.owner = v.owner AND e.created_at < $3
+         ) b
+         ON CONFLICT (owner, granularity, period_start) DO NOTHING",
+    )
+    .bind(granularity)
+    .bind(period_start)
+    .bind(period_end)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+/// Copies the closing hourly snapshot (23:00) of the day starting at `day_start` into a daily
+/// snapshot. Existing daily snapshots are kept. Returns the rows written.
+pub async fn balance_snapshot_rollup_daily(
+    pool: &PgPool,
+    day_start: time::OffsetDateTime,
+) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query(
+        "INSERT INTO balance_snapshots
+             (owner, balance, locked_balance, available, yield_deposited, yield_accrued,
+              granularity, period_start, recorded_at)
+         SELECT owner, balance, locked_balance, available, yield_deposited, yield_accrued,
+                'daily', $1, NOW()
+         FROM balance_snapshots
+         WHERE granularity = 'hourly' AND period_start = $1 + INTERVAL '23 hours'
+         ON CONFLICT (owner, granularity, period_start) DO NOTHING",
+    )
+    .bind(day_start)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+/// Deletes hourly snapshots before `before` whose day already has a daily snapshot.
+pub async fn balance_snapshot_prune_hourly(
+    pool: &PgPool,
+    before: time::OffsetDateTime,
+) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query(
+        "DELETE FROM balance_snapshots h
+         WHERE h.granularity = 'hourly' AND h.period_start < $1
+           AND EXISTS (
+               SELECT 1 FROM balance_snapshots d
+               WHERE d.owner = h.owner AND d.granularity = 'daily'
+                 AND d.period_start = date_trunc('day', h.period_start AT TIME ZONE 'UTC') AT TIME ZONE 'UTC'
+           )",
+    )
+    .bind(before)
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
+
+/// `owner`'s snapshots of `granularity` with `period_start` in `[from, to]`, oldest first.
+pub async fn balance_snapshots_list(
+    pool: &PgPool,
+    owner: &str,
+    granularity: &str,
+    from: time::OffsetDateTime,
+    to: time::OffsetDateTime,
+    limit: i64,
+) -> Result<Vec<BalanceSnapshot>, sqlx::Error> {
+    sqlx::query_as::<_, BalanceSnapshot>(
+        "SELECT period_start, balance, available, locked_balance, yield_deposited, yield_accrued
+         FROM balance_snapshots
+         WHERE owner = $1 AND granularity = $2 AND period_start BETWEEN $3 AND $4
+         ORDER BY period_start ASC
+         LIMIT $5",
+    )
+    .bind(owner)
+    .bind(granularity)
+    .bind(from)
+    .bind(to)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+// -----------------
+// TVL
+// -----------------
+
+/// Sum of the ledger balances of every registered vault.
+pub async fn tvl_current(pool: &PgPool) -> Result<Tvl, sqlx::Error> {
+    sqlx::query_as::<_, Tvl>(
+        "SELECT COALESCE(SUM(b.available), 0)::BIGINT AS available,
+                COALESCE(SUM(b.locked), 0)::BIGINT AS locked,
+                COALESCE(SUM(b.yield_deposited + b.yield_accrued), 0)::BIGINT AS in_yield
+         FROM vaults v
+         JOIN ledger_vault_balances b ON b.owner = v.owner",
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// TVL per `unit` (`date_trunc`) bucket between `from` and `to`, summed over the vaults'
+/// `granularity` snapshots of the bucket's closing period. Oldest first.
+pub async fn tvl_series(
+    pool: &PgPool,
+    granularity: &str,
+    unit: &str,
+    from: time::OffsetDateTime,
+    to: time::OffsetDateTime,
+    limit: i64,
+) -> Result<Vec<TvlPoint>, sqlx::Error> {
+    sqlx::query_as::<_, TvlPoint>(
+        "WITH buckets AS (
+             SELECT date_trunc($2, period_start AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' AS bucket,
+                    MAX(period_start) AS closing
+             FROM balance_snapshots
+             WHERE granularity = $1 AND period_start BETWEEN $3 AND $4
+             GROUP BY 1
+         )
+         SELECT b.bucket AS period_start,
+                SUM(s.available)::BIGINT AS available,
+                SUM(s.locked_balance)::BIGINT AS locked,
+                SUM(s.yield_deposited + s.yield_accrued)::BIGINT AS in_yield
+         FROM buckets b
+         JOIN balance_snapshots s ON s.granularity = $1 AND s.period_start = b.closing
+         GROUP BY b.bucket
+         ORDER BY b.bucket ASC
+         LIMIT $5",
+    )
+    .bind(granularity)
+    .bind(unit)
+    .bind(from)
+    .bind(to)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+// -----------------
+// Reconciliation cases
+// -----------------
+
+const CASE_COLUMNS: &str = "id, owner, status, db_state, chain_state, discrepancies, max_discrepancy, occurrences, opened_at, last_seen_at, resolution, resolved_by, resolved_at";
+
+/// Balances of every vault snapshot, compared with the chain by reconciliation.
+pub async fn vault_snapshot_balances(pool: &PgPool) -> Result<Vec<SnapshotRow>, sqlx::Error> {
+    sqlx::query_as::<_, SnapshotRow>(
+        "SELECT owner, token_account, total_balance, locked_balance, yield_deposited_balance,
+                yield_accrued_balance
+         FROM vaults ORDER BY owner",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Overwrites the balances of `owner`'s snapshot. Returns false if the vault does not exist.
+pub async fn vault_rewrite_balances(
+    executor: impl PgExecutor<'_>,
+    owner: &str,
+    balances: &VaultBalances,
+) -> Result<bool, sqlx::Error> {
+    let res = sqlx::query(
+        "UPDATE vaults SET total_balance = $2, locked_balance = $3, yield_deposited_balance = $4,
+                yield_accrued_balance = $5, updated_at = NOW()
+         WHERE owner = $1",
+    )
+    .bind(owner)
+    .bind(balances.total_balance)
+    .bind(balances.locked_balance)
+    .bind(balances.yield_deposited_balance)
+    .bind(balances.yield_accrued_balance)
+    .execute(executor)
+    .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+/// Records the balances read from `owner`'s vault account by a reconciliation run that
+/// started at `slot`.
+pub async fn vault_mark_reconciled(
+    pool: &PgPool,
+    owner: &str,
+    balances: &VaultBalances,
+    slot: u64,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE vaults SET reconciled_balance = $2, reconciled_locked_balance = $3,
+                reconciled_slot = $4, reconciled_at = NOW()
+         WHERE owner = $1",
+    )
+    .bind(owner)
+    .bind(balances.total_balance)
+    .bind(balances.locked_balance)
+    .bind(slot as i64)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn reconciliation_case_insert(
+    executor: impl PgExecutor<'_>,
+    owner: &str,
+    status: &str,
+    db_state: &serde_json::Value,
+    chain_state: &serde_json::Value,
+    discrepancies: &serde_json::Value,
+    max_discrepancy: i64,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        "INSERT INTO reconciliation_cases
+             (owner, status, db_state, chain_state, discrepancies, max_discrepancy)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         RETURNING id",
+    )
+    .bind(owner)
+    .bind(status)
+    .bind(db_state)
+    .bind(chain_state)
+    .bind(discrepancies)
+    .bind(max_discrepancy)
+    .fetch_one(executor)
+    .await
+}
+
+/// Whether `owner` has an escalated case.
+pub async fn reconciliation_case_escalated(
+    pool: &PgPool,
+    owner: &str,
+) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM reconciliation_cases WHERE owner = $1 AND status = 'escalated')",
+    )
+    .bind(owner)
+    .fetch_one(pool)
+    .await
+}
+
+/// Opens an escalated case for `owner`, or records another occurrence on the open one.
+/// Returns (id, whether it was opened).
+pub async fn reconciliation_case_escalate(
+    executor: impl PgExecutor<'_>,
+    owner: &str,
+    db_state: &serde_json::Value,
+    chain_state: &serde_json::Value,
+    discrepancies: &serde_json::Value,
+    max_discrepancy: i64,
+) -> Result<(i64, bool), sqlx::Error> {
+    sqlx::query_as::<_, (i64, bool)>(
+        "INSERT INTO reconciliation_cases
+             (owner, status, db_state, chain_state, discrepancies, max_discrepancy)
+         VALUES ($1, 'escalated', $2, $3, $4, $5)
+         ON CONFLICT (owner) WHERE status = 'escalated' DO UPDATE SET
+             db_state = EXCLUDED.db_state,
+             chain_state = EXCLUDED.chain_state,
+             discrepancies = EXCLUDED.discrepancies,
+             max_discrepancy = EXCLUDED.max_discrepancy,
+             occurrences = reconciliation_cases.occurrences + 1,
+             last_seen_at = NOW()
+         RETURNING id, xmax = 0",
+    )
+    .bind(owner)
+    .bind(db_state)
+    .bind(chain_state)
+    .bind(discrepancies)
+    .bind(max_discrepancy)
+    .fetch_one(executor)
+    .await
+}
+
+/// Newest first, optionally filtered by status and owner.
+pub async fn reconciliation_cases_list(
+    pool: &PgPool,
+    status: Option<&str>,
+    owner: Option<&str>,
+    limit: i64,
+) -> Result<Vec<ReconciliationCase>, sqlx::Error> {
+    sqlx::query_as::<_, ReconciliationCase>(&format!(
+        "SELECT {CASE_COLUMNS} FROM reconciliation_cases
+         WHERE ($1::TEXT IS NULL OR status = $1) AND ($2::TEXT IS NULL OR owner = $2)
+         ORDER BY id DESC LIMIT $3"
+    ))
+    .bind(status)
+    .bind(owner)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+}
+
+/// Resolves an escalated case. Returns None if no escalated case has this id.
+pub async fn reconciliation_case_resolve(
+    executor: impl PgExecutor<'_>,
+    id: i64,
+    resolution: &str,
+    resolved_by: &str,
+) -> Result<Option<ReconciliationCase>, sqlx::Error> {
+    sqlx::query_as::<_, ReconciliationCase>(&format!(
+        "UPDATE reconciliation_cases
+         SET status = 'resolved', resolution = $2, resolved_by = $3, resolved_at = NOW()
+         WHERE id = $1 AND status = 'escalated'
+         RETURNING {CASE_COLUMNS}"
+    ))
+    .bind(id)
+    .bind(resolution)
+    .bind(resolved_by)
+    .fetch_optional(executor)
+    .await
+}
+
+// -----------------
+// Proof of reserves
+// -----------------
+
+const RESERVE_SNAPSHOT_COLUMNS: &str =
+    "id, root, leaf_count, total_balance, total_locked, anchor_signature, created_at";
+
+/// Reconciled state of every vault without an escalated reconciliation case, by owner (byte
+/// order).
+pub async fn reserve_leaves_current(pool: &PgPool) -> Result<Vec<Leaf>, sqlx::Error> {
+    sqlx::query_as::<_, Leaf>(
+        "SELECT v.owner, v.reconciled_balance AS balance, v.reconciled_locked_balance AS locked,
+                v.reconciled_slot AS slot
+         FROM vaults v
+         WHERE v.reconciled_slot IS NOT NULL
+           AND NOT EXISTS (SELECT 1 FROM reconciliation_cases c
+                           WHERE c.owner = v.owner AND c.status = 'escalated')
+         ORDER BY v.owner COLLATE \"C\"",
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Stores a snapshot with its tree and leaves, in leaf order.
+pub async fn reserve_snapshot_insert(
+    pool: &PgPool,
+    root: &str,
+    tree: &[Vec<String>],
+    leaves: &[Leaf],
+) -> Result<ReserveSnapshot, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let snapshot = sqlx::query_as::<_, ReserveSnapshot>(&format!(
+        "INSERT INTO reserve_snapshots (root, leaf_count, total_balance, total_locked, tree)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING {RESERVE_SNAPSHOT_COLUMNS}"
+    ))
+    .bind(root)
+    .bind(leaves.len() as i32)
+    .bind(leaves.iter().map(|l| l.balance).sum::<i64>())
+    .bind(leaves.iter().map(|l| l.locked).sum::<i64>())
+    .bind(sqlx::types::Json(tree))
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query(
+        "INSERT INTO reserve_leaves (snapshot_id, leaf_index, owner, balance, locked, slot)
+         SELECT $1, ordinality - 1, owner, balance, locked, slot
+         FROM UNNEST($2::TEXT[], $3::BIGINT[], $4::BIGINT[], $5::BIGINT[])
+              WITH ORDINALITY AS l(owner, balance, locked, slot, ordinality)",
+    )
+    .bind(snapshot.id)
+    .bind(leaves.iter().map(|l| l.owner.clone()).collect::<Vec<_>>())
+    .bind(leaves.iter().map(|l| l.balance).collect::<Vec<_>>())
+    .bind(leaves.iter().map(|l| l.locked).collect::<Vec<_>>())
+    .bind(leaves.iter().map(|l| l.slot).collect::<Vec<_>>())
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(snapshot)
+}
+
+pub async fn reserve_snapshot_set_anchor(
+    pool: &PgPool,
+    id: i64,
+    signature: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE reserve_snapshots SET anchor_signature = $2 WHERE id = $1")
+        .bind(id)
+        .bind(signature)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// `owner`'s leaf in the newest snapshot that has one: (snapshot, leaf index, leaf).
+pub async fn reserve_leaf_latest(
+    pool: &PgPool,
+    owner: &str,
+) -> Result<Option<(ReserveSnapshot, i32, Leaf)>, sqlx::Error> {
+    let row = sqlx::query_as::<_, (i64, i32, i64, i64, i64)>(
+        "SELECT snapshot_id, leaf_index, balance, locked, slot FROM reserve_leaves
+         WHERE owner = $1 ORDER BY snapshot_id DESC LIMIT 1",
+    )
+    .bind(owner)
+    .fetch_optional(pool)
+    .await?;
+    let Some((snapshot_id, leaf_index, balance, locked, slot)) = row else {
+        return Ok(None);
+    };
+    let snapshot = sqlx::query_as::<_, ReserveSnapshot>(&format!(
+        "SELECT {RESERVE_SNAPSHOT_COLUMNS} FROM reserve_snapshots WHERE id = $1"
+    ))
+    .bind(snapshot_id)
+    .fetch_one(pool)
+    .await?;
+    let leaf = Leaf {
+        owner: owner.to_string(),
+        balance,
+        locked,
+        slot,
+    };
+    Ok(Some((snapshot, leaf_index, leaf)))
+}
+
+/// Levels of a snapshot's tree as hex hashes, leaves first.
+pub async fn reserve_snapshot_tree(
+    pool: &PgPool,
+    id: i64,
+) -> Result<Vec<Vec<String>>, sqlx::Error> {
+    let tree = sqlx::query_scalar::<_, sqlx::types::Json<Vec<Vec<String>>>>(
+        "SELECT tree FROM reserve_snapshots WHERE id = $1",
+    )
+    .bind(id)
+    .fetch_one(pool)
+    .await?;
+    Ok(tree.0)
+}
+
+pub async fn list_vaults(pool: &PgPool) -> Result<Vec<Vault>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, Vault>(&format!("SELECT {VAULT_COLUMNS} FROM vaults"))
+        .fetch_all(pool)
+        .await?;
     Ok(rows)
 }
 
@@ -0,0 +1,306 @@
+//! Email notifications: templates, recipient checks and the SMTP transport.
+//!
+//! Emails are rendered when queued into `email_deliveries` and sent by the email dispatcher
+//! task. Recipients come from `ms_signer_contacts`; owners opt out per kind or unsubscribe
+//! entirely through the link in every email.
+
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use data_encoding::HEXLOWER;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Address, AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::time::Duration;
+use tracing::info;
+
+use crate::config::AppConfig;
+use crate::db;
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailKind {
+    MsRequestSignature,
+    MsPartialTxReady,
+    TimelockDueSoon,
+    TimelockAvailable,
+    LowBalance,
+    SecurityAlert,
+}
+
+impl EmailKind {
+    pub const ALL: [EmailKind; 6] = [
+        EmailKind::MsRequestSignature,
+        EmailKind::MsPartialTxReady,
+        EmailKind::TimelockDueSoon,
+        EmailKind::TimelockAvailable,
+        EmailKind::LowBalance,
+        EmailKind::SecurityAlert,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailKind::MsRequestSignature => "ms_request_signature",
+            EmailKind::MsPartialTxReady => "ms_partial_tx_ready",
+            EmailKind::TimelockDueSoon => "timelock_due_soon",
+            EmailKind::TimelockAvailable => "timelock_available",
+            EmailKind::LowBalance => "low_balance",
+            EmailKind::SecurityAlert => "security_alert",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<EmailKind> {
+        EmailKind::ALL.into_iter().find(|k| k.as_str() == s)
+    }
+}
+
+fn field(context: &Value, key: &str) -> String {
+    match context.get(key) {
+        Some(Value::String(s)) => s.clone(),
+        Some(v) => v.to_string(),
+        None => "-".to_string(),
+    }
+}
+
+/// Subject and plain-text body for `kind`, with the unsubscribe footer.
+pub fn render(
+    kind: EmailKind,
+    pubkey: &str,
+    context: &Value,
+    unsubscribe_url: Option<&str>,
+) -> (String, String) {
+    let f = |key: &str| field(context, key);
+    let (subject, mut body) = match kind {
+        EmailKind::MsRequestSignature => (
+            format!("Signature requested for withdrawal {}", f("proposal_id")),
+            format!(
+                "Vault {} proposed a withdrawal of {}.\nYour signature as {} is required to approve it.\n\nProposal: {}\n",
+                f("owner"),
+                f("amount"),
+                f("signer"),
+                f("proposal_id"),
+            ),
+        ),
+        EmailKind::MsPartialTxReady => (
+            format!("Withdrawal {} is ready to co-sign", f("proposal_id")),
+            format!(
+                "The withdrawal of {} from vault {} reached its approval threshold.\nCo-sign and submit the partially signed transaction below.\n\nProposal: {}\nTransaction (base64):\n{}\n",
+                f("amount"),
+                f("owner"),
+                f("proposal_id"),
+                f("tx_base64"),
+            ),
+        ),
+        EmailKind::TimelockDueSoon => (
+            "Timelocked funds unlock soon".to_string(),
+            format!("{} in your vault unlocks at {}.\n", f("amount"), f("unlock_at")),
+        ),
+        EmailKind::TimelockAvailable => (
+            "Timelocked funds are available".to_string(),
+            format!(
+                "{} in your vault unlocked at {} and can now be withdrawn.\n",
+                f("amount"),
+                f("unlock_at")
+            ),
+        ),
+        EmailKind::LowBalance => (
+            "Low vault balance".to_string(),
+            format!(
+                "Your available balance of {} is below the alert threshold of {}.\n",
+                f("available"),
+                f("threshold")
+            ),
+        ),
+        EmailKind::SecurityAlert => (
+            format!("Security alert: {}", f("kind")),
+            format!(
+                "Severity: {}\nDetails: {}\n",
+                f("severity"),
+                context.get("details").unwrap_or(&Value::Null)
+            ),
+        ),
+    };
+    body.push_str(&format!(
+        "\n--\nYou receive this because email notifications are enabled for {pubkey}.\n"
+    ));
+    if let Some(url) = unsubscribe_url {
+        body.push_str(&format!("Unsubscribe: {url}\n"));
+    }
+    (subject, body)
+}
+
+pub fn is_valid_address(email: &str) -> bool {
+    email.parse::<Address>().is_ok()
+}
+
+/// `alice@example.com` → `a***@example.com`, for responses that must not leak the address.
+pub fn mask_address(email: &str) -> String {
+    match email.split_once('@') {
+        Some((user, domain)) => {
+            let first = user.chars().next().unwrap_or('*');
+            format!("{first}***@{domain}")
+        }
+        None => "***".to_string(),
+    }
+}
+
+pub fn generate_unsubscribe_token() -> String {
+    let mut bytes = [0u8; 24];
+    OsRng.fill_bytes(&mut bytes);
+    HEXLOWER.encode(&bytes)
+}
+
+/// Renders and queues an email to `pubkey`'s contact address. Returns false when email is
+/// disabled, the contact has no address, opted out of `kind` or unsubscribed, the hourly
+/// rate limit is reached, or `dedup_key` was already queued.
+pub async fn queue(
+    pool: &PgPool,
+    cfg: &AppConfig,
+    pubkey: &str,
+    kind: EmailKind,
+    context: &Value,
+    dedup_key: &str,
+) -> Result<bool, sqlx::Error> {
+    if cfg.smtp_host.is_empty() {
+        return Ok(false);
+    }
+    let Some((Some(email), disabled, unsubscribed, token)) =
+        db::notification_contact_get(pool, pubkey).await?
+    else {
+        return Ok(false);
+    };
+    if unsubscribed || disabled.iter().any(|k| k == kind.as_str()) {
+        return Ok(false);
+    }
+    if db::email_recent_count(pool, &email, 3600).await? >= cfg.email_rate_limit_per_hour {
+        info!(%pubkey, kind = kind.as_str(), "email rate limit reached, dropping notification");
+        return Ok(false);
+    }
+    let unsubscribe_url = token.map(|token| {
+        format!(
+            "{}/notifications/unsubscribe?token={token}",
+            cfg.public_base_url.trim_end_matches('/')
+        )
+    });
+    let (subject, body) = render(kind, pubkey, context, unsubscribe_url.as_deref());
+    db::email_enqueue(
+        pool,
+        &format!("{}:{dedup_key}", kind.as_str()),
+        pubkey,
+        &email,
+        kind.as_str(),
+        &subject,
+        &body,
+    )
+    .await
+}
+
+/// SMTP transport plus the sender mailbox.
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl Mailer {
+    /// `tls` is `starttls`, `tls` (implicit TLS) or `none` (local relays and tests only).
+    pub fn new(
+        host: &str,
+        port: u16,
+        tls: &str,
+        username: &str,
+        password: &str,
+        from: &str,
+    ) -> Result<Self, String> {
+        let builder = match tls {
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            "tls" => {
+                AsyncSmtpTransport::<Tokio1Executor>::relay(host).map_err(|e| e.to_string())?
+            }
+            _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .map_err(|e| e.to_string())?,
+        };
+        let mut builder = builder.port(port).timeout(Some(SEND_TIMEOUT));
+        if !username.is_empty() {
+            builder =
+                builder.credentials(Credentials::new(username.to_string(), password.to_string()));
+        }
+        Ok(Self {
+            transport: builder.build(),
+            from: from
+                .parse()
+                .map_err(|e| format!("invalid SMTP_FROM: {e}"))?,
+        })
+    }
+
+    /// `None` when `SMTP_HOST` is unset.
+    pub fn from_config(cfg: &AppConfig) -> Result<Option<Self>, String> {
+        if cfg.smtp_host.is_empty() {
+            return Ok(None);
+        }
+        Self::new(
+            &cfg.smtp_host,
+            cfg.smtp_port,
+            &cfg.smtp_tls,
+            &cfg.smtp_username,
+            &cfg.smtp_password,
+            &cfg.smtp_from,
+        )
+        .map(Some)
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, body: String) -> Result<(), String> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse().map_err(|e| format!("invalid recipient: {e}"))?)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)
+            .map_err(|e| e.to_string())?;
+        self.transport
+            .send(message)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kinds_roundtrip() {
+        for kind in EmailKind::ALL {
+            assert_eq!(EmailKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(EmailKind::parse("deposit"), None);
+    }
+
+    #[test]
+    fn test_render_fills_fields_and_footer() {
+        let context = serde_json::json!({ "proposal_id": "p1", "owner": "o1", "amount": 500, "signer": "s1" });
+        let (subject, body) = render(
+            EmailKind::MsRequestSignature,
+            "s1",
+            &context,
+            Some("http://x/notifications/unsubscribe?token=t"),
+        );
+        assert_eq!(subject, "Signature requested for withdrawal p1");
+        assert!(body.contains("withdrawal of 500"));
+        assert!(body.contains("as s1"));
+        assert!(body.ends_with("Unsubscribe: http://x/notifications/unsubscribe?token=t\n"));
+
+        let (_, body) = render(EmailKind::LowBalance, "o1", &serde_json::json!({}), None);
+        assert!(body.contains("balance of -"));
+        assert!(!body.contains("Unsubscribe"));
+    }
+
+    #[test]
+    fn test_address_helpers() {
+        assert!(is_valid_address("alice@example.com"));
+        assert!(!is_valid_address("alice"));
+        assert_eq!(mask_address("alice@example.com"), "a***@example.com");
+        assert_eq!(generate_unsubscribe_token().len(), 48);
+    }
+}
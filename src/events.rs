@@ -0,0 +1,315 @@
+//! Typed domain events published through [`crate::notify::Notifier`].
+//!
+//! Every event is wrapped in an [`EventEnvelope`] and serialized as
+//! `{ "id", "cursor", "version", "timestamp", "owner", "correlation_id", "type", "data" }`.
+//! See docs/EVENTS.md for the schema of each `type`.
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// Bumped whenever a payload changes incompatibly.
+pub const EVENT_SCHEMA_VERSION: u16 = 1;
+
+/// Broadcast topics. The string form is what WebSocket clients subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Topic {
+    DepositEvent,
+    WithdrawEvent,
+    LockEvent,
+    UnlockEvent,
+    TimelockEvent,
+    VaultBalanceUpdate,
+    TvlUpdate,
+    SecurityAlert,
+    AnalyticsUpdate,
+}
+
+impl Topic {
+    pub const ALL: [Topic; 9] = [
+        Topic::DepositEvent,
+        Topic::WithdrawEvent,
+        Topic::LockEvent,
+        Topic::UnlockEvent,
+        Topic::TimelockEvent,
+        Topic::VaultBalanceUpdate,
+        Topic::TvlUpdate,
+        Topic::SecurityAlert,
+        Topic::AnalyticsUpdate,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Topic::DepositEvent => "deposit_event",
+            Topic::WithdrawEvent => "withdraw_event",
+            Topic::LockEvent => "lock_event",
+            Topic::UnlockEvent => "unlock_event",
+            Topic::TimelockEvent => "timelock_event",
+            Topic::VaultBalanceUpdate => "vault_balance_update",
+            Topic::TvlUpdate => "tvl_update",
+            Topic::SecurityAlert => "security_alert",
+            Topic::AnalyticsUpdate => "analytics_update",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Topic> {
+        Topic::ALL.into_iter().find(|t| t.as_str() == s)
+    }
+
+    /// Topics whose events belong to a single owner. Only TVL and analytics are global.
+    pub fn is_owner_scoped(&self) -> bool {
+        !matches!(self, Topic::TvlUpdate | Topic::AnalyticsUpdate)
+    }
+}
+
+/// On-chain token movement (deposit, withdraw, lock, unlock).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TxEvent {
+    pub amount: Option<u64>,
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelockStatus {
+    Scheduled,
+    DueSoon,
+    Available,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimelockEvent {
+    pub status: TimelockStatus,
+    pub amount: i64,
+    #[serde(with = "time::serde::rfc3339")]
+    pub unlock_at: OffsetDateTime,
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BalanceUpdate {
+    pub balance: u64,
+    pub previous_balance: Option<u64>,
+    pub delta: Option<i64>,
+}
+
+/// DB and chain balances disagree by more than the configured threshold.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReconciliationMismatch {
+    pub token_account: String,
+    pub db_balance: i64,
+    pub chain_balance: u64,
+    pub discrepancy: i64,
+    pub threshold: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TvlUpdate {
+    pub tvl: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Severity> {
+        [Severity::Info, Severity::Warning, Severity::Critical]
+            .into_iter()
+            .find(|severity| severity.as_str() == s)
+    }
+}
+
+/// Security-relevant occurrence. `kind` is a stable snake_case identifier
+/// (e.g. `emergency_withdraw`, `low_balance`, `twofa_disabled`); `details` is kind-specific.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SecurityAlert {
+    pub kind: String,
+    pub severity: Severity,
+    pub details: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnalyticsSnapshot {
+    pub vaults: i64,
+    pub users: i64,
+    pub volume_24h: i64,
+    pub avg_apy: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum VaultEvent {
+    Deposit(TxEvent),
+    Withdraw(TxEvent),
+    Lock(TxEvent),
+    Unlock(TxEvent),
+    Timelock(TimelockEvent),
+    BalanceUpdate(BalanceUpdate),
+    ReconciliationMismatch(ReconciliationMismatch),
+    Tvl(TvlUpdate),
+    SecurityAlert(SecurityAlert),
+    Analytics(AnalyticsSnapshot),
+}
+
+/// Every `type` value, in declaration order. Used to validate webhook event filters.
+pub const EVENT_TYPES: [&str; 10] = [
+    "deposit",
+    "withdraw",
+    "lock",
+    "unlock",
+    "timelock",
+    "balance_update",
+    "reconciliation_mismatch",
+    "tvl",
+    "security_alert",
+    "analytics",
+];
+
+impl VaultEvent {
+    /// The serialized `type` tag.
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            VaultEvent::Deposit(_) => "deposit",
+            VaultEvent::Withdraw(_) => "withdraw",
+            VaultEvent::Lock(_) => "lock",
+            VaultEvent::Unlock(_) => "unlock",
+            VaultEvent::Timelock(_) => "timelock",
+            VaultEvent::BalanceUpdate(_) => "balance_update",
+            VaultEvent::ReconciliationMismatch(_) => "reconciliation_mismatch",
+            VaultEvent::Tvl(_) => "tvl",
+            VaultEvent::SecurityAlert(_) => "security_alert",
+            VaultEvent::Analytics(_) => "analytics",
+        }
+    }
+
+    pub fn topic(&self) -> Topic {
+        match self {
+            VaultEvent::Deposit(_) => Topic::DepositEvent,
+            VaultEvent::Withdraw(_) => Topic::WithdrawEvent,
+            VaultEvent::Lock(_) => Topic::LockEvent,
+            VaultEvent::Unlock(_) => Topic::UnlockEvent,
+            VaultEvent::Timelock(_) => Topic::TimelockEvent,
+            VaultEvent::BalanceUpdate(_) | VaultEvent::ReconciliationMismatch(_) => {
+                Topic::VaultBalanceUpdate
+            }
+            VaultEvent::Tvl(_) => Topic::TvlUpdate,
+            VaultEvent::SecurityAlert(_) => Topic::SecurityAlert,
+            VaultEvent::Analytics(_) => Topic::AnalyticsUpdate,
+        }
+    }
+
+    pub fn security(kind: &str, severity: Severity, details: serde_json::Value) -> Self {
+        VaultEvent::SecurityAlert(SecurityAlert {
+            kind: kind.to_string(),
+            severity,
+            details,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EventEnvelope {
+    pub id: Uuid,
+    /// Outbox row id, assigned once the event is persisted. Clients resume from it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<i64>,
+    pub version: u16,
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    pub owner: Option<String>,
+    pub correlation_id: Option<String>,
+    #[serde(flatten)]
+    pub event: VaultEvent,
+}
+
+impl EventEnvelope {
+    pub fn new(event: VaultEvent) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            cursor: None,
+            version: EVENT_SCHEMA_VERSION,
+            timestamp: OffsetDateTime::now_utc(),
+            owner: None,
+            correlation_id: None,
+            event,
+        }
+    }
+
+    pub fn with_owner(mut self, owner: impl Into<String>) -> Self {
+        self.owner = Some(owner.into());
+        self
+    }
+
+    /// Links events that belong to the same operation, typically the transaction signature.
+    pub fn with_correlation_id(mut self, correlation_id: impl Into<String>) -> Self {
+        self.correlation_id = Some(correlation_id.into());
+        self
+    }
+
+    pub fn topic(&self) -> Topic {
+        self.event.topic()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_serializes_flat_with_type_and_data() {
+        let envelope = EventEnvelope::new(VaultEvent::Deposit(TxEvent {
+            amount: Some(42),
+            signature: "sig".to_string(),
+        }))
+        .with_owner("owner1")
+        .with_correlation_id("sig");
+
+        let json: serde_json::Value = serde_json::from_str(&envelope.to_json()).unwrap();
+        assert_eq!(json["type"], "deposit");
+        assert_eq!(json["data"]["amount"], 42);
+        assert_eq!(json["owner"], "owner1");
+        assert_eq!(json["version"], EVENT_SCHEMA_VERSION);
+        assert!(json["timestamp"].as_str().unwrap().contains('T'));
+
+        let back: EventEnvelope = serde_json::from_value(json).unwrap();
+        assert_eq!(back, envelope);
+    }
+
+    #[test]
+    fn test_topics_roundtrip_and_routing() {
+        for topic in Topic::ALL {
+            assert_eq!(Topic::parse(topic.as_str()), Some(topic));
+        }
+        let alert = VaultEvent::security("low_balance", Severity::Warning, serde_json::json!({}));
+        assert_eq!(alert.topic(), Topic::SecurityAlert);
+        let recon = VaultEvent::ReconciliationMismatch(ReconciliationMismatch {
+            token_account: "t".into(),
+            db_balance: 1,
+            chain_balance: 2,
+            discrepancy: 1,
+            threshold: 0,
+        });
+        assert_eq!(recon.topic(), Topic::VaultBalanceUpdate);
+        let json = serde_json::to_value(&recon).unwrap();
+        assert_eq!(json["type"], recon.event_type());
+        assert!(EVENT_TYPES.contains(&recon.event_type()));
+    }
+}
@@ -0,0 +1,461 @@
+//! Append-only double-entry ledger for vault balance movements.
+//!
+//! Every balance-changing transaction is journaled once per (signature, kind) as postings
+//! that sum to zero across the vault accounts of [`Account`]; value entering or leaving the
+//! vaults is booked against [`Account::External`]. The `ledger_*` tables reject updates and
+//! deletes, so corrections are new entries. Balances are derived from the postings
+//! (`ledger_vault_balances`) rather than kept as counters.
+//!
+//! A withdrawal we submit is held in `in_transit` until [`settle_withdrawals`] (or the event
+//! indexer) sees it finalized or failed. [`check_invariants`] verifies that every entry
+//! balances, no vault account is negative, and the custody accounts match the vault token
+//! account on chain.
+
+use serde::Serialize;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use sqlx::PgPool;
+use std::str::FromStr;
+use tracing::warn;
+
+use crate::db;
+use crate::solana_client::{get_token_balance, SolanaClient};
+
+/// Ledger accounts, per vault owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Account {
+    Available,
+    /// Collateral locked by the position manager.
+    Locked,
+    /// Principal deposited into a yield program.
+    YieldDeposited,
+    YieldAccrued,
+    /// Withdrawals submitted but not yet finalized.
+    InTransit,
+    /// Contra account for value entering or leaving the vaults.
+    External,
+}
+
+impl Account {
+    pub const ALL: [Account; 6] = [
+        Account::Available,
+        Account::Locked,
+        Account::YieldDeposited,
+        Account::YieldAccrued,
+        Account::InTransit,
+        Account::External,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Account::Available => "available",
+            Account::Locked => "locked",
+            Account::YieldDeposited => "yield_deposited",
+            Account::YieldAccrued => "yield_accrued",
+            Account::InTransit => "in_transit",
+            Account::External => "external",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Account> {
+        Account::ALL.into_iter().find(|a| a.as_str() == s)
+    }
+}
+
+/// Journal entry kinds; each moves an amount between two accounts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    Deposit,
+    /// Withdrawal seen on chain that was not submitted through this service.
+    Withdraw,
+    WithdrawSubmitted,
+    WithdrawSettled,
+    WithdrawFailed,
+    Lock,
+    Unlock,
+    YieldDeposit,
+    YieldWithdraw,
+    YieldAccrual,
+    /// Locked collateral moved to another vault.
+    Transfer,
+}
+
+impl EntryKind {
+    pub const ALL: [EntryKind; 11] = [
+        EntryKind::Deposit,
+        EntryKind::Withdraw,
+        EntryKind::WithdrawSubmitted,
+        EntryKind::WithdrawSettled,
+        EntryKind::WithdrawFailed,
+        EntryKind::Lock,
+        EntryKind::Unlock,
+        EntryKind::YieldDeposit,
+        EntryKind::YieldWithdraw,
+        EntryKind::YieldAccrual,
+        EntryKind::Transfer,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntryKind::Deposit => "deposit",
+            EntryKind::Withdraw => "withdraw",
+            EntryKind::WithdrawSubmitted => "withdraw_submitted",
+            EntryKind::WithdrawSettled => "withdraw_settled",
+            EntryKind::WithdrawFailed => "withdraw_failed",
+            EntryKind::Lock => "lock",
+            EntryKind::Unlock => "unlock",
+            EntryKind::YieldDeposit => "yield_deposit",
+            EntryKind::YieldWithdraw => "yield_withdraw",
+            EntryKind::YieldAccrual => "yield_accrual",
+            EntryKind::Transfer => "transfer",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<EntryKind> {
+        EntryKind::ALL.into_iter().find(|k| k.as_str() == s)
+    }
+
+    /// (debited, credited) account.
+    pub fn movement(&self) -> (Account, Account) {
+        match self {
+            EntryKind::Deposit => (Account::External, Account::Available),
+            EntryKind::Withdraw => (Account::Available, Account::External),
+            EntryKind::WithdrawSubmitted => (Account::Available, Account::InTransit),
+            EntryKind::WithdrawSettled => (Account::InTransit, Account::External),
+            EntryKind::WithdrawFailed => (Account::InTransit, Account::Available),
+            EntryKind::Lock => (Account::Available, Account::Locked),
+            EntryKind::Unlock => (Account::Locked, Account::Available),
+            EntryKind::YieldDeposit => (Account::Available, Account::YieldDeposited),
+            EntryKind::YieldWithdraw => (Account::YieldDeposited, Account::Available),
+            EntryKind::YieldAccrual => (Account::External, Account::YieldAccrued),
+            EntryKind::Transfer => (Account::Locked, Account::Available),
+        }
+    }
+}
+
+/// Postings for moving `amount` from `from_owner`'s debited account to `to_owner`'s credited
+/// account. Only [`EntryKind::Transfer`] uses two different owners.
+pub fn postings(
+    kind: EntryKind,
+    from_owner: &str,
+    to_owner: &str,
+    amount: i64,
+) -> Vec<(String, String, i64)> {
+    let (from, to) = kind.movement();
+    vec![
+        (from_owner.to_string(), from.as_str().to_string(), -amount),
+        (to_owner.to_string(), to.as_str().to_string(), amount),
+    ]
+}
+
+/// Journals `amount` for `owner`. Returns false if the entry already exists or the amount is
+/// not positive.
+pub async fn record(
+    pool: &PgPool,
+    kind: EntryKind,
+    signature: &str,
+    owner: &str,
+    amount: i64,
+) -> Result<bool, sqlx::Error> {
+    if amount <= 0 {
+        return Ok(false);
+    }
+    db::ledger_post(
+        pool,
+        signature,
+        kind.as_str(),
+        &postings(kind, owner, owner, amount),
+    )
+    .await
+}
+
+/// Journals locked collateral moved from `from_owner` to `to_owner`'s available balance.
+pub async fn record_transfer(
+    pool: &PgPool,
+    signature: &str,
+    from_owner: &str,
+    to_owner: &str,
+    amount: i64,
+) -> Result<bool, sqlx::Error> {
+    if amount <= 0 {
+        return Ok(false);
+    }
+    let kind = EntryKind::Transfer;
+    db::ledger_post(
+        pool,
+        signature,
+        kind.as_str(),
+        &postings(kind, from_owner, to_owner, amount),
+    )
+    .await
+}
+
+/// Journals a withdrawal observed on chain: settles it if we submitted it and it has not been
+/// written off as failed, otherwise books it directly against `available`.
+pub async fn record_withdraw_confirmed(
+    pool: &PgPool,
+    signature: &str,
+    owner: &str,
+    amount: i64,
+) -> Result<bool, sqlx::Error> {
+    let failed =
+        db::ledger_entry_exists(pool, signature, EntryKind::WithdrawFailed.as_str()).await?;
+    match db::ledger_in_transit_amount(pool, signature).await? {
+        Some((submitter, in_transit)) if !failed => {
+            record(
+                pool,
+                EntryKind::WithdrawSettled,
+                signature,
+                &submitter,
+                in_transit,
+            )
+            .await
+        }
+        _ => record(pool, EntryKind::Withdraw, signature, owner, amount).await,
+    }
+}
+
+/// Settles submitted withdrawals older than `settle_after_seconds` by their finalized status.
+/// A signature the cluster does not know by then expired with its blockhash and is failed
+/// back to `available`. Returns the number of entries written.
+pub async fn settle_withdrawals(
+    pool: &PgPool,
+    sol: &SolanaClient,
+    settle_after_seconds: i64,
+) -> Result<usize, sqlx::Error> {
+    let mut written = 0;
+    for (signature, owner, amount, _) in
+        db::ledger_unsettled_withdrawals(pool, settle_after_seconds).await?
+    {
+        let Ok(sig) = Signature::from_str(&signature) else {
+            warn!("ledger: unparseable withdrawal signature {signature}");
+            continue;
+        };
+        let status = match sol
+            .rpc
+            .get_signature_status_with_commitment_and_history(
+                &sig,
+                CommitmentConfig::finalized(),
+                true,
+            )
+            .await
+        {
+            Ok(status) => status,
+            Err(e) => {
+                warn!("ledger: signature status for {signature}: {e}");
+                continue;
+            }
+        };
+        let kind = match status {
+            Some(Ok(())) => EntryKind::WithdrawSettled,
+            Some(Err(_)) | None => EntryKind::WithdrawFailed,
+        };
+        if record(pool, kind, &signature, &owner, amount).await? {
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+/// Balances derived from the ledger (`ledger_vault_balances`).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct LedgerBalances {
+    pub owner: String,
+    pub available: i64,
+    pub locked: i64,
+    pub in_transit: i64,
+    pub yield_deposited: i64,
+    pub yield_accrued: i64,
+}
+
+impl LedgerBalances {
+    /// Held in the vault token account: everything not deposited into yield programs.
+    /// In-transit withdrawals stay until finalized.
+    pub fn custody(&self) -> i64 {
+        self.available + self.locked + self.in_transit
+    }
+}
+
+/// One posting with its entry.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct LedgerPosting {
+    pub entry_id: i64,
+    pub signature: String,
+    pub kind: String,
+    pub owner: String,
+    pub account: String,
+    pub amount: i64,
+    pub created_at: time::OffsetDateTime,
+}
+
+/// Derived balances for `owner`; all zero if nothing was journaled.
+pub async fn balances(pool: &PgPool, owner: &str) -> Result<LedgerBalances, sqlx::Error> {
+    Ok(db::ledger_vault_balances(pool, owner)
+        .await?
+        .unwrap_or_else(|| LedgerBalances {
+            owner: owner.to_string(),
+            ..Default::default()
+        }))
+}
+
+/// A broken ledger invariant.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Violation {
+    Unbalanced {
+        entry_id: i64,
+        owner: String,
+        sum: i64,
+    },
+    NegativeBalance {
+        owner: String,
+        account: String,
+        balance: i64,
+    },
+    /// The vault token account holds an amount outside the custody range the ledger allows.
+    CustodyMismatch {
+        owner: String,
+        token_account: String,
+        ledger: i64,
+        chain: i64,
+    },
+}
+
+impl Violation {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Violation::Unbalanced { .. } => "unbalanced",
+            Violation::NegativeBalance { .. } => "negative_balance",
+            Violation::CustodyMismatch { .. } => "custody_mismatch",
+        }
+    }
+
+    pub fn owner(&self) -> &str {
+        match self {
+            Violation::Unbalanced { owner, .. }
+            | Violation::NegativeBalance { owner, .. }
+            | Violation::CustodyMismatch { owner, .. } => owner,
+        }
+    }
+}
+
+/// Whether `chain` is consistent with the ledger: the in-transit part may or may not have left
+/// the token account yet, and differences up to `tolerance` are ignored.
+pub fn custody_matches(balances: &LedgerBalances, chain: i64, tolerance: i64) -> bool {
+    let held = balances.available + balances.locked;
+    chain >= held - tolerance && chain <= held + balances.in_transit + tolerance
+}
+
+/// Checks every invariant. Vaults whose token balance cannot be read are skipped.
+pub async fn check_invariants(
+    pool: &PgPool,
+    sol: &SolanaClient,
+    tolerance: i64,
+) -> Result<Vec<Violation>, sqlx::Error> {
+    let mut violations: Vec<Violation> = db::ledger_unbalanced_entries(pool)
+        .await?
+        .into_iter()
+        .map(|(entry_id, owner, sum)| Violation::Unbalanced {
+            entry_id,
+            owner,
+            sum,
+        })
+        .collect();
+    violations.extend(db::ledger_negative_balances(pool).await?.into_iter().map(
+        |(owner, account, balance)| Violation::NegativeBalance {
+            owner,
+            account,
+            balance,
+        },
+    ));
+
+    for vault in db::list_vaults(pool).await? {
+        let Some(token_account) = vault.token_account else {
+            continue;
+        };
+        let Ok(pk) = Pubkey::from_str(&token_account) else {
+            continue;
+        };
+        let chain = match get_token_balance(sol, &pk).await {
+            Ok(amount) => amount as i64,
+            Err(e) => {
+                warn!("ledger: token balance for {}: {e}", vault.owner);
+                continue;
+            }
+        };
+        let ledger = balances(pool, &vault.owner).await?;
+        if !custody_matches(&ledger, chain, tolerance) {
+            violations.push(Violation::CustodyMismatch {
+                owner: vault.owner,
+                token_account,
+                ledger: ledger.custody(),
+                chain,
+            });
+        }
+    }
+    Ok(violations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names_round_trip() {
+        for account in Account::ALL {
+            assert_eq!(Account::parse(account.as_str()), Some(account));
+        }
+        for kind in EntryKind::ALL {
+            assert_eq!(EntryKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(Account::parse("savings"), None);
+        assert_eq!(EntryKind::parse("opening"), None);
+    }
+
+    #[test]
+    fn test_postings_balance() {
+        for kind in EntryKind::ALL {
+            let postings = postings(kind, "a", "b", 250);
+            assert_eq!(postings.iter().map(|(_, _, amount)| amount).sum::<i64>(), 0);
+            let (from, to) = kind.movement();
+            assert_ne!(from, to, "{} moves nothing", kind.as_str());
+            assert_eq!(postings[0], ("a".into(), from.as_str().into(), -250));
+            assert_eq!(postings[1], ("b".into(), to.as_str().into(), 250));
+        }
+    }
+
+    #[test]
+    fn test_withdraw_lifecycle_nets_out() {
+        // Submitted then settled leaves nothing in transit and the amount in `external`
+        let mut net = std::collections::HashMap::new();
+        for kind in [EntryKind::WithdrawSubmitted, EntryKind::WithdrawSettled] {
+            for (_, account, amount) in postings(kind, "a", "a", 100) {
+                *net.entry(account).or_insert(0) += amount;
+            }
+        }
+        assert_eq!(net["available"], -100);
+        assert_eq!(net["in_transit"], 0);
+        assert_eq!(net["external"], 100);
+    }
+
+    #[test]
+    fn test_custody_matches() {
+        let balances = LedgerBalances {
+            owner: "a".into(),
+            available: 700,
+            locked: 200,
+            in_transit: 100,
+            ..Default::default()
+        };
+        assert_eq!(balances.custody(), 1000);
+        // Before and after the in-transit withdrawal lands
+        assert!(custody_matches(&balances, 1000, 0));
+        assert!(custody_matches(&balances, 900, 0));
+        assert!(!custody_matches(&balances, 899, 0));
+        assert!(!custody_matches(&balances, 1001, 0));
+        assert!(custody_matches(&balances, 1005, 10));
+    }
+}
@@ -1,16 +1,31 @@
+pub mod account_hub;
+pub mod alerts;
+pub mod anomaly;
 pub mod api;
 pub mod auth;
 pub mod cache;
+pub mod circuit_breaker;
 pub mod config;
 pub mod cpi;
 pub mod db;
+pub mod email;
 pub mod error;
+pub mod events;
+pub mod ledger;
 pub mod metrics;
+pub mod migrate;
 pub mod notify;
 pub mod ops;
+pub mod proof_of_reserves;
 pub mod protocols;
+pub mod reconciliation;
+pub mod repo;
 pub mod security;
+pub mod snapshots;
 pub mod solana_client;
 pub mod tasks;
 pub mod telemetry;
+pub mod tvl;
 pub mod vault;
+pub mod webauthn;
+pub mod webhooks;
@@ -6,7 +6,7 @@ use dotenvy::dotenv;
 use tracing::info;
 
 use cvmsback::{
-    api, api::AppState, cache::Cache, config::AppConfig, db, metrics::Metrics, notify::Notifier, ops::RateLimiter,
+    account_hub::AccountHub, api, api::AppState, cache::{self, Cache, CacheSettings}, config::AppConfig, db, metrics::Metrics, migrate, notify::Notifier, ops::RateLimiter, repo::Repos, security,
     solana_client::SolanaClient, tasks, telemetry,
 };
 
@@ -18,37 +18,54 @@ async fn main() -> Result<()> {
 
     let cfg = AppConfig::from_env();
     let pool = db::connect(&cfg.database_url).await?;
-    let _ = db::init(&pool).await; // best effort to init tables
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("migrate") {
+        return run_migrate(&pool, &args[1..]).await;
+    }
+    // Refuse to serve against a schema this build does not match
+    migrate::ensure_current(&pool).await?;
+    security::validate_envelope_key(&cfg.twofa_encryption_key)
+        .map_err(|e| anyhow::anyhow!("TWOFA_ENCRYPTION_KEY: {e}"))?;
 
     let sol = SolanaClient::new(&cfg.solana_rpc_url);
-    let notifier = Notifier::new(1024);
+    let account_hub = AccountHub::for_rpc_url(&cfg.solana_rpc_url);
     let rate_limiter = std::sync::Arc::new(RateLimiter::new(10));
     
-    // Initialize cache (optional, fails gracefully if Redis unavailable)
-    let cache = if cfg.redis_url.is_empty() {
-        info!("Redis cache disabled (REDIS_URL is empty)");
+    // Initialize metrics
+    let metrics = Metrics::new().expect("Failed to initialize metrics");
+    let notifier =
+        Notifier::with_outbox(1024, pool.clone(), metrics.event_outbox_dropped.clone());
+
+    // Initialize cache: in-process tier always, Redis tier if configured and reachable
+    let redis = if cfg.redis_url.is_empty() {
+        info!("Redis cache disabled (REDIS_URL is empty), using in-process cache only");
         None
     } else {
-        match Cache::new(&cfg.redis_url, cfg.cache_ttl_seconds) {
-            Ok(c) => {
+        match cache::connect_redis(&cfg.redis_url).await {
+            Ok(conn) => {
                 info!("Redis cache initialized");
-                Some(std::sync::Arc::new(c))
+                Some(conn)
             }
             Err(e) => {
-                info!("Redis cache unavailable: {}, continuing without cache", e);
+                info!("Redis cache unavailable: {}, using in-process cache only", e);
                 None
             }
         }
     };
-    
-    // Initialize metrics
-    let metrics = Metrics::new().expect("Failed to initialize metrics");
+    let cache = std::sync::Arc::new(Cache::new(
+        CacheSettings::from_config(&cfg),
+        redis,
+        metrics.clone(),
+    ));
     
     let state = AppState {
         pool: pool.clone(),
+        repos: Repos::postgres(pool.clone()),
         cfg: cfg.clone(),
         sol,
         notifier: notifier.clone(),
+        account_hub,
         rate_limiter: rate_limiter.clone(),
         cache: cache.clone(),
         metrics: metrics.clone(),
@@ -89,6 +106,60 @@ async fn main() -> Result<()> {
             tasks::yield_tasks::run_yield_scheduler(recon_state).await;
         });
     }
+    {
+        let cache_state = state.clone();
+        tokio::spawn(async move {
+            tasks::cache_invalidation::run_cache_invalidation_listener(cache_state).await;
+        });
+    }
+    {
+        let ledger_state = state.clone();
+        tokio::spawn(async move {
+            tasks::ledger::run_ledger_checker(ledger_state).await;
+        });
+    }
+    {
+        let snapshot_state = state.clone();
+        tokio::spawn(async move {
+            tasks::snapshots::run_balance_snapshots(snapshot_state).await;
+        });
+    }
+    {
+        let reserves_state = state.clone();
+        tokio::spawn(async move {
+            tasks::proof_of_reserves::run_proof_of_reserves(reserves_state).await;
+        });
+    }
+    {
+        let nonce_state = state.clone();
+        tokio::spawn(async move {
+            tasks::nonces::run_nonce_cleanup(nonce_state).await;
+        });
+    }
+    {
+        let outbox_state = state.clone();
+        tokio::spawn(async move {
+            tasks::outbox::run_outbox_pruner(outbox_state).await;
+        });
+    }
+    {
+        let webhook_state = state.clone();
+        tokio::spawn(async move {
+            tasks::webhooks::run_webhook_dispatcher(webhook_state).await;
+        });
+    }
+    {
+        let email_state = state.clone();
+        tokio::spawn(async move {
+            tasks::email::run_email_dispatcher(email_state).await;
+        });
+    }
+    {
+        let twofa_state = state.clone();
+        tokio::spawn(async move {
+            tasks::twofa::run_twofa_reset_worker(twofa_state).await;
+        });
+    }
     {
         let balance_state = state.clone();
         let balance_notifier = notifier.clone();
@@ -111,3 +182,32 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// `migrate up [VERSION]`, `migrate down [STEPS]` (default 1) or `migrate status`.
+async fn run_migrate(pool: &sqlx::PgPool, args: &[String]) -> Result<()> {
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("up"), target) => {
+            let target = target.map(|t| t.parse::<i64>()).transpose()?;
+            let applied = migrate::up(pool, target).await?;
+            info!(?applied, "migrations applied");
+        }
+        (Some("down"), steps) => {
+            let steps = steps.map(|s| s.parse::<usize>()).transpose()?.unwrap_or(1);
+            let reverted = migrate::down(pool, steps).await?;
+            info!(?reverted, "migrations reverted");
+        }
+        (Some("status"), None) => {
+            let applied = migrate::applied(pool).await?;
+            for m in migrate::MIGRATIONS {
+                match applied.iter().find(|(v, ..)| *v == m.version) {
+                    Some((_, _, _, at)) => {
+                        println!("{:04} {:<24} applied {}", m.version, m.name, at)
+                    }
+                    None => println!("{:04} {:<24} pending", m.version, m.name),
+                }
+            }
+        }
+        _ => anyhow::bail!("usage: cvmsback migrate <up [VERSION] | down [STEPS] | status>"),
+    }
+    Ok(())
+}
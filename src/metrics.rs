@@ -1,6 +1,6 @@
 use prometheus::{
-    register_counter, register_gauge, register_histogram, Counter, Gauge, Histogram, HistogramOpts,
-    Opts, Registry,
+    register_counter, register_counter_vec, register_gauge, register_gauge_vec, register_histogram,
+    Counter, CounterVec, Gauge, GaugeVec, Histogram, HistogramOpts, Opts, Registry,
 };
 use std::sync::Arc;
 
@@ -19,6 +19,26 @@ pub struct Metrics {
     pub request_duration: Histogram,
     pub balance_query_duration: Histogram,
     pub transaction_duration: Histogram,
+    pub webhook_deliveries: Counter,
+    pub webhook_delivery_failures: Counter,
+    pub webhook_dead_letters: Counter,
+    pub webhook_delivery_duration: Histogram,
+    pub emails_sent: Counter,
+    pub email_failures: Counter,
+    /// Events broadcast live but not persisted because the outbox write queue was full.
+    pub event_outbox_dropped: Counter,
+    /// Cache lookups by key class (`class` label); hits also by `tier` (local, redis).
+    pub cache_hits: CounterVec,
+    pub cache_misses: CounterVec,
+    pub cache_errors: CounterVec,
+    /// Stale entries served while a refresh runs.
+    pub cache_stale_hits: CounterVec,
+    /// Misses served by another caller's load instead of loading again.
+    pub cache_coalesced: CounterVec,
+    /// Keys evicted, by `class` and `origin` (local, remote replica).
+    pub cache_invalidations: CounterVec,
+    /// Open ledger invariant violations by `kind`, as of the last check.
+    pub ledger_violations: GaugeVec,
     pub registry: Registry,
 }
 
@@ -116,6 +136,107 @@ impl Metrics {
         let transaction_duration = register_histogram!(transaction_duration_opts)?;
         registry.register(Box::new(transaction_duration.clone()))?;
 
+        let webhook_deliveries = register_counter!(Opts::new(
+            "webhook_deliveries_total",
+            "Total number of successful webhook deliveries"
+        ))?;
+        registry.register(Box::new(webhook_deliveries.clone()))?;
+
+        let webhook_delivery_failures = register_counter!(Opts::new(
+            "webhook_delivery_failures_total",
+            "Total number of failed webhook delivery attempts"
+        ))?;
+        registry.register(Box::new(webhook_delivery_failures.clone()))?;
+
+        let webhook_dead_letters = register_counter!(Opts::new(
+            "webhook_dead_letters_total",
+            "Total number of webhook deliveries moved to the dead-letter queue"
+        ))?;
+        registry.register(Box::new(webhook_dead_letters.clone()))?;
+
+        let webhook_delivery_duration_opts = HistogramOpts::new(
+            "webhook_delivery_duration_seconds",
+            "Webhook delivery attempt duration in seconds",
+        )
+        .buckets(vec![0.05, 0.1, 0.5, 1.0, 2.0, 5.0, 10.0]);
+        let webhook_delivery_duration = register_histogram!(webhook_delivery_duration_opts)?;
+        registry.register(Box::new(webhook_delivery_duration.clone()))?;
+
+        let emails_sent = register_counter!(Opts::new(
+            "emails_sent_total",
+            "Total number of notification emails sent"
+        ))?;
+        registry.register(Box::new(emails_sent.clone()))?;
+
+        let email_failures = register_counter!(Opts::new(
+            "email_failures_total",
+            "Total number of failed email send attempts"
+        ))?;
+        registry.register(Box::new(email_failures.clone()))?;
+
+        let event_outbox_dropped = register_counter!(Opts::new(
+            "event_outbox_dropped_total",
+            "Total number of events not persisted because the outbox write queue was full"
+        ))?;
+        registry.register(Box::new(event_outbox_dropped.clone()))?;
+
+        let cache_hits = register_counter_vec!(
+            Opts::new("cache_hits_total", "Total number of cache hits"),
+            &["class", "tier"]
+        )?;
+        registry.register(Box::new(cache_hits.clone()))?;
+
+        let cache_misses = register_counter_vec!(
+            Opts::new("cache_misses_total", "Total number of cache misses"),
+            &["class"]
+        )?;
+        registry.register(Box::new(cache_misses.clone()))?;
+
+        let cache_errors = register_counter_vec!(
+            Opts::new(
+                "cache_errors_total",
+                "Total number of failed or undecodable cache operations"
+            ),
+            &["class"]
+        )?;
+        registry.register(Box::new(cache_errors.clone()))?;
+
+        let cache_stale_hits = register_counter_vec!(
+            Opts::new(
+                "cache_stale_hits_total",
+                "Total number of stale cache entries served during a refresh"
+            ),
+            &["class"]
+        )?;
+        registry.register(Box::new(cache_stale_hits.clone()))?;
+
+        let cache_coalesced = register_counter_vec!(
+            Opts::new(
+                "cache_coalesced_total",
+                "Total number of cache misses served by a concurrent load"
+            ),
+            &["class"]
+        )?;
+        registry.register(Box::new(cache_coalesced.clone()))?;
+
+        let cache_invalidations = register_counter_vec!(
+            Opts::new(
+                "cache_invalidations_total",
+                "Total number of cache keys invalidated"
+            ),
+            &["class", "origin"]
+        )?;
+        registry.register(Box::new(cache_invalidations.clone()))?;
+
+        let ledger_violations = register_gauge_vec!(
+            Opts::new(
+                "ledger_violations",
+                "Ledger invariant violations found by the last check"
+            ),
+            &["kind"]
+        )?;
+        registry.register(Box::new(ledger_violations.clone()))?;
+
         Ok(Arc::new(Self {
             vault_operations,
             vault_deposits,
@@ -131,6 +252,20 @@ impl Metrics {
             request_duration,
             balance_query_duration,
             transaction_duration,
+            webhook_deliveries,
+            webhook_delivery_failures,
+            webhook_dead_letters,
+            webhook_delivery_duration,
+            emails_sent,
+            email_failures,
+            event_outbox_dropped,
+            cache_hits,
+            cache_misses,
+            cache_errors,
+            cache_stale_hits,
+            cache_coalesced,
+            cache_invalidations,
+            ledger_violations,
             registry,
         }))
     }
@@ -0,0 +1,273 @@
+//! Versioned schema migrations.
+//!
+//! Each migration is a pair of scripts in `migrations/` (`NNNN_name.up.sql` and
+//! `NNNN_name.down.sql`) embedded at compile time and listed in [`MIGRATIONS`]. Applied
+//! versions are recorded in `_migrations` with a checksum of the up script, so an applied
+//! script that was edited afterwards is reported instead of silently diverging. Every script
+//! runs in its own transaction under an advisory lock, so concurrent runs apply it once.
+//!
+//! `0001_baseline` is the schema `db::init` used to create at startup. Its statements are
+//! idempotent, so `migrate up` on an existing deployment records it without changes. The
+//! server refuses to start while migrations are pending.
+
+use data_encoding::HEXLOWER;
+use sha2::{Digest, Sha256};
+use sqlx::{Executor, PgPool};
+use thiserror::Error;
+
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+impl Migration {
+    pub fn checksum(&self) -> String {
+        HEXLOWER.encode(&Sha256::digest(self.up.as_bytes()))
+    }
+}
+
+/// All migrations, in version order.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        up: include_str!("../migrations/0001_baseline.up.sql"),
+        down: include_str!("../migrations/0001_baseline.down.sql"),
+    },
+    Migration {
+        version: 2,
+        name: "ledger",
+        up: include_str!("../migrations/0002_ledger.up.sql"),
+        down: include_str!("../migrations/0002_ledger.down.sql"),
+    },
+    Migration {
+        version: 3,
+        name: "balance_snapshots",
+        up: include_str!("../migrations/0003_balance_snapshots.up.sql"),
+        down: include_str!("../migrations/0003_balance_snapshots.down.sql"),
+    },
+    Migration {
+        version: 4,
+        name: "tvl_series",
+        up: include_str!("../migrations/0004_tvl_series.up.sql"),
+        down: include_str!("../migrations/0004_tvl_series.down.sql"),
+    },
+    Migration {
+        version: 5,
+        name: "reconciliation_cases",
+        up: include_str!("../migrations/0005_reconciliation_cases.up.sql"),
+        down: include_str!("../migrations/0005_reconciliation_cases.down.sql"),
+    },
+    Migration {
+        version: 6,
+        name: "proof_of_reserves",
+        up: include_str!("../migrations/0006_proof_of_reserves.up.sql"),
+        down: include_str!("../migrations/0006_proof_of_reserves.down.sql"),
+    },
+    Migration {
+        version: 7,
+        name: "twofa_reset_pending",
+        up: include_str!("../migrations/0007_twofa_reset_pending.up.sql"),
+        down: include_str!("../migrations/0007_twofa_reset_pending.down.sql"),
+    },
+    Migration {
+        version: 8,
+        name: "twofa_required",
+        up: include_str!("../migrations/0008_twofa_required.up.sql"),
+        down: include_str!("../migrations/0008_twofa_required.down.sql"),
+    },
+    Migration {
+        version: 9,
+        name: "webhook_delivery_claims",
+        up: include_str!("../migrations/0009_webhook_delivery_claims.up.sql"),
+        down: include_str!("../migrations/0009_webhook_delivery_claims.down.sql"),
+    },
+    Migration {
+        version: 10,
+        name: "signed_signer_webhooks",
+        up: include_str!("../migrations/0010_signed_signer_webhooks.up.sql"),
+        down: include_str!("../migrations/0010_signed_signer_webhooks.down.sql"),
+    },
+];
+
+/// Serializes migration runs across processes.
+const LOCK_KEY: i64 = 0x0063_766d_736d_6967;
+
+#[derive(Error, Debug)]
+pub enum MigrateError {
+    #[error("Database error: {0}")]
+    Db(#[from] sqlx::Error),
+    #[error("migration {version} ({name}) was modified after it was applied")]
+    ChecksumMismatch { version: i64, name: String },
+    #[error("database has migration {0}, which this build does not know")]
+    UnknownVersion(i64),
+    #[error("{0} pending migration(s); run `cvmsback migrate up`")]
+    Pending(usize),
+}
+
+/// Applied migration: (version, name, checksum, applied_at).
+pub type AppliedRow = (i64, String, String, time::OffsetDateTime);
+
+async fn ensure_history_table(pool: &PgPool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "CREATE TABLE IF NOT EXISTS _migrations (
+            version BIGINT PRIMARY KEY,
+            name TEXT NOT NULL,
+            checksum TEXT NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        )",
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Applied migrations in version order, after checking each against this build.
+pub async fn applied(pool: &PgPool) -> Result<Vec<AppliedRow>, MigrateError> {
+    ensure_history_table(pool).await?;
+    let rows = sqlx::query_as::<_, AppliedRow>(
+        "SELECT version, name, checksum, applied_at FROM _migrations ORDER BY version",
+    )
+    .fetch_all(pool)
+    .await?;
+    for (version, name, checksum, _) in &rows {
+        let known = MIGRATIONS
+            .iter()
+            .find(|m| m.version == *version)
+            .ok_or(MigrateError::UnknownVersion(*version))?;
+        if known.checksum() != *checksum {
+            return Err(MigrateError::ChecksumMismatch {
+                version: *version,
+                name: name.clone(),
+            });
+        }
+    }
+    Ok(rows)
+}
+
+/// Migrations not yet applied, in version order.
+pub async fn pending(pool: &PgPool) -> Result<Vec<&'static Migration>, MigrateError> {
+    let applied = applied(pool).await?;
+    Ok(MIGRATIONS
+        .iter()
+        .filter(|m| !applied.iter().any(|(v, ..)| *v == m.version))
+        .collect())
+}
+
+/// Fails unless every migration is applied; checked before serving.
+pub async fn ensure_current(pool: &PgPool) -> Result<(), MigrateError> {
+    match pending(pool).await?.len() {
+        0 => Ok(()),
+        n => Err(MigrateError::Pending(n)),
+    }
+}
+
+/// Applies pending migrations up to and including `target` (all if `None`).
+/// Returns the versions applied.
+pub async fn up(pool: &PgPool, target: Option<i64>) -> Result<Vec<i64>, MigrateError> {
+    let mut done = Vec::new();
+    for migration in pending(pool).await? {
+        if target.is_some_and(|t| migration.version > t) {
+            break;
+        }
+        let mut tx = pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(LOCK_KEY)
+            .execute(&mut *tx)
+            .await?;
+        // Another process may have applied it while we waited for the lock
+        let already: bool =
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM _migrations WHERE version = $1)")
+                .bind(migration.version)
+                .fetch_one(&mut *tx)
+                .await?;
+        if !already {
+            (&mut *tx).execute(migration.up).await?;
+            sqlx::query("INSERT INTO _migrations (version, name, checksum) VALUES ($1, $2, $3)")
+                .bind(migration.version)
+                .bind(migration.name)
+                .bind(migration.checksum())
+                .execute(&mut *tx)
+                .await?;
+            done.push(migration.version);
+        }
+        tx.commit().await?;
+    }
+    Ok(done)
+}
+
+/// Reverts the `steps` most recently applied migrations. Returns the versions reverted.
+pub async fn down(pool: &PgPool, steps: usize) -> Result<Vec<i64>, MigrateError> {
+    let applied = applied(pool).await?;
+    let mut done = Vec::new();
+    for (version, ..) in applied.iter().rev().take(steps) {
+        let migration = MIGRATIONS
+            .iter()
+            .find(|m| m.version == *version)
+            .ok_or(MigrateError::UnknownVersion(*version))?;
+        let mut tx = pool.begin().await?;
+        sqlx::query("SELECT pg_advisory_xact_lock($1)")
+            .bind(LOCK_KEY)
+            .execute(&mut *tx)
+            .await?;
+        let removed = sqlx::query("DELETE FROM _migrations WHERE version = $1")
+            .bind(version)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        if removed > 0 {
+            (&mut *tx).execute(migration.down).await?;
+            done.push(*version);
+        }
+        tx.commit().await?;
+    }
+    Ok(done)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrations_are_ordered_and_reversible() {
+        for pair in MIGRATIONS.windows(2) {
+            assert!(pair[0].version < pair[1].version);
+        }
+        for m in MIGRATIONS {
+            assert!(!m.up.trim().is_empty(), "{} has no up script", m.name);
+            assert!(!m.down.trim().is_empty(), "{} has no down script", m.name);
+        }
+    }
+
+    #[test]
+    fn test_baseline_is_idempotent() {
+        // Existing deployments already have these objects; every statement must tolerate that
+        let sql: String = MIGRATIONS[0]
+            .up
+            .lines()
+            .filter(|l| !l.trim_start().starts_with("--"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        for statement in sql.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            assert!(
+                statement.contains("IF NOT EXISTS") || statement.ends_with("DROP NOT NULL"),
+                "not idempotent: {statement}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_checksum_tracks_up_script() {
+        let m = &MIGRATIONS[0];
+        assert_eq!(m.checksum().len(), 64);
+        let edited = Migration {
+            version: m.version,
+            name: m.name,
+            up: "SELECT 1",
+            down: m.down,
+        };
+        assert_ne!(edited.checksum(), m.checksum());
+    }
+}
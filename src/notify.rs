@@ -1,40 +1,353 @@
+use prometheus::Counter;
+use sqlx::PgPool;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, mpsc};
+use tracing::warn;
 
+use crate::db;
+use crate::events::{EventEnvelope, Topic, VaultEvent};
+
+/// Rows fetched per outbox query when replaying.
+const REPLAY_PAGE: i64 = 500;
+/// Events waiting for the outbox writer. When full, new events are broadcast live without
+/// being persisted (and counted), rather than queueing without bound behind a slow database.
+const OUTBOX_QUEUE_CAPACITY: usize = 10_000;
+
+/// Per-topic channels plus a firehose carrying every event, so multi-topic subscribers see
+/// events in publish order.
 #[derive(Clone)]
+struct Channels {
+    topics: HashMap<Topic, broadcast::Sender<EventEnvelope>>,
+    all: broadcast::Sender<EventEnvelope>,
+}
+
+/// In-process pub/sub: one broadcast channel per [`Topic`], so a noisy topic cannot make
+/// subscribers of another topic lag. With an outbox, every event is persisted first and
+/// carries its row id as `cursor`, so subscribers can resume after a disconnect.
 pub struct Notifier {
-    pub deposit_tx: broadcast::Sender<String>,
-    pub withdraw_tx: broadcast::Sender<String>,
-    pub lock_tx: broadcast::Sender<String>,
-    pub unlock_tx: broadcast::Sender<String>,
-    pub timelock_tx: broadcast::Sender<String>,
-    pub vault_balance_tx: broadcast::Sender<String>,
-    pub tvl_tx: broadcast::Sender<String>,
-    pub security_tx: broadcast::Sender<String>,
-    pub analytics_tx: broadcast::Sender<String>,
+    channels: Channels,
+    outbox: Option<Outbox>,
+}
+
+struct Outbox {
+    pool: PgPool,
+    queue: mpsc::Sender<EventEnvelope>,
+    dropped: Counter,
 }
 
 impl Notifier {
+    /// Live-only notifier; events are lost once they fall out of the broadcast buffer.
     pub fn new(capacity: usize) -> Arc<Self> {
-        let (deposit_tx, _) = broadcast::channel(capacity);
-        let (withdraw_tx, _) = broadcast::channel(capacity);
-        let (lock_tx, _) = broadcast::channel(capacity);
-        let (unlock_tx, _) = broadcast::channel(capacity);
-        let (timelock_tx, _) = broadcast::channel(capacity);
-        let (vault_balance_tx, _) = broadcast::channel(capacity);
-        let (tvl_tx, _) = broadcast::channel(capacity);
-        let (security_tx, _) = broadcast::channel(capacity);
-        let (analytics_tx, _) = broadcast::channel(capacity);
         Arc::new(Self {
-            deposit_tx,
-            withdraw_tx,
-            lock_tx,
-            unlock_tx,
-            timelock_tx,
-            vault_balance_tx,
-            tvl_tx,
-            security_tx,
-            analytics_tx,
+            channels: channels(capacity),
+            outbox: None,
         })
     }
+
+    /// Notifier backed by the `event_outbox` table. A single writer task persists events in
+    /// publish order before broadcasting them; `db::outbox_insert` orders ids across
+    /// processes, so cursors are monotonic. `dropped` counts events that found the write queue
+    /// full.
+    pub fn with_outbox(capacity: usize, pool: PgPool, dropped: Counter) -> Arc<Self> {
+        let channels = channels(capacity);
+        let (queue, rx) = mpsc::channel(OUTBOX_QUEUE_CAPACITY);
+        tokio::spawn(run_outbox_writer(pool.clone(), channels.clone(), rx));
+        Arc::new(Self {
+            channels,
+            outbox: Some(Outbox {
+                pool,
+                queue,
+                dropped,
+            }),
+        })
+    }
+
+    /// Routes the event to its topic. Returns the number of live subscribers on that topic.
+    pub fn publish(&self, envelope: EventEnvelope) -> usize {
+        match &self.outbox {
+            Some(outbox) => {
+                let subscribers = self.channels.topics[&envelope.topic()].receiver_count();
+                match outbox.queue.try_send(envelope) {
+                    Ok(()) => subscribers,
+                    Err(mpsc::error::TrySendError::Full(envelope)) => {
+                        outbox.dropped.inc();
+                        broadcast(&self.channels, envelope)
+                    }
+                    Err(mpsc::error::TrySendError::Closed(envelope)) => {
+                        broadcast(&self.channels, envelope)
+                    }
+                }
+            }
+            None => broadcast(&self.channels, envelope),
+        }
+    }
+
+    /// Shorthand for publishing an event about `owner` with no correlation id.
+    pub fn publish_for(&self, owner: &str, event: VaultEvent) -> usize {
+        self.publish(EventEnvelope::new(event).with_owner(owner))
+    }
+
+    pub fn subscribe(&self, topic: Topic) -> broadcast::Receiver<EventEnvelope> {
+        self.channels.topics[&topic].subscribe()
+    }
+
+    pub fn is_durable(&self) -> bool {
+        self.outbox.is_some()
+    }
+
+    /// Persisted events on `topics` after `cursor`, oldest first. `owner` only filters the
+    /// owner-scoped topics. Empty without an outbox.
+    pub async fn replay(
+        &self,
+        topics: &[Topic],
+        owner: Option<&str>,
+        cursor: i64,
+        limit: i64,
+    ) -> Result<Vec<EventEnvelope>, sqlx::Error> {
+        let Some(outbox) = &self.outbox else {
+            return Ok(Vec::new());
+        };
+        let (scoped, global): (Vec<Topic>, Vec<Topic>) =
+            topics.iter().partition(|topic| topic.is_owner_scoped());
+        let scoped: Vec<&str> = scoped.iter().map(Topic::as_str).collect();
+        let global: Vec<&str> = global.iter().map(Topic::as_str).collect();
+        let rows = db::outbox_after(&outbox.pool, &scoped, &global, owner, cursor, limit).await?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(id, payload)| {
+                let mut envelope: EventEnvelope = serde_json::from_value(payload).ok()?;
+                envelope.cursor = Some(id);
+                Some(envelope)
+            })
+            .collect())
+    }
+
+    /// Subscribes to `topics`, first replaying persisted events after `cursor` (if given).
+    /// The live receiver is attached before the replay query, so nothing is missed in between.
+    pub fn subscribe_from(
+        self: &Arc<Self>,
+        topics: &[Topic],
+        owner: Option<String>,
+        cursor: Option<i64>,
+    ) -> Subscription {
+        let rx = match topics {
+            [topic] => self.subscribe(*topic),
+            _ => self.channels.all.subscribe(),
+        };
+        Subscription {
+            notifier: self.clone(),
+            rx,
+            topics: topics.to_vec(),
+            owner,
+            last: cursor,
+            backlog: VecDeque::new(),
+            catch_up: cursor.is_some(),
+        }
+    }
+}
+
+fn channels(capacity: usize) -> Channels {
+    Channels {
+        topics: Topic::ALL
+            .into_iter()
+            .map(|topic| (topic, broadcast::channel(capacity).0))
+            .collect(),
+        all: broadcast::channel(capacity).0,
+    }
+}
+
+fn broadcast(channels: &Channels, envelope: EventEnvelope) -> usize {
+    let _ = channels.all.send(envelope.clone());
+    channels
+        .topics
+        .get(&envelope.topic())
+        .and_then(|tx| tx.send(envelope).ok())
+        .unwrap_or(0)
+}
+
+async fn run_outbox_writer(
+    pool: PgPool,
+    channels: Channels,
+    mut rx: mpsc::Receiver<EventEnvelope>,
+) {
+    while let Some(mut envelope) = rx.recv().await {
+        let payload = serde_json::to_value(&envelope).unwrap_or_default();
+        match db::outbox_insert(
+            &pool,
+            envelope.id,
+            envelope.topic().as_str(),
+            envelope.owner.as_deref(),
+            envelope.event.event_type(),
+            &payload,
+        )
+        .await
+        {
+            Ok(id) => envelope.cursor = Some(id),
+            // Still deliver live; the event just cannot be replayed
+            Err(e) => warn!("event outbox write failed: {e}"),
+        }
+        broadcast(&channels, envelope);
+    }
+}
+
+/// Ordered, de-duplicated event stream for a set of topics (and optionally one owner).
+///
+/// Replays from the outbox on start and whenever the live receiver lags, so a slow consumer
+/// catches up instead of silently dropping events.
+pub struct Subscription {
+    notifier: Arc<Notifier>,
+    rx: broadcast::Receiver<EventEnvelope>,
+    topics: Vec<Topic>,
+    owner: Option<String>,
+    last: Option<i64>,
+    backlog: VecDeque<EventEnvelope>,
+    catch_up: bool,
+}
+
+impl Subscription {
+    /// Next event, or `None` once the notifier is gone.
+    pub async fn next(&mut self) -> Option<EventEnvelope> {
+        loop {
+            if let Some(envelope) = self.backlog.pop_front() {
+                self.last = envelope.cursor.or(self.last);
+                return Some(envelope);
+            }
+            if self.catch_up {
+                self.catch_up = false;
+                if let Some(after) = self.last {
+                    match self
+                        .notifier
+                        .replay(&self.topics, self.owner.as_deref(), after, REPLAY_PAGE)
+                        .await
+                    {
+                        Ok(page) => {
+                            self.catch_up = page.len() as i64 == REPLAY_PAGE;
+                            self.backlog.extend(page);
+                        }
+                        Err(e) => warn!("event replay failed: {e}"),
+                    }
+                    continue;
+                }
+            }
+            match self.rx.recv().await {
+                Ok(envelope) => {
+                    if !self.topics.contains(&envelope.topic()) {
+                        continue;
+                    }
+                    if self.owner.is_some()
+                        && envelope.topic().is_owner_scoped()
+                        && envelope.owner != self.owner
+                    {
+                        continue;
+                    }
+                    // Already delivered by a replay
+                    if matches!((envelope.cursor, self.last), (Some(c), Some(l)) if c <= l) {
+                        continue;
+                    }
+                    self.last = envelope.cursor.or(self.last);
+                    return Some(envelope);
+                }
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!(skipped, "event subscriber lagged");
+                    self.catch_up = self.notifier.is_durable();
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{Severity, TvlUpdate, TxEvent};
+
+    fn deposit(owner: &str, amount: u64) -> EventEnvelope {
+        EventEnvelope::new(VaultEvent::Deposit(TxEvent {
+            amount: Some(amount),
+            signature: format!("sig{amount}"),
+        }))
+        .with_owner(owner)
+    }
+
+    #[tokio::test]
+    async fn test_subscription_filters_owner_and_survives_lag() {
+        let notifier = Notifier::new(2);
+        let mut sub = notifier.subscribe_from(&[Topic::DepositEvent], Some("alice".into()), None);
+        for amount in 1..=5 {
+            notifier.publish(deposit("alice", amount));
+        }
+        notifier.publish(deposit("bob", 6));
+
+        // The first events were overwritten, but the stream keeps going instead of ending
+        let envelope = sub.next().await.unwrap();
+        assert_eq!(envelope.owner.as_deref(), Some("alice"));
+        assert!(matches!(
+            envelope.event,
+            VaultEvent::Deposit(TxEvent {
+                amount: Some(5),
+                ..
+            })
+        ));
+
+        notifier.publish(deposit("alice", 7));
+        let envelope = sub.next().await.unwrap();
+        assert!(matches!(
+            envelope.event,
+            VaultEvent::Deposit(TxEvent {
+                amount: Some(7),
+                ..
+            })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_multi_topic_subscription_keeps_publish_order() {
+        let notifier = Notifier::new(16);
+        let mut sub = notifier.subscribe_from(&[Topic::DepositEvent, Topic::TvlUpdate], None, None);
+        notifier.publish(EventEnvelope::new(VaultEvent::Tvl(TvlUpdate { tvl: 1 })));
+        let alert = VaultEvent::security("low_balance", Severity::Info, serde_json::json!({}));
+        notifier.publish_for("alice", alert);
+        notifier.publish(deposit("alice", 2));
+
+        assert_eq!(sub.next().await.unwrap().topic(), Topic::TvlUpdate);
+        assert_eq!(sub.next().await.unwrap().topic(), Topic::DepositEvent);
+    }
+
+    #[tokio::test]
+    async fn test_owner_filter_skips_global_topics() {
+        let notifier = Notifier::new(16);
+        let topics = [Topic::DepositEvent, Topic::TvlUpdate];
+        let mut sub = notifier.subscribe_from(&topics, Some("alice".into()), None);
+        notifier.publish(deposit("bob", 1));
+        notifier.publish(EventEnvelope::new(VaultEvent::Tvl(TvlUpdate { tvl: 2 })));
+        notifier.publish(deposit("alice", 3));
+
+        assert_eq!(sub.next().await.unwrap().topic(), Topic::TvlUpdate);
+        assert_eq!(sub.next().await.unwrap().owner.as_deref(), Some("alice"));
+    }
+
+    #[tokio::test]
+    async fn test_full_outbox_queue_drops_to_live_only() {
+        // The writer cannot run before this test yields, so the queue fills up
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect_lazy("postgres://localhost:1/unused")
+            .unwrap();
+        let dropped = Counter::new("test_outbox_dropped", "test").unwrap();
+        let notifier = Notifier::with_outbox(16, pool, dropped.clone());
+        let mut rx = notifier.subscribe(Topic::DepositEvent);
+        for amount in 0..OUTBOX_QUEUE_CAPACITY as u64 + 3 {
+            notifier.publish(deposit("alice", amount));
+        }
+
+        assert_eq!(dropped.get() as u64, 3);
+        // Dropped events still reach live subscribers, without a cursor
+        let envelope = rx.try_recv().unwrap();
+        assert_eq!(envelope.cursor, None);
+        assert!(matches!(
+            envelope.event,
+            VaultEvent::Deposit(TxEvent { amount: Some(a), .. }) if a == OUTBOX_QUEUE_CAPACITY as u64
+        ));
+    }
 }
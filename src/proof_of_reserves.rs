@@ -0,0 +1,311 @@
+//! Proof of reserves: Merkle snapshots of the vault balances confirmed on chain.
+//!
+//! Each leaf commits to a vault's total and locked balance as last read from its on-chain
+//! account by the reconciliation task, and the slot of that run. Vaults never reconciled, or
+//! with an escalated reconciliation case, are left out. Leaves are sorted by owner and hashed
+//! with SHA-256:
+//!
+//! - leaf = H(0x00 ‖ owner length (u32 LE) ‖ owner (UTF-8) ‖ balance ‖ locked ‖ slot), the
+//!   numbers as u64 LE
+//! - node = H(0x01 ‖ left ‖ right); a level with an odd count carries its last node up as is
+//!
+//! A vault owner checks the proof from `GET /proof/:owner` with [`verify`] against the
+//! published root. Roots can be anchored on chain in a memo `cvms-por:<snapshot id>:<root>`.
+
+use data_encoding::HEXLOWER;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::transaction::Transaction;
+use sqlx::PgPool;
+use time::OffsetDateTime;
+
+use crate::db;
+use crate::error::{AppError, AppResult};
+use crate::solana_client::{build_instruction_memo, send_transaction_with_retries, SolanaClient};
+
+pub type Hash = [u8; 32];
+
+/// Reconciled state of one vault.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Leaf {
+    pub owner: String,
+    pub balance: i64,
+    pub locked: i64,
+    pub slot: i64,
+}
+
+impl Leaf {
+    pub fn hash(&self) -> Hash {
+        let mut hasher = Sha256::new();
+        hasher.update([0x00]);
+        hasher.update((self.owner.len() as u32).to_le_bytes());
+        hasher.update(self.owner.as_bytes());
+        hasher.update((self.balance as u64).to_le_bytes());
+        hasher.update((self.locked as u64).to_le_bytes());
+        hasher.update((self.slot as u64).to_le_bytes());
+        hasher.finalize().into()
+    }
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0x01]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+fn decode_hash(hex: &str) -> Option<Hash> {
+    HEXLOWER.decode(hex.as_bytes()).ok()?.try_into().ok()
+}
+
+/// Which side of the running hash a proof sibling goes on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Position {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProofStep {
+    pub hash: String,
+    pub position: Position,
+}
+
+/// Every level of the tree, leaves first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    pub fn new(leaves: Vec<Hash>) -> Self {
+        let mut levels = vec![leaves];
+        while levels.last().map_or(0, Vec::len) > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [last] => *last,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        MerkleTree { levels }
+    }
+
+    /// None for a tree without leaves.
+    pub fn root(&self) -> Option<Hash> {
+        self.levels.last().and_then(|level| level.first()).copied()
+    }
+
+    /// Siblings from the leaf at `index` up to the root.
+    pub fn proof(&self, index: usize) -> Option<Vec<ProofStep>> {
+        if index >= self.levels[0].len() {
+            return None;
+        }
+        let mut index = index;
+        let mut steps = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            let position = if sibling > index {
+                Position::Right
+            } else {
+                Position::Left
+            };
+            if let Some(hash) = level.get(sibling) {
+                steps.push(ProofStep {
+                    hash: HEXLOWER.encode(hash),
+                    position,
+                });
+            }
+            index /= 2;
+        }
+        Some(steps)
+    }
+
+    pub fn to_hex(&self) -> Vec<Vec<String>> {
+        self.levels
+            .iter()
+            .map(|level| level.iter().map(|h| HEXLOWER.encode(h)).collect())
+            .collect()
+    }
+
+    pub fn from_hex(levels: &[Vec<String>]) -> Option<Self> {
+        let levels = levels
+            .iter()
+            .map(|level| level.iter().map(|h| decode_hash(h)).collect())
+            .collect::<Option<Vec<Vec<Hash>>>>()?;
+        if levels.is_empty() {
+            return None;
+        }
+        Some(MerkleTree { levels })
+    }
+}
+
+/// Whether `proof` leads from `leaf` to `root` (hex).
+pub fn verify(leaf: &Leaf, proof: &[ProofStep], root: &str) -> bool {
+    let mut hash = leaf.hash();
+    for step in proof {
+        let Some(sibling) = decode_hash(&step.hash) else {
+            return false;
+        };
+        hash = match step.position {
+            Position::Left => node_hash(&sibling, &hash),
+            Position::Right => node_hash(&hash, &sibling),
+        };
+    }
+    HEXLOWER.encode(&hash) == root
+}
+
+/// Stored snapshot (`reserve_snapshots`), without its tree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct ReserveSnapshot {
+    pub id: i64,
+    pub root: String,
+    pub leaf_count: i32,
+    pub total_balance: i64,
+    pub total_locked: i64,
+    pub anchor_signature: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// Inclusion proof of an owner's leaf in a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InclusionProof {
+    pub snapshot: ReserveSnapshot,
+    pub leaf: Leaf,
+    pub leaf_index: i32,
+    pub leaf_hash: String,
+    pub proof: Vec<ProofStep>,
+}
+
+/// Builds and stores a snapshot of the reconciled vaults. Returns None if there are none.
+pub async fn build_snapshot(pool: &PgPool) -> Result<Option<ReserveSnapshot>, sqlx::Error> {
+    let leaves = db::reserve_leaves_current(pool).await?;
+    let tree = MerkleTree::new(leaves.iter().map(Leaf::hash).collect());
+    let Some(root) = tree.root() else {
+        return Ok(None);
+    };
+    let snapshot =
+        db::reserve_snapshot_insert(pool, &HEXLOWER.encode(&root), &tree.to_hex(), &leaves).await?;
+    Ok(Some(snapshot))
+}
+
+/// Proof for `owner`'s leaf in the newest snapshot that has one.
+pub async fn proof_for(pool: &PgPool, owner: &str) -> Result<Option<InclusionProof>, sqlx::Error> {
+    let Some((snapshot, leaf_index, leaf)) = db::reserve_leaf_latest(pool, owner).await? else {
+        return Ok(None);
+    };
+    let levels = db::reserve_snapshot_tree(pool, snapshot.id).await?;
+    let Some(proof) =
+        MerkleTree::from_hex(&levels).and_then(|tree| tree.proof(leaf_index as usize))
+    else {
+        return Err(sqlx::Error::Protocol(format!(
+            "reserve snapshot {} has a malformed tree",
+            snapshot.id
+        )));
+    };
+    Ok(Some(InclusionProof {
+        leaf_hash: HEXLOWER.encode(&leaf.hash()),
+        snapshot,
+        leaf,
+        leaf_index,
+        proof,
+    }))
+}
+
+pub fn anchor_memo(snapshot: &ReserveSnapshot) -> String {
+    format!("cvms-por:{}:{}", snapshot.id, snapshot.root)
+}
+
+/// Publishes the snapshot root in a memo transaction paid by `payer`.
+pub async fn anchor(
+    sol: &SolanaClient,
+    payer: &Keypair,
+    snapshot: &ReserveSnapshot,
+) -> AppResult<Signature> {
+    let ix = build_instruction_memo(&payer.pubkey(), anchor_memo(snapshot).as_bytes());
+    let blockhash = sol
+        .rpc
+        .get_latest_blockhash()
+        .await
+        .map_err(|e| AppError::Solana(format!("blockhash: {e}")))?;
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&payer.pubkey()), &[payer], blockhash);
+    send_transaction_with_retries(sol, &tx, 3).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(n: usize) -> Vec<Leaf> {
+        (0..n)
+            .map(|i| Leaf {
+                owner: format!("owner{i}"),
+                balance: 1_000 * i as i64,
+                locked: 10 * i as i64,
+                slot: 42,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_every_leaf_verifies() {
+        for n in 1..=9 {
+            let leaves = leaves(n);
+            let tree = MerkleTree::new(leaves.iter().map(Leaf::hash).collect());
+            let root = HEXLOWER.encode(&tree.root().unwrap());
+            for (i, leaf) in leaves.iter().enumerate() {
+                let proof = tree.proof(i).unwrap();
+                assert!(verify(leaf, &proof, &root), "leaf {i} of {n}");
+            }
+            assert!(tree.proof(n).is_none());
+        }
+    }
+
+    #[test]
+    fn test_single_leaf_is_root() {
+        let leaf = &leaves(1)[0];
+        let tree = MerkleTree::new(vec![leaf.hash()]);
+        assert_eq!(tree.root(), Some(leaf.hash()));
+        assert!(tree.proof(0).unwrap().is_empty());
+        assert_eq!(MerkleTree::new(Vec::new()).root(), None);
+    }
+
+    #[test]
+    fn test_tampering_fails() {
+        let leaves = leaves(5);
+        let tree = MerkleTree::new(leaves.iter().map(Leaf::hash).collect());
+        let root = HEXLOWER.encode(&tree.root().unwrap());
+        let proof = tree.proof(2).unwrap();
+
+        let inflated = Leaf {
+            balance: leaves[2].balance + 1,
+            ..leaves[2].clone()
+        };
+        assert!(!verify(&inflated, &proof, &root));
+        assert!(!verify(&leaves[3], &proof, &root));
+
+        let mut swapped = proof.clone();
+        swapped[0].position = Position::Left;
+        assert!(!verify(&leaves[2], &swapped, &root));
+        let mut garbled = proof;
+        garbled[0].hash = "zz".to_string();
+        assert!(!verify(&leaves[2], &garbled, &root));
+    }
+
+    #[test]
+    fn test_hex_round_trip() {
+        let tree = MerkleTree::new(leaves(6).iter().map(Leaf::hash).collect());
+        assert_eq!(MerkleTree::from_hex(&tree.to_hex()), Some(tree));
+        assert_eq!(MerkleTree::from_hex(&[vec!["00".to_string()]]), None);
+        assert_eq!(MerkleTree::from_hex(&[]), None);
+    }
+}
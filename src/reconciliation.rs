@@ -0,0 +1,295 @@
+//! Reconciliation of the vault snapshot (`vaults`) with the decoded on-chain vault account.
+//!
+//! Every balance the program keeps (total, locked, yield deposited and accrued) is compared.
+//! Drift up to `RECONCILIATION_THRESHOLD` is ignored. Drift up to
+//! `RECONCILIATION_AUTO_HEAL_MAX` is corrected by rewriting the snapshot from the chain,
+//! audited and recorded as a `healed` case. Anything larger opens an `escalated` case and a
+//! `security_alert`; the case stays open, and later drift for the vault is added to it instead
+//! of being healed, until an admin resolves it.
+
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+
+use crate::db;
+use crate::events::{Severity, VaultEvent};
+use crate::notify::Notifier;
+use crate::solana_client::VaultOnchainSnapshot;
+
+/// Balances compared between the snapshot and the chain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::FromRow)]
+pub struct VaultBalances {
+    pub total_balance: i64,
+    pub locked_balance: i64,
+    pub yield_deposited_balance: i64,
+    pub yield_accrued_balance: i64,
+}
+
+impl VaultBalances {
+    fn fields(&self) -> [(&'static str, i64); 4] {
+        [
+            ("total_balance", self.total_balance),
+            ("locked_balance", self.locked_balance),
+            ("yield_deposited_balance", self.yield_deposited_balance),
+            ("yield_accrued_balance", self.yield_accrued_balance),
+        ]
+    }
+}
+
+impl From<&VaultOnchainSnapshot> for VaultBalances {
+    fn from(v: &VaultOnchainSnapshot) -> Self {
+        VaultBalances {
+            total_balance: v.total_balance as i64,
+            locked_balance: v.locked_balance as i64,
+            yield_deposited_balance: v.yield_deposited_balance as i64,
+            yield_accrued_balance: v.yield_accrued_balance as i64,
+        }
+    }
+}
+
+/// Snapshot of one registered vault.
+#[derive(Debug, Clone, PartialEq, Eq, sqlx::FromRow)]
+pub struct SnapshotRow {
+    pub owner: String,
+    pub token_account: Option<String>,
+    #[sqlx(flatten)]
+    pub balances: VaultBalances,
+}
+
+/// Chain minus snapshot, for each balance that differs.
+pub fn discrepancies(db: &VaultBalances, chain: &VaultBalances) -> BTreeMap<&'static str, i64> {
+    db.fields()
+        .into_iter()
+        .zip(chain.fields())
+        .filter(|((_, d), (_, c))| c != d)
+        .map(|((name, d), (_, c))| (name, c - d))
+        .collect()
+}
+
+/// What a drift of `max_discrepancy` calls for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    InSync,
+    Healed,
+    Escalated,
+}
+
+pub fn classify(max_discrepancy: i64, threshold: i64, auto_heal_max: i64) -> Outcome {
+    if max_discrepancy <= threshold {
+        Outcome::InSync
+    } else if max_discrepancy <= auto_heal_max {
+        Outcome::Healed
+    } else {
+        Outcome::Escalated
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaseStatus {
+    /// Corrected automatically.
+    Healed,
+    /// Waiting for an admin.
+    Escalated,
+    Resolved,
+}
+
+impl CaseStatus {
+    pub const ALL: [CaseStatus; 3] = [
+        CaseStatus::Healed,
+        CaseStatus::Escalated,
+        CaseStatus::Resolved,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CaseStatus::Healed => "healed",
+            CaseStatus::Escalated => "escalated",
+            CaseStatus::Resolved => "resolved",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<CaseStatus> {
+        CaseStatus::ALL.into_iter().find(|c| c.as_str() == s)
+    }
+}
+
+/// A drift between the snapshot and the chain (`reconciliation_cases`). The states are those
+/// of the last run that saw it.
+#[derive(Debug, Clone, PartialEq, Serialize, sqlx::FromRow)]
+pub struct ReconciliationCase {
+    pub id: i64,
+    pub owner: String,
+    pub status: String,
+    pub db_state: serde_json::Value,
+    pub chain_state: serde_json::Value,
+    pub discrepancies: serde_json::Value,
+    pub max_discrepancy: i64,
+    pub occurrences: i32,
+    #[serde(with = "time::serde::rfc3339")]
+    pub opened_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub last_seen_at: OffsetDateTime,
+    pub resolution: Option<String>,
+    pub resolved_by: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub resolved_at: Option<OffsetDateTime>,
+}
+
+/// Compares `owner`'s snapshot with the chain and heals or escalates the drift.
+pub async fn reconcile_vault(
+    pool: &PgPool,
+    notifier: &Notifier,
+    owner: &str,
+    db_state: &VaultBalances,
+    chain_state: &VaultBalances,
+    threshold: i64,
+    auto_heal_max: i64,
+) -> Result<Outcome, sqlx::Error> {
+    let found = discrepancies(db_state, chain_state);
+    let max = found.values().map(|d| d.abs()).max().unwrap_or(0);
+    let mut outcome = classify(max, threshold, auto_heal_max);
+    if outcome == Outcome::InSync {
+        return Ok(outcome);
+    }
+    if outcome == Outcome::Healed && db::reconciliation_case_escalated(pool, owner).await? {
+        outcome = Outcome::Escalated;
+    }
+
+    let db_json = serde_json::json!(db_state);
+    let chain_json = serde_json::json!(chain_state);
+    let found = serde_json::json!(found);
+    // The snapshot, the case and the audit entry are written together or not at all
+    let mut tx = pool.begin().await?;
+    if outcome == Outcome::Healed {
+        db::vault_rewrite_balances(&mut *tx, owner, chain_state).await?;
+        let id = db::reconciliation_case_insert(
+            &mut *tx,
+            owner,
+            CaseStatus::Healed.as_str(),
+            &db_json,
+            &chain_json,
+            &found,
+            max,
+        )
+        .await?;
+        let details = serde_json::json!({
+            "case_id": id,
+            "before": db_json,
+            "after": chain_json,
+            "discrepancies": found,
+        });
+        db::insert_audit_log(&mut *tx, Some(owner), "reconciliation_auto_heal", details).await?;
+        tx.commit().await?;
+        return Ok(outcome);
+    }
+
+    let (id, opened) =
+        db::reconciliation_case_escalate(&mut *tx, owner, &db_json, &chain_json, &found, max)
+            .await?;
+    if opened {
+        let details = serde_json::json!({
+            "case_id": id,
+            "db": db_json,
+            "chain": chain_json,
+            "discrepancies": found,
+            "max_discrepancy": max,
+        });
+        db::insert_audit_log(
+            &mut *tx,
+            Some(owner),
+            "reconciliation_escalated",
+            details.clone(),
+        )
+        .await?;
+        tx.commit().await?;
+        notifier.publish_for(
+            owner,
+            VaultEvent::security("reconciliation_drift", Severity::Critical, details),
+        );
+    } else {
+        tx.commit().await?;
+    }
+    Ok(outcome)
+}
+
+/// Resolves an escalated case, first rewriting the snapshot from its last seen chain state if
+/// `apply_chain` is set. Returns None if no escalated case has this id.
+pub async fn resolve(
+    pool: &PgPool,
+    id: i64,
+    resolution: &str,
+    resolved_by: &str,
+    apply_chain: bool,
+) -> Result<Option<ReconciliationCase>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let Some(case) = db::reconciliation_case_resolve(&mut *tx, id, resolution, resolved_by).await?
+    else {
+        return Ok(None);
+    };
+    if apply_chain {
+        let chain: VaultBalances = serde_json::from_value(case.chain_state.clone())
+            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        db::vault_rewrite_balances(&mut *tx, &case.owner, &chain).await?;
+    }
+    let details = serde_json::json!({
+        "case_id": id,
+        "resolution": resolution,
+        "by": resolved_by,
+        "applied_chain_state": apply_chain,
+    });
+    db::insert_audit_log(
+        &mut *tx,
+        Some(&case.owner),
+        "reconciliation_resolved",
+        details,
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(Some(case))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_case_status_round_trip() {
+        for status in CaseStatus::ALL {
+            assert_eq!(CaseStatus::parse(status.as_str()), Some(status));
+        }
+        assert_eq!(CaseStatus::parse("open"), None);
+    }
+
+    #[test]
+    fn test_discrepancies() {
+        let db = VaultBalances {
+            total_balance: 1_000,
+            locked_balance: 200,
+            yield_deposited_balance: 300,
+            yield_accrued_balance: 10,
+        };
+        assert!(discrepancies(&db, &db).is_empty());
+        let chain = VaultBalances {
+            total_balance: 990,
+            yield_accrued_balance: 15,
+            ..db
+        };
+        let found = discrepancies(&db, &chain);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found["total_balance"], -10);
+        assert_eq!(found["yield_accrued_balance"], 5);
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(classify(0, 0, 0), Outcome::InSync);
+        assert_eq!(classify(5, 5, 100), Outcome::InSync);
+        assert_eq!(classify(6, 5, 100), Outcome::Healed);
+        assert_eq!(classify(100, 5, 100), Outcome::Healed);
+        assert_eq!(classify(101, 5, 100), Outcome::Escalated);
+        // Auto-heal disabled
+        assert_eq!(classify(1, 0, 0), Outcome::Escalated);
+    }
+}
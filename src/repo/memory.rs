@@ -0,0 +1,559 @@
+use async_trait::async_trait;
+use sqlx::types::Json;
+use std::sync::{Arc, Mutex, MutexGuard};
+use time::OffsetDateTime;
+
+use super::{
+    LedgerRepo, Proposal, ProposalRepo, Timelock, TimelockRepo, Transaction, TxRepo, Vault,
+    VaultRepo,
+};
+use crate::tvl::Tvl;
+
+/// In-memory repositories with the same semantics as the Postgres queries.
+#[derive(Clone, Default)]
+pub struct MemoryRepo {
+    store: Arc<Mutex<Store>>,
+}
+
+#[derive(Default)]
+struct Store {
+    vaults: Vec<Vault>,
+    txs: Vec<Transaction>,
+    timelocks: Vec<Timelock>,
+    proposals: Vec<Proposal>,
+    /// (proposal_id, signer)
+    approvals: Vec<(String, String)>,
+    /// (signature, kind) of every ledger entry
+    ledger_entries: Vec<(String, String)>,
+    /// (owner, account, amount)
+    postings: Vec<(String, String, i64)>,
+    next_id: i64,
+}
+
+impl Store {
+    fn next_id(&mut self) -> i64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn vault_mut(&mut self, owner: &str) -> &mut Vault {
+        let idx = match self.vaults.iter().position(|v| v.owner == owner) {
+            Some(idx) => idx,
+            None => {
+                self.vaults.push(Vault {
+                    owner: owner.to_string(),
+                    token_account: None,
+                    total_deposits: 0,
+                    total_withdrawals: 0,
+                    total_balance: 0,
+                    locked_balance: 0,
+                    status: "active".to_string(),
+                    updated_at: OffsetDateTime::now_utc(),
+                });
+                self.vaults.len() - 1
+            }
+        };
+        &mut self.vaults[idx]
+    }
+}
+
+impl MemoryRepo {
+    fn lock(&self) -> MutexGuard<'_, Store> {
+        self.store.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl VaultRepo for MemoryRepo {
+    async fn get(&self, owner: &str) -> Result<Option<Vault>, sqlx::Error> {
+        Ok(self
+            .lock()
+            .vaults
+            .iter()
+            .find(|v| v.owner == owner)
+            .cloned())
+    }
+
+    async fn list(&self) -> Result<Vec<Vault>, sqlx::Error> {
+        Ok(self.lock().vaults.clone())
+    }
+
+    async fn upsert_token_account(
+        &self,
+        owner: &str,
+        token_account: &str,
+    ) -> Result<(), sqlx::Error> {
+        self.lock().vault_mut(owner).token_account = Some(token_account.to_string());
+        Ok(())
+    }
+
+    async fn update_snapshot(
+        &self,
+        owner: &str,
+        total_balance: i64,
+        deposit_delta: i64,
+        withdraw_delta: i64,
+    ) -> Result<(), sqlx::Error> {
+        let mut store = self.lock();
+        let vault = store.vault_mut(owner);
+        vault.total_deposits += deposit_delta.max(0);
+        vault.total_withdrawals += withdraw_delta.max(0);
+        vault.total_balance = total_balance;
+        vault.updated_at = OffsetDateTime::now_utc();
+        Ok(())
+    }
+
+    async fn locked_balance(&self, owner: &str) -> Result<i64, sqlx::Error> {
+        Ok(self
+            .lock()
+            .vaults
+            .iter()
+            .find(|v| v.owner == owner)
+            .map_or(0, |v| v.locked_balance))
+    }
+
+    async fn add_locked(&self, owner: &str, delta: i64) -> Result<(), sqlx::Error> {
+        let mut store = self.lock();
+        let vault = store.vault_mut(owner);
+        vault.locked_balance = (vault.locked_balance + delta).max(0);
+        vault.updated_at = OffsetDateTime::now_utc();
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TxRepo for MemoryRepo {
+    async fn insert(
+        &self,
+        owner: &str,
+        signature: &str,
+        amount: Option<i64>,
+        kind: &str,
+        status: &str,
+    ) -> Result<(), sqlx::Error> {
+        let mut store = self.lock();
+        if store.txs.iter().any(|t| t.signature == signature) {
+            return Ok(());
+        }
+        let id = store.next_id();
+        let now = OffsetDateTime::now_utc();
+        store.txs.push(Transaction {
+            id,
+            owner: owner.to_string(),
+            signature: signature.to_string(),
+            amount,
+            kind: kind.to_string(),
+            status: status.to_string(),
+            retry_count: 0,
+            created_at: now,
+            updated_at: now,
+        });
+        Ok(())
+    }
+
+    async fn list_for_owner(
+        &self,
+        owner: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Transaction>, sqlx::Error> {
+        Ok(self
+            .lock()
+            .txs
+            .iter()
+            .rev()
+            .filter(|t| t.owner == owner)
+            .skip(offset.max(0) as usize)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn pending(&self, limit: i64) -> Result<Vec<Transaction>, sqlx::Error> {
+        Ok(self
+            .lock()
+            .txs
+            .iter()
+            .filter(|t| t.status == "pending")
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn set_status(&self, signature: &str, status: &str) -> Result<(), sqlx::Error> {
+        if let Some(tx) = self
+            .lock()
+            .txs
+            .iter_mut()
+            .find(|t| t.signature == signature)
+        {
+            tx.status = status.to_string();
+            tx.updated_at = OffsetDateTime::now_utc();
+        }
+        Ok(())
+    }
+
+    async fn increment_retry(&self, signature: &str) -> Result<(), sqlx::Error> {
+        if let Some(tx) = self
+            .lock()
+            .txs
+            .iter_mut()
+            .find(|t| t.signature == signature)
+        {
+            tx.retry_count += 1;
+            tx.updated_at = OffsetDateTime::now_utc();
+        }
+        Ok(())
+    }
+
+    async fn withdrawn_since(
+        &self,
+        owner: &str,
+        since: OffsetDateTime,
+    ) -> Result<i64, sqlx::Error> {
+        Ok(self
+            .lock()
+            .txs
+            .iter()
+            .filter(|t| t.owner == owner && t.kind == "withdraw" && t.created_at >= since)
+            .filter_map(|t| t.amount)
+            .sum())
+    }
+
+    async fn owner_count(&self) -> Result<i64, sqlx::Error> {
+        let store = self.lock();
+        let owners: std::collections::HashSet<_> = store.txs.iter().map(|t| &t.owner).collect();
+        Ok(owners.len() as i64)
+    }
+
+    async fn volume_since(&self, since: OffsetDateTime) -> Result<i64, sqlx::Error> {
+        Ok(self
+            .lock()
+            .txs
+            .iter()
+            .filter(|t| t.created_at > since)
+            .filter_map(|t| t.amount)
+            .map(i64::abs)
+            .sum())
+    }
+}
+
+#[async_trait]
+impl TimelockRepo for MemoryRepo {
+    async fn insert(
+        &self,
+        owner: &str,
+        amount: i64,
+        unlock_at: OffsetDateTime,
+    ) -> Result<(), sqlx::Error> {
+        let mut store = self.lock();
+        let id = store.next_id();
+        store.timelocks.push(Timelock {
+            id,
+            owner: owner.to_string(),
+            amount,
+            unlock_at,
+            status: "scheduled".to_string(),
+        });
+        Ok(())
+    }
+
+    async fn list_for_owner(&self, owner: &str) -> Result<Vec<Timelock>, sqlx::Error> {
+        let mut rows: Vec<Timelock> = self
+            .lock()
+            .timelocks
+            .iter()
+            .filter(|t| t.owner == owner)
+            .cloned()
+            .collect();
+        rows.sort_by_key(|t| t.unlock_at);
+        Ok(rows)
+    }
+
+    async fn due_within(&self, seconds: i64) -> Result<Vec<Timelock>, sqlx::Error> {
+        let cutoff = OffsetDateTime::now_utc() + time::Duration::seconds(seconds);
+        let mut rows: Vec<Timelock> = self
+            .lock()
+            .timelocks
+            .iter()
+            .filter(|t| t.status == "scheduled" && t.unlock_at <= cutoff)
+            .cloned()
+            .collect();
+        rows.sort_by_key(|t| t.unlock_at);
+        Ok(rows)
+    }
+
+    async fn set_status(&self, id: i64, status: &str) -> Result<(), sqlx::Error> {
+        if let Some(t) = self.lock().timelocks.iter_mut().find(|t| t.id == id) {
+            t.status = status.to_string();
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ProposalRepo for MemoryRepo {
+    async fn create(
+        &self,
+        id: &str,
+        owner: &str,
+        amount: i64,
+        threshold: i32,
+        signers: &[String],
+    ) -> Result<(), sqlx::Error> {
+        let mut store = self.lock();
+        if store.proposals.iter().any(|p| p.id == id) {
+            return Err(sqlx::Error::Protocol(format!("duplicate proposal {id}")));
+        }
+        store.proposals.push(Proposal {
+            id: id.to_string(),
+            owner: owner.to_string(),
+            amount,
+            threshold,
+            signers: Json(signers.to_vec()),
+            status: "pending".to_string(),
+            created_at: OffsetDateTime::now_utc(),
+        });
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Proposal>, sqlx::Error> {
+        Ok(self.lock().proposals.iter().find(|p| p.id == id).cloned())
+    }
+
+    async fn set_status(&self, id: &str, status: &str) -> Result<(), sqlx::Error> {
+        if let Some(p) = self.lock().proposals.iter_mut().find(|p| p.id == id) {
+            p.status = status.to_string();
+        }
+        Ok(())
+    }
+
+    async fn add_approval(
+        &self,
+        id: &str,
+        signer: &str,
+        _signature: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let mut store = self.lock();
+        if store.approvals.iter().any(|(p, s)| p == id && s == signer) {
+            return Ok(false);
+        }
+        store.approvals.push((id.to_string(), signer.to_string()));
+        Ok(true)
+    }
+
+    async fn approval_count(&self, id: &str) -> Result<i64, sqlx::Error> {
+        Ok(self
+            .lock()
+            .approvals
+            .iter()
+            .filter(|(p, _)| p == id)
+            .count() as i64)
+    }
+
+    async fn approvers(&self, id: &str) -> Result<Vec<String>, sqlx::Error> {
+        Ok(self
+            .lock()
+            .approvals
+            .iter()
+            .filter(|(p, _)| p == id)
+            .map(|(_, s)| s.clone())
+            .collect())
+    }
+}
+
+#[async_trait]
+impl LedgerRepo for MemoryRepo {
+    async fn post(
+        &self,
+        signature: &str,
+        kind: &str,
+        postings: &[(String, String, i64)],
+    ) -> Result<bool, sqlx::Error> {
+        let mut store = self.lock();
+        if store
+            .ledger_entries
+            .iter()
+            .any(|(s, k)| s == signature && k == kind)
+        {
+            return Ok(false);
+        }
+        if postings.iter().map(|(_, _, amount)| amount).sum::<i64>() != 0 {
+            return Err(sqlx::Error::Protocol(format!(
+                "ledger entry {kind} {signature} does not balance"
+            )));
+        }
+        store
+            .ledger_entries
+            .push((signature.to_string(), kind.to_string()));
+        store.postings.extend_from_slice(postings);
+        Ok(true)
+    }
+
+    async fn tvl(&self) -> Result<Tvl, sqlx::Error> {
+        let store = self.lock();
+        let mut tvl = Tvl::default();
+        for (owner, account, amount) in &store.postings {
+            if !store.vaults.iter().any(|v| &v.owner == owner) {
+                continue;
+            }
+            match account.as_str() {
+                "available" => tvl.available += amount,
+                "locked" => tvl.locked += amount,
+                "yield_deposited" | "yield_accrued" => tvl.in_yield += amount,
+                _ => {}
+            }
+        }
+        Ok(tvl)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Repos;
+    use time::OffsetDateTime;
+
+    #[tokio::test]
+    async fn test_transactions_dedupe_and_pending() {
+        let repos = Repos::memory();
+        let txs = &repos.txs;
+        txs.insert("alice", "sig1", Some(100), "deposit", "pending")
+            .await
+            .unwrap();
+        txs.insert("alice", "sig1", Some(999), "deposit", "pending")
+            .await
+            .unwrap();
+        txs.insert("alice", "sig2", Some(30), "withdraw", "confirmed")
+            .await
+            .unwrap();
+        txs.insert("bob", "sig3", None, "withdraw", "pending")
+            .await
+            .unwrap();
+
+        let listed = txs.list_for_owner("alice", 10, 0).await.unwrap();
+        assert_eq!(
+            listed
+                .iter()
+                .map(|t| t.signature.as_str())
+                .collect::<Vec<_>>(),
+            ["sig2", "sig1"]
+        );
+        assert_eq!(
+            txs.list_for_owner("alice", 1, 1).await.unwrap()[0].signature,
+            "sig1"
+        );
+
+        assert_eq!(txs.pending(10).await.unwrap().len(), 2);
+        txs.set_status("sig1", "confirmed").await.unwrap();
+        txs.increment_retry("sig3").await.unwrap();
+        let pending = txs.pending(10).await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].retry_count, 1);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_aggregates() {
+        let txs = Repos::memory().txs;
+        let before = OffsetDateTime::now_utc() - time::Duration::seconds(1);
+        txs.insert("alice", "sig1", Some(100), "deposit", "confirmed")
+            .await
+            .unwrap();
+        txs.insert("alice", "sig2", Some(30), "withdraw", "confirmed")
+            .await
+            .unwrap();
+        txs.insert("alice", "sig3", Some(20), "withdraw", "pending")
+            .await
+            .unwrap();
+        txs.insert("bob", "sig4", Some(-5), "withdraw", "confirmed")
+            .await
+            .unwrap();
+
+        assert_eq!(txs.withdrawn_since("alice", before).await.unwrap(), 50);
+        let later = OffsetDateTime::now_utc() + time::Duration::seconds(1);
+        assert_eq!(txs.withdrawn_since("alice", later).await.unwrap(), 0);
+        assert_eq!(txs.owner_count().await.unwrap(), 2);
+        assert_eq!(txs.volume_since(before).await.unwrap(), 155);
+        assert_eq!(txs.volume_since(later).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_ledger_rejects_unbalanced_entries() {
+        let ledger = Repos::memory().ledger;
+        let posting = |account: &str, amount| ("alice".to_string(), account.to_string(), amount);
+        assert!(ledger
+            .post("sig1", "deposit", &[posting("available", 10)])
+            .await
+            .is_err());
+        let balanced = [posting("available", 10), posting("external", -10)];
+        assert!(ledger.post("sig1", "deposit", &balanced).await.unwrap());
+        assert!(!ledger.post("sig1", "deposit", &balanced).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_vault_snapshot_and_locked_balance_clamp() {
+        let vaults = Repos::memory().vaults;
+        assert_eq!(vaults.locked_balance("alice").await.unwrap(), 0);
+        vaults
+            .update_snapshot("alice", 500, 500, -10)
+            .await
+            .unwrap();
+        vaults.update_snapshot("alice", 400, 0, 100).await.unwrap();
+        vaults.add_locked("alice", 50).await.unwrap();
+        vaults.add_locked("alice", -80).await.unwrap();
+        vaults.upsert_token_account("alice", "ata1").await.unwrap();
+
+        let vault = vaults.get("alice").await.unwrap().unwrap();
+        assert_eq!(vault.total_deposits, 500);
+        assert_eq!(vault.total_withdrawals, 100);
+        assert_eq!(vault.total_balance, 400);
+        assert_eq!(vault.locked_balance, 0);
+        assert_eq!(vault.token_account.as_deref(), Some("ata1"));
+        assert_eq!(vaults.list().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_timelocks_due_within() {
+        let timelocks = Repos::memory().timelocks;
+        let now = OffsetDateTime::now_utc();
+        timelocks
+            .insert("alice", 10, now + time::Duration::hours(2))
+            .await
+            .unwrap();
+        timelocks
+            .insert("alice", 20, now + time::Duration::seconds(5))
+            .await
+            .unwrap();
+
+        let listed = timelocks.list_for_owner("alice").await.unwrap();
+        assert_eq!(listed[0].amount, 20);
+        let due = timelocks.due_within(60).await.unwrap();
+        assert_eq!(due.len(), 1);
+        timelocks.set_status(due[0].id, "available").await.unwrap();
+        assert!(timelocks.due_within(60).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_proposal_approvals_dedupe() {
+        let proposals = Repos::memory().proposals;
+        let signers = vec!["s1".to_string(), "s2".to_string()];
+        proposals
+            .create("p1", "alice", 100, 2, &signers)
+            .await
+            .unwrap();
+        assert!(proposals
+            .create("p1", "alice", 100, 2, &signers)
+            .await
+            .is_err());
+
+        assert!(proposals.add_approval("p1", "s1", "sig").await.unwrap());
+        assert!(!proposals.add_approval("p1", "s1", "sig").await.unwrap());
+        assert!(proposals.add_approval("p1", "s2", "sig").await.unwrap());
+        assert_eq!(proposals.approval_count("p1").await.unwrap(), 2);
+        assert_eq!(proposals.approvers("p1").await.unwrap(), signers);
+
+        proposals.set_status("p1", "approved").await.unwrap();
+        let proposal = proposals.get("p1").await.unwrap().unwrap();
+        assert_eq!(proposal.status, "approved");
+        assert_eq!(proposal.signers.0, signers);
+    }
+}
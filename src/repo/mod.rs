@@ -0,0 +1,213 @@
+//! Typed repositories for vaults, transactions, timelocks, multisig proposals and ledger
+//! balances.
+//!
+//! Handlers and tasks reach these tables through [`Repos`] on `AppState` rather than
+//! calling `db` directly, so they can run against [`MemoryRepo`] in tests. Other tables, and
+//! writes that must share a database transaction, still go through `db`. [`PgRepo`]
+//! implements every trait on top of the `db` queries.
+
+use async_trait::async_trait;
+use serde::Serialize;
+use sqlx::types::Json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::tvl::Tvl;
+
+mod memory;
+mod postgres;
+
+pub use memory::MemoryRepo;
+pub use postgres::PgRepo;
+
+/// Off-chain snapshot of a vault (`vaults`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct Vault {
+    pub owner: String,
+    pub token_account: Option<String>,
+    pub total_deposits: i64,
+    pub total_withdrawals: i64,
+    pub total_balance: i64,
+    pub locked_balance: i64,
+    pub status: String,
+    pub updated_at: time::OffsetDateTime,
+}
+
+/// Submitted or indexed transaction (`transactions`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct Transaction {
+    pub id: i64,
+    pub owner: String,
+    pub signature: String,
+    pub amount: Option<i64>,
+    pub kind: String,
+    pub status: String,
+    pub retry_count: i32,
+    pub created_at: time::OffsetDateTime,
+    pub updated_at: time::OffsetDateTime,
+}
+
+/// Scheduled withdrawal (`timelocks`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct Timelock {
+    pub id: i64,
+    pub owner: String,
+    pub amount: i64,
+    pub unlock_at: time::OffsetDateTime,
+    pub status: String,
+}
+
+/// Multisig withdrawal proposal (`ms_proposals`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct Proposal {
+    pub id: String,
+    pub owner: String,
+    pub amount: i64,
+    pub threshold: i32,
+    pub signers: Json<Vec<String>>,
+    pub status: String,
+    pub created_at: time::OffsetDateTime,
+}
+
+#[async_trait]
+pub trait VaultRepo: Send + Sync {
+    async fn get(&self, owner: &str) -> Result<Option<Vault>, sqlx::Error>;
+    async fn list(&self) -> Result<Vec<Vault>, sqlx::Error>;
+    async fn upsert_token_account(
+        &self,
+        owner: &str,
+        token_account: &str,
+    ) -> Result<(), sqlx::Error>;
+    /// Sets the balance and adds the (non-negative parts of the) deposit/withdraw deltas.
+    async fn update_snapshot(
+        &self,
+        owner: &str,
+        total_balance: i64,
+        deposit_delta: i64,
+        withdraw_delta: i64,
+    ) -> Result<(), sqlx::Error>;
+    async fn locked_balance(&self, owner: &str) -> Result<i64, sqlx::Error>;
+    /// Adds `delta` to the locked balance, never going below zero.
+    async fn add_locked(&self, owner: &str, delta: i64) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait TxRepo: Send + Sync {
+    /// Records a transaction; a known signature is ignored.
+    async fn insert(
+        &self,
+        owner: &str,
+        signature: &str,
+        amount: Option<i64>,
+        kind: &str,
+        status: &str,
+    ) -> Result<(), sqlx::Error>;
+    /// Newest first.
+    async fn list_for_owner(
+        &self,
+        owner: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Transaction>, sqlx::Error>;
+    /// Oldest first.
+    async fn pending(&self, limit: i64) -> Result<Vec<Transaction>, sqlx::Error>;
+    async fn set_status(&self, signature: &str, status: &str) -> Result<(), sqlx::Error>;
+    async fn increment_retry(&self, signature: &str) -> Result<(), sqlx::Error>;
+    /// Sum of `owner`'s withdrawals created at or after `since`.
+    async fn withdrawn_since(
+        &self,
+        owner: &str,
+        since: time::OffsetDateTime,
+    ) -> Result<i64, sqlx::Error>;
+    /// Number of distinct owners with at least one transaction.
+    async fn owner_count(&self) -> Result<i64, sqlx::Error>;
+    /// Sum of absolute amounts of transactions created after `since`.
+    async fn volume_since(&self, since: time::OffsetDateTime) -> Result<i64, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait TimelockRepo: Send + Sync {
+    async fn insert(
+        &self,
+        owner: &str,
+        amount: i64,
+        unlock_at: time::OffsetDateTime,
+    ) -> Result<(), sqlx::Error>;
+    /// Earliest unlock first.
+    async fn list_for_owner(&self, owner: &str) -> Result<Vec<Timelock>, sqlx::Error>;
+    /// Scheduled timelocks unlocking within `seconds` from now, earliest first.
+    async fn due_within(&self, seconds: i64) -> Result<Vec<Timelock>, sqlx::Error>;
+    async fn set_status(&self, id: i64, status: &str) -> Result<(), sqlx::Error>;
+}
+
+#[async_trait]
+pub trait ProposalRepo: Send + Sync {
+    async fn create(
+        &self,
+        id: &str,
+        owner: &str,
+        amount: i64,
+        threshold: i32,
+        signers: &[String],
+    ) -> Result<(), sqlx::Error>;
+    async fn get(&self, id: &str) -> Result<Option<Proposal>, sqlx::Error>;
+    async fn set_status(&self, id: &str, status: &str) -> Result<(), sqlx::Error>;
+    /// Returns false if `signer` already approved.
+    async fn add_approval(
+        &self,
+        id: &str,
+        signer: &str,
+        signature: &str,
+    ) -> Result<bool, sqlx::Error>;
+    async fn approval_count(&self, id: &str) -> Result<i64, sqlx::Error>;
+    /// Approving signers in approval order.
+    async fn approvers(&self, id: &str) -> Result<Vec<String>, sqlx::Error>;
+}
+
+#[async_trait]
+pub trait LedgerRepo: Send + Sync {
+    /// Appends an entry with its `(owner, account, amount)` postings, which must sum to zero.
+    /// Returns false if an entry of this kind already exists for `signature`.
+    async fn post(
+        &self,
+        signature: &str,
+        kind: &str,
+        postings: &[(String, String, i64)],
+    ) -> Result<bool, sqlx::Error>;
+    /// Sum of the ledger balances of every registered vault.
+    async fn tvl(&self) -> Result<Tvl, sqlx::Error>;
+}
+
+/// The repositories shared through `AppState`.
+#[derive(Clone)]
+pub struct Repos {
+    pub vaults: Arc<dyn VaultRepo>,
+    pub txs: Arc<dyn TxRepo>,
+    pub timelocks: Arc<dyn TimelockRepo>,
+    pub proposals: Arc<dyn ProposalRepo>,
+    pub ledger: Arc<dyn LedgerRepo>,
+}
+
+impl Repos {
+    pub fn postgres(pool: PgPool) -> Self {
+        Self::from_store(Arc::new(PgRepo::new(pool)))
+    }
+
+    /// Empty in-memory store, for tests that run without a database.
+    pub fn memory() -> Self {
+        Self::from_store(Arc::new(MemoryRepo::default()))
+    }
+
+    fn from_store<R>(store: Arc<R>) -> Self
+    where
+        R: VaultRepo + TxRepo + TimelockRepo + ProposalRepo + LedgerRepo + 'static,
+    {
+        Self {
+            vaults: store.clone(),
+            txs: store.clone(),
+            timelocks: store.clone(),
+            proposals: store.clone(),
+            ledger: store,
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use async_trait::async_trait;
+use sqlx::PgPool;
+
+use super::{
+    LedgerRepo, Proposal, ProposalRepo, Timelock, TimelockRepo, Transaction, TxRepo, Vault,
+    VaultRepo,
+};
+use crate::db;
+use crate::tvl::Tvl;
+
+/// Repositories backed by PostgreSQL.
+#[derive(Clone)]
+pub struct PgRepo {
+    pool: PgPool,
+}
+
+impl PgRepo {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl VaultRepo for PgRepo {
+    async fn get(&self, owner: &str) -> Result<Option<Vault>, sqlx::Error> {
+        db::get_vault(&self.pool, owner).await
+    }
+
+    async fn list(&self) -> Result<Vec<Vault>, sqlx::Error> {
+        db::list_vaults(&self.pool).await
+    }
+
+    async fn upsert_token_account(
+        &self,
+        owner: &str,
+        token_account: &str,
+    ) -> Result<(), sqlx::Error> {
+        db::upsert_vault_token_account(&self.pool, owner, token_account).await
+    }
+
+    async fn update_snapshot(
+        &self,
+        owner: &str,
+        total_balance: i64,
+        deposit_delta: i64,
+        withdraw_delta: i64,
+    ) -> Result<(), sqlx::Error> {
+        db::update_vault_snapshot(
+            &self.pool,
+            owner,
+            total_balance,
+            deposit_delta,
+            withdraw_delta,
+        )
+        .await
+    }
+
+    async fn locked_balance(&self, owner: &str) -> Result<i64, sqlx::Error> {
+        db::get_locked_balance(&self.pool, owner).await
+    }
+
+    async fn add_locked(&self, owner: &str, delta: i64) -> Result<(), sqlx::Error> {
+        db::increment_locked_balance(&self.pool, owner, delta).await
+    }
+}
+
+#[async_trait]
+impl TxRepo for PgRepo {
+    async fn insert(
+        &self,
+        owner: &str,
+        signature: &str,
+        amount: Option<i64>,
+        kind: &str,
+        status: &str,
+    ) -> Result<(), sqlx::Error> {
+        db::insert_transaction_with_status(&self.pool, owner, signature, amount, kind, status).await
+    }
+
+    async fn list_for_owner(
+        &self,
+        owner: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<Transaction>, sqlx::Error> {
+        db::list_transactions(&self.pool, owner, limit, offset).await
+    }
+
+    async fn pending(&self, limit: i64) -> Result<Vec<Transaction>, sqlx::Error> {
+        db::get_pending_transactions(&self.pool, limit).await
+    }
+
+    async fn set_status(&self, signature: &str, status: &str) -> Result<(), sqlx::Error> {
+        db::update_transaction_status(&self.pool, signature, status).await
+    }
+
+    async fn increment_retry(&self, signature: &str) -> Result<(), sqlx::Error> {
+        db::increment_transaction_retry(&self.pool, signature).await
+    }
+
+    async fn withdrawn_since(
+        &self,
+        owner: &str,
+        since: time::OffsetDateTime,
+    ) -> Result<i64, sqlx::Error> {
+        db::sum_withdrawals_since(&self.pool, owner, since).await
+    }
+
+    async fn owner_count(&self) -> Result<i64, sqlx::Error> {
+        db::count_transaction_owners(&self.pool).await
+    }
+
+    async fn volume_since(&self, since: time::OffsetDateTime) -> Result<i64, sqlx::Error> {
+        db::transaction_volume_since(&self.pool, since).await
+    }
+}
+
+#[async_trait]
+impl TimelockRepo for PgRepo {
+    async fn insert(
+        &self,
+        owner: &str,
+        amount: i64,
+        unlock_at: time::OffsetDateTime,
+    ) -> Result<(), sqlx::Error> {
+        db::timelock_insert(&self.pool, owner, amount, unlock_at).await
+    }
+
+    async fn list_for_owner(&self, owner: &str) -> Result<Vec<Timelock>, sqlx::Error> {
+        db::timelock_list(&self.pool, owner).await
+    }
+
+    async fn due_within(&self, seconds: i64) -> Result<Vec<Timelock>, sqlx::Error> {
+        db::timelock_due_within(&self.pool, seconds).await
+    }
+
+    async fn set_status(&self, id: i64, status: &str) -> Result<(), sqlx::Error> {
+        db::timelock_mark_status(&self.pool, id, status).await
+    }
+}
+
+#[async_trait]
+impl ProposalRepo for PgRepo {
+    async fn create(
+        &self,
+        id: &str,
+        owner: &str,
+        amount: i64,
+        threshold: i32,
+        signers: &[String],
+    ) -> Result<(), sqlx::Error> {
+        let signers = serde_json::json!(signers);
+        db::ms_create_proposal(&self.pool, id, owner, amount, threshold, &signers).await
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Proposal>, sqlx::Error> {
+        db::ms_get_proposal(&self.pool, id).await
+    }
+
+    async fn set_status(&self, id: &str, status: &str) -> Result<(), sqlx::Error> {
+        db::ms_set_status(&self.pool, id, status).await
+    }
+
+    async fn add_approval(
+        &self,
+        id: &str,
+        signer: &str,
+        signature: &str,
+    ) -> Result<bool, sqlx::Error> {
+        db::ms_insert_approval(&self.pool, id, signer, signature).await
+    }
+
+    async fn approval_count(&self, id: &str) -> Result<i64, sqlx::Error> {
+        db::ms_count_approvals(&self.pool, id).await
+    }
+
+    async fn approvers(&self, id: &str) -> Result<Vec<String>, sqlx::Error> {
+        db::ms_list_approvals(&self.pool, id).await
+    }
+}
+
+#[async_trait]
+impl LedgerRepo for PgRepo {
+    async fn post(
+        &self,
+        signature: &str,
+        kind: &str,
+        postings: &[(String, String, i64)],
+    ) -> Result<bool, sqlx::Error> {
+        db::ledger_post(&self.pool, signature, kind, postings).await
+    }
+
+    async fn tvl(&self) -> Result<Tvl, sqlx::Error> {
+        db::tvl_current(&self.pool).await
+    }
+}
@@ -1,14 +1,274 @@
-use data_encoding::BASE32;
-use totp_rs::{Algorithm, TOTP};
+use aes_gcm::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use data_encoding::{BASE32_NOPAD, BASE32, HEXLOWER};
+use sha2::{Digest, Sha256};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::error::{AppError, AppResult};
+
+const TOTP_STEP_SECONDS: u64 = 30;
+const SEALED_SECRET_VERSION: &str = "v1";
+
+pub struct TotpEnrollment {
+    pub secret_base32: String,
+    pub otpauth_url: String,
+}
+
+/// Generates a fresh 160-bit TOTP secret and the matching `otpauth://` URI for authenticator apps.
+pub fn generate_totp_enrollment(issuer: &str, account: &str) -> AppResult<TotpEnrollment> {
+    let secret = Secret::generate_secret()
+        .to_bytes()
+        .map_err(|e| AppError::Internal(format!("totp secret: {e}")))?;
+    let totp = TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        TOTP_STEP_SECONDS,
+        secret,
+        Some(issuer.to_string()),
+        account.to_string(),
+    )
+    .map_err(|e| AppError::Internal(format!("totp: {e}")))?;
+    Ok(TotpEnrollment {
+        secret_base32: totp.get_secret_base32(),
+        otpauth_url: totp.get_url(),
+    })
+}
+
+/// Returns the time step the code is valid for (current step ±1), or `None` if it does not match.
+/// Callers persist the step to reject a code that was already used in the same window.
+pub fn totp_matching_step(base32_secret: &str, code: &str) -> Option<u64> {
+    let secret_bytes = BASE32.decode(base32_secret.as_bytes()).ok()?;
+    let totp = TOTP::new(
+        Algorithm::SHA1,
+        6,
+        0,
+        TOTP_STEP_SECONDS,
+        secret_bytes,
+        None,
+        String::new(),
+    )
+    .ok()?;
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .ok()?
+        .as_secs();
+    let current = now / TOTP_STEP_SECONDS;
+    [current.saturating_sub(1), current, current + 1]
+        .into_iter()
+        .find(|step| totp.check(code, step * TOTP_STEP_SECONDS))
+}
 
 pub fn verify_totp(base32_secret: &str, code: &str) -> bool {
-    let secret_bytes = match BASE32.decode(base32_secret.as_bytes()) {
-        Ok(b) => b,
-        Err(_) => return false,
-    };
-    let totp = match TOTP::new(Algorithm::SHA1, 6, 1, 30, secret_bytes, None, String::new()) {
-        Ok(t) => t,
-        Err(_) => return false,
+    TotpVerifier {
+        secret_base32: base32_secret.to_string(),
+    }
+    .verify(&SecondFactorProof::Totp(code.to_string()))
+    .is_some()
+}
+
+/// A second-factor proof presented alongside a wallet-signed request.
+pub enum SecondFactorProof {
+    Totp(String),
+    WebAuthn(crate::webauthn::Assertion),
+}
+
+/// A factor enrolled for an owner. `verify` returns the replay counter carried by a valid
+/// proof (TOTP time step, WebAuthn sign count); callers persist it and reject any proof whose
+/// counter does not advance.
+pub trait SecondFactorVerifier {
+    fn verify(&self, proof: &SecondFactorProof) -> Option<u64>;
+}
+
+pub struct TotpVerifier {
+    pub secret_base32: String,
+}
+
+impl SecondFactorVerifier for TotpVerifier {
+    fn verify(&self, proof: &SecondFactorProof) -> Option<u64> {
+        match proof {
+            SecondFactorProof::Totp(code) => totp_matching_step(&self.secret_base32, code),
+            _ => None,
+        }
+    }
+}
+
+pub struct WebAuthnVerifier<'a> {
+    pub rp: &'a crate::webauthn::RelyingParty,
+    pub challenge: &'a str,
+    pub public_key: &'a [u8],
+}
+
+impl SecondFactorVerifier for WebAuthnVerifier<'_> {
+    fn verify(&self, proof: &SecondFactorProof) -> Option<u64> {
+        match proof {
+            SecondFactorProof::WebAuthn(assertion) => {
+                crate::webauthn::verify_assertion(self.rp, self.challenge, self.public_key, assertion)
+                    .ok()
+                    .map(u64::from)
+            }
+            _ => None,
+        }
+    }
+}
+
+fn envelope_cipher(key_b64: &str) -> AppResult<Aes256Gcm> {
+    let key = STANDARD
+        .decode(key_b64)
+        .map_err(|_| AppError::Internal("2fa encryption key is not base64".to_string()))?;
+    Aes256Gcm::new_from_slice(&key)
+        .map_err(|_| AppError::Internal("2fa encryption key must be 32 bytes".to_string()))
+}
+
+/// Checks that `key_b64` is usable as the envelope key, so a misconfigured key fails at
+/// startup rather than on the first enrollment.
+pub fn validate_envelope_key(key_b64: &str) -> AppResult<()> {
+    envelope_cipher(key_b64).map(|_| ())
+}
+
+/// Envelope-encrypts a secret: a random data key encrypts the secret and the configured key
+/// encrypts the data key. Output is `v1:<wrapped key>:<ciphertext>`, each part base64 of
+/// nonce || ciphertext.
+pub fn seal_secret(key_b64: &str, plaintext: &str) -> AppResult<String> {
+    let kek = envelope_cipher(key_b64)?;
+    let dek_bytes = Aes256Gcm::generate_key(OsRng);
+    let dek = Aes256Gcm::new(&dek_bytes);
+
+    let data_nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = dek
+        .encrypt(&data_nonce, plaintext.as_bytes())
+        .map_err(|_| AppError::Internal("encrypt secret failed".to_string()))?;
+    let key_nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let wrapped = kek
+        .encrypt(&key_nonce, &dek_bytes[..])
+        .map_err(|_| AppError::Internal("wrap data key failed".to_string()))?;
+
+    let join = |nonce: &[u8], body: &[u8]| STANDARD.encode([nonce, body].concat());
+    Ok(format!(
+        "{SEALED_SECRET_VERSION}:{}:{}",
+        join(&key_nonce, &wrapped),
+        join(&data_nonce, &ciphertext)
+    ))
+}
+
+pub fn open_secret(key_b64: &str, sealed: &str) -> AppResult<String> {
+    let bad = || AppError::Internal("malformed sealed secret".to_string());
+    let mut parts = sealed.splitn(3, ':');
+    if parts.next() != Some(SEALED_SECRET_VERSION) {
+        return Err(bad());
+    }
+    let wrapped = STANDARD.decode(parts.next().ok_or_else(bad)?).map_err(|_| bad())?;
+    let body = STANDARD.decode(parts.next().ok_or_else(bad)?).map_err(|_| bad())?;
+    if wrapped.len() < 12 || body.len() < 12 {
+        return Err(bad());
+    }
+
+    let split_nonce = |buf: &[u8]| -> AppResult<(Nonce<_>, Vec<u8>)> {
+        let (nonce, rest) = buf.split_at(12);
+        let nonce: [u8; 12] = nonce.try_into().map_err(|_| bad())?;
+        Ok((Nonce::from(nonce), rest.to_vec()))
     };
-    totp.check_current(code).unwrap_or(false)
+
+    let kek = envelope_cipher(key_b64)?;
+    let (key_nonce, wrapped) = split_nonce(&wrapped)?;
+    let dek_bytes = kek
+        .decrypt(&key_nonce, wrapped.as_ref())
+        .map_err(|_| AppError::Internal("unwrap data key failed".to_string()))?;
+    let dek = Aes256Gcm::new_from_slice(&dek_bytes).map_err(|_| bad())?;
+    let (data_nonce, ciphertext) = split_nonce(&body)?;
+    let plaintext = dek
+        .decrypt(&data_nonce, ciphertext.as_ref())
+        .map_err(|_| AppError::Internal("decrypt secret failed".to_string()))?;
+    String::from_utf8(plaintext).map_err(|_| bad())
+}
+
+/// Returns the base32 TOTP secret from a stored value, which is either a sealed envelope or a
+/// legacy plaintext secret written before encryption at rest.
+pub fn reveal_totp_secret(key_b64: &str, stored: &str) -> AppResult<String> {
+    if stored.starts_with(&format!("{SEALED_SECRET_VERSION}:")) {
+        open_secret(key_b64, stored)
+    } else {
+        Ok(stored.to_string())
+    }
+}
+
+/// Generates `count` random recovery codes formatted as `XXXXX-XXXXX` (50 bits each).
+pub fn generate_recovery_codes(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|_| {
+            let mut bytes = [0u8; 7];
+            OsRng.fill_bytes(&mut bytes);
+            let encoded = BASE32_NOPAD.encode(&bytes);
+            format!("{}-{}", &encoded[..5], &encoded[5..10])
+        })
+        .collect()
+}
+
+/// Hash stored for a recovery code; input is normalized so dashes and case do not matter.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized: String = code
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    HEXLOWER.encode(&Sha256::digest(normalized.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_key() -> String {
+        STANDARD.encode([7u8; 32])
+    }
+
+    #[test]
+    fn test_seal_open_roundtrip() {
+        let sealed = seal_secret(&test_key(), "JBSWY3DPEHPK3PXP").unwrap();
+        assert!(sealed.starts_with("v1:"));
+        assert!(!sealed.contains("JBSWY3DPEHPK3PXP"));
+        assert_eq!(open_secret(&test_key(), &sealed).unwrap(), "JBSWY3DPEHPK3PXP");
+
+        let other_key = STANDARD.encode([8u8; 32]);
+        assert!(open_secret(&other_key, &sealed).is_err());
+    }
+
+    #[test]
+    fn test_validate_envelope_key() {
+        assert!(validate_envelope_key(&test_key()).is_ok());
+        assert!(validate_envelope_key("").is_err());
+        assert!(validate_envelope_key("not base64!").is_err());
+        assert!(validate_envelope_key(&STANDARD.encode([7u8; 16])).is_err());
+    }
+
+    #[test]
+    fn test_enrollment_code_matches_current_step() {
+        let enrollment = generate_totp_enrollment("CVMS", "owner").unwrap();
+        assert!(enrollment.otpauth_url.starts_with("otpauth://totp/"));
+
+        let secret = BASE32.decode(enrollment.secret_base32.as_bytes()).unwrap();
+        let totp =
+            TOTP::new(Algorithm::SHA1, 6, 0, TOTP_STEP_SECONDS, secret, None, String::new())
+                .unwrap();
+        let code = totp.generate_current().unwrap();
+        assert!(totp_matching_step(&enrollment.secret_base32, &code).is_some());
+        assert!(totp_matching_step(&enrollment.secret_base32, "000000x").is_none());
+    }
+
+    #[test]
+    fn test_recovery_codes_are_unique_and_hash_normalized() {
+        let codes = generate_recovery_codes(10);
+        assert_eq!(codes.len(), 10);
+        let uniq: std::collections::HashSet<_> = codes.iter().collect();
+        assert_eq!(uniq.len(), 10);
+        assert!(codes.iter().all(|c| c.len() == 11 && c.as_bytes()[5] == b'-'));
+
+        let code = &codes[0];
+        let compact = code.replace('-', "").to_lowercase();
+        assert_eq!(hash_recovery_code(code), hash_recovery_code(&compact));
+        assert_ne!(hash_recovery_code(code), hash_recovery_code(&codes[1]));
+    }
 }
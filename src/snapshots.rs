@@ -0,0 +1,190 @@
+//! Hourly and daily balance snapshots for charts (`balance_snapshots`).
+//!
+//! A snapshot holds a vault's balances at the end of its period, computed from the ledger as
+//! of that instant, so periods missed while the service was down are backfilled with the
+//! values they would have had. Daily snapshots roll up the closing hourly snapshot of the day;
+//! hourly snapshots older than the retention window are then dropped.
+
+use serde::Serialize;
+use sqlx::PgPool;
+use time::{Duration, OffsetDateTime, Time, UtcOffset};
+
+use crate::db;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Granularity {
+    Hourly,
+    Daily,
+}
+
+impl Granularity {
+    pub const ALL: [Granularity; 2] = [Granularity::Hourly, Granularity::Daily];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Granularity::Hourly => "hourly",
+            Granularity::Daily => "daily",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Granularity> {
+        Granularity::ALL.into_iter().find(|g| g.as_str() == s)
+    }
+
+    pub fn period(&self) -> Duration {
+        match self {
+            Granularity::Hourly => Duration::HOUR,
+            Granularity::Daily => Duration::DAY,
+        }
+    }
+
+    /// Start (UTC) of the period containing `t`.
+    pub fn truncate(&self, t: OffsetDateTime) -> OffsetDateTime {
+        let t = t.to_offset(UtcOffset::UTC);
+        match self {
+            Granularity::Hourly => {
+                t.replace_time(Time::MIDNIGHT) + Duration::hours(t.hour() as i64)
+            }
+            Granularity::Daily => t.replace_time(Time::MIDNIGHT),
+        }
+    }
+}
+
+/// Starts of the closed periods not yet snapshotted: after `last` (or from `floor` if nothing
+/// was recorded), never before `floor`, up to but excluding the period containing `now`.
+pub fn missing_periods(
+    granularity: Granularity,
+    last: Option<OffsetDateTime>,
+    floor: OffsetDateTime,
+    now: OffsetDateTime,
+) -> Vec<OffsetDateTime> {
+    let step = granularity.period();
+    let floor = granularity.truncate(floor);
+    let mut start = last
+        .map(|l| granularity.truncate(l) + step)
+        .map_or(floor, |s| s.max(floor));
+    let end = granularity.truncate(now);
+    let mut periods = Vec::new();
+    while start < end {
+        periods.push(start);
+        start += step;
+    }
+    periods
+}
+
+/// One stored snapshot. `balance` is the custody total (available + locked + in transit).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, sqlx::FromRow)]
+pub struct BalanceSnapshot {
+    #[serde(with = "time::serde::rfc3339")]
+    pub period_start: OffsetDateTime,
+    pub balance: i64,
+    pub available: i64,
+    pub locked_balance: i64,
+    pub yield_deposited: i64,
+    pub yield_accrued: i64,
+}
+
+/// Rows written by one [`run_once`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SnapshotRun {
+    pub hourly: u64,
+    pub daily: u64,
+    pub pruned: u64,
+}
+
+/// Writes every missing hourly snapshot within the retention window and every missing daily
+/// snapshot within `backfill_days`, then prunes hourly rows past retention. Nothing is
+/// backfilled from before the first ledger entry.
+pub async fn run_once(
+    pool: &PgPool,
+    now: OffsetDateTime,
+    hourly_retention_days: i64,
+    backfill_days: i64,
+) -> Result<SnapshotRun, sqlx::Error> {
+    let mut run = SnapshotRun::default();
+    let Some(first_entry) = db::ledger_first_entry_at(pool).await? else {
+        return Ok(run);
+    };
+    let floor = first_entry.max(now - Duration::days(backfill_days));
+    let hourly_floor = floor.max(now - Duration::days(hourly_retention_days));
+
+    let hourly = Granularity::Hourly;
+    let last = db::balance_snapshot_last_period(pool, hourly.as_str()).await?;
+    for start in missing_periods(hourly, last, hourly_floor, now) {
+        run.hourly +=
+            db::balance_snapshot_from_ledger(pool, hourly.as_str(), start, start + hourly.period())
+                .await?;
+    }
+
+    let daily = Granularity::Daily;
+    let last = db::balance_snapshot_last_period(pool, daily.as_str()).await?;
+    for start in missing_periods(daily, last, floor, now) {
+        // The closing hourly snapshot is the day's balance; vaults without one (outside the
+        // hourly window) are computed from the ledger
+        run.daily += db::balance_snapshot_rollup_daily(pool, start).await?;
+        run.daily +=
+            db::balance_snapshot_from_ledger(pool, daily.as_str(), start, start + daily.period())
+                .await?;
+    }
+
+    let cutoff = daily.truncate(now - Duration::days(hourly_retention_days));
+    run.pruned = db::balance_snapshot_prune_hourly(pool, cutoff).await?;
+    Ok(run)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::{Date, Month};
+
+    fn at(day: u8, hour: u8, minute: u8) -> OffsetDateTime {
+        Date::from_calendar_date(2024, Month::March, day)
+            .unwrap()
+            .with_hms(hour, minute, 0)
+            .unwrap()
+            .assume_utc()
+    }
+
+    #[test]
+    fn test_truncate() {
+        let t = at(5, 17, 42);
+        assert_eq!(Granularity::Hourly.truncate(t), at(5, 17, 0));
+        assert_eq!(Granularity::Daily.truncate(t), at(5, 0, 0));
+        // Other offsets are truncated in UTC
+        assert_eq!(
+            Granularity::Daily
+                .truncate(at(6, 1, 0).replace_offset(UtcOffset::from_hms(2, 0, 0).unwrap())),
+            at(5, 0, 0)
+        );
+        for g in Granularity::ALL {
+            assert_eq!(Granularity::parse(g.as_str()), Some(g));
+        }
+    }
+
+    #[test]
+    fn test_missing_periods_resumes_after_last() {
+        let now = at(5, 17, 42);
+        let floor = at(1, 0, 0);
+        let periods = missing_periods(Granularity::Hourly, Some(at(5, 14, 0)), floor, now);
+        // 15:00 and 16:00 are closed; 17:00 is still open
+        assert_eq!(periods, vec![at(5, 15, 0), at(5, 16, 0)]);
+        assert!(missing_periods(Granularity::Hourly, Some(at(5, 16, 0)), floor, now).is_empty());
+    }
+
+    #[test]
+    fn test_missing_periods_backfills_from_floor() {
+        let now = at(5, 17, 42);
+        let floor = at(2, 9, 30);
+        let days = missing_periods(Granularity::Daily, None, floor, now);
+        assert_eq!(days, vec![at(2, 0, 0), at(3, 0, 0), at(4, 0, 0)]);
+        // A last period older than the floor does not reach back past it
+        let days = missing_periods(
+            Granularity::Daily,
+            Some(at(1, 0, 0) - Duration::days(60)),
+            floor,
+            now,
+        );
+        assert_eq!(days.len(), 3);
+    }
+}
@@ -789,6 +789,18 @@ pub fn build_compute_budget_instructions(units: u32, micro_lamports: u64) -> Vec
     ]
 }
 
+/// SPL Memo program (v2).
+pub const MEMO_PROGRAM_ID: Pubkey =
+    solana_sdk::pubkey!("MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr");
+
+pub fn build_instruction_memo(signer: &Pubkey, memo: &[u8]) -> Instruction {
+    Instruction {
+        program_id: MEMO_PROGRAM_ID,
+        accounts: vec![AccountMeta::new_readonly(*signer, true)],
+        data: memo.to_vec(),
+    }
+}
+
 pub fn load_deployer_keypair(path: &str) -> AppResult<Arc<Keypair>> {
     // Prefer env-based secret if provided (DEPLOYER_KEYPAIR_BASE64)
     if let Ok(b64) = std::env::var("DEPLOYER_KEYPAIR_BASE64") {
@@ -902,6 +914,18 @@ mod tests {
         assert_eq!(ixs[1].program_id, solana_sdk::compute_budget::id());
     }
 
+    #[test]
+    fn test_memo_instruction() {
+        let signer = Pubkey::new_unique();
+        let ix = build_instruction_memo(&signer, b"cvms-por:1:abcd");
+        assert_eq!(
+            ix.program_id.to_string(),
+            "MemoSq4gqABAXKb96qnH8TysNcWxMyWCqXgDLGmfcHr"
+        );
+        assert_eq!(ix.accounts, vec![AccountMeta::new_readonly(signer, true)]);
+        assert_eq!(ix.data, b"cvms-por:1:abcd");
+    }
+
     #[test]
     fn test_initialize_vault_instruction_layout() {
         let program_id = Pubkey::new_unique();
@@ -1,4 +1,10 @@
-use crate::{api::AppState, db, notify::Notifier, solana_client::get_token_balance};
+use crate::{
+    api::AppState,
+    events::{BalanceUpdate, VaultEvent},
+    notify::Notifier,
+    repo::Vault,
+    solana_client::get_token_balance,
+};
 use std::str::FromStr;
 use tracing::{info, warn};
 
@@ -10,9 +16,14 @@ pub async fn run_balance_monitor(
     let mut last_balances: std::collections::HashMap<String, u64> = std::collections::HashMap::new();
     
     loop {
-        match db::list_vaults(&state.pool).await {
+        match state.repos.vaults.list().await {
             Ok(vaults) => {
-                for (owner, token_account_opt, _db_balance) in vaults {
+                for Vault {
+                    owner,
+                    token_account: token_account_opt,
+                    ..
+                } in vaults
+                {
                     if let Some(token_account) = token_account_opt {
                         if let Ok(pk) = solana_sdk::pubkey::Pubkey::from_str(&token_account) {
                             match get_token_balance(&state.sol, &pk).await {
@@ -28,40 +39,20 @@ pub async fn run_balance_monitor(
                                                 delta = delta,
                                                 "Balance change detected"
                                             );
+                                            state.cache.invalidate_balance(&owner).await;
                                             
                                             // Notify via WebSocket
-                                            let _ = notifier.vault_balance_tx.send(
-                                                serde_json::json!({
-                                                    "owner": owner,
-                                                    "balance": chain_balance,
-                                                    "previous_balance": *last_balance,
-                                                    "delta": delta,
-                                                    "timestamp": time::OffsetDateTime::now_utc(),
-                                                })
-                                                .to_string(),
+                                            notifier.publish_for(
+                                                &owner,
+                                                VaultEvent::BalanceUpdate(BalanceUpdate {
+                                                    balance: chain_balance,
+                                                    previous_balance: Some(*last_balance),
+                                                    delta: Some(delta),
+                                                }),
                                             );
                                         }
                                     }
                                     last_balances.insert(owner.clone(), chain_balance);
-                                    
-                                    // Proactive low balance alert
-                                    let available = db::get_locked_balance(&state.pool, &owner)
-                                        .await
-                                        .map(|locked| chain_balance as i64 - locked)
-                                        .unwrap_or(chain_balance as i64);
-                                    
-                                    if available < state.cfg.low_balance_threshold && available > 0 {
-                                        let _ = notifier.security_tx.send(
-                                            serde_json::json!({
-                                                "type": "low_balance_alert",
-                                                "owner": owner,
-                                                "available_balance": available,
-                                                "threshold": state.cfg.low_balance_threshold,
-                                                "timestamp": time::OffsetDateTime::now_utc(),
-                                            })
-                                            .to_string(),
-                                        );
-                                    }
                                 }
                                 Err(e) => {
                                     warn!(owner = %owner, error = %e, "Failed to get balance");
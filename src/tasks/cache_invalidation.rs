@@ -0,0 +1,50 @@
+use crate::{
+    api::AppState,
+    cache::{invalidation_channel, Invalidation},
+};
+use futures::StreamExt;
+use tracing::{info, warn};
+
+/// Evicts keys invalidated by other replicas from this replica's local cache tier.
+/// Invalidations sent while disconnected are lost, so the local tier is cleared on every
+/// (re)subscribe.
+pub async fn run_cache_invalidation_listener(state: AppState) {
+    if state.cfg.redis_url.is_empty() {
+        return;
+    }
+    let channel = invalidation_channel(state.cache.namespace());
+    loop {
+        match subscribe(&state.cfg.redis_url, &channel).await {
+            Ok(mut pubsub) => {
+                state.cache.clear_local();
+                info!(%channel, "cache invalidation listener subscribed");
+                let mut messages = pubsub.on_message();
+                while let Some(msg) = messages.next().await {
+                    let payload: String = match msg.get_payload() {
+                        Ok(p) => p,
+                        Err(e) => {
+                            warn!("invalid cache invalidation payload: {e}");
+                            continue;
+                        }
+                    };
+                    match serde_json::from_str::<Invalidation>(&payload) {
+                        Ok(invalidation) => {
+                            state.cache.apply_invalidation(&invalidation);
+                        }
+                        Err(e) => warn!("undecodable cache invalidation: {e}"),
+                    }
+                }
+                warn!("cache invalidation subscription closed");
+            }
+            Err(e) => warn!("cache invalidation subscribe error: {e}"),
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+    }
+}
+
+async fn subscribe(redis_url: &str, channel: &str) -> redis::RedisResult<redis::aio::PubSub> {
+    let client = redis::Client::open(redis_url)?;
+    let mut pubsub = client.get_async_connection().await?.into_pubsub();
+    pubsub.subscribe(channel).await?;
+    Ok(pubsub)
+}
@@ -0,0 +1,164 @@
+use crate::{
+    api::AppState,
+    db,
+    email::{self, EmailKind, Mailer},
+    events::{EventEnvelope, Severity, TimelockStatus, Topic, VaultEvent},
+    webhooks,
+};
+use std::time::Duration;
+use tracing::{info, warn};
+
+const DELIVERY_BATCH: i64 = 50;
+const MAX_ATTEMPTS: i32 = 5;
+
+/// Queues emails for owner events and sends due emails over SMTP. Multisig emails are
+/// queued directly by the proposal routes. Does nothing unless `SMTP_HOST` is set.
+pub async fn run_email_dispatcher(state: AppState) {
+    let mailer = match Mailer::from_config(&state.cfg) {
+        Ok(Some(mailer)) => mailer,
+        Ok(None) => {
+            info!("email notifications disabled (SMTP_HOST is empty)");
+            return;
+        }
+        Err(e) => {
+            warn!("email notifications disabled: {e}");
+            return;
+        }
+    };
+
+    let listener_state = state.clone();
+    tokio::spawn(async move {
+        let mut subscription = listener_state.notifier.subscribe_from(
+            &[Topic::TimelockEvent, Topic::SecurityAlert],
+            None,
+            None,
+        );
+        while let Some(envelope) = subscription.next().await {
+            if let Err(e) = queue_for_event(&listener_state, &envelope).await {
+                warn!("email queue error: {e}");
+            }
+        }
+    });
+
+    loop {
+        if let Err(e) = send_due(&state, &mailer).await {
+            warn!("email delivery error: {e}");
+        }
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+}
+
+/// Email kind, template context and dedup key for an event, if it is one owners get mail for.
+fn email_for(envelope: &EventEnvelope) -> Option<(EmailKind, serde_json::Value, String)> {
+    let owner = envelope.owner.as_deref()?;
+    match &envelope.event {
+        VaultEvent::Timelock(timelock) => {
+            let kind = match timelock.status {
+                TimelockStatus::DueSoon => EmailKind::TimelockDueSoon,
+                TimelockStatus::Available => EmailKind::TimelockAvailable,
+                TimelockStatus::Scheduled => return None,
+            };
+            // The timelock cron re-publishes while an item stays due; mail it once
+            let dedup = format!(
+                "{owner}:{}:{}",
+                timelock.unlock_at.unix_timestamp(),
+                timelock.amount
+            );
+            Some((kind, serde_json::to_value(timelock).ok()?, dedup))
+        }
+        VaultEvent::SecurityAlert(alert) if alert.kind == "low_balance" => {
+            // At most one low-balance email per owner per hour
+            let hour = envelope.timestamp.unix_timestamp() / 3600;
+            Some((
+                EmailKind::LowBalance,
+                alert.details.clone(),
+                format!("{owner}:{hour}"),
+            ))
+        }
+        VaultEvent::SecurityAlert(alert) if alert.severity >= Severity::Warning => Some((
+            EmailKind::SecurityAlert,
+            serde_json::to_value(alert).ok()?,
+            envelope.id.to_string(),
+        )),
+        _ => None,
+    }
+}
+
+async fn queue_for_event(state: &AppState, envelope: &EventEnvelope) -> Result<(), sqlx::Error> {
+    let (Some(owner), Some((kind, context, dedup))) =
+        (envelope.owner.as_deref(), email_for(envelope))
+    else {
+        return Ok(());
+    };
+    email::queue(&state.pool, &state.cfg, owner, kind, &context, &dedup).await?;
+    Ok(())
+}
+
+async fn send_due(state: &AppState, mailer: &Mailer) -> Result<(), sqlx::Error> {
+    for (id, recipient, subject, body, attempts) in
+        db::email_deliveries_due(&state.pool, DELIVERY_BATCH).await?
+    {
+        match mailer.send(&recipient, &subject, body).await {
+            Ok(()) => {
+                state.metrics.emails_sent.inc();
+                db::email_delivery_sent(&state.pool, id).await?;
+            }
+            Err(e) => {
+                state.metrics.email_failures.inc();
+                let attempts = attempts + 1;
+                let delay = (attempts < MAX_ATTEMPTS).then(|| webhooks::backoff_seconds(attempts));
+                if delay.is_none() {
+                    info!(
+                        email_id = id,
+                        attempts, "email delivery failed permanently: {e}"
+                    );
+                }
+                db::email_delivery_retry(&state.pool, id, &e, delay).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::{TimelockEvent, TvlUpdate};
+
+    #[test]
+    fn test_email_for_events() {
+        let timelock = |status| {
+            EventEnvelope::new(VaultEvent::Timelock(TimelockEvent {
+                status,
+                amount: 10,
+                unlock_at: time::OffsetDateTime::UNIX_EPOCH,
+                signature: None,
+            }))
+            .with_owner("alice")
+        };
+        let (kind, context, dedup) = email_for(&timelock(TimelockStatus::DueSoon)).unwrap();
+        assert_eq!(kind, EmailKind::TimelockDueSoon);
+        assert_eq!(context["amount"], 10);
+        assert_eq!(dedup, "alice:0:10");
+        assert!(email_for(&timelock(TimelockStatus::Scheduled)).is_none());
+
+        let alert = |kind, severity| {
+            EventEnvelope::new(VaultEvent::security(kind, severity, serde_json::json!({})))
+                .with_owner("alice")
+        };
+        assert_eq!(
+            email_for(&alert("low_balance", Severity::Warning))
+                .unwrap()
+                .0,
+            EmailKind::LowBalance
+        );
+        assert_eq!(
+            email_for(&alert("twofa_disabled", Severity::Critical))
+                .unwrap()
+                .0,
+            EmailKind::SecurityAlert
+        );
+        assert!(email_for(&alert("ms_proposal", Severity::Info)).is_none());
+        assert!(email_for(&EventEnvelope::new(VaultEvent::Tvl(TvlUpdate { tvl: 1 }))).is_none());
+    }
+}
@@ -1,4 +1,10 @@
-use crate::{api::AppState, db, notify::Notifier};
+use crate::{
+    api::AppState,
+    db,
+    events::{BalanceUpdate, EventEnvelope, TxEvent, VaultEvent},
+    ledger::{self, EntryKind},
+    notify::Notifier,
+};
 use bs58;
 use futures::StreamExt;
 use solana_client::nonblocking::pubsub_client::PubsubClient;
@@ -40,39 +46,46 @@ pub async fn run_event_indexer(state: AppState, notifier: Arc<Notifier>) {
                             let kind = infer_event_kind(&logs.value.logs);
                             match parse_and_update(&state, &sig, &kind).await {
                                 Ok((owner, amount_opt)) => {
-                                    match db::insert_transaction(
-                                        &state.pool,
-                                        &owner,
-                                        &sig,
-                                        amount_opt.map(|a| a as i64),
-                                        &kind,
-                                    )
-                                    .await
+                                    if logs.value.err.is_none() {
+                                        if let Err(e) =
+                                            record_ledger(&state, &kind, &sig, &owner, amount_opt)
+                                                .await
+                                        {
+                                            warn!("failed to journal {sig}: {e}");
+                                        }
+                                    }
+                                    match state
+                                        .repos
+                                        .txs
+                                        .insert(
+                                            &owner,
+                                            &sig,
+                                            amount_opt.map(|a| a as i64),
+                                            &kind,
+                                            "pending",
+                                        )
+                                        .await
                                     {
                                         Ok(_) => {
-                                            let payload = serde_json::json!({
-                                                "signature": sig,
-                                                "kind": kind,
-                                                "owner": owner,
-                                                "amount": amount_opt
-                                            });
-                                            let message = payload.to_string();
-                                            let _ = match payload
-                                                .get("kind")
-                                                .and_then(|k| k.as_str())
-                                            {
-                                                Some("deposit") => {
-                                                    notifier.deposit_tx.send(message.clone())
-                                                }
-                                                Some("withdraw") => {
-                                                    notifier.withdraw_tx.send(message.clone())
-                                                }
-                                                Some("lock") => {
-                                                    notifier.lock_tx.send(message.clone())
-                                                }
-                                                Some("unlock") => notifier.unlock_tx.send(message),
-                                                _ => Ok(0),
+                                            state.cache.invalidate_tvl().await;
+                                            let tx = TxEvent {
+                                                amount: amount_opt,
+                                                signature: sig.clone(),
                                             };
+                                            let event = match kind.as_str() {
+                                                "deposit" => Some(VaultEvent::Deposit(tx)),
+                                                "withdraw" => Some(VaultEvent::Withdraw(tx)),
+                                                "lock" => Some(VaultEvent::Lock(tx)),
+                                                "unlock" => Some(VaultEvent::Unlock(tx)),
+                                                _ => None,
+                                            };
+                                            if let Some(event) = event {
+                                                notifier.publish(
+                                                    EventEnvelope::new(event)
+                                                        .with_owner(&owner)
+                                                        .with_correlation_id(sig.clone()),
+                                                );
+                                            }
                                         }
                                         Err(e) => warn!("failed to persist transaction {sig}: {e}"),
                                     }
@@ -92,22 +105,68 @@ pub async fn run_event_indexer(state: AppState, notifier: Arc<Notifier>) {
 }
 
 fn infer_event_kind(logs: &[String]) -> String {
-    let joined = logs.join("\n").to_lowercase();
-    if joined.contains("deposit") {
-        return "deposit".to_string();
-    }
-    if joined.contains("withdraw") {
-        return "withdraw".to_string();
-    }
-    if joined.contains("lock") {
-        return "lock".to_string();
-    }
-    if joined.contains("unlock") {
-        return "unlock".to_string();
+    // Anchor logs instruction names in CamelCase ("Instruction: YieldDeposit")
+    let joined = logs.join("\n").to_lowercase().replace('_', "");
+    // Most specific first: "yielddeposit" contains "deposit", "unlock" contains "lock", and
+    // withdraw settings, requests and timelocks move no funds
+    for (needle, kind) in [
+        ("setwithdraw", "config"),
+        ("timelock", "timelock"),
+        ("withdrawwhitelist", "config"),
+        ("requestwithdraw", "request_withdraw"),
+        ("compound", "compound"),
+        ("yielddeposit", "yield_deposit"),
+        ("yieldwithdraw", "yield_withdraw"),
+        ("deposit", "deposit"),
+        ("withdraw", "withdraw"),
+        ("unlock", "unlock"),
+        ("lock", "lock"),
+    ] {
+        if joined.contains(needle) {
+            return kind.to_string();
+        }
     }
     "unknown".to_string()
 }
 
+/// Journals a confirmed event. Kinds without a ledger movement and events without an amount
+/// are ignored.
+async fn record_ledger(
+    state: &AppState,
+    kind: &str,
+    signature: &str,
+    owner: &str,
+    amount: Option<u64>,
+) -> Result<bool, sqlx::Error> {
+    let Some(amount) = amount.map(|a| a as i64) else {
+        return Ok(false);
+    };
+    let entry = match kind {
+        "withdraw" => {
+            return ledger::record_withdraw_confirmed(&state.pool, signature, owner, amount).await
+        }
+        "deposit" => EntryKind::Deposit,
+        "lock" => EntryKind::Lock,
+        "unlock" => EntryKind::Unlock,
+        "yield_deposit" => EntryKind::YieldDeposit,
+        "yield_withdraw" => EntryKind::YieldWithdraw,
+        "compound" => EntryKind::YieldAccrual,
+        _ => return Ok(false),
+    };
+    let written = ledger::record(&state.pool, entry, signature, owner, amount).await?;
+    // The vault snapshot mirrors the on-chain yield balances for reconciliation
+    let (deposited, accrued) = match entry {
+        EntryKind::YieldDeposit => (amount, 0),
+        EntryKind::YieldWithdraw => (-amount, 0),
+        EntryKind::YieldAccrual => (0, amount),
+        _ => (0, 0),
+    };
+    if written && (deposited != 0 || accrued != 0) {
+        db::vault_add_yield(&state.pool, owner, deposited, accrued).await?;
+    }
+    Ok(written)
+}
+
 async fn parse_and_update(
     state: &AppState,
     signature_str: &str,
@@ -152,8 +211,9 @@ async fn parse_and_update(
                 let data_bytes = bs58::decode(&ix.data)
                     .into_vec()
                     .map_err(|e| format!("data decode: {e}"))?;
-                let amount_opt = if data_bytes.len() >= 9 {
-                    Some(u64::from_le_bytes(data_bytes[1..9].try_into().unwrap()))
+                // 8-byte Anchor discriminator, then the u64 amount
+                let amount_opt = if data_bytes.len() >= 16 {
+                    Some(u64::from_le_bytes(data_bytes[8..16].try_into().unwrap()))
                 } else {
                     None
                 };
@@ -164,10 +224,13 @@ async fn parse_and_update(
         _ => ("unknown".to_string(), None),
     };
 
-    let (token_account_opt, prev_balance) = db::get_vault(&state.pool, &owner)
+    let (token_account_opt, prev_balance) = state
+        .repos
+        .vaults
+        .get(&owner)
         .await
         .map_err(|e| format!("db get_vault: {e}"))?
-        .unwrap_or((None, 0));
+        .map_or((None, 0), |v| (v.token_account, v.total_balance));
     let (dep_delta, wd_delta) = match kind {
         "deposit" => (amount_opt.unwrap_or(0) as i64, 0i64),
         "withdraw" => (0i64, amount_opt.unwrap_or(0) as i64),
@@ -190,10 +253,45 @@ async fn parse_and_update(
         fallback_balance
     };
 
-    let _ = db::update_vault_snapshot(&state.pool, &owner, new_balance, dep_delta, wd_delta).await;
     let _ = state
-        .notifier
-        .vault_balance_tx
-        .send(serde_json::json!({ "owner": owner, "balance": new_balance }).to_string());
+        .repos
+        .vaults
+        .update_snapshot(&owner, new_balance, dep_delta, wd_delta)
+        .await;
+    state.cache.invalidate_balance(&owner).await;
+    state.notifier.publish(
+        EventEnvelope::new(VaultEvent::BalanceUpdate(BalanceUpdate {
+            balance: new_balance.max(0) as u64,
+            previous_balance: None,
+            delta: None,
+        }))
+        .with_owner(&owner)
+        .with_correlation_id(signature_str),
+    );
     Ok((owner, amount_opt))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind_of(line: &str) -> String {
+        infer_event_kind(&[format!("Program log: Instruction: {line}")])
+    }
+
+    #[test]
+    fn test_infer_event_kind() {
+        assert_eq!(kind_of("Deposit"), "deposit");
+        assert_eq!(kind_of("Withdraw"), "withdraw");
+        assert_eq!(kind_of("EmergencyWithdraw"), "withdraw");
+        assert_eq!(kind_of("LockCollateral"), "lock");
+        assert_eq!(kind_of("UnlockCollateral"), "unlock");
+        assert_eq!(kind_of("YieldDeposit"), "yield_deposit");
+        assert_eq!(kind_of("YieldWithdraw"), "yield_withdraw");
+        assert_eq!(kind_of("CompoundYield"), "compound");
+        assert_eq!(kind_of("RequestWithdraw"), "request_withdraw");
+        assert_eq!(kind_of("SetWithdrawMinDelay"), "config");
+        assert_eq!(kind_of("ScheduleTimelock"), "timelock");
+        assert_eq!(kind_of("InitializeVault"), "unknown");
+    }
+}
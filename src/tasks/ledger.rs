@@ -0,0 +1,71 @@
+use crate::{
+    alerts::AlertManager,
+    api::AppState,
+    events::Severity,
+    ledger::{self, Violation},
+};
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+
+/// Alert kind raised per owner while the ledger disagrees with itself or the chain.
+pub const ALERT_KIND: &str = "ledger_invariant";
+
+pub async fn run_ledger_checker(state: AppState) {
+    let interval = std::time::Duration::from_secs(state.cfg.ledger_check_interval_seconds);
+    loop {
+        match check_once(&state).await {
+            Ok(violations) if violations.is_empty() => {}
+            Ok(violations) => warn!(count = violations.len(), "ledger invariant violations"),
+            Err(e) => warn!("ledger check error: {e}"),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Settles due withdrawals, then checks the invariants, updating the `ledger_violations`
+/// gauge and the per-owner alerts.
+pub async fn check_once(state: &AppState) -> Result<Vec<Violation>, sqlx::Error> {
+    let settled = ledger::settle_withdrawals(
+        &state.pool,
+        &state.sol,
+        state.cfg.ledger_settle_after_seconds,
+    )
+    .await?;
+    if settled > 0 {
+        info!(settled, "settled in-transit withdrawals");
+        // Failed withdrawals return to available
+        state.cache.invalidate_tvl().await;
+    }
+
+    let violations =
+        ledger::check_invariants(&state.pool, &state.sol, state.cfg.reconciliation_threshold)
+            .await?;
+    for kind in ["unbalanced", "negative_balance", "custody_mismatch"] {
+        let count = violations.iter().filter(|v| v.kind() == kind).count();
+        state
+            .metrics
+            .ledger_violations
+            .with_label_values(&[kind])
+            .set(count as f64);
+    }
+
+    let mut by_owner: BTreeMap<String, Vec<&Violation>> = BTreeMap::new();
+    for violation in &violations {
+        by_owner
+            .entry(violation.owner().to_string())
+            .or_default()
+            .push(violation);
+    }
+    let active = by_owner
+        .into_iter()
+        .map(|(owner, found)| (owner, serde_json::json!({ "violations": found })))
+        .collect();
+    AlertManager::new(
+        state.pool.clone(),
+        state.notifier.clone(),
+        state.cfg.alert_renotify_seconds,
+    )
+    .reconcile(ALERT_KIND, Severity::Critical, active)
+    .await?;
+    Ok(violations)
+}
@@ -1,6 +1,15 @@
 pub mod balance_monitor;
+pub mod cache_invalidation;
+pub mod email;
 pub mod event_indexer;
+pub mod ledger;
 pub mod monitor;
+pub mod nonces;
+pub mod outbox;
+pub mod proof_of_reserves;
 pub mod reconciliation;
+pub mod snapshots;
 pub mod timelocks;
+pub mod twofa;
+pub mod webhooks;
 pub mod yield_tasks;
@@ -1,83 +1,86 @@
-use crate::{api::AppState, notify::Notifier};
-use sqlx::Row;
+use crate::{
+    alerts::AlertManager,
+    anomaly::{self, RulesEngine},
+    api::AppState,
+    db,
+    events::{AnalyticsSnapshot, EventEnvelope, Severity, TvlUpdate, VaultEvent},
+    notify::Notifier,
+};
+use tracing::warn;
 
 pub async fn run_monitor(state: AppState, notifier: std::sync::Arc<Notifier>) {
     let low_threshold = state.cfg.low_balance_threshold;
+    let alerts = AlertManager::new(
+        state.pool.clone(),
+        notifier.clone(),
+        state.cfg.alert_renotify_seconds,
+    );
+    let rules = RulesEngine::new(
+        state.pool.clone(),
+        notifier.clone(),
+        state.cfg.alert_renotify_seconds,
+    );
+    match anomaly::configured_rules(&state.cfg.anomaly_rules) {
+        Ok(configured) => {
+            if let Err(e) = anomaly::seed_rules(&state.pool, &configured).await {
+                warn!("seeding anomaly rules: {e}");
+            }
+        }
+        Err(e) => warn!("ignoring ANOMALY_RULES: {e}"),
+    }
     loop {
         // TVL compute and broadcast
-        let tvl_row = sqlx::query_scalar::<_, i64>(
-			"SELECT COALESCE(SUM(CASE WHEN kind = 'deposit' THEN amount ELSE -amount END), 0) AS tvl FROM transactions"
-		).fetch_one(&state.pool).await;
-        if let Ok(tvl) = tvl_row {
-            let _ = notifier
-                .tvl_tx
-                .send(serde_json::json!({ "tvl": tvl }).to_string());
+        if let Ok(tvl) = state.repos.ledger.tvl().await {
+            let tvl = tvl.total();
+            state.metrics.total_value_locked.set(tvl as f64);
+            notifier.publish(EventEnvelope::new(VaultEvent::Tvl(TvlUpdate { tvl })));
         }
 
         // Analytics: vault count, users, 24h volume
-        let vault_count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*)::BIGINT FROM vaults")
-            .fetch_one(&state.pool)
+        let vaults = state.repos.vaults.list().await;
+        let vault_count = vaults.as_ref().map_or(0, |v| v.len() as i64);
+        let user_count = state.repos.txs.owner_count().await.unwrap_or(0);
+        let day_ago = time::OffsetDateTime::now_utc() - time::Duration::days(1);
+        let volume_24h = state.repos.txs.volume_since(day_ago).await.unwrap_or(0);
+        let avg_apy = db::average_protocol_apy_since(&state.pool, day_ago)
             .await
-            .unwrap_or(0);
-        let user_count =
-            sqlx::query_scalar::<_, i64>("SELECT COUNT(DISTINCT owner)::BIGINT FROM transactions")
-                .fetch_one(&state.pool)
-                .await
-                .unwrap_or(0);
-        let volume_24h = sqlx::query_scalar::<_, i64>(
-			"SELECT COALESCE(SUM(ABS(amount)), 0)::BIGINT FROM transactions WHERE created_at > NOW() - INTERVAL '24 hours'"
-		).fetch_one(&state.pool).await.unwrap_or(0);
-        let _ = notifier.analytics_tx.send(serde_json::json!({
-			"vaults": vault_count,
-			"users": user_count,
-			"volume_24h": volume_24h,
-			"avg_apy": sqlx::query_scalar::<_, f64>("SELECT COALESCE(AVG(apy), 0.0) FROM protocol_apy WHERE recorded_at > NOW() - INTERVAL '1 day'")
-				.fetch_one(&state.pool).await.unwrap_or(0.0),
-		}).to_string());
+            .unwrap_or(0.0);
+        notifier.publish(EventEnvelope::new(VaultEvent::Analytics(
+            AnalyticsSnapshot {
+                vaults: vault_count,
+                users: user_count,
+                volume_24h,
+                avg_apy,
+            },
+        )));
 
         // Low-balance alerts
         if low_threshold > 0 {
-            let rows = sqlx::query("SELECT owner, total_balance, locked_balance FROM vaults")
-                .fetch_all(&state.pool)
-                .await;
-            if let Ok(rows) = rows {
-                for row in rows {
-                    let owner: String = row.try_get("owner").unwrap_or_default();
-                    let total_balance: i64 = row.try_get("total_balance").unwrap_or(0);
-                    let locked_balance: i64 = row.try_get("locked_balance").unwrap_or(0);
-                    let available = total_balance - locked_balance;
-                    if available < low_threshold {
-                        let _ = notifier.security_tx.send(
-                            serde_json::json!({
-                                "owner": owner,
-                                "type": "low_balance",
-                                "available": available,
-                                "threshold": low_threshold,
-                            })
-                            .to_string(),
-                        );
-                    }
+            if let Ok(vaults) = vaults {
+                let low = vaults
+                    .into_iter()
+                    .filter_map(|vault| {
+                        let available = vault.total_balance - vault.locked_balance;
+                        (available < low_threshold).then(|| {
+                            (
+                                vault.owner,
+                                serde_json::json!({ "available": available, "threshold": low_threshold }),
+                            )
+                        })
+                    })
+                    .collect();
+                if let Err(e) = alerts
+                    .reconcile("low_balance", Severity::Warning, low)
+                    .await
+                {
+                    warn!("low balance alerts: {e}");
                 }
             }
         }
 
-        // Unusual activity: > N tx in last minute
-        let rows = sqlx::query(
-			"SELECT owner, COUNT(*)::BIGINT as cnt FROM transactions WHERE created_at > NOW() - INTERVAL '60 seconds' GROUP BY owner HAVING COUNT(*) > 10"
-		).fetch_all(&state.pool).await;
-        if let Ok(rows) = rows {
-            for row in rows {
-                let owner: String = row.try_get("owner").unwrap_or_default();
-                let cnt: i64 = row.try_get("cnt").unwrap_or(0);
-                let _ = notifier.security_tx.send(
-                    serde_json::json!({
-                        "owner": owner,
-                        "type": "unusual_activity",
-                        "count": cnt,
-                    })
-                    .to_string(),
-                );
-            }
+        // Anomaly rules (includes the unusual-activity transaction rate)
+        if let Err(e) = rules.run_once().await {
+            warn!("anomaly rules: {e}");
         }
 
         tokio::time::sleep(std::time::Duration::from_secs(30)).await;
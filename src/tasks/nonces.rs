@@ -0,0 +1,19 @@
+use crate::{api::AppState, db};
+use tracing::{info, warn};
+
+pub async fn run_nonce_cleanup(state: AppState) {
+    let interval = std::time::Duration::from_secs(state.cfg.nonce_cleanup_interval_seconds);
+    loop {
+        match db::prune_nonces(&state.pool).await {
+            Ok(0) => {}
+            Ok(n) => info!(pruned = n, "pruned expired/used nonces"),
+            Err(e) => warn!("nonce cleanup error: {e}"),
+        }
+        match db::prune_auth_failures(&state.pool).await {
+            Ok(0) => {}
+            Ok(n) => info!(pruned = n, "pruned old auth failures"),
+            Err(e) => warn!("auth failure cleanup error: {e}"),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
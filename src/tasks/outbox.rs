@@ -0,0 +1,15 @@
+use crate::{api::AppState, db};
+use tracing::{info, warn};
+
+/// Drops outbox events older than `event_retention_hours`; clients cannot resume past that.
+pub async fn run_outbox_pruner(state: AppState) {
+    let interval = std::time::Duration::from_secs(3600);
+    loop {
+        match db::outbox_prune(&state.pool, state.cfg.event_retention_hours).await {
+            Ok(0) => {}
+            Ok(n) => info!(pruned = n, "pruned event outbox"),
+            Err(e) => warn!("event outbox prune error: {e}"),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{api::AppState, db, proof_of_reserves, solana_client::load_deployer_keypair};
+use tracing::{info, warn};
+
+pub async fn run_proof_of_reserves(state: AppState) {
+    let interval = std::time::Duration::from_secs(state.cfg.proof_of_reserves_interval_seconds);
+    loop {
+        match proof_of_reserves::build_snapshot(&state.pool).await {
+            Ok(Some(snapshot)) => {
+                info!(
+                    id = snapshot.id,
+                    root = %snapshot.root,
+                    leaves = snapshot.leaf_count,
+                    "proof of reserves snapshot built"
+                );
+                if state.cfg.proof_of_reserves_anchor {
+                    anchor(&state, &snapshot).await;
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!("proof of reserves error: {e}"),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Best effort: an unanchored snapshot is still served.
+async fn anchor(state: &AppState, snapshot: &proof_of_reserves::ReserveSnapshot) {
+    let payer = match load_deployer_keypair(&state.cfg.deployer_keypair_path) {
+        Ok(kp) => kp,
+        Err(e) => {
+            warn!("proof of reserves anchor: {e}");
+            return;
+        }
+    };
+    match proof_of_reserves::anchor(&state.sol, &payer, snapshot).await {
+        Ok(sig) => {
+            if let Err(e) =
+                db::reserve_snapshot_set_anchor(&state.pool, snapshot.id, &sig.to_string()).await
+            {
+                warn!("proof of reserves anchor {sig}: {e}");
+            }
+        }
+        Err(e) => warn!("proof of reserves anchor: {e}"),
+    }
+}
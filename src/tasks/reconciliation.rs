@@ -1,51 +1,113 @@
-use crate::{api::AppState, db, notify::Notifier, solana_client::get_token_balance};
+use crate::{
+    api::AppState,
+    db,
+    events::{ReconciliationMismatch, VaultEvent},
+    notify::Notifier,
+    reconciliation::{self, Outcome, VaultBalances},
+    solana_client::fetch_vault_yield_info,
+};
+use solana_sdk::pubkey::Pubkey;
 use std::str::FromStr;
 use tracing::warn;
 
 pub async fn run_reconciliation(state: AppState, notifier: std::sync::Arc<Notifier>) {
-    let threshold = state.cfg.reconciliation_threshold;
+    let program = match Pubkey::from_str(&state.cfg.program_id) {
+        Ok(p) => p,
+        Err(_) => {
+            warn!("program_id not configured; skipping reconciliation");
+            return;
+        }
+    };
     loop {
-        match db::list_vaults(&state.pool).await {
-            Ok(vaults) => {
-                for (owner, token_account_opt, db_balance) in vaults {
-                    if let Some(token_account) = token_account_opt.clone() {
-                        if let Ok(pk) = solana_sdk::pubkey::Pubkey::from_str(&token_account) {
-                            match get_token_balance(&state.sol, &pk).await {
-                                Ok(chain_balance) => {
-                                    let discrepancy = (chain_balance as i64) - db_balance;
-                                    if discrepancy.abs() > threshold {
-                                        // Update metrics
-                                        state.metrics.reconciliation_discrepancies.inc();
-                                        
-                                        let _ = db::insert_reconciliation_log(
-                                            &state.pool,
-                                            &owner,
-                                            Some(&token_account),
-                                            db_balance,
-                                            chain_balance as i64,
-                                            discrepancy,
-                                            threshold,
-                                        )
-                                        .await;
-                                        let payload = serde_json::json!({
-                                            "owner": owner,
-                                            "token_account": token_account,
-                                            "db_balance": db_balance,
-                                            "chain_balance": chain_balance,
-                                            "discrepancy": discrepancy,
-                                            "threshold": threshold,
-                                        });
-                                        let _ = notifier.vault_balance_tx.send(payload.to_string());
-                                    }
-                                }
-                                Err(e) => warn!("recon get balance error: {e}"),
-                            }
-                        }
-                    }
-                }
-            }
-            Err(e) => warn!("recon list_vaults error: {e}"),
+        if let Err(e) = reconcile_once(&state, &notifier, &program).await {
+            warn!("recon error: {e}");
         }
         tokio::time::sleep(std::time::Duration::from_secs(60)).await;
     }
 }
+
+/// Compares every vault snapshot with its decoded on-chain account, skipping vaults that
+/// cannot be read or reconciled. In-sync and healed vaults record their chain state for
+/// proof of reserves.
+pub async fn reconcile_once(
+    state: &AppState,
+    notifier: &Notifier,
+    program: &Pubkey,
+) -> Result<(), sqlx::Error> {
+    let threshold = state.cfg.reconciliation_threshold;
+    // Every account below is read at or after this slot
+    let slot = match state.sol.rpc.get_slot().await {
+        Ok(slot) => Some(slot),
+        Err(e) => {
+            warn!("recon get_slot error: {e}");
+            None
+        }
+    };
+    for row in db::vault_snapshot_balances(&state.pool).await? {
+        let Ok(owner_pk) = Pubkey::from_str(&row.owner) else {
+            continue;
+        };
+        let chain = match fetch_vault_yield_info(&state.sol, &owner_pk, program).await {
+            Ok(vault) => VaultBalances::from(&vault),
+            Err(e) => {
+                warn!("recon vault account for {}: {e}", row.owner);
+                continue;
+            }
+        };
+        let outcome = match reconciliation::reconcile_vault(
+            &state.pool,
+            notifier,
+            &row.owner,
+            &row.balances,
+            &chain,
+            threshold,
+            state.cfg.reconciliation_auto_heal_max,
+        )
+        .await
+        {
+            Ok(outcome) => outcome,
+            Err(e) => {
+                warn!("recon vault {}: {e}", row.owner);
+                continue;
+            }
+        };
+        if let (Some(slot), Outcome::InSync | Outcome::Healed) = (slot, outcome) {
+            if let Err(e) = db::vault_mark_reconciled(&state.pool, &row.owner, &chain, slot).await {
+                warn!("recon mark reconciled {}: {e}", row.owner);
+            }
+        }
+        if outcome == Outcome::InSync {
+            continue;
+        }
+        state.metrics.reconciliation_discrepancies.inc();
+        // The cached balance may come from either side
+        state.cache.invalidate_balance(&row.owner).await;
+
+        let db_balance = row.balances.total_balance;
+        let chain_balance = chain.total_balance;
+        let discrepancy = chain_balance - db_balance;
+        if discrepancy.abs() > threshold {
+            let _ = db::insert_reconciliation_log(
+                &state.pool,
+                &row.owner,
+                row.token_account.as_deref(),
+                db_balance,
+                chain_balance,
+                discrepancy,
+                threshold,
+            )
+            .await;
+            notifier.publish_for(
+                &row.owner,
+                VaultEvent::ReconciliationMismatch(ReconciliationMismatch {
+                    token_account: row.token_account.clone().unwrap_or_default(),
+                    db_balance,
+                    chain_balance: chain_balance as u64,
+                    discrepancy,
+                    threshold,
+                }),
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,26 @@
+use crate::{api::AppState, snapshots};
+use tracing::{info, warn};
+
+pub async fn run_balance_snapshots(state: AppState) {
+    let interval = std::time::Duration::from_secs(state.cfg.balance_snapshot_interval_seconds);
+    loop {
+        match snapshots::run_once(
+            &state.pool,
+            time::OffsetDateTime::now_utc(),
+            state.cfg.balance_snapshot_hourly_retention_days,
+            state.cfg.balance_snapshot_backfill_days,
+        )
+        .await
+        {
+            Ok(run) if run == snapshots::SnapshotRun::default() => {}
+            Ok(run) => info!(
+                hourly = run.hourly,
+                daily = run.daily,
+                pruned = run.pruned,
+                "balance snapshots written"
+            ),
+            Err(e) => warn!("balance snapshot error: {e}"),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
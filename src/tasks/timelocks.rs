@@ -1,36 +1,40 @@
-use crate::{api::AppState, db, notify::Notifier};
+use crate::{
+    api::AppState,
+    events::{TimelockEvent, TimelockStatus, VaultEvent},
+    notify::Notifier,
+};
 use tracing::warn;
 
 pub async fn run_timelock_cron(state: AppState, notifier: std::sync::Arc<Notifier>) {
     loop {
         // Notify items due within next 5 minutes
-        if let Ok(rows) = db::timelock_due_within(&state.pool, 300).await {
-            for (_id, owner, amount, unlock_at) in rows.iter() {
-                let _ = notifier.timelock_tx.send(
-                    serde_json::json!({
-                        "owner": owner,
-                        "amount": amount,
-                        "unlock_at": unlock_at,
-                        "kind": "due_soon",
-                    })
-                    .to_string(),
+        if let Ok(rows) = state.repos.timelocks.due_within(300).await {
+            for t in rows.iter() {
+                notifier.publish_for(
+                    &t.owner,
+                    VaultEvent::Timelock(TimelockEvent {
+                        status: TimelockStatus::DueSoon,
+                        amount: t.amount,
+                        unlock_at: t.unlock_at,
+                        signature: None,
+                    }),
                 );
             }
         }
         // Mark available ones and notify
-        if let Ok(rows) = db::timelock_due_within(&state.pool, 0).await {
-            for (id, owner, amount, unlock_at) in rows.iter() {
-                if let Err(e) = db::timelock_mark_status(&state.pool, *id, "available").await {
+        if let Ok(rows) = state.repos.timelocks.due_within(0).await {
+            for t in rows.iter() {
+                if let Err(e) = state.repos.timelocks.set_status(t.id, "available").await {
                     warn!("timelock_mark_status error: {e}");
                 } else {
-                    let _ = notifier.timelock_tx.send(
-                        serde_json::json!({
-                            "owner": owner,
-                            "amount": amount,
-                            "unlock_at": unlock_at,
-                            "kind": "available",
-                        })
-                        .to_string(),
+                    notifier.publish_for(
+                        &t.owner,
+                        VaultEvent::Timelock(TimelockEvent {
+                            status: TimelockStatus::Available,
+                            amount: t.amount,
+                            unlock_at: t.unlock_at,
+                            signature: None,
+                        }),
                     );
                     // Optional: auto-execute withdraw transaction (best-effort)
                     // This requires an admin payer; omitted here for safety
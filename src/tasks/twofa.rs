@@ -0,0 +1,46 @@
+use crate::{
+    api::AppState,
+    db,
+    events::{Severity, VaultEvent},
+};
+use tracing::{info, warn};
+
+/// Applies admin-initiated 2FA resets once their cooling-off period has elapsed.
+pub async fn run_twofa_reset_worker(state: AppState) {
+    loop {
+        if let Err(e) = apply_due_resets(&state).await {
+            warn!("twofa reset worker error: {e}");
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+    }
+}
+
+/// Applies every due reset. A reset whose second factors cannot be removed stays pending and
+/// is retried on the next run.
+pub async fn apply_due_resets(state: &AppState) -> Result<(), sqlx::Error> {
+    for (id, owner, requested_by) in db::twofa_resets_due(&state.pool).await? {
+        // Re-check status so a cancel racing with this tick wins
+        match db::twofa_reset_apply(&state.pool, id, &owner).await {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                warn!("twofa reset {id} apply error: {e}");
+                continue;
+            }
+        }
+        let details = serde_json::json!({ "reset_id": id, "requested_by": requested_by });
+        let _ = db::insert_audit_log(
+            &state.pool,
+            Some(&owner),
+            "twofa_reset_applied",
+            details.clone(),
+        )
+        .await;
+        state.notifier.publish_for(
+            &owner,
+            VaultEvent::security("twofa_reset_applied", Severity::Critical, details),
+        );
+        info!(reset_id = id, owner = %owner, "applied 2fa reset");
+    }
+    Ok(())
+}
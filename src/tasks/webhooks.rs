@@ -0,0 +1,123 @@
+use crate::{api::AppState, db, security::open_secret, webhooks};
+use std::time::Duration;
+use tracing::{info, warn};
+
+const FANOUT_BATCH: i64 = 500;
+const DELIVERY_BATCH: i64 = 50;
+/// How long a claimed batch stays with this dispatcher: longer than a batch of attempts at the
+/// 10s delivery timeout takes, so only a dead dispatcher's deliveries are taken over.
+const DELIVERY_LEASE_SECONDS: i64 = 15 * 60;
+
+/// Fans persisted events out to matching endpoints, then attempts due deliveries.
+/// Failed attempts back off exponentially; after `webhook_max_attempts` they are dead-lettered.
+pub async fn run_webhook_dispatcher(state: AppState) {
+    loop {
+        if let Err(e) = fan_out(&state).await {
+            warn!("webhook fan-out error: {e}");
+        }
+        if let Err(e) = deliver_due(&state).await {
+            warn!("webhook delivery error: {e}");
+        }
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    }
+}
+
+/// Runs in one transaction under the fan-out lock, so concurrent dispatchers neither enqueue
+/// the same batch twice nor move the cursor backwards.
+async fn fan_out(state: &AppState) -> Result<(), sqlx::Error> {
+    let mut tx = state.pool.begin().await?;
+    if !db::webhook_fanout_try_lock(&mut *tx).await? {
+        return Ok(());
+    }
+    let cursor = match db::webhook_fanout_cursor(&mut *tx).await? {
+        Some(cursor) => cursor,
+        None => {
+            // First run: start from now rather than flooding endpoints with history
+            let cursor = db::outbox_max_id(&mut *tx).await?;
+            db::webhook_set_fanout_cursor(&mut *tx, cursor).await?;
+            return tx.commit().await;
+        }
+    };
+    let events = db::outbox_after_all(&mut *tx, cursor, FANOUT_BATCH).await?;
+    let Some((last, ..)) = events.last() else {
+        return Ok(());
+    };
+    let last = *last;
+    let endpoints = db::webhook_endpoints_active(&mut *tx).await?;
+    for (id, event_id, owner, event_type, mut payload) in events {
+        payload["cursor"] = serde_json::json!(id);
+        for (endpoint_id, endpoint_owner, url, event_types) in &endpoints {
+            if endpoint_owner.is_some() && endpoint_owner != &owner {
+                continue;
+            }
+            if !webhooks::matches_filter(event_types, &event_type) {
+                continue;
+            }
+            db::webhook_delivery_enqueue(
+                &mut *tx,
+                *endpoint_id,
+                url,
+                Some(event_id),
+                &event_type,
+                &payload,
+            )
+            .await?;
+        }
+    }
+    db::webhook_set_fanout_cursor(&mut *tx, last).await?;
+    tx.commit().await
+}
+
+async fn deliver_due(state: &AppState) -> Result<(), sqlx::Error> {
+    for (id, url, secret_enc, event_id, payload, attempts) in
+        db::webhook_deliveries_claim(&state.pool, DELIVERY_BATCH, DELIVERY_LEASE_SECONDS).await?
+    {
+        let secret = match open_secret(&state.cfg.twofa_encryption_key, &secret_enc) {
+            Ok(secret) => secret,
+            Err(e) => {
+                warn!(delivery_id = id, "cannot open webhook secret: {e}");
+                continue;
+            }
+        };
+
+        let start = std::time::Instant::now();
+        let result = webhooks::deliver(
+            state.cfg.webhook_allow_private_targets,
+            &url,
+            &secret,
+            event_id,
+            id,
+            payload.to_string(),
+        )
+        .await;
+        state
+            .metrics
+            .webhook_delivery_duration
+            .observe(start.elapsed().as_secs_f64());
+
+        match result {
+            Ok(()) => {
+                state.metrics.webhook_deliveries.inc();
+                db::webhook_delivery_succeeded(&state.pool, id).await?;
+            }
+            Err(e) => {
+                state.metrics.webhook_delivery_failures.inc();
+                let attempts = attempts + 1;
+                if attempts >= state.cfg.webhook_max_attempts {
+                    state.metrics.webhook_dead_letters.inc();
+                    info!(delivery_id = id, %url, attempts, "webhook delivery dead-lettered: {e}");
+                    db::webhook_delivery_dead_letter(&state.pool, id, &e).await?;
+                } else {
+                    db::webhook_delivery_retry(
+                        &state.pool,
+                        id,
+                        &e,
+                        webhooks::backoff_seconds(attempts),
+                    )
+                    .await?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
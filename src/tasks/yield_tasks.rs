@@ -11,10 +11,10 @@ pub async fn run_yield_scheduler(state: AppState) {
         let _ = db::insert_protocol_apy(&state.pool, "marginfi", marginfi_apy).await;
 
         // Placeholder: iterate vaults and record a compound check event
-        if let Ok(vaults) = db::list_vaults(&state.pool).await {
-            for (owner, _token_acc, _bal) in vaults.into_iter() {
-                let _ =
-                    db::insert_yield_event(&state.pool, &owner, "auto", 0, "compound_check").await;
+        if let Ok(vaults) = state.repos.vaults.list().await {
+            for vault in vaults.into_iter() {
+                let _ = db::insert_yield_event(&state.pool, &vault.owner, "auto", 0, "compound_check")
+                    .await;
             }
         }
 
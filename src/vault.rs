@@ -1,7 +1,7 @@
 use crate::{
     api::AppState,
-    db,
     error::{AppError, AppResult},
+    ledger::{self, EntryKind},
     solana_client::{
         build_compute_budget_instructions, build_instruction_deposit,
         build_instruction_initialize_vault, build_instruction_withdraw, load_deployer_keypair,
@@ -67,13 +67,22 @@ impl VaultManager {
             recent_blockhash,
         );
         let sig = send_transaction_with_retries(&self.state.sol, &tx, 3).await?;
+        let _ = ledger::record(
+            &self.state.pool,
+            EntryKind::WithdrawSubmitted,
+            &sig.to_string(),
+            &owner.to_string(),
+            amount as i64,
+        )
+        .await;
+        self.state.cache.invalidate_tvl().await;
         Ok(sig.to_string())
     }
 
     pub async fn query_balance_by_owner(&self, owner: &str) -> AppResult<u64> {
         // Prefer DB-mapped token account; fallback to interpreting owner as token account
-        if let Some((token_acc_opt, _)) = db::get_vault(&self.state.pool, owner).await? {
-            if let Some(token_acc) = token_acc_opt {
+        if let Some(vault) = self.state.repos.vaults.get(owner).await? {
+            if let Some(token_acc) = vault.token_account {
                 if let Ok(pk) = Pubkey::from_str(&token_acc) {
                     return crate::solana_client::get_token_balance(&self.state.sol, &pk).await;
                 }
@@ -88,10 +97,18 @@ impl VaultManager {
     }
 
     pub async fn available_balance(&self, owner: &str) -> AppResult<i64> {
-        let (_token_opt, total) = db::get_vault(&self.state.pool, owner)
+        let total = self
+            .state
+            .repos
+            .vaults
+            .get(owner)
             .await?
-            .unwrap_or((None, 0));
-        let locked = db::get_locked_balance(&self.state.pool, owner)
+            .map_or(0, |v| v.total_balance);
+        let locked = self
+            .state
+            .repos
+            .vaults
+            .locked_balance(owner)
             .await
             .unwrap_or(0);
         Ok(total - locked)
@@ -0,0 +1,198 @@
+//! Minimal WebAuthn relying-party checks for ES256 (P-256) credentials.
+//!
+//! Registration uses attestation conveyance `none`: the attestation statement is not
+//! verified, only the client data, authenticator data and credential public key.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use ciborium::value::Value;
+use p256::ecdsa::{signature::Verifier, Signature, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::error::{AppError, AppResult};
+
+const FLAG_USER_PRESENT: u8 = 0x01;
+const FLAG_ATTESTED_CREDENTIAL: u8 = 0x40;
+const COSE_KTY_EC2: i128 = 2;
+const COSE_ALG_ES256: i128 = -7;
+const COSE_CRV_P256: i128 = 1;
+
+/// Relying-party identity the credentials are scoped to.
+#[derive(Debug, Clone)]
+pub struct RelyingParty {
+    pub id: String,
+    pub origin: String,
+}
+
+/// A credential accepted at registration time.
+#[derive(Debug, Clone)]
+pub struct RegisteredCredential {
+    pub credential_id: String,
+    pub public_key: Vec<u8>,
+    pub sign_count: u32,
+}
+
+/// Assertion produced by `navigator.credentials.get`, all fields base64url without padding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assertion {
+    pub credential_id: String,
+    pub client_data_json: String,
+    pub authenticator_data: String,
+    pub signature: String,
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: String,
+    origin: String,
+}
+
+fn bad(msg: &str) -> AppError {
+    AppError::BadRequest(format!("webauthn: {msg}"))
+}
+
+pub fn b64url_decode(value: &str) -> AppResult<Vec<u8>> {
+    URL_SAFE_NO_PAD
+        .decode(value.trim_end_matches('='))
+        .map_err(|_| bad("invalid base64url"))
+}
+
+pub fn b64url_encode(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Returns the challenge embedded in `clientDataJSON`, decoded to a UTF-8 string. The backend
+/// issues nonces as challenges, so this is the nonce to consume before verifying.
+pub fn client_data_challenge(client_data_json: &[u8]) -> AppResult<String> {
+    let data: ClientData =
+        serde_json::from_slice(client_data_json).map_err(|_| bad("invalid client data"))?;
+    String::from_utf8(b64url_decode(&data.challenge)?).map_err(|_| bad("invalid challenge"))
+}
+
+fn check_client_data(
+    rp: &RelyingParty,
+    client_data_json: &[u8],
+    expected_type: &str,
+    expected_challenge: &str,
+) -> AppResult<()> {
+    let data: ClientData =
+        serde_json::from_slice(client_data_json).map_err(|_| bad("invalid client data"))?;
+    if data.kind != expected_type {
+        return Err(bad("unexpected client data type"));
+    }
+    if b64url_decode(&data.challenge)? != expected_challenge.as_bytes() {
+        return Err(bad("challenge mismatch"));
+    }
+    if data.origin != rp.origin {
+        return Err(bad("origin mismatch"));
+    }
+    Ok(())
+}
+
+/// Checks rpIdHash and the user-present flag, returning (flags, sign count).
+fn check_authenticator_data(rp: &RelyingParty, auth_data: &[u8]) -> AppResult<(u8, u32)> {
+    if auth_data.len() < 37 {
+        return Err(bad("authenticator data too short"));
+    }
+    if auth_data[..32] != Sha256::digest(rp.id.as_bytes())[..] {
+        return Err(bad("rp id mismatch"));
+    }
+    let flags = auth_data[32];
+    if flags & FLAG_USER_PRESENT == 0 {
+        return Err(bad("user not present"));
+    }
+    let sign_count = u32::from_be_bytes([auth_data[33], auth_data[34], auth_data[35], auth_data[36]]);
+    Ok((flags, sign_count))
+}
+
+fn map_get<'a>(map: &'a [(Value, Value)], key: &Value) -> Option<&'a Value> {
+    map.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+}
+
+/// Converts a COSE_Key (EC2, ES256, P-256) into an uncompressed SEC1 point.
+fn cose_to_sec1(cose: &Value) -> AppResult<Vec<u8>> {
+    let map = cose.as_map().ok_or_else(|| bad("credential key is not a map"))?;
+    let int = |k: i64| map_get(map, &Value::Integer(k.into()));
+    let as_i128 = |v: Option<&Value>| v.and_then(|v| v.as_integer()).map(i128::from);
+    if as_i128(int(1)) != Some(COSE_KTY_EC2)
+        || as_i128(int(3)) != Some(COSE_ALG_ES256)
+        || as_i128(int(-1)) != Some(COSE_CRV_P256)
+    {
+        return Err(bad("only ES256 P-256 credentials are supported"));
+    }
+    let x = int(-2).and_then(|v| v.as_bytes()).ok_or_else(|| bad("missing x"))?;
+    let y = int(-3).and_then(|v| v.as_bytes()).ok_or_else(|| bad("missing y"))?;
+    if x.len() != 32 || y.len() != 32 {
+        return Err(bad("invalid coordinate length"));
+    }
+    let sec1 = [&[0x04u8][..], x, y].concat();
+    VerifyingKey::from_sec1_bytes(&sec1).map_err(|_| bad("invalid public key"))?;
+    Ok(sec1)
+}
+
+/// Verifies a `navigator.credentials.create` response against the issued challenge.
+pub fn verify_registration(
+    rp: &RelyingParty,
+    expected_challenge: &str,
+    client_data_json: &[u8],
+    attestation_object: &[u8],
+) -> AppResult<RegisteredCredential> {
+    check_client_data(rp, client_data_json, "webauthn.create", expected_challenge)?;
+
+    let attestation: Value =
+        ciborium::de::from_reader(attestation_object).map_err(|_| bad("invalid attestation"))?;
+    let map = attestation
+        .as_map()
+        .ok_or_else(|| bad("attestation is not a map"))?;
+    let auth_data = map_get(map, &Value::Text("authData".to_string()))
+        .and_then(|v| v.as_bytes())
+        .ok_or_else(|| bad("missing authData"))?;
+
+    let (flags, sign_count) = check_authenticator_data(rp, auth_data)?;
+    if flags & FLAG_ATTESTED_CREDENTIAL == 0 {
+        return Err(bad("no attested credential data"));
+    }
+    // aaguid (16) || credentialIdLength (2) || credentialId || credentialPublicKey
+    let rest = &auth_data[37..];
+    if rest.len() < 18 {
+        return Err(bad("attested credential data too short"));
+    }
+    let id_len = u16::from_be_bytes([rest[16], rest[17]]) as usize;
+    let rest = &rest[18..];
+    if rest.len() < id_len {
+        return Err(bad("credential id truncated"));
+    }
+    let (credential_id, key_bytes) = rest.split_at(id_len);
+    let cose: Value =
+        ciborium::de::from_reader(key_bytes).map_err(|_| bad("invalid credential key"))?;
+
+    Ok(RegisteredCredential {
+        credential_id: b64url_encode(credential_id),
+        public_key: cose_to_sec1(&cose)?,
+        sign_count,
+    })
+}
+
+/// Verifies a `navigator.credentials.get` assertion and returns the authenticator's sign count.
+pub fn verify_assertion(
+    rp: &RelyingParty,
+    expected_challenge: &str,
+    public_key: &[u8],
+    assertion: &Assertion,
+) -> AppResult<u32> {
+    let client_data_json = b64url_decode(&assertion.client_data_json)?;
+    let auth_data = b64url_decode(&assertion.authenticator_data)?;
+    let signature = b64url_decode(&assertion.signature)?;
+
+    check_client_data(rp, &client_data_json, "webauthn.get", expected_challenge)?;
+    let (_, sign_count) = check_authenticator_data(rp, &auth_data)?;
+
+    let key = VerifyingKey::from_sec1_bytes(public_key).map_err(|_| bad("invalid stored key"))?;
+    let signature = Signature::from_der(&signature).map_err(|_| bad("invalid signature"))?;
+    let signed = [&auth_data[..], &Sha256::digest(&client_data_json)[..]].concat();
+    key.verify(&signed, &signature)
+        .map_err(|_| AppError::Unauthorized)?;
+    Ok(sign_count)
+}
@@ -0,0 +1,258 @@
+//! Outbound webhooks: endpoint secrets, request signing and the retry schedule.
+//!
+//! Every request carries `X-CVMS-Timestamp` (unix seconds) and
+//! `X-CVMS-Signature: v1=<hex HMAC-SHA256(secret, "{timestamp}.{body}")>`. Receivers should
+//! recompute the signature over the raw body and reject stale timestamps.
+//!
+//! Deliveries do not follow redirects and, unless private targets are allowed, only reach
+//! public addresses: the host is resolved first and the request is pinned to the checked
+//! addresses, so a DNS answer cannot point it at an internal service.
+
+use aes_gcm::aead::{rand_core::RngCore, OsRng};
+use data_encoding::HEXLOWER;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::events::EVENT_TYPES;
+
+pub const SIGNATURE_HEADER: &str = "x-cvms-signature";
+pub const TIMESTAMP_HEADER: &str = "x-cvms-timestamp";
+pub const EVENT_ID_HEADER: &str = "x-cvms-event-id";
+pub const DELIVERY_ID_HEADER: &str = "x-cvms-delivery-id";
+
+/// Multisig notifications sent to the webhook endpoints a signer registered; they are not
+/// outbox events, so only endpoints owned by the signer receive them.
+pub const SIGNER_EVENT_TYPES: [&str; 2] = ["ms_request_signature", "ms_partial_tx_ready"];
+
+const SIGNATURE_VERSION: &str = "v1";
+const BASE_BACKOFF_SECONDS: i64 = 30;
+const MAX_BACKOFF_SECONDS: i64 = 6 * 3600;
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Random per-endpoint signing secret, returned to the caller once at registration.
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    format!("whsec_{}", HEXLOWER.encode(&bytes))
+}
+
+fn mac(secret: &str, timestamp: i64, body: &[u8]) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// Value of the signature header for `body` sent at `timestamp`.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let tag = mac(secret, timestamp, body).finalize().into_bytes();
+    format!("{SIGNATURE_VERSION}={}", HEXLOWER.encode(&tag))
+}
+
+/// Constant-time check of a signature header, as a receiver would do it.
+pub fn verify_signature(secret: &str, timestamp: i64, body: &[u8], header: &str) -> bool {
+    let Some(hex) = header.strip_prefix(&format!("{SIGNATURE_VERSION}=")) else {
+        return false;
+    };
+    let Ok(tag) = HEXLOWER.decode(hex.as_bytes()) else {
+        return false;
+    };
+    mac(secret, timestamp, body).verify_slice(&tag).is_ok()
+}
+
+/// Delay before the next attempt after `attempts` failures: 30s doubling, capped at 6h.
+pub fn backoff_seconds(attempts: i32) -> i64 {
+    let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+    BASE_BACKOFF_SECONDS
+        .saturating_mul(1i64 << exponent)
+        .min(MAX_BACKOFF_SECONDS)
+}
+
+/// An empty filter subscribes to every event type.
+pub fn matches_filter(event_types: &[String], event_type: &str) -> bool {
+    event_types.is_empty() || event_types.iter().any(|t| t == event_type)
+}
+
+/// Checks the endpoint URL and event filter submitted at registration. Owner endpoints must
+/// use https; integrator endpoints registered by an admin may also use http.
+pub fn validate_endpoint(
+    url: &str,
+    event_types: &[String],
+    require_https: bool,
+) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|_| "invalid url".to_string())?;
+    if !matches!(parsed.scheme(), "https" | "http") || parsed.host_str().is_none() {
+        return Err("url must be http(s) with a host".to_string());
+    }
+    if require_https && parsed.scheme() != "https" {
+        return Err("url must use https".to_string());
+    }
+    if let Some(unknown) = event_types
+        .iter()
+        .find(|t| !EVENT_TYPES.contains(&t.as_str()) && !SIGNER_EVENT_TYPES.contains(&t.as_str()))
+    {
+        return Err(format!("unknown event type: {unknown}"));
+    }
+    Ok(())
+}
+
+/// True for addresses a webhook must not reach: loopback, private, link-local, unique-local,
+/// carrier-grade NAT, unspecified, broadcast and multicast, including IPv4-mapped forms.
+pub fn is_blocked_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            v4.is_loopback()
+                || v4.is_private()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast()
+                || v4.is_multicast()
+                || a == 0
+                || (a == 100 && (b & 0xc0) == 64)
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_blocked_ip(IpAddr::V4(v4)),
+            None => {
+                let first = v6.segments()[0];
+                v6.is_loopback()
+                    || v6.is_unspecified()
+                    || v6.is_multicast()
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80
+            }
+        },
+    }
+}
+
+/// Resolves the host of `url` and fails if any address it names is blocked.
+async fn resolve_public(url: &reqwest::Url) -> Result<Vec<SocketAddr>, String> {
+    let host = url.host_str().ok_or("url has no host")?;
+    let port = url.port_or_known_default().ok_or("url has no port")?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("cannot resolve {host}: {e}"))?
+        .collect();
+    if let Some(addr) = addrs.iter().find(|addr| is_blocked_ip(addr.ip())) {
+        return Err(format!("{host} resolves to blocked address {}", addr.ip()));
+    }
+    if addrs.is_empty() {
+        return Err(format!("cannot resolve {host}"));
+    }
+    Ok(addrs)
+}
+
+/// POSTs one signed delivery. Only a 2xx answer counts as delivered;
+/// redirects are not followed. Unless `allow_private`, the target must resolve to public
+/// addresses only.
+pub async fn deliver(
+    allow_private: bool,
+    url: &str,
+    secret: &str,
+    event_id: Option<Uuid>,
+    delivery_id: i64,
+    body: String,
+) -> Result<(), String> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| e.to_string())?;
+    let mut builder = reqwest::Client::builder()
+        .timeout(DELIVERY_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy();
+    if !allow_private {
+        let addrs = resolve_public(&parsed).await?;
+        if let Some(domain) = parsed.domain() {
+            builder = builder.resolve_to_addrs(domain, &addrs);
+        }
+    }
+    let client = builder.build().map_err(|e| e.to_string())?;
+
+    let timestamp = time::OffsetDateTime::now_utc().unix_timestamp();
+    let mut request = client
+        .post(parsed)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(TIMESTAMP_HEADER, timestamp)
+        .header(SIGNATURE_HEADER, sign(secret, timestamp, body.as_bytes()))
+        .header(DELIVERY_ID_HEADER, delivery_id);
+    if let Some(event_id) = event_id {
+        request = request.header(EVENT_ID_HEADER, event_id.to_string());
+    }
+    let response = request.body(body).send().await.map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(format!("endpoint answered {}", response.status()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let secret = generate_secret();
+        let body = br#"{"type":"deposit"}"#;
+        let header = sign(&secret, 1_700_000_000, body);
+        assert!(header.starts_with("v1="));
+        assert!(verify_signature(&secret, 1_700_000_000, body, &header));
+        assert!(!verify_signature(&secret, 1_700_000_001, body, &header));
+        assert!(!verify_signature(
+            "whsec_other",
+            1_700_000_000,
+            body,
+            &header
+        ));
+        assert!(!verify_signature(&secret, 1_700_000_000, b"{}", &header));
+    }
+
+    #[test]
+    fn test_backoff_doubles_and_caps() {
+        assert_eq!(backoff_seconds(1), 30);
+        assert_eq!(backoff_seconds(2), 60);
+        assert_eq!(backoff_seconds(5), 480);
+        assert_eq!(backoff_seconds(40), MAX_BACKOFF_SECONDS);
+    }
+
+    #[test]
+    fn test_endpoint_validation_and_filter() {
+        assert!(validate_endpoint("https://example.com/hook", &["deposit".into()], true).is_ok());
+        assert!(validate_endpoint("ftp://example.com", &[], false).is_err());
+        assert!(validate_endpoint("https://example.com", &["deposits".into()], true).is_err());
+        assert!(validate_endpoint("http://example.com/hook", &[], true).is_err());
+        assert!(validate_endpoint("http://example.com/hook", &[], false).is_ok());
+        let signer_types = ["ms_request_signature".into(), "deposit".into()];
+        assert!(validate_endpoint("https://example.com/hook", &signer_types, true).is_ok());
+        assert!(matches_filter(&[], "tvl"));
+        assert!(matches_filter(&["tvl".into()], "tvl"));
+        assert!(!matches_filter(&["deposit".into()], "tvl"));
+    }
+
+    #[test]
+    fn test_blocked_addresses() {
+        for blocked in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(is_blocked_ip(blocked.parse().unwrap()), "{blocked}");
+        }
+        for public in ["93.184.216.34", "8.8.8.8", "2606:4700::1111"] {
+            assert!(!is_blocked_ip(public.parse().unwrap()), "{public}");
+        }
+    }
+}
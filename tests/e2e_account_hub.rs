@@ -0,0 +1,222 @@
+// Account hub tests against a mock Solana pubsub server: shared upstreams over one connection,
+// ref-counting, reconnect
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        extract::{
+            ws::{Message, WebSocket, WebSocketUpgrade},
+            State,
+        },
+        response::IntoResponse,
+        routing::get,
+        Router,
+    };
+    use cvmsback::account_hub::AccountHub;
+    use serde_json::{json, Value};
+    use solana_sdk::pubkey::Pubkey;
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::sync::broadcast;
+
+    #[derive(Clone, Debug)]
+    enum Command {
+        /// Push an account notification with this slot to every subscription
+        Notify(u64),
+        /// Drop every connection
+        Disconnect,
+    }
+
+    struct MockRpc {
+        connections: AtomicUsize,
+        closed: AtomicUsize,
+        subscribes: AtomicUsize,
+        unsubscribes: AtomicUsize,
+        next_sid: AtomicU64,
+        commands: broadcast::Sender<Command>,
+    }
+
+    /// Starts a minimal `accountSubscribe` server on an ephemeral port.
+    async fn mock_rpc() -> (String, Arc<MockRpc>) {
+        let rpc = Arc::new(MockRpc {
+            connections: AtomicUsize::new(0),
+            closed: AtomicUsize::new(0),
+            subscribes: AtomicUsize::new(0),
+            unsubscribes: AtomicUsize::new(0),
+            next_sid: AtomicU64::new(1),
+            commands: broadcast::channel(16).0,
+        });
+        let app = Router::new()
+            .route("/", get(upgrade))
+            .with_state(rpc.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        (format!("ws://{addr}/"), rpc)
+    }
+
+    async fn upgrade(State(rpc): State<Arc<MockRpc>>, ws: WebSocketUpgrade) -> impl IntoResponse {
+        ws.on_upgrade(move |socket| serve(rpc, socket))
+    }
+
+    async fn serve(rpc: Arc<MockRpc>, mut socket: WebSocket) {
+        rpc.connections.fetch_add(1, Ordering::SeqCst);
+        let mut commands = rpc.commands.subscribe();
+        let mut sids = Vec::new();
+        loop {
+            tokio::select! {
+                msg = socket.recv() => {
+                    let Some(Ok(Message::Text(text))) = msg else { break };
+                    let req: Value = serde_json::from_str(&text).unwrap();
+                    let result = match req["method"].as_str() {
+                        Some("accountSubscribe") => {
+                            rpc.subscribes.fetch_add(1, Ordering::SeqCst);
+                            let sid = rpc.next_sid.fetch_add(1, Ordering::SeqCst);
+                            sids.push(sid);
+                            json!(sid)
+                        }
+                        Some("accountUnsubscribe") => {
+                            rpc.unsubscribes.fetch_add(1, Ordering::SeqCst);
+                            let sid = req["params"][0].as_u64();
+                            sids.retain(|s| Some(*s) != sid);
+                            json!(true)
+                        }
+                        _ => Value::Null,
+                    };
+                    let reply = json!({ "jsonrpc": "2.0", "result": result, "id": req["id"] });
+                    let _ = socket.send(Message::Text(reply.to_string())).await;
+                }
+                cmd = commands.recv() => match cmd {
+                    Ok(Command::Notify(slot)) => {
+                        for sid in &sids {
+                            let _ = socket.send(Message::Text(notification(*sid, slot))).await;
+                        }
+                    }
+                    Ok(Command::Disconnect) | Err(_) => break,
+                },
+            }
+        }
+        rpc.closed.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn notification(sid: u64, slot: u64) -> String {
+        json!({
+            "jsonrpc": "2.0",
+            "method": "accountNotification",
+            "params": {
+                "subscription": sid,
+                "result": {
+                    "context": { "slot": slot },
+                    "value": {
+                        "lamports": 1,
+                        "data": ["AQID", "base64"],
+                        "owner": Pubkey::default().to_string(),
+                        "executable": false,
+                        "rentEpoch": 0,
+                        "space": 3
+                    }
+                }
+            }
+        })
+        .to_string()
+    }
+
+    async fn eventually(mut check: impl FnMut() -> bool) {
+        for _ in 0..100 {
+            if check() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("condition not reached");
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_share_one_upstream() {
+        let (url, rpc) = mock_rpc().await;
+        let hub = AccountHub::new(url);
+        let pubkey = Pubkey::new_unique();
+
+        let mut first = hub.subscribe(pubkey);
+        let mut second = hub.subscribe(pubkey);
+        assert_eq!(hub.upstream_count(), 1);
+        assert_eq!(hub.subscriber_count(&pubkey), 2);
+        eventually(|| rpc.subscribes.load(Ordering::SeqCst) == 1).await;
+
+        rpc.commands.send(Command::Notify(42)).unwrap();
+        for sub in [&mut first, &mut second] {
+            let update = tokio::time::timeout(Duration::from_secs(5), sub.next())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(update.pubkey, pubkey);
+            assert_eq!(update.slot, 42);
+            assert_eq!(update.data_len, 3);
+        }
+        assert_eq!(rpc.connections.load(Ordering::SeqCst), 1);
+
+        drop(first);
+        assert_eq!(hub.subscriber_count(&pubkey), 1);
+        drop(second);
+        assert_eq!(hub.upstream_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_pubkeys_share_one_connection() {
+        let (url, rpc) = mock_rpc().await;
+        let hub = AccountHub::new(url);
+        let pubkeys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let mut subs: Vec<_> = pubkeys.iter().map(|pk| hub.subscribe(*pk)).collect();
+        assert_eq!(hub.upstream_count(), 3);
+        eventually(|| rpc.subscribes.load(Ordering::SeqCst) == 3).await;
+        assert_eq!(rpc.connections.load(Ordering::SeqCst), 1);
+
+        rpc.commands.send(Command::Notify(9)).unwrap();
+        for (sub, pubkey) in subs.iter_mut().zip(&pubkeys) {
+            let update = tokio::time::timeout(Duration::from_secs(5), sub.next())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(update.pubkey, *pubkey);
+            assert_eq!(update.slot, 9);
+        }
+
+        // Releasing a pubkey unsubscribes it on the shared connection
+        drop(subs.remove(0));
+        eventually(|| rpc.unsubscribes.load(Ordering::SeqCst) == 1).await;
+        assert_eq!(rpc.closed.load(Ordering::SeqCst), 0);
+
+        // The connection is closed once nothing is watched
+        subs.clear();
+        eventually(|| rpc.closed.load(Ordering::SeqCst) == 1).await;
+        assert_eq!(hub.upstream_count(), 0);
+        let _sub = hub.subscribe(pubkeys[0]);
+        eventually(|| rpc.connections.load(Ordering::SeqCst) == 2).await;
+    }
+
+    #[tokio::test]
+    async fn test_upstream_reconnects() {
+        let (url, rpc) = mock_rpc().await;
+        let hub = AccountHub::new(url);
+        let pubkeys = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut subs = pubkeys.map(|pk| hub.subscribe(pk));
+        eventually(|| rpc.subscribes.load(Ordering::SeqCst) == 2).await;
+
+        // Every watched pubkey is subscribed again on the new connection
+        rpc.commands.send(Command::Disconnect).unwrap();
+        eventually(|| rpc.subscribes.load(Ordering::SeqCst) == 4).await;
+        assert_eq!(rpc.connections.load(Ordering::SeqCst), 2);
+
+        rpc.commands.send(Command::Notify(7)).unwrap();
+        for sub in &mut subs {
+            let update = tokio::time::timeout(Duration::from_secs(5), sub.next())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(update.slot, 7);
+        }
+    }
+}
@@ -0,0 +1,147 @@
+// Alert manager tests: transition-only notifications, re-notify, acknowledgement, resolution
+
+mod test_utils;
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use cvmsback::alerts::{AlertManager, Transition, RESOLVED_KIND};
+    use cvmsback::db;
+    use cvmsback::events::{EventEnvelope, Severity, Topic, VaultEvent};
+    use cvmsback::notify::Notifier;
+    use tokio::sync::broadcast;
+
+    fn next_alert(rx: &mut broadcast::Receiver<EventEnvelope>) -> (String, serde_json::Value) {
+        match rx.try_recv().expect("expected an alert").event {
+            VaultEvent::SecurityAlert(alert) => (alert.kind, alert.details),
+            other => panic!("unexpected event {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_alert_lifecycle() {
+        let ctx = TestContext::new().await;
+        let notifier = Notifier::new(64);
+        let mut rx = notifier.subscribe(Topic::SecurityAlert);
+        let alerts = AlertManager::new(ctx.pool.clone(), notifier.clone(), 3600);
+        let owner = TestContext::generate_test_owner();
+        let details = serde_json::json!({ "available": 5, "threshold": 10 });
+
+        let raise = || alerts.raise("low_balance", &owner, Severity::Warning, details.clone());
+        assert_eq!(raise().await.unwrap(), Transition::Opened);
+        let (kind, sent) = next_alert(&mut rx);
+        assert_eq!(kind, "low_balance");
+        assert_eq!(sent["renotify"], false);
+        let id = sent["alert_id"].as_i64().unwrap();
+
+        // Still active within the re-notify interval: nothing published
+        assert_eq!(raise().await.unwrap(), Transition::Suppressed);
+        assert!(rx.try_recv().is_err());
+
+        sqlx::query(
+            "UPDATE alerts SET last_notified_at = NOW() - INTERVAL '2 hours' WHERE id = $1",
+        )
+        .bind(id)
+        .execute(&ctx.pool)
+        .await
+        .unwrap();
+        assert_eq!(raise().await.unwrap(), Transition::Renotified);
+        assert_eq!(next_alert(&mut rx).1["renotify"], true);
+
+        // Acknowledged alerts are not re-notified
+        assert!(db::alert_acknowledge(&ctx.pool, id, "ops").await.unwrap());
+        assert!(!db::alert_acknowledge(&ctx.pool, id, "ops").await.unwrap());
+        sqlx::query(
+            "UPDATE alerts SET last_notified_at = NOW() - INTERVAL '2 hours' WHERE id = $1",
+        )
+        .bind(id)
+        .execute(&ctx.pool)
+        .await
+        .unwrap();
+        assert_eq!(raise().await.unwrap(), Transition::Suppressed);
+        assert!(rx.try_recv().is_err());
+
+        // Condition cleared: one resolved notification
+        alerts
+            .reconcile("low_balance", Severity::Warning, Vec::new())
+            .await
+            .unwrap();
+        let (kind, sent) = next_alert(&mut rx);
+        assert_eq!(kind, RESOLVED_KIND);
+        assert_eq!(sent["alert_id"], id);
+        assert!(!alerts.resolve("low_balance", &owner).await.unwrap());
+
+        // A new occurrence opens a new alert
+        assert_eq!(raise().await.unwrap(), Transition::Opened);
+        assert_ne!(next_alert(&mut rx).1["alert_id"], id);
+        let open = db::alerts_list(&ctx.pool, Some("open"), 500).await.unwrap();
+        assert_eq!(open.iter().filter(|a| a.2 == owner).count(), 1);
+
+        let _ = sqlx::query("DELETE FROM alerts WHERE owner = $1")
+            .bind(&owner)
+            .execute(&ctx.pool)
+            .await;
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_reconcile_continues_past_failing_owner() {
+        let ctx = TestContext::new().await;
+        let notifier = Notifier::new(64);
+        let mut rx = notifier.subscribe(Topic::SecurityAlert);
+        let alerts = AlertManager::new(ctx.pool.clone(), notifier.clone(), 3600);
+        let failing = TestContext::generate_test_owner();
+        let cleared = TestContext::generate_test_owner();
+        let details = serde_json::json!({ "available": 5, "threshold": 10 });
+        alerts
+            .raise("low_balance", &cleared, Severity::Warning, details.clone())
+            .await
+            .unwrap();
+        let id = next_alert(&mut rx).1["alert_id"].clone();
+
+        sqlx::query(
+            "CREATE FUNCTION alerts_write_fails() RETURNS trigger AS $$
+             BEGIN RAISE EXCEPTION 'write refused'; END $$ LANGUAGE plpgsql",
+        )
+        .execute(&ctx.pool)
+        .await
+        .expect("Failed to create function");
+        sqlx::query(&format!(
+            "CREATE TRIGGER alerts_write_fails BEFORE INSERT OR UPDATE ON alerts FOR EACH ROW
+             WHEN (NEW.owner = '{failing}') EXECUTE FUNCTION alerts_write_fails()"
+        ))
+        .execute(&ctx.pool)
+        .await
+        .expect("Failed to create trigger");
+
+        // The failing raise is counted and the cleared owner still resolves
+        let failed = alerts
+            .reconcile(
+                "low_balance",
+                Severity::Warning,
+                vec![(failing.clone(), details)],
+            )
+            .await
+            .unwrap();
+        assert_eq!(failed, 1);
+        let (kind, sent) = next_alert(&mut rx);
+        assert_eq!(kind, RESOLVED_KIND);
+        assert_eq!(sent["alert_id"], id);
+        assert!(rx.try_recv().is_err());
+
+        let _ = sqlx::query("DROP TRIGGER IF EXISTS alerts_write_fails ON alerts")
+            .execute(&ctx.pool)
+            .await;
+        let _ = sqlx::query("DROP FUNCTION IF EXISTS alerts_write_fails()")
+            .execute(&ctx.pool)
+            .await;
+        let _ = sqlx::query("DELETE FROM alerts WHERE owner = $1 OR owner = $2")
+            .bind(&failing)
+            .bind(&cleared)
+            .execute(&ctx.pool)
+            .await;
+        ctx.cleanup().await;
+    }
+}
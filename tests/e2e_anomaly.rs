@@ -0,0 +1,172 @@
+// Anomaly rules engine tests: configured rule, actions on first match, state, alert resolution
+
+mod test_utils;
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use cvmsback::anomaly::{self, RulesEngine};
+    use cvmsback::events::{EventEnvelope, Topic, VaultEvent};
+    use cvmsback::notify::Notifier;
+    use tokio::sync::broadcast;
+
+    /// Security alert kinds published for `owner` so far.
+    fn drain(rx: &mut broadcast::Receiver<EventEnvelope>, owner: &str) -> Vec<String> {
+        let mut kinds = Vec::new();
+        while let Ok(envelope) = rx.try_recv() {
+            if let (Some(o), VaultEvent::SecurityAlert(alert)) = (&envelope.owner, envelope.event) {
+                if o == owner {
+                    kinds.push(alert.kind);
+                }
+            }
+        }
+        kinds
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_rule_actions_run_once_per_match() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        create_test_vault(&ctx, &owner).await.unwrap();
+        let rules = anomaly::configured_rules(
+            r#"[{ "id": "test_burst", "kind": "tx_rate", "params": { "max_count": 2 },
+                  "severity": "critical", "actions": ["alert", "freeze", "require_2fa"] }]"#,
+        )
+        .unwrap();
+        anomaly::seed_rules(&ctx.pool, &rules).await.unwrap();
+        for _ in 0..3 {
+            insert_test_transaction(
+                &ctx,
+                &owner,
+                &generate_test_signature(),
+                10,
+                "deposit",
+                "confirmed",
+            )
+            .await
+            .unwrap();
+        }
+
+        let notifier = Notifier::new(64);
+        let mut rx = notifier.subscribe(Topic::SecurityAlert);
+        let engine = RulesEngine::new(ctx.pool.clone(), notifier.clone(), 3600);
+        engine.run_once().await.unwrap();
+
+        let status: String = sqlx::query_scalar("SELECT status FROM vaults WHERE owner = $1")
+            .bind(&owner)
+            .fetch_one(&ctx.pool)
+            .await
+            .unwrap();
+        assert_eq!(status, "frozen");
+        let kinds = drain(&mut rx, &owner);
+        assert!(kinds.contains(&"vault_frozen".to_string()));
+        assert!(kinds.contains(&"test_burst".to_string()));
+
+        // Still matching: state persists, nothing is repeated
+        engine.run_once().await.unwrap();
+        assert!(drain(&mut rx, &owner).is_empty());
+        let active: bool = sqlx::query_scalar(
+            "SELECT active FROM anomaly_rule_state WHERE rule_id = 'test_burst' AND owner = $1",
+        )
+        .bind(&owner)
+        .fetch_one(&ctx.pool)
+        .await
+        .unwrap();
+        assert!(active);
+
+        // Rule stops matching: the alert resolves
+        sqlx::query("DELETE FROM transactions WHERE owner = $1")
+            .bind(&owner)
+            .execute(&ctx.pool)
+            .await
+            .unwrap();
+        engine.run_once().await.unwrap();
+        assert_eq!(drain(&mut rx, &owner), vec!["alert_resolved".to_string()]);
+
+        let _ = sqlx::query("DELETE FROM anomaly_rules WHERE id = 'test_burst'")
+            .execute(&ctx.pool)
+            .await;
+        let _ = sqlx::query("DELETE FROM anomaly_rule_state WHERE owner = $1")
+            .bind(&owner)
+            .execute(&ctx.pool)
+            .await;
+        let _ = sqlx::query("DELETE FROM alerts WHERE owner = $1")
+            .bind(&owner)
+            .execute(&ctx.pool)
+            .await;
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_require_2fa_blocks_owner_without_second_factor() {
+        use axum::{
+            body::Body,
+            http::{header, Request, StatusCode},
+        };
+        use cvmsback::db;
+        use solana_sdk::signature::{Keypair, Signer};
+
+        let ctx = TestContext::new().await;
+        let keypair = Keypair::new();
+        let owner = keypair.pubkey().to_string();
+        create_test_vault(&ctx, &owner).await.unwrap();
+        let rules = anomaly::configured_rules(
+            r#"[{ "id": "test_enforce", "kind": "tx_rate", "params": { "max_count": 1 },
+                  "severity": "warning", "actions": ["require_2fa"] }]"#,
+        )
+        .unwrap();
+        anomaly::seed_rules(&ctx.pool, &rules).await.unwrap();
+        for _ in 0..2 {
+            insert_test_transaction(
+                &ctx,
+                &owner,
+                &generate_test_signature(),
+                10,
+                "deposit",
+                "confirmed",
+            )
+            .await
+            .unwrap();
+        }
+        // The owner narrowed their policy before the rule matched
+        db::twofa_policy_set(&ctx.pool, &owner, &[]).await.unwrap();
+
+        RulesEngine::new(ctx.pool.clone(), Notifier::new(64), 3600)
+            .run_once()
+            .await
+            .unwrap();
+
+        // No factor enrolled: the operation is refused instead of passing unchecked
+        let nonce = generate_test_signature();
+        db::insert_nonce(&ctx.pool, &nonce, &owner, "request_withdraw", 300)
+            .await
+            .unwrap();
+        let message = format!("request_withdraw:{owner}:100:{nonce}");
+        let body = serde_json::json!({
+            "owner": owner,
+            "amount": 100,
+            "nonce": nonce,
+            "signature": keypair.sign_message(message.as_bytes()).to_string(),
+        });
+        let request = Request::post("/vault/request-withdraw")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let (status, body) = send(&ctx, request).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"], "2fa enrollment required");
+
+        let _ = sqlx::query("DELETE FROM anomaly_rules WHERE id = 'test_enforce'")
+            .execute(&ctx.pool)
+            .await;
+        for table in ["anomaly_rule_state", "twofa_required", "twofa_policies"] {
+            let _ = sqlx::query(&format!("DELETE FROM {table} WHERE owner = $1"))
+                .bind(&owner)
+                .execute(&ctx.pool)
+                .await;
+        }
+        ctx.cleanup().await;
+    }
+}
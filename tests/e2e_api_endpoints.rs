@@ -5,7 +5,10 @@ mod test_utils;
 #[cfg(test)]
 mod tests {
     use crate::test_utils::*;
-    use cvmsback::db;
+    use cvmsback::{
+        db,
+        ledger::{self, EntryKind},
+    };
     use solana_sdk::pubkey::Pubkey;
     use std::str::FromStr;
 
@@ -70,26 +73,33 @@ mod tests {
     async fn test_vault_tvl_endpoint() {
         let ctx = TestContext::new().await;
         
-        // Insert transactions to calculate TVL
+        // TVL is summed from the vaults' ledger balances
         let owner1 = TestContext::generate_test_owner();
         let owner2 = TestContext::generate_test_owner();
-        
-        insert_test_transaction(&ctx, &owner1, "sig1", 10000, "deposit", "confirmed")
-            .await.expect("Failed to insert");
-        insert_test_transaction(&ctx, &owner2, "sig2", 20000, "deposit", "confirmed")
-            .await.expect("Failed to insert");
-        insert_test_transaction(&ctx, &owner1, "sig3", 3000, "withdraw", "confirmed")
-            .await.expect("Failed to insert");
+        create_test_vault(&ctx, &owner1).await.expect("Failed to create vault");
+        create_test_vault(&ctx, &owner2).await.expect("Failed to create vault");
+        
+        for (owner, kind, amount) in [
+            (&owner1, EntryKind::Deposit, 10000),
+            (&owner2, EntryKind::Deposit, 20000),
+            (&owner2, EntryKind::Lock, 5000),
+            (&owner1, EntryKind::YieldDeposit, 2000),
+        ] {
+            ledger::record(&ctx.pool, kind, &generate_test_signature(), owner, amount)
+                .await.expect("Failed to record");
+        }
+        ledger::record_withdraw_confirmed(&ctx.pool, &generate_test_signature(), &owner1, 3000)
+            .await.expect("Failed to record");
         
         // Test TVL calculation directly
-        let tvl: i64 = sqlx::query_scalar(
-            "SELECT COALESCE(SUM(CASE WHEN kind = 'deposit' THEN amount ELSE -amount END), 0) AS tvl FROM transactions"
-        )
-        .fetch_one(&ctx.pool)
-        .await.expect("Failed to calculate TVL");
-        
-        // TVL should be 10000 + 20000 - 3000 = 27000
-        assert_eq!(tvl, 27000);
+        let tvl = db::tvl_current(&ctx.pool)
+            .await.expect("Failed to calculate TVL");
+        
+        // TVL should be 10000 + 20000 - 3000 = 27000, of which 5000 locked and 2000 in yield
+        assert_eq!(tvl.total(), 27000);
+        assert_eq!(tvl.locked, 5000);
+        assert_eq!(tvl.in_yield, 2000);
+        assert_eq!(tvl.available, 20000);
         
         ctx.cleanup().await;
     }
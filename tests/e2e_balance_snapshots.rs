@@ -0,0 +1,192 @@
+// End-to-end tests for balance snapshots: ledger-derived periods, daily rollup, pruning and
+// the balance history endpoint
+
+mod test_utils;
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use axum::http::StatusCode;
+    use cvmsback::{db, ledger::EntryKind};
+    use time::{Date, Month, OffsetDateTime};
+
+    fn at(day: u8, hour: u8, minute: u8) -> OffsetDateTime {
+        Date::from_calendar_date(2020, Month::January, day)
+            .unwrap()
+            .with_hms(hour, minute, 0)
+            .unwrap()
+            .assume_utc()
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_snapshots_hold_ledger_balances_at_period_end() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        create_test_vault(&ctx, &owner)
+            .await
+            .expect("Failed to create vault");
+        insert_test_ledger_entry_at(&ctx, &owner, EntryKind::Deposit, 1_000, at(1, 10, 15))
+            .await
+            .expect("Failed to journal");
+        insert_test_ledger_entry_at(&ctx, &owner, EntryKind::Lock, 300, at(1, 11, 30))
+            .await
+            .expect("Failed to journal");
+        insert_test_ledger_entry_at(&ctx, &owner, EntryKind::YieldDeposit, 200, at(1, 11, 45))
+            .await
+            .expect("Failed to journal");
+
+        for hour in [10, 11] {
+            db::balance_snapshot_from_ledger(
+                &ctx.pool,
+                "hourly",
+                at(1, hour, 0),
+                at(1, hour + 1, 0),
+            )
+            .await
+            .expect("Failed to snapshot");
+        }
+        // Backfilling a recorded period again keeps the first snapshot
+        db::balance_snapshot_from_ledger(&ctx.pool, "hourly", at(1, 10, 0), at(1, 12, 0))
+            .await
+            .expect("Failed to snapshot");
+
+        let rows =
+            db::balance_snapshots_list(&ctx.pool, &owner, "hourly", at(1, 0, 0), at(2, 0, 0), 100)
+                .await
+                .expect("Failed to list snapshots");
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            (rows[0].available, rows[0].locked_balance, rows[0].balance),
+            (1_000, 0, 1_000)
+        );
+        assert_eq!(rows[1].period_start, at(1, 11, 0));
+        assert_eq!(rows[1].available, 500);
+        assert_eq!(rows[1].locked_balance, 300);
+        assert_eq!(rows[1].yield_deposited, 200);
+        assert_eq!(rows[1].balance, 800);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_daily_rollup_and_hourly_pruning() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        create_test_vault(&ctx, &owner)
+            .await
+            .expect("Failed to create vault");
+        insert_test_ledger_entry_at(&ctx, &owner, EntryKind::Deposit, 5_000, at(3, 8, 0))
+            .await
+            .expect("Failed to journal");
+
+        db::balance_snapshot_from_ledger(&ctx.pool, "hourly", at(3, 23, 0), at(4, 0, 0))
+            .await
+            .expect("Failed to snapshot");
+        // Rolled up from the closing hour, so the later deposit is not included
+        insert_test_ledger_entry_at(&ctx, &owner, EntryKind::Deposit, 1, at(3, 23, 59))
+            .await
+            .expect("Failed to journal");
+        db::balance_snapshot_rollup_daily(&ctx.pool, at(3, 0, 0))
+            .await
+            .expect("Failed to roll up");
+        db::balance_snapshot_from_ledger(&ctx.pool, "daily", at(3, 0, 0), at(4, 0, 0))
+            .await
+            .expect("Failed to snapshot");
+
+        let daily =
+            db::balance_snapshots_list(&ctx.pool, &owner, "daily", at(3, 0, 0), at(3, 0, 0), 10)
+                .await
+                .expect("Failed to list snapshots");
+        assert_eq!(daily.len(), 1);
+        assert_eq!(daily[0].available, 5_000);
+
+        db::balance_snapshot_prune_hourly(&ctx.pool, at(4, 0, 0))
+            .await
+            .expect("Failed to prune");
+        let hourly =
+            db::balance_snapshots_list(&ctx.pool, &owner, "hourly", at(3, 0, 0), at(4, 0, 0), 10)
+                .await
+                .expect("Failed to list snapshots");
+        assert!(hourly.is_empty());
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_balance_history_endpoint() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        create_test_vault(&ctx, &owner)
+            .await
+            .expect("Failed to create vault");
+        insert_test_ledger_entry_at(&ctx, &owner, EntryKind::Deposit, 700, at(5, 0, 5))
+            .await
+            .expect("Failed to journal");
+        for day in [5, 6, 7] {
+            db::balance_snapshot_from_ledger(&ctx.pool, "daily", at(day, 0, 0), at(day + 1, 0, 0))
+                .await
+                .expect("Failed to snapshot");
+        }
+
+        let (status, body) = get_json(
+            &ctx,
+            &format!("/vault/balance-history/{owner}?granularity=daily&from=2020-01-06T00:00:00Z&to=2020-01-07T00:00:00Z"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["granularity"], "daily");
+        let points = body["points"].as_array().expect("points");
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0]["available"], 700);
+        assert_eq!(points[0]["period_start"], "2020-01-06T00:00:00Z");
+        assert_eq!(body["to"], "2020-01-07T00:00:00Z");
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_insert_balance_snapshot_keeps_one_row_per_period() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        db::insert_balance_snapshot(&ctx.pool, &owner, 1_000, 100, "hourly")
+            .await
+            .expect("Failed to insert");
+        db::insert_balance_snapshot(&ctx.pool, &owner, 2_000, 100, "hourly")
+            .await
+            .expect("Failed to insert");
+
+        let now = OffsetDateTime::now_utc();
+        let rows = db::balance_snapshots_list(
+            &ctx.pool,
+            &owner,
+            "hourly",
+            now - time::Duration::HOUR,
+            now,
+            10,
+        )
+        .await
+        .expect("Failed to list snapshots");
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].balance, 2_000);
+        assert_eq!(rows[0].available, 1_900);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn test_balance_history_rejects_bad_params() {
+        let ctx = TestContext::in_memory();
+        let (status, _) = get_json(&ctx, "/vault/balance-history/owner?granularity=weekly").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get_json(
+            &ctx,
+            "/vault/balance-history/owner?from=2020-01-02T00:00:00Z&to=2020-01-01T00:00:00Z",
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
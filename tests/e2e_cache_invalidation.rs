@@ -0,0 +1,60 @@
+// Cache invalidation tests: evictions published over Redis reach other replicas
+
+mod test_utils;
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use cvmsback::cache::{self, Cache, CacheSettings, KeyClass};
+    use cvmsback::tasks::cache_invalidation::run_cache_invalidation_listener;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    #[ignore] // Requires database and Redis
+    async fn test_invalidation_reaches_other_replica() {
+        let ctx = TestContext::new().await;
+        assert!(ctx.state.cache.has_redis(), "Redis is required");
+        let owner = TestContext::generate_test_owner();
+
+        // Second replica: own local tier, same Redis and namespace
+        let mut replica = ctx.state.clone();
+        let redis = cache::connect_redis(&replica.cfg.redis_url).await.ok();
+        replica.cache = Arc::new(Cache::new(
+            CacheSettings::from_config(&replica.cfg),
+            redis,
+            replica.metrics.clone(),
+        ));
+        let listener = tokio::spawn(run_cache_invalidation_listener(replica.clone()));
+        // Let the listener subscribe (it clears the local tier when it does)
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        ctx.state
+            .cache
+            .set_json(KeyClass::Balance, &owner, &100u64)
+            .await;
+        assert_eq!(
+            replica.cache.get_json(KeyClass::Balance, &owner).await,
+            Some(100u64)
+        );
+
+        ctx.state.cache.invalidate_balance(&owner).await;
+        let mut evicted = false;
+        for _ in 0..50 {
+            if replica
+                .cache
+                .get_json::<u64>(KeyClass::Balance, &owner)
+                .await
+                .is_none()
+            {
+                evicted = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(evicted, "replica kept serving the invalidated balance");
+
+        listener.abort();
+        ctx.cleanup().await;
+    }
+}
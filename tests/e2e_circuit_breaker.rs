@@ -0,0 +1,167 @@
+// Circuit breaker tests: vault freeze/unfreeze and global kill switches
+
+mod test_utils;
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use cvmsback::circuit_breaker::{self, Blocked, Operation};
+    use cvmsback::events::{Topic, VaultEvent};
+    use cvmsback::notify::Notifier;
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_freeze_blocks_every_operation_until_unfrozen() {
+        let ctx = TestContext::new().await;
+        let notifier = Notifier::new(64);
+        let mut rx = notifier.subscribe(Topic::SecurityAlert);
+        let owner = TestContext::generate_test_owner();
+        let other = TestContext::generate_test_owner();
+        create_test_vault(&ctx, &owner).await.unwrap();
+        create_test_vault(&ctx, &other).await.unwrap();
+
+        assert!(
+            circuit_breaker::freeze(&ctx.pool, &notifier, &owner, "investigating", "ops")
+                .await
+                .unwrap()
+        );
+        assert!(
+            !circuit_breaker::freeze(&ctx.pool, &notifier, &owner, "again", "ops")
+                .await
+                .unwrap()
+        );
+        match rx.try_recv().unwrap().event {
+            VaultEvent::SecurityAlert(alert) => {
+                assert_eq!(alert.kind, "vault_frozen");
+                assert_eq!(alert.details["reason"], "investigating");
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+        assert!(rx.try_recv().is_err());
+
+        for op in Operation::ALL {
+            let blocked = circuit_breaker::check(&ctx.pool, &[&owner], op)
+                .await
+                .unwrap();
+            assert_eq!(
+                blocked,
+                Some(Blocked::VaultFrozen {
+                    owner: owner.clone(),
+                    reason: Some("investigating".to_string()),
+                })
+            );
+        }
+        // A transfer is blocked if either side is frozen
+        assert!(
+            circuit_breaker::check(&ctx.pool, &[&other, &owner], Operation::Transfers)
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            circuit_breaker::check(&ctx.pool, &[&other], Operation::Transfers)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        assert!(
+            circuit_breaker::unfreeze(&ctx.pool, &notifier, &owner, Some("cleared"), "ops")
+                .await
+                .unwrap()
+        );
+        assert!(
+            circuit_breaker::check(&ctx.pool, &[&owner], Operation::Withdrawals)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let audited: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM audit_trail WHERE owner = $1
+             AND action IN ('vault_frozen', 'vault_unfrozen')",
+        )
+        .bind(&owner)
+        .fetch_one(&ctx.pool)
+        .await
+        .unwrap();
+        assert_eq!(audited, 2);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_kill_switch_blocks_one_operation_for_all_vaults() {
+        let ctx = TestContext::new().await;
+        let notifier = Notifier::new(64);
+        let mut rx = notifier.subscribe(Topic::SecurityAlert);
+        let owner = TestContext::generate_test_owner();
+        create_test_vault(&ctx, &owner).await.unwrap();
+
+        assert!(circuit_breaker::set_operation(
+            &ctx.pool,
+            &notifier,
+            Operation::Yield,
+            false,
+            Some("protocol incident"),
+            "ops",
+        )
+        .await
+        .unwrap());
+        assert_eq!(
+            circuit_breaker::check(&ctx.pool, &[&owner], Operation::Yield)
+                .await
+                .unwrap(),
+            Some(Blocked::OperationDisabled {
+                operation: Operation::Yield,
+                reason: Some("protocol incident".to_string()),
+            })
+        );
+        assert!(
+            circuit_breaker::check(&ctx.pool, &[&owner], Operation::Deposits)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        // Re-enabling publishes once; enabling again changes nothing
+        assert!(circuit_breaker::set_operation(
+            &ctx.pool,
+            &notifier,
+            Operation::Yield,
+            true,
+            None,
+            "ops"
+        )
+        .await
+        .unwrap());
+        assert!(!circuit_breaker::set_operation(
+            &ctx.pool,
+            &notifier,
+            Operation::Yield,
+            true,
+            None,
+            "ops"
+        )
+        .await
+        .unwrap());
+        let kinds: Vec<String> = std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|envelope| match envelope.event {
+                VaultEvent::SecurityAlert(alert) => Some(alert.kind),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(kinds, vec!["operation_disabled", "operation_enabled"]);
+        assert!(
+            circuit_breaker::check(&ctx.pool, &[&owner], Operation::Yield)
+                .await
+                .unwrap()
+                .is_none()
+        );
+
+        let _ = sqlx::query("DELETE FROM operation_switches WHERE operation = 'yield'")
+            .execute(&ctx.pool)
+            .await;
+        ctx.cleanup().await;
+    }
+}
@@ -112,10 +112,10 @@ mod tests {
             .await.expect("Failed to list transactions");
         
         assert_eq!(transactions.len(), 2, "Should have 2 transactions");
-        assert!(transactions.iter().any(|(_, sig, _, kind, _)| 
-            sig == &deposit_sig && kind == "deposit"), "Should have deposit transaction");
-        assert!(transactions.iter().any(|(_, sig, _, kind, _)| 
-            sig == &withdraw_sig && kind == "withdraw"), "Should have withdraw transaction");
+        assert!(transactions.iter().any(|tx| 
+            tx.signature == deposit_sig && tx.kind == "deposit"), "Should have deposit transaction");
+        assert!(transactions.iter().any(|tx| 
+            tx.signature == withdraw_sig && tx.kind == "withdraw"), "Should have withdraw transaction");
         
         // ===== 8. BALANCE TRACKER: Balance Snapshots =====
         db::insert_balance_snapshot(&ctx.pool, &owner, final_balance as i64, (lock_amount - unlock_amount) as i64, "hourly")
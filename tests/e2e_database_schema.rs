@@ -26,9 +26,9 @@ mod tests {
             .await.expect("Failed to get vault");
         
         assert!(vault.is_some());
-        let (token_account, balance) = vault.unwrap();
-        assert_eq!(token_account, Some(owner.clone()));
-        assert_eq!(balance, 50000);
+        let vault = vault.unwrap();
+        assert_eq!(vault.token_account, Some(owner.clone()));
+        assert_eq!(vault.total_balance, 50000);
         
         ctx.cleanup().await;
     }
@@ -222,4 +222,26 @@ mod tests {
         
         ctx.cleanup().await;
     }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_migrations_current_and_rerun_is_noop() {
+        let ctx = TestContext::new().await;
+
+        cvmsback::migrate::ensure_current(&ctx.pool)
+            .await
+            .expect("schema should be current after TestContext::new");
+        let applied = cvmsback::migrate::up(&ctx.pool, None).await.unwrap();
+        assert!(applied.is_empty());
+
+        let history = cvmsback::migrate::applied(&ctx.pool).await.unwrap();
+        let versions: Vec<i64> = history.iter().map(|(v, ..)| *v).collect();
+        let known: Vec<i64> = cvmsback::migrate::MIGRATIONS
+            .iter()
+            .map(|m| m.version)
+            .collect();
+        assert_eq!(versions, known);
+
+        ctx.cleanup().await;
+    }
 }
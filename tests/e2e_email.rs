@@ -0,0 +1,187 @@
+// Email channel tests: SMTP delivery against a local sink and recipient checks
+
+mod test_utils;
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use cvmsback::db;
+    use cvmsback::email::{self, EmailKind, Mailer};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    /// Minimal SMTP server that accepts every message and forwards its DATA section.
+    async fn smtp_sink() -> (u16, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let (read, mut write) = stream.into_split();
+                    let mut lines = BufReader::new(read).lines();
+                    write.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let command = line.to_ascii_uppercase();
+                        let reply: &[u8] = if command.starts_with("EHLO") {
+                            b"250-sink\r\n250 8BITMIME\r\n"
+                        } else if command.starts_with("DATA") {
+                            write.write_all(b"354 go ahead\r\n").await.unwrap();
+                            let mut data = String::new();
+                            while let Ok(Some(line)) = lines.next_line().await {
+                                if line == "." {
+                                    break;
+                                }
+                                data.push_str(&line);
+                                data.push('\n');
+                            }
+                            let _ = tx.send(data);
+                            b"250 queued\r\n"
+                        } else if command.starts_with("QUIT") {
+                            let _ = write.write_all(b"221 bye\r\n").await;
+                            break;
+                        } else {
+                            b"250 ok\r\n"
+                        };
+                        write.write_all(reply).await.unwrap();
+                    }
+                });
+            }
+        });
+        (port, rx)
+    }
+
+    #[tokio::test]
+    async fn test_mailer_delivers_to_smtp_sink() {
+        let (port, mut rx) = smtp_sink().await;
+        let mailer = Mailer::new(
+            "127.0.0.1",
+            port,
+            "none",
+            "",
+            "",
+            "CVMS <no-reply@cvms.test>",
+        )
+        .unwrap();
+        let (subject, body) = email::render(
+            EmailKind::TimelockAvailable,
+            "owner1",
+            &serde_json::json!({ "amount": 250, "unlock_at": "2026-01-01T00:00:00Z" }),
+            Some("http://localhost:8080/notifications/unsubscribe?token=abc"),
+        );
+
+        mailer
+            .send("alice@cvms.test", &subject, body)
+            .await
+            .unwrap();
+
+        let data = rx.recv().await.unwrap();
+        assert!(data.contains("To: alice@cvms.test"));
+        assert!(data.contains("Subject: Timelocked funds are available"));
+        assert!(data.contains("250 in your vault unlocked"));
+        // Body is quoted-printable, so '=' is escaped
+        assert!(data.contains("token=3Dabc"));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_recipient_is_rejected_before_sending() {
+        let (port, _rx) = smtp_sink().await;
+        let mailer = Mailer::new("127.0.0.1", port, "none", "", "", "no-reply@cvms.test").unwrap();
+        assert!(mailer
+            .send("not-an-address", "s", "b".to_string())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_queue_respects_unsubscribe_preferences_and_rate_limit() {
+        let ctx = TestContext::new().await;
+        let mut cfg = ctx.state.cfg.clone();
+        cfg.smtp_host = "127.0.0.1".to_string();
+        cfg.email_rate_limit_per_hour = 2;
+        let owner = TestContext::generate_test_owner();
+        let context = serde_json::json!({ "available": 5, "threshold": 10 });
+
+        db::notification_contact_set_email(&ctx.pool, &owner, Some("owner@cvms.test"), "tok-1")
+            .await
+            .unwrap();
+        assert!(email::queue(
+            &ctx.pool,
+            &cfg,
+            &owner,
+            EmailKind::LowBalance,
+            &context,
+            "a"
+        )
+        .await
+        .unwrap());
+        // Same dedup key is only queued once
+        assert!(!email::queue(
+            &ctx.pool,
+            &cfg,
+            &owner,
+            EmailKind::LowBalance,
+            &context,
+            "a"
+        )
+        .await
+        .unwrap());
+
+        db::notification_contact_set_disabled(&ctx.pool, &owner, &["low_balance".to_string()])
+            .await
+            .unwrap();
+        assert!(!email::queue(
+            &ctx.pool,
+            &cfg,
+            &owner,
+            EmailKind::LowBalance,
+            &context,
+            "b"
+        )
+        .await
+        .unwrap());
+        assert!(email::queue(
+            &ctx.pool,
+            &cfg,
+            &owner,
+            EmailKind::SecurityAlert,
+            &context,
+            "c"
+        )
+        .await
+        .unwrap());
+        // Rate limit of 2 per hour reached
+        assert!(!email::queue(
+            &ctx.pool,
+            &cfg,
+            &owner,
+            EmailKind::SecurityAlert,
+            &context,
+            "d"
+        )
+        .await
+        .unwrap());
+
+        assert!(db::notification_unsubscribe(&ctx.pool, "tok-1")
+            .await
+            .unwrap());
+        let (_, _, unsubscribed, _) = db::notification_contact_get(&ctx.pool, &owner)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(unsubscribed);
+
+        let _ = sqlx::query("DELETE FROM email_deliveries WHERE pubkey = $1")
+            .bind(&owner)
+            .execute(&ctx.pool)
+            .await;
+        let _ = sqlx::query("DELETE FROM ms_signer_contacts WHERE pubkey = $1")
+            .bind(&owner)
+            .execute(&ctx.pool)
+            .await;
+        ctx.cleanup().await;
+    }
+}
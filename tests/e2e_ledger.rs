@@ -0,0 +1,186 @@
+// End-to-end tests for the double-entry ledger: idempotent posting, derived balances,
+// append-only enforcement and withdrawal settlement
+
+mod test_utils;
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use cvmsback::{
+        db,
+        ledger::{self, EntryKind},
+    };
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_record_is_idempotent_per_signature_and_kind() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        let sig = generate_test_signature();
+
+        assert!(ledger::record(&ctx.pool, EntryKind::Deposit, &sig, &owner, 1000)
+            .await
+            .expect("Failed to record"));
+        assert!(!ledger::record(&ctx.pool, EntryKind::Deposit, &sig, &owner, 1000)
+            .await
+            .expect("Failed to record"));
+        // Non-positive amounts are not journaled
+        assert!(!ledger::record(&ctx.pool, EntryKind::Deposit, &generate_test_signature(), &owner, 0)
+            .await
+            .expect("Failed to record"));
+
+        let balances = ledger::balances(&ctx.pool, &owner).await.expect("Failed to get balances");
+        assert_eq!(balances.available, 1000);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_derived_balances_follow_entries() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+
+        for (kind, amount) in [
+            (EntryKind::Deposit, 10_000),
+            (EntryKind::Lock, 4_000),
+            (EntryKind::Unlock, 1_000),
+            (EntryKind::YieldDeposit, 2_000),
+            (EntryKind::YieldAccrual, 50),
+            (EntryKind::WithdrawSubmitted, 3_000),
+        ] {
+            ledger::record(&ctx.pool, kind, &generate_test_signature(), &owner, amount)
+                .await
+                .expect("Failed to record");
+        }
+
+        let balances = ledger::balances(&ctx.pool, &owner).await.expect("Failed to get balances");
+        assert_eq!(balances.available, 2_000);
+        assert_eq!(balances.locked, 3_000);
+        assert_eq!(balances.yield_deposited, 2_000);
+        assert_eq!(balances.yield_accrued, 50);
+        assert_eq!(balances.in_transit, 3_000);
+        assert_eq!(balances.custody(), 8_000);
+
+        let postings = db::ledger_postings_for(&ctx.pool, &owner, 2)
+            .await
+            .expect("Failed to list postings");
+        assert_eq!(postings[0].kind, "withdraw_submitted");
+        assert!(postings.iter().all(|p| p.kind == "withdraw_submitted" || p.kind == "yield_accrual"));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_withdrawal_settles_or_fails_once() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        ledger::record(&ctx.pool, EntryKind::Deposit, &generate_test_signature(), &owner, 5_000)
+            .await
+            .expect("Failed to record");
+
+        // Submitted through the service, then seen on chain
+        let settled = generate_test_signature();
+        ledger::record(&ctx.pool, EntryKind::WithdrawSubmitted, &settled, &owner, 1_000)
+            .await
+            .expect("Failed to record");
+        assert!(ledger::record_withdraw_confirmed(&ctx.pool, &settled, &owner, 1_000)
+            .await
+            .expect("Failed to settle"));
+        assert!(!ledger::record_withdraw_confirmed(&ctx.pool, &settled, &owner, 1_000)
+            .await
+            .expect("Failed to settle"));
+
+        // Submitted but failed back to available
+        let failed = generate_test_signature();
+        ledger::record(&ctx.pool, EntryKind::WithdrawSubmitted, &failed, &owner, 500)
+            .await
+            .expect("Failed to record");
+        ledger::record(&ctx.pool, EntryKind::WithdrawFailed, &failed, &owner, 500)
+            .await
+            .expect("Failed to record");
+
+        // Withdrawn outside the service
+        assert!(ledger::record_withdraw_confirmed(&ctx.pool, &generate_test_signature(), &owner, 200)
+            .await
+            .expect("Failed to record"));
+
+        let balances = ledger::balances(&ctx.pool, &owner).await.expect("Failed to get balances");
+        assert_eq!(balances.available, 3_800);
+        assert_eq!(balances.in_transit, 0);
+        let unsettled = db::ledger_unsettled_withdrawals(&ctx.pool, 0)
+            .await
+            .expect("Failed to list unsettled");
+        assert!(unsettled.iter().all(|(_, o, _, _)| o != &owner));
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_transfer_moves_locked_collateral_between_vaults() {
+        let ctx = TestContext::new().await;
+        let from = TestContext::generate_test_owner();
+        let to = TestContext::generate_test_owner();
+        ledger::record(&ctx.pool, EntryKind::Deposit, &generate_test_signature(), &from, 1_000)
+            .await
+            .expect("Failed to record");
+        ledger::record(&ctx.pool, EntryKind::Lock, &generate_test_signature(), &from, 600)
+            .await
+            .expect("Failed to record");
+
+        ledger::record_transfer(&ctx.pool, &generate_test_signature(), &from, &to, 600)
+            .await
+            .expect("Failed to record transfer");
+
+        let from_balances = ledger::balances(&ctx.pool, &from).await.expect("Failed to get balances");
+        let to_balances = ledger::balances(&ctx.pool, &to).await.expect("Failed to get balances");
+        assert_eq!(from_balances.locked, 0);
+        assert_eq!(from_balances.available, 400);
+        assert_eq!(to_balances.available, 600);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_ledger_is_append_only_and_balanced() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        let sig = generate_test_signature();
+        ledger::record(&ctx.pool, EntryKind::Deposit, &sig, &owner, 1_000)
+            .await
+            .expect("Failed to record");
+
+        let update = sqlx::query("UPDATE ledger_postings SET amount = amount * 2 WHERE owner = $1")
+            .bind(&owner)
+            .execute(&ctx.pool)
+            .await;
+        assert!(update.is_err());
+        let delete = sqlx::query("DELETE FROM ledger_entries WHERE signature = $1")
+            .bind(&sig)
+            .execute(&ctx.pool)
+            .await;
+        assert!(delete.is_err());
+
+        // A one-sided entry is rejected at commit
+        let unbalanced = db::ledger_post(
+            &ctx.pool,
+            &generate_test_signature(),
+            "deposit",
+            &[(owner.clone(), "available".to_string(), 500)],
+        )
+        .await;
+        assert!(unbalanced.is_err());
+
+        let balances = ledger::balances(&ctx.pool, &owner).await.expect("Failed to get balances");
+        assert_eq!(balances.available, 1_000);
+        let unbalanced = db::ledger_unbalanced_entries(&ctx.pool)
+            .await
+            .expect("Failed to check entries");
+        assert!(unbalanced.is_empty());
+
+        ctx.cleanup().await;
+    }
+}
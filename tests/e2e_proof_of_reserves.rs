@@ -0,0 +1,107 @@
+// End-to-end tests for proof of reserves: Merkle snapshots of reconciled vault state and
+// inclusion proofs served by the API
+
+mod test_utils;
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use axum::http::StatusCode;
+    use cvmsback::{
+        db,
+        proof_of_reserves::{self, Leaf, ProofStep},
+        reconciliation::VaultBalances,
+    };
+
+    async fn reconciled(ctx: &TestContext, owner: &str, balance: i64, locked: i64) {
+        let balances = VaultBalances {
+            total_balance: balance,
+            locked_balance: locked,
+            ..Default::default()
+        };
+        db::vault_mark_reconciled(&ctx.pool, owner, &balances, 77)
+            .await
+            .expect("Failed to mark reconciled");
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_snapshot_proofs_verify() {
+        let ctx = TestContext::new().await;
+        let owners: Vec<String> = (0..5).map(|_| TestContext::generate_test_owner()).collect();
+        for owner in &owners {
+            create_test_vault(&ctx, owner)
+                .await
+                .expect("Failed to create vault");
+        }
+        let [a, b, c, unreconciled, escalated] = &owners[..] else {
+            unreachable!()
+        };
+        reconciled(&ctx, a, 1_000, 100).await;
+        reconciled(&ctx, b, 2_000, 0).await;
+        reconciled(&ctx, c, 3_000, 500).await;
+        reconciled(&ctx, escalated, 4_000, 0).await;
+        let state = serde_json::json!({});
+        db::reconciliation_case_escalate(&ctx.pool, escalated, &state, &state, &state, 9_999)
+            .await
+            .expect("Failed to escalate");
+
+        let first = proof_of_reserves::build_snapshot(&ctx.pool)
+            .await
+            .expect("Failed to build snapshot")
+            .expect("snapshot");
+        assert!(first.leaf_count >= 3);
+
+        let (status, body) = get_json(&ctx, &format!("/proof/{b}")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["snapshot"]["id"], first.id);
+        let leaf: Leaf = serde_json::from_value(body["leaf"].clone()).expect("leaf");
+        let proof: Vec<ProofStep> = serde_json::from_value(body["proof"].clone()).expect("proof");
+        assert_eq!(
+            leaf,
+            Leaf {
+                owner: b.clone(),
+                balance: 2_000,
+                locked: 0,
+                slot: 77,
+            }
+        );
+        assert!(proof_of_reserves::verify(&leaf, &proof, &first.root));
+        let inflated = Leaf {
+            balance: 2_001,
+            ..leaf.clone()
+        };
+        assert!(!proof_of_reserves::verify(&inflated, &proof, &first.root));
+
+        for owner in [unreconciled, escalated] {
+            let (status, _) = get_json(&ctx, &format!("/proof/{owner}")).await;
+            assert_eq!(status, StatusCode::NOT_FOUND);
+        }
+
+        // Proofs come from the newest snapshot
+        reconciled(&ctx, b, 2_500, 0).await;
+        let second = proof_of_reserves::build_snapshot(&ctx.pool)
+            .await
+            .expect("Failed to build snapshot")
+            .expect("snapshot");
+        assert_ne!(second.root, first.root);
+        let (_, body) = get_json(&ctx, &format!("/proof/{b}")).await;
+        assert_eq!(body["snapshot"]["id"], second.id);
+        assert_eq!(body["leaf"]["balance"], 2_500);
+        let leaf: Leaf = serde_json::from_value(body["leaf"].clone()).expect("leaf");
+        let proof: Vec<ProofStep> = serde_json::from_value(body["proof"].clone()).expect("proof");
+        assert!(proof_of_reserves::verify(&leaf, &proof, &second.root));
+        assert!(!proof_of_reserves::verify(&leaf, &proof, &first.root));
+
+        let _ = sqlx::query("DELETE FROM reserve_snapshots WHERE id IN ($1, $2)")
+            .bind(first.id)
+            .bind(second.id)
+            .execute(&ctx.pool)
+            .await;
+        let _ = sqlx::query("DELETE FROM reconciliation_cases WHERE owner = $1")
+            .bind(escalated)
+            .execute(&ctx.pool)
+            .await;
+        ctx.cleanup().await;
+    }
+}
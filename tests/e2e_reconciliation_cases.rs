@@ -0,0 +1,242 @@
+// End-to-end tests for reconciliation cases: auto-healing small drift, escalating large drift
+// and resolving cases through the admin endpoints
+
+mod test_utils;
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use axum::{
+        body::Body,
+        http::{header, Request, StatusCode},
+    };
+    use cvmsback::{
+        db,
+        events::{Topic, VaultEvent},
+        reconciliation::{self, Outcome, VaultBalances},
+    };
+
+    async fn snapshot(ctx: &TestContext, owner: &str) -> VaultBalances {
+        db::vault_snapshot_balances(&ctx.pool)
+            .await
+            .expect("Failed to load snapshots")
+            .into_iter()
+            .find(|row| row.owner == owner)
+            .expect("vault snapshot")
+            .balances
+    }
+
+    async fn reconcile(ctx: &TestContext, owner: &str, chain: &VaultBalances) -> Outcome {
+        let db_state = snapshot(ctx, owner).await;
+        reconciliation::reconcile_vault(
+            &ctx.pool,
+            &ctx.state.notifier,
+            owner,
+            &db_state,
+            chain,
+            ctx.state.cfg.reconciliation_threshold,
+            ctx.state.cfg.reconciliation_auto_heal_max,
+        )
+        .await
+        .expect("Failed to reconcile")
+    }
+
+    async fn cleanup(ctx: &TestContext, owner: &str) {
+        let _ = sqlx::query("DELETE FROM reconciliation_cases WHERE owner = $1")
+            .bind(owner)
+            .execute(&ctx.pool)
+            .await;
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_small_drift_is_healed() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        create_test_vault(&ctx, &owner)
+            .await
+            .expect("Failed to create vault");
+        db::update_vault_snapshot(&ctx.pool, &owner, 5_000, 0, 0)
+            .await
+            .expect("Failed to set balance");
+
+        // Within RECONCILIATION_THRESHOLD (1000 in tests)
+        let mut chain = snapshot(&ctx, &owner).await;
+        chain.yield_accrued_balance = 400;
+        assert_eq!(reconcile(&ctx, &owner, &chain).await, Outcome::InSync);
+
+        chain.total_balance = 7_000;
+        assert_eq!(reconcile(&ctx, &owner, &chain).await, Outcome::Healed);
+        assert_eq!(snapshot(&ctx, &owner).await, chain);
+        assert_eq!(reconcile(&ctx, &owner, &chain).await, Outcome::InSync);
+
+        let (status, body) = admin_get(
+            &ctx,
+            &format!("/admin/reconciliation/cases?status=healed&owner={owner}"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let items = body["items"].as_array().expect("items");
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0]["status"], "healed");
+        assert_eq!(items[0]["max_discrepancy"], 2_000);
+        assert_eq!(items[0]["discrepancies"]["total_balance"], 2_000);
+        assert_eq!(items[0]["discrepancies"]["yield_accrued_balance"], 400);
+        assert_eq!(items[0]["db_state"]["total_balance"], 5_000);
+
+        let audited: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM audit_trail WHERE owner = $1 AND action = 'reconciliation_auto_heal'",
+        )
+        .bind(&owner)
+        .fetch_one(&ctx.pool)
+        .await
+        .expect("Failed to count audit entries");
+        assert_eq!(audited, 1);
+
+        cleanup(&ctx, &owner).await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_large_drift_escalates_until_resolved() {
+        let ctx = TestContext::new().await;
+        let mut rx = ctx.state.notifier.subscribe(Topic::SecurityAlert);
+        let owner = TestContext::generate_test_owner();
+        create_test_vault(&ctx, &owner)
+            .await
+            .expect("Failed to create vault");
+        db::update_vault_snapshot(&ctx.pool, &owner, 5_000, 0, 0)
+            .await
+            .expect("Failed to set balance");
+        let before = snapshot(&ctx, &owner).await;
+
+        let chain = VaultBalances {
+            locked_balance: 50_000,
+            ..before
+        };
+        assert_eq!(reconcile(&ctx, &owner, &chain).await, Outcome::Escalated);
+        match rx.try_recv().expect("security alert").event {
+            VaultEvent::SecurityAlert(alert) => {
+                assert_eq!(alert.kind, "reconciliation_drift");
+                assert_eq!(alert.details["discrepancies"]["locked_balance"], 50_000);
+            }
+            other => panic!("unexpected event {other:?}"),
+        }
+
+        // Small enough to heal, but the vault has an escalated case
+        let chain = VaultBalances {
+            total_balance: 7_000,
+            ..before
+        };
+        assert_eq!(reconcile(&ctx, &owner, &chain).await, Outcome::Escalated);
+        assert!(rx.try_recv().is_err());
+        assert_eq!(snapshot(&ctx, &owner).await, before);
+
+        let (status, body) = admin_get(
+            &ctx,
+            &format!("/admin/reconciliation/cases?status=escalated&owner={owner}"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let items = body["items"].as_array().expect("items");
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0]["occurrences"], 2);
+        assert_eq!(items[0]["chain_state"]["total_balance"], 7_000);
+        let id = items[0]["id"].as_i64().expect("id");
+        let uri = format!("/admin/reconciliation/cases/{id}/resolve");
+
+        let (status, _) = admin_post(&ctx, &uri, serde_json::json!({ "resolution": " " })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, body) = admin_post(
+            &ctx,
+            &uri,
+            serde_json::json!({ "resolution": "indexer missed a deposit", "apply_chain": true }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["case"]["status"], "resolved");
+        assert_eq!(body["case"]["resolved_by"], "test-admin");
+        assert_eq!(snapshot(&ctx, &owner).await, chain);
+
+        let (status, _) =
+            admin_post(&ctx, &uri, serde_json::json!({ "resolution": "again" })).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        cleanup(&ctx, &owner).await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_failed_heal_leaves_snapshot_untouched() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        create_test_vault(&ctx, &owner)
+            .await
+            .expect("Failed to create vault");
+        db::update_vault_snapshot(&ctx.pool, &owner, 5_000, 0, 0)
+            .await
+            .expect("Failed to set balance");
+        let before = snapshot(&ctx, &owner).await;
+
+        // The audit entry is written last; make it fail for this owner
+        sqlx::query(
+            "CREATE FUNCTION audit_insert_fails() RETURNS trigger AS $$
+             BEGIN RAISE EXCEPTION 'audit refused'; END $$ LANGUAGE plpgsql",
+        )
+        .execute(&ctx.pool)
+        .await
+        .expect("Failed to create function");
+        sqlx::query(&format!(
+            "CREATE TRIGGER audit_insert_fails BEFORE INSERT ON audit_trail FOR EACH ROW
+             WHEN (NEW.owner = '{owner}') EXECUTE FUNCTION audit_insert_fails()"
+        ))
+        .execute(&ctx.pool)
+        .await
+        .expect("Failed to create trigger");
+
+        let chain = VaultBalances {
+            total_balance: 7_000,
+            ..before
+        };
+        let result = reconciliation::reconcile_vault(
+            &ctx.pool,
+            &ctx.state.notifier,
+            &owner,
+            &before,
+            &chain,
+            ctx.state.cfg.reconciliation_threshold,
+            ctx.state.cfg.reconciliation_auto_heal_max,
+        )
+        .await;
+        let _ = sqlx::query("DROP TRIGGER IF EXISTS audit_insert_fails ON audit_trail")
+            .execute(&ctx.pool)
+            .await;
+        let _ = sqlx::query("DROP FUNCTION IF EXISTS audit_insert_fails()")
+            .execute(&ctx.pool)
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(snapshot(&ctx, &owner).await, before);
+        let cases = db::reconciliation_cases_list(&ctx.pool, None, Some(&owner), 10)
+            .await
+            .expect("Failed to list cases");
+        assert!(cases.is_empty());
+
+        cleanup(&ctx, &owner).await;
+    }
+
+    #[tokio::test]
+    async fn test_cases_endpoint_rejects_bad_requests() {
+        let ctx = TestContext::in_memory();
+        let request = Request::get("/admin/reconciliation/cases")
+            .header(header::AUTHORIZATION, "Bearer not-a-token")
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send(&ctx, request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = admin_get(&ctx, "/admin/reconciliation/cases?status=open").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
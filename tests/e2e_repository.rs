@@ -0,0 +1,177 @@
+// Repository tests: API handlers running against the in-memory repositories (no database)
+
+mod test_utils;
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn test_transactions_endpoint_pages_newest_first() {
+        let ctx = TestContext::in_memory();
+        let owner = TestContext::generate_test_owner();
+        for i in 0..3 {
+            ctx.state
+                .repos
+                .txs
+                .insert(
+                    &owner,
+                    &format!("sig{i}"),
+                    Some(100),
+                    "deposit",
+                    "confirmed",
+                )
+                .await
+                .unwrap();
+        }
+
+        let (status, body) = get_json(&ctx, &format!("/vault/transactions/{owner}?limit=2")).await;
+        assert_eq!(status, StatusCode::OK);
+        let sigs: Vec<&str> = body["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|t| t["signature"].as_str().unwrap())
+            .collect();
+        assert_eq!(sigs, ["sig2", "sig1"]);
+        assert_eq!(body["pagination"]["next"], 2);
+
+        let (_, body) = get_json(
+            &ctx,
+            &format!("/vault/transactions/{owner}?limit=2&offset=2"),
+        )
+        .await;
+        assert_eq!(body["items"][0]["signature"], "sig0");
+        assert!(body["pagination"]["next"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_tvl_endpoint_uses_repository() {
+        let ctx = TestContext::in_memory();
+        for owner in ["a", "b"] {
+            ctx.state
+                .repos
+                .vaults
+                .upsert_token_account(owner, &format!("ata-{owner}"))
+                .await
+                .unwrap();
+        }
+        let ledger = &ctx.state.repos.ledger;
+        // Moves `amount` between two of `owner`'s ledger accounts
+        let transfer = |signature: &'static str, owner: &str, from: &str, to: &str, amount: i64| {
+            let postings = vec![
+                (owner.to_string(), from.to_string(), -amount),
+                (owner.to_string(), to.to_string(), amount),
+            ];
+            async move { ledger.post(signature, "test", &postings).await.unwrap() }
+        };
+        transfer("sig1", "a", "external", "available", 10_000).await;
+        transfer("sig2", "b", "external", "available", 20_000).await;
+        transfer("sig3", "a", "available", "locked", 2_000).await;
+        transfer("sig4", "b", "available", "yield_deposited", 5_000).await;
+        // Withdrawals in transit have left the vault
+        transfer("sig5", "a", "available", "in_transit", 3_000).await;
+        // Not a registered vault
+        transfer("sig6", "c", "external", "available", 1_000).await;
+        assert!(!transfer("sig1", "a", "external", "available", 10_000).await);
+
+        let (status, body) = get_json(&ctx, "/vault/tvl").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["tvl"], 27_000);
+        assert_eq!(body["available"], 20_000);
+        assert_eq!(body["locked"], 2_000);
+        assert_eq!(body["in_yield"], 5_000);
+        assert_eq!(body["cached"], false);
+    }
+
+    #[tokio::test]
+    async fn test_timelocks_endpoint_lists_earliest_first() {
+        let ctx = TestContext::in_memory();
+        let owner = TestContext::generate_test_owner();
+        let now = time::OffsetDateTime::now_utc();
+        let timelocks = &ctx.state.repos.timelocks;
+        timelocks
+            .insert(&owner, 500, now + time::Duration::hours(1))
+            .await
+            .unwrap();
+        timelocks
+            .insert(&owner, 200, now - time::Duration::minutes(1))
+            .await
+            .unwrap();
+
+        let (status, body) = get_json(&ctx, &format!("/vault/timelocks/{owner}")).await;
+        assert_eq!(status, StatusCode::OK);
+        let items = body["items"].as_array().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0]["amount"], 200);
+        assert_eq!(items[0]["remaining_seconds"], 0);
+        assert!(items[1]["remaining_seconds"].as_i64().unwrap() > 3500);
+    }
+
+    #[tokio::test]
+    async fn test_proposal_status_endpoint() {
+        let ctx = TestContext::in_memory();
+        let (status, _) = get_json(&ctx, "/vault/proposal/missing").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let proposals = &ctx.state.repos.proposals;
+        let signers = vec!["s1".to_string(), "s2".to_string()];
+        proposals
+            .create("p1", "owner", 1_000, 2, &signers)
+            .await
+            .unwrap();
+        proposals.add_approval("p1", "s1", "sig").await.unwrap();
+
+        let (status, body) = get_json(&ctx, "/vault/proposal/p1").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "pending");
+        assert_eq!(body["approvals"], 1);
+        assert_eq!(body["threshold"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_limits_endpoint_sums_recent_withdrawals() {
+        let ctx = TestContext::in_memory();
+        let owner = TestContext::generate_test_owner();
+        let txs = &ctx.state.repos.txs;
+        txs.insert(&owner, "w1", Some(300), "withdraw", "confirmed")
+            .await
+            .unwrap();
+        txs.insert(&owner, "w2", Some(200), "withdraw", "pending")
+            .await
+            .unwrap();
+        txs.insert(&owner, "d1", Some(1_000), "deposit", "confirmed")
+            .await
+            .unwrap();
+        txs.insert("someone-else", "w3", Some(50), "withdraw", "confirmed")
+            .await
+            .unwrap();
+
+        let (status, body) = get_json(&ctx, &format!("/vault/limits/{owner}")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["used"], 500);
+        assert_eq!(body["window_seconds"], 86400);
+    }
+
+    #[tokio::test]
+    async fn test_distribution_and_utilization_endpoints() {
+        let ctx = TestContext::in_memory();
+        let vaults = &ctx.state.repos.vaults;
+        vaults.update_snapshot("a", 500, 500, 0).await.unwrap();
+        vaults.update_snapshot("b", 5_000, 5_000, 0).await.unwrap();
+        vaults.update_snapshot("c", 0, 0, 0).await.unwrap();
+        vaults.add_locked("b", 1_100).await.unwrap();
+
+        let (status, body) = get_json(&ctx, "/analytics/distribution").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            body["buckets"],
+            serde_json::json!([1, 0, 1, 1, 0, 0, 0, 0, 0, 0])
+        );
+
+        let (status, body) = get_json(&ctx, "/analytics/utilization").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["utilization"], 0.2);
+    }
+}
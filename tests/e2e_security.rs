@@ -145,11 +145,51 @@ mod tests {
             .await.expect("Failed to get locked");
         let total = db::get_vault(&ctx.pool, &owner)
             .await.expect("Failed to get vault")
-            .map(|(_, total)| total)
+            .map(|v| v.total_balance)
             .unwrap_or(0);
         
         assert!(locked <= total, "Locked balance should not exceed total");
         
         ctx.cleanup().await;
     }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_request_withdraw_requires_second_factor() {
+        use axum::{
+            body::Body,
+            http::{header, Request, StatusCode},
+        };
+        use solana_sdk::signature::{Keypair, Signer};
+
+        let ctx = TestContext::new().await;
+        let keypair = Keypair::new();
+        let owner = keypair.pubkey().to_string();
+        db::twofa_upsert(&ctx.pool, &owner, "v1:sealed", true)
+            .await
+            .expect("Failed to enable 2FA");
+        let nonce = generate_test_signature();
+        db::insert_nonce(&ctx.pool, &nonce, &owner, "request_withdraw", 300)
+            .await
+            .expect("Failed to insert nonce");
+        let message = format!("request_withdraw:{owner}:100:{nonce}");
+        let body = serde_json::json!({
+            "owner": owner,
+            "amount": 100,
+            "nonce": nonce,
+            "signature": keypair.sign_message(message.as_bytes()).to_string(),
+        });
+
+        // Wallet signature and nonce are valid, but the owner has 2FA enabled
+        let request = Request::post("/vault/request-withdraw")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let (status, body) = send(&ctx, request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"], "2fa code required");
+
+        let _ = db::twofa_delete(&ctx.pool, &owner).await;
+        ctx.cleanup().await;
+    }
 }
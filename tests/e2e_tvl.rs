@@ -0,0 +1,197 @@
+// End-to-end tests for TVL: breakdown from vault balances, cache invalidation and the
+// snapshot-based series
+
+mod test_utils;
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use axum::http::StatusCode;
+    use cvmsback::{
+        cache::KeyClass,
+        db,
+        ledger::{self, EntryKind},
+        tvl::Tvl,
+    };
+    use time::{Date, Month, OffsetDateTime};
+
+    /// February 2021; the 1st is a Monday.
+    fn at(day: u8, hour: u8) -> OffsetDateTime {
+        Date::from_calendar_date(2021, Month::February, day)
+            .unwrap()
+            .with_hms(hour, 0, 0)
+            .unwrap()
+            .assume_utc()
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_tvl_breakdown_and_invalidation() {
+        let ctx = TestContext::new().await;
+        ctx.state.cache.invalidate_tvl().await;
+        let owner = TestContext::generate_test_owner();
+        create_test_vault(&ctx, &owner)
+            .await
+            .expect("Failed to create vault");
+        for (kind, amount) in [
+            (EntryKind::Deposit, 10_000),
+            (EntryKind::Lock, 2_500),
+            (EntryKind::YieldDeposit, 1_000),
+            (EntryKind::YieldAccrual, 50),
+            (EntryKind::WithdrawSubmitted, 500),
+        ] {
+            ledger::record(&ctx.pool, kind, &generate_test_signature(), &owner, amount)
+                .await
+                .expect("Failed to record");
+        }
+        // Balances of owners without a vault are not counted
+        ledger::record(
+            &ctx.pool,
+            EntryKind::Deposit,
+            &generate_test_signature(),
+            &TestContext::generate_test_owner(),
+            99_999,
+        )
+        .await
+        .expect("Failed to record");
+
+        let (status, body) = get_json(&ctx, "/vault/tvl").await;
+        assert_eq!(status, StatusCode::OK);
+        // The withdrawal in transit has left the vault
+        assert_eq!(body["tvl"], 9_550);
+        assert_eq!(body["available"], 6_000);
+        assert_eq!(body["locked"], 2_500);
+        assert_eq!(body["in_yield"], 1_050);
+        assert_eq!(body["cached"], false);
+
+        ledger::record(
+            &ctx.pool,
+            EntryKind::Deposit,
+            &generate_test_signature(),
+            &owner,
+            450,
+        )
+        .await
+        .expect("Failed to record");
+        let (_, body) = get_json(&ctx, "/vault/tvl").await;
+        assert_eq!(body["tvl"], 9_550);
+        assert_eq!(body["cached"], true);
+
+        ctx.state.cache.invalidate_tvl().await;
+        let (_, body) = get_json(&ctx, "/vault/tvl").await;
+        assert_eq!(body["tvl"], 10_000);
+        assert_eq!(body["cached"], false);
+
+        ctx.state.cache.invalidate_tvl().await;
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_tvl_series_granularities() {
+        let ctx = TestContext::new().await;
+        sqlx::query("DELETE FROM balance_snapshots WHERE period_start BETWEEN $1 AND $2")
+            .bind(at(1, 0))
+            .bind(at(28, 0))
+            .execute(&ctx.pool)
+            .await
+            .expect("Failed to clear snapshots");
+        let a = TestContext::generate_test_owner();
+        let b = TestContext::generate_test_owner();
+        for owner in [&a, &b] {
+            create_test_vault(&ctx, owner)
+                .await
+                .expect("Failed to create vault");
+        }
+        for (owner, kind, amount, created_at) in [
+            (&a, EntryKind::Deposit, 1_000, at(1, 10)),
+            (&b, EntryKind::Deposit, 500, at(2, 9)),
+            (&b, EntryKind::Lock, 200, at(3, 12)),
+            (&a, EntryKind::YieldDeposit, 300, at(9, 8)),
+        ] {
+            insert_test_ledger_entry_at(&ctx, owner, kind, amount, created_at)
+                .await
+                .expect("Failed to journal");
+        }
+        for day in 1..=10 {
+            db::balance_snapshot_from_ledger(&ctx.pool, "daily", at(day, 0), at(day + 1, 0))
+                .await
+                .expect("Failed to snapshot");
+        }
+
+        let range = "from=2021-02-01T00:00:00Z&to=2021-02-10T00:00:00Z";
+        let (status, body) = get_json(
+            &ctx,
+            &format!("/analytics/tvl-series?granularity=daily&{range}"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let series = body["series"].as_array().expect("series");
+        assert_eq!(series.len(), 10);
+        assert_eq!(series[0]["t"], "2021-02-01T00:00:00Z");
+        assert_eq!(series[0]["tvl"], 1_000);
+        assert_eq!(series[2]["tvl"], 1_500);
+        assert_eq!(series[2]["locked"], 200);
+        assert_eq!(series[9]["in_yield"], 300);
+        assert_eq!(series[9]["available"], 1_000);
+
+        // Weeks close on their last daily snapshot within the range
+        let (_, body) = get_json(
+            &ctx,
+            &format!("/analytics/tvl-series?granularity=weekly&{range}"),
+        )
+        .await;
+        let series = body["series"].as_array().expect("series");
+        assert_eq!(series.len(), 2);
+        assert_eq!(series[0]["t"], "2021-02-01T00:00:00Z");
+        assert_eq!(series[0]["in_yield"], 0);
+        assert_eq!(series[1]["t"], "2021-02-08T00:00:00Z");
+        assert_eq!(series[1]["in_yield"], 300);
+        assert_eq!(series[1]["tvl"], 1_500);
+
+        let (_, body) = get_json(
+            &ctx,
+            &format!("/analytics/tvl-series?granularity=monthly&{range}"),
+        )
+        .await;
+        assert_eq!(body["series"].as_array().expect("series").len(), 1);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn test_tvl_endpoint_serves_cached_breakdown() {
+        let ctx = TestContext::in_memory();
+        let tvl = Tvl {
+            available: 700,
+            locked: 200,
+            in_yield: 100,
+        };
+        ctx.state
+            .cache
+            .get_or_load(KeyClass::Tvl, "", move || async move {
+                Ok::<_, sqlx::Error>(tvl)
+            })
+            .await
+            .expect("Failed to load");
+
+        let (status, body) = get_json(&ctx, "/vault/tvl").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["tvl"], 1_000);
+        assert_eq!(body["in_yield"], 100);
+        assert_eq!(body["cached"], true);
+    }
+
+    #[tokio::test]
+    async fn test_tvl_series_rejects_bad_params() {
+        let ctx = TestContext::in_memory();
+        let (status, _) = get_json(&ctx, "/analytics/tvl-series?granularity=yearly").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = get_json(
+            &ctx,
+            "/analytics/tvl-series?from=2021-02-02T00:00:00Z&to=2021-02-01T00:00:00Z",
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,146 @@
+// End-to-end tests for admin 2FA resets applied by the background worker after the cooling-off
+
+mod test_utils;
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use cvmsback::{db, tasks::twofa::apply_due_resets};
+
+    async fn reset_status(ctx: &TestContext, id: i64) -> String {
+        sqlx::query_scalar("SELECT status FROM twofa_resets WHERE id = $1")
+            .bind(id)
+            .fetch_one(&ctx.pool)
+            .await
+            .expect("Failed to load reset")
+    }
+
+    async fn cleanup(ctx: &TestContext, owner: &str) {
+        let _ = sqlx::query("DROP TRIGGER IF EXISTS twofa_delete_fails ON twofa")
+            .execute(&ctx.pool)
+            .await;
+        let _ = sqlx::query("DROP FUNCTION IF EXISTS twofa_delete_fails()")
+            .execute(&ctx.pool)
+            .await;
+        let _ = sqlx::query("DELETE FROM twofa_resets WHERE owner = $1")
+            .bind(owner)
+            .execute(&ctx.pool)
+            .await;
+        let _ = db::twofa_delete(&ctx.pool, owner).await;
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_reset_stays_pending_when_delete_fails() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        db::twofa_upsert(&ctx.pool, &owner, "v1:sealed", true)
+            .await
+            .expect("Failed to enable 2FA");
+        db::twofa_replace_recovery_codes(&ctx.pool, &owner, &["hash".to_string()])
+            .await
+            .expect("Failed to store recovery codes");
+        let (id, _, created) = db::twofa_reset_request(&ctx.pool, &owner, "admin", None, 0)
+            .await
+            .expect("Failed to request reset");
+        assert!(created);
+
+        sqlx::query(
+            "CREATE FUNCTION twofa_delete_fails() RETURNS trigger AS $$
+             BEGIN RAISE EXCEPTION 'delete refused'; END $$ LANGUAGE plpgsql",
+        )
+        .execute(&ctx.pool)
+        .await
+        .expect("Failed to create function");
+        sqlx::query(&format!(
+            "CREATE TRIGGER twofa_delete_fails BEFORE DELETE ON twofa FOR EACH ROW
+             WHEN (OLD.owner = '{owner}') EXECUTE FUNCTION twofa_delete_fails()"
+        ))
+        .execute(&ctx.pool)
+        .await
+        .expect("Failed to create trigger");
+
+        apply_due_resets(&ctx.state)
+            .await
+            .expect("Failed to apply resets");
+        assert_eq!(reset_status(&ctx, id).await, "pending");
+        assert!(db::twofa_get(&ctx.pool, &owner).await.unwrap().is_some());
+        // The recovery codes are deleted in the same transaction
+        let codes: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM twofa_recovery_codes WHERE owner = $1")
+                .bind(&owner)
+                .fetch_one(&ctx.pool)
+                .await
+                .unwrap();
+        assert_eq!(codes, 1);
+
+        // Retried on the next run once the delete goes through
+        sqlx::query("DROP TRIGGER twofa_delete_fails ON twofa")
+            .execute(&ctx.pool)
+            .await
+            .expect("Failed to drop trigger");
+        apply_due_resets(&ctx.state)
+            .await
+            .expect("Failed to apply resets");
+        assert_eq!(reset_status(&ctx, id).await, "applied");
+        assert!(db::twofa_get(&ctx.pool, &owner).await.unwrap().is_none());
+
+        cleanup(&ctx, &owner).await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_cancelled_reset_is_not_applied() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        db::twofa_upsert(&ctx.pool, &owner, "v1:sealed", true)
+            .await
+            .expect("Failed to enable 2FA");
+        let (id, _, _) = db::twofa_reset_request(&ctx.pool, &owner, "admin", None, 0)
+            .await
+            .expect("Failed to request reset");
+        assert_eq!(
+            db::twofa_reset_cancel(&ctx.pool, &owner).await.unwrap(),
+            Some(id)
+        );
+
+        assert!(!db::twofa_reset_apply(&ctx.pool, id, &owner).await.unwrap());
+        assert_eq!(reset_status(&ctx, id).await, "cancelled");
+        assert!(db::twofa_get(&ctx.pool, &owner).await.unwrap().is_some());
+
+        cleanup(&ctx, &owner).await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_concurrent_requests_leave_one_pending_reset() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        let requests = (0..8).map(|i| {
+            let pool = ctx.pool.clone();
+            let owner = owner.clone();
+            tokio::spawn(async move {
+                db::twofa_reset_request(&pool, &owner, &format!("admin{i}"), None, 3600).await
+            })
+        });
+        let results: Vec<_> = futures::future::join_all(requests)
+            .await
+            .into_iter()
+            .map(|r| r.unwrap().expect("Failed to request reset"))
+            .collect();
+
+        assert_eq!(results.iter().filter(|(_, _, created)| *created).count(), 1);
+        assert!(results.iter().all(|(id, _, _)| *id == results[0].0));
+        let pending: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM twofa_resets WHERE owner = $1 AND status = 'pending'",
+        )
+        .bind(&owner)
+        .fetch_one(&ctx.pool)
+        .await
+        .unwrap();
+        assert_eq!(pending, 1);
+
+        cleanup(&ctx, &owner).await;
+    }
+}
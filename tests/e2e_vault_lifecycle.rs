@@ -95,10 +95,10 @@ mod tests {
             .await.expect("Failed to list transactions");
         
         assert_eq!(transactions.len(), 2); // deposit and withdraw
-        assert!(transactions.iter().any(|(_, sig, _, kind, _)| 
-            sig == &deposit_sig && kind == "deposit"));
-        assert!(transactions.iter().any(|(_, sig, _, kind, _)| 
-            sig == &withdraw_sig && kind == "withdraw"));
+        assert!(transactions.iter().any(|tx| 
+            tx.signature == deposit_sig && tx.kind == "deposit"));
+        assert!(transactions.iter().any(|tx| 
+            tx.signature == withdraw_sig && tx.kind == "withdraw"));
         
         ctx.cleanup().await;
     }
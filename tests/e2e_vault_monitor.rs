@@ -5,6 +5,10 @@ mod test_utils;
 #[cfg(test)]
 mod tests {
     use crate::test_utils::*;
+    use cvmsback::{
+        db,
+        ledger::{self, EntryKind},
+    };
     use sqlx::Row;
 
     #[tokio::test]
@@ -17,27 +21,33 @@ mod tests {
         let owner2 = TestContext::generate_test_owner();
         let owner3 = TestContext::generate_test_owner();
         
+        for owner in [&owner1, &owner2, &owner3] {
+            create_test_vault(&ctx, owner).await.expect("Failed to create vault");
+        }
+        
         // Deposits
-        insert_test_transaction(&ctx, &owner1, "sig1", 10000, "deposit", "confirmed")
-            .await.expect("Failed to insert");
-        insert_test_transaction(&ctx, &owner2, "sig2", 20000, "deposit", "confirmed")
-            .await.expect("Failed to insert");
-        insert_test_transaction(&ctx, &owner3, "sig3", 15000, "deposit", "confirmed")
-            .await.expect("Failed to insert");
+        for (owner, amount) in [(&owner1, 10000), (&owner2, 20000), (&owner3, 15000)] {
+            ledger::record(&ctx.pool, EntryKind::Deposit, &generate_test_signature(), owner, amount)
+                .await.expect("Failed to record");
+        }
         
         // Withdrawals
-        insert_test_transaction(&ctx, &owner1, "sig4", 3000, "withdraw", "confirmed")
-            .await.expect("Failed to insert");
+        ledger::record_withdraw_confirmed(&ctx.pool, &generate_test_signature(), &owner1, 3000)
+            .await.expect("Failed to record");
+        
+        // Locks and unlocks move funds within the vault
+        ledger::record(&ctx.pool, EntryKind::Lock, &generate_test_signature(), &owner2, 4000)
+            .await.expect("Failed to record");
+        ledger::record(&ctx.pool, EntryKind::Unlock, &generate_test_signature(), &owner2, 1000)
+            .await.expect("Failed to record");
         
         // Calculate TVL
-        let row = sqlx::query_scalar::<_, i64>(
-            "SELECT COALESCE(SUM(CASE WHEN kind = 'deposit' THEN amount ELSE -amount END), 0) AS tvl FROM transactions"
-        )
-        .fetch_one(&ctx.pool)
-        .await.expect("Failed to calculate TVL");
+        let tvl = db::tvl_current(&ctx.pool)
+            .await.expect("Failed to calculate TVL");
         
         // Expected: 10000 + 20000 + 15000 - 3000 = 42000
-        assert_eq!(row, 42000);
+        assert_eq!(tvl.total(), 42000);
+        assert_eq!(tvl.locked, 3000);
         
         ctx.cleanup().await;
     }
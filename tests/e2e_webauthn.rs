@@ -0,0 +1,278 @@
+// WebAuthn / passkey tests driven by a software authenticator
+
+mod test_utils;
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use ciborium::value::Value;
+    use cvmsback::db;
+    use cvmsback::security::{SecondFactorProof, SecondFactorVerifier, WebAuthnVerifier};
+    use cvmsback::webauthn::{
+        b64url_encode, verify_assertion, verify_registration, Assertion, RelyingParty,
+    };
+    use p256::ecdsa::{signature::Signer, Signature, SigningKey};
+    use sha2::{Digest, Sha256};
+
+    /// Minimal ES256 authenticator: "none" attestation, increments its counter on every use.
+    struct SoftAuthenticator {
+        key: SigningKey,
+        credential_id: Vec<u8>,
+        counter: u32,
+    }
+
+    impl SoftAuthenticator {
+        fn new(seed: u8) -> Self {
+            Self {
+                key: SigningKey::from_bytes(&[seed; 32]).unwrap(),
+                credential_id: vec![seed; 16],
+                counter: 0,
+            }
+        }
+
+        fn client_data(kind: &str, rp: &RelyingParty, challenge: &str) -> Vec<u8> {
+            serde_json::json!({
+                "type": kind,
+                "challenge": b64url_encode(challenge.as_bytes()),
+                "origin": rp.origin,
+            })
+            .to_string()
+            .into_bytes()
+        }
+
+        fn auth_data(&self, rp: &RelyingParty, flags: u8) -> Vec<u8> {
+            let mut data = Sha256::digest(rp.id.as_bytes()).to_vec();
+            data.push(flags);
+            data.extend_from_slice(&self.counter.to_be_bytes());
+            data
+        }
+
+        fn cose_key(&self) -> Vec<u8> {
+            let point = self.key.verifying_key().to_encoded_point(false);
+            let cose = Value::Map(vec![
+                (Value::Integer(1.into()), Value::Integer(2.into())),
+                (Value::Integer(3.into()), Value::Integer((-7).into())),
+                (Value::Integer((-1).into()), Value::Integer(1.into())),
+                (Value::Integer((-2).into()), Value::Bytes(point.x().unwrap().to_vec())),
+                (Value::Integer((-3).into()), Value::Bytes(point.y().unwrap().to_vec())),
+            ]);
+            let mut out = Vec::new();
+            ciborium::ser::into_writer(&cose, &mut out).unwrap();
+            out
+        }
+
+        /// Returns (clientDataJSON, attestationObject) for `navigator.credentials.create`.
+        fn create(&self, rp: &RelyingParty, challenge: &str) -> (Vec<u8>, Vec<u8>) {
+            let mut auth_data = self.auth_data(rp, 0x41);
+            auth_data.extend_from_slice(&[0u8; 16]);
+            auth_data.extend_from_slice(&(self.credential_id.len() as u16).to_be_bytes());
+            auth_data.extend_from_slice(&self.credential_id);
+            auth_data.extend_from_slice(&self.cose_key());
+            let attestation = Value::Map(vec![
+                (Value::Text("fmt".into()), Value::Text("none".into())),
+                (Value::Text("attStmt".into()), Value::Map(vec![])),
+                (Value::Text("authData".into()), Value::Bytes(auth_data)),
+            ]);
+            let mut out = Vec::new();
+            ciborium::ser::into_writer(&attestation, &mut out).unwrap();
+            (Self::client_data("webauthn.create", rp, challenge), out)
+        }
+
+        fn get(&mut self, rp: &RelyingParty, challenge: &str) -> Assertion {
+            self.counter += 1;
+            let client_data = Self::client_data("webauthn.get", rp, challenge);
+            let auth_data = self.auth_data(rp, 0x05);
+            let signed = [&auth_data[..], &Sha256::digest(&client_data)[..]].concat();
+            let signature: Signature = self.key.sign(&signed);
+            Assertion {
+                credential_id: b64url_encode(&self.credential_id),
+                client_data_json: b64url_encode(&client_data),
+                authenticator_data: b64url_encode(&auth_data),
+                signature: b64url_encode(signature.to_der().as_bytes()),
+            }
+        }
+    }
+
+    fn rp() -> RelyingParty {
+        RelyingParty {
+            id: "localhost".to_string(),
+            origin: "http://localhost:8080".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_register_then_assert() {
+        let rp = rp();
+        let mut authenticator = SoftAuthenticator::new(1);
+        let (client_data, attestation) = authenticator.create(&rp, "reg-nonce");
+        let credential = verify_registration(&rp, "reg-nonce", &client_data, &attestation).unwrap();
+        assert_eq!(credential.credential_id, b64url_encode(&authenticator.credential_id));
+        assert_eq!(credential.public_key.len(), 65);
+
+        let assertion = authenticator.get(&rp, "assert-nonce");
+        let count = verify_assertion(&rp, "assert-nonce", &credential.public_key, &assertion).unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_registration_rejects_wrong_challenge_and_origin() {
+        let rp = rp();
+        let authenticator = SoftAuthenticator::new(2);
+        let (client_data, attestation) = authenticator.create(&rp, "reg-nonce");
+        assert!(verify_registration(&rp, "other-nonce", &client_data, &attestation).is_err());
+
+        let evil = RelyingParty {
+            id: rp.id.clone(),
+            origin: "https://evil.example".to_string(),
+        };
+        assert!(verify_registration(&evil, "reg-nonce", &client_data, &attestation).is_err());
+    }
+
+    #[test]
+    fn test_assertion_rejects_other_key_and_tampering() {
+        let rp = rp();
+        let mut authenticator = SoftAuthenticator::new(3);
+        let (client_data, attestation) = authenticator.create(&rp, "reg");
+        let credential = verify_registration(&rp, "reg", &client_data, &attestation).unwrap();
+
+        let mut impostor = SoftAuthenticator::new(4);
+        let forged = impostor.get(&rp, "challenge");
+        assert!(verify_assertion(&rp, "challenge", &credential.public_key, &forged).is_err());
+
+        let mut assertion = authenticator.get(&rp, "challenge");
+        assert!(verify_assertion(&rp, "other", &credential.public_key, &assertion).is_err());
+        assertion.authenticator_data = b64url_encode(&authenticator.auth_data(&rp, 0x05)[..36]);
+        assert!(verify_assertion(&rp, "challenge", &credential.public_key, &assertion).is_err());
+    }
+
+    #[test]
+    fn test_webauthn_verifier_ignores_totp_proof() {
+        let rp = rp();
+        let mut authenticator = SoftAuthenticator::new(5);
+        let (client_data, attestation) = authenticator.create(&rp, "reg");
+        let credential = verify_registration(&rp, "reg", &client_data, &attestation).unwrap();
+        let verifier = WebAuthnVerifier {
+            rp: &rp,
+            challenge: "c1",
+            public_key: &credential.public_key,
+        };
+        let assertion = authenticator.get(&rp, "c1");
+        assert_eq!(verifier.verify(&SecondFactorProof::WebAuthn(assertion)), Some(1));
+        assert_eq!(verifier.verify(&SecondFactorProof::Totp("123456".into())), None);
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_credential_sign_count_must_advance() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        let rp = rp();
+        let mut authenticator = SoftAuthenticator::new(6);
+        let (client_data, attestation) = authenticator.create(&rp, "reg");
+        let credential = verify_registration(&rp, "reg", &client_data, &attestation).unwrap();
+
+        let inserted = db::webauthn_credential_insert(
+            &ctx.pool,
+            &owner,
+            &credential.credential_id,
+            &credential.public_key,
+            credential.sign_count as i64,
+            Some("soft key"),
+        )
+        .await
+        .unwrap();
+        assert!(inserted);
+        assert_eq!(db::webauthn_credential_count(&ctx.pool, &owner).await.unwrap(), 1);
+
+        let assertion = authenticator.get(&rp, "c1");
+        let count = verify_assertion(&rp, "c1", &credential.public_key, &assertion).unwrap();
+        assert!(db::webauthn_credential_touch(&ctx.pool, &owner, &credential.credential_id, count as i64)
+            .await
+            .unwrap());
+        // Replaying the same counter looks like a cloned authenticator
+        assert!(!db::webauthn_credential_touch(&ctx.pool, &owner, &credential.credential_id, count as i64)
+            .await
+            .unwrap());
+
+        db::twofa_delete(&ctx.pool, &owner).await.unwrap();
+        assert_eq!(db::webauthn_credential_count(&ctx.pool, &owner).await.unwrap(), 0);
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_passkey_only_owner_gets_recovery_codes_and_admin_reset() {
+        use axum::{
+            body::Body,
+            http::{header, Request, StatusCode},
+        };
+        use solana_sdk::signature::{Keypair, Signer};
+
+        let ctx = TestContext::new().await;
+        let keypair = Keypair::new();
+        let owner = keypair.pubkey().to_string();
+        let post = |uri: &str, body: serde_json::Value| {
+            Request::post(uri)
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap()
+        };
+        let signed_nonce = |purpose: &str| {
+            let nonce = generate_test_signature();
+            let signature = keypair
+                .sign_message(format!("{purpose}:{owner}:{nonce}").as_bytes())
+                .to_string();
+            (nonce, signature)
+        };
+
+        // First passkey: recovery codes are issued with it
+        let rp = rp();
+        let (nonce, signature) = signed_nonce("webauthn_register");
+        db::insert_nonce(&ctx.pool, &nonce, &owner, "webauthn_register", 300)
+            .await
+            .unwrap();
+        let (client_data, attestation) = SoftAuthenticator::new(9).create(&rp, &nonce);
+        let body = serde_json::json!({
+            "owner": owner,
+            "nonce": nonce,
+            "signature": signature,
+            "client_data_json": b64url_encode(&client_data),
+            "attestation_object": b64url_encode(&attestation),
+        });
+        let (status, body) = send(&ctx, post("/2fa/webauthn/register", body)).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        let codes = body["recovery_codes"].as_array().unwrap();
+        assert_eq!(codes.len(), 10);
+        let code = codes[0].as_str().unwrap().to_string();
+
+        // An admin can schedule a reset for a passkey-only owner
+        let (status, _) = admin_post(
+            &ctx,
+            "/admin/2fa/reset",
+            serde_json::json!({ "owner": owner, "reason": "lost passkey" }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::ACCEPTED);
+
+        // The recovery code disables the passkey without a TOTP secret
+        let (nonce, signature) = signed_nonce("twofa_disable");
+        db::insert_nonce(&ctx.pool, &nonce, &owner, "twofa_disable", 300)
+            .await
+            .unwrap();
+        let body = serde_json::json!({
+            "owner": owner,
+            "nonce": nonce,
+            "signature": signature,
+            "recovery_code": code,
+        });
+        let (status, body) = send(&ctx, post("/2fa/disable", body)).await;
+        assert_eq!(status, StatusCode::OK, "{body}");
+        assert_eq!(db::webauthn_credential_count(&ctx.pool, &owner).await.unwrap(), 0);
+
+        let _ = sqlx::query("DELETE FROM twofa_resets WHERE owner = $1")
+            .bind(&owner)
+            .execute(&ctx.pool)
+            .await;
+        ctx.cleanup().await;
+    }
+}
@@ -0,0 +1,274 @@
+// Webhook delivery tests: signing over real HTTP and the dead-letter lifecycle
+
+mod test_utils;
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::*;
+    use axum::{http::HeaderMap, routing::post, Router};
+    use cvmsback::db;
+    use cvmsback::webhooks::{
+        deliver, generate_secret, verify_signature, SIGNATURE_HEADER, TIMESTAMP_HEADER,
+    };
+    use tokio::sync::mpsc;
+
+    /// Starts a receiver on an ephemeral port that forwards (headers, body) and answers `status`.
+    async fn receiver(status: u16) -> (String, mpsc::UnboundedReceiver<(HeaderMap, String)>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let app = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: String| {
+                let tx = tx.clone();
+                async move {
+                    let _ = tx.send((headers, body));
+                    axum::http::StatusCode::from_u16(status).unwrap()
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        (format!("http://{addr}/hook"), rx)
+    }
+
+    #[tokio::test]
+    async fn test_delivery_is_signed() {
+        let (url, mut rx) = receiver(200).await;
+        let secret = generate_secret();
+        let body = serde_json::json!({ "type": "tvl", "data": { "tvl": 1 } }).to_string();
+
+        deliver(true, &url, &secret, None, 7, body.clone()).await.unwrap();
+
+        let (headers, received) = rx.recv().await.unwrap();
+        assert_eq!(received, body);
+        let timestamp: i64 = headers[TIMESTAMP_HEADER].to_str().unwrap().parse().unwrap();
+        let signature = headers[SIGNATURE_HEADER].to_str().unwrap();
+        assert!(verify_signature(&secret, timestamp, received.as_bytes(), signature));
+        assert!(!verify_signature("whsec_wrong", timestamp, received.as_bytes(), signature));
+    }
+
+    #[tokio::test]
+    async fn test_non_2xx_is_an_error() {
+        let (url, _rx) = receiver(503).await;
+        assert!(deliver(true, &url, "whsec_test", None, 1, "{}".to_string()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_private_targets_and_redirects_are_refused() {
+        let (url, mut rx) = receiver(200).await;
+        let localhost = url.replace("127.0.0.1", "localhost");
+        for target in [&url, &localhost] {
+            let err = deliver(false, target, "whsec_test", None, 1, "{}".to_string())
+                .await
+                .unwrap_err();
+            assert!(err.contains("blocked address"), "{err}");
+        }
+        assert!(rx.try_recv().is_err());
+
+        // A redirect is not followed and does not count as delivered
+        let target = url.clone();
+        let app = Router::new().route(
+            "/hook",
+            post(move || async move { axum::response::Redirect::temporary(&target) }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        let redirecting = format!("http://{addr}/hook");
+        let result = deliver(true, &redirecting, "whsec_test", None, 1, "{}".to_string()).await;
+        assert!(result.is_err());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_dead_letter_replay_requeues_delivery() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        let endpoint_id = db::webhook_endpoint_insert(
+            &ctx.pool,
+            Some(&owner),
+            "http://127.0.0.1:9/hook",
+            "sealed",
+            &[],
+            &owner,
+        )
+        .await
+        .unwrap();
+        let event_id = uuid::Uuid::new_v4();
+        let payload = serde_json::json!({ "type": "deposit" });
+        for _ in 0..2 {
+            db::webhook_delivery_enqueue(
+                &ctx.pool,
+                endpoint_id,
+                "http://127.0.0.1:9/hook",
+                Some(event_id),
+                "deposit",
+                &payload,
+            )
+            .await
+            .unwrap();
+        }
+        let due: Vec<_> = db::webhook_deliveries_claim(&ctx.pool, 1000, 60)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|d| d.3 == Some(event_id))
+            .collect();
+        assert_eq!(due.len(), 1, "enqueue is idempotent per endpoint and event");
+        let delivery_id = due[0].0;
+
+        db::webhook_delivery_dead_letter(&ctx.pool, delivery_id, "connection refused")
+            .await
+            .unwrap();
+        let dead = db::webhook_dead_letters_list(&ctx.pool, 100).await.unwrap();
+        let letter = dead.iter().find(|d| d.1 == delivery_id).unwrap();
+        assert_eq!(letter.6.as_deref(), Some("connection refused"));
+
+        assert!(db::webhook_dead_letter_replay(&ctx.pool, letter.0).await.unwrap());
+        assert!(!db::webhook_dead_letter_replay(&ctx.pool, letter.0).await.unwrap());
+        let due = db::webhook_deliveries_claim(&ctx.pool, 1000, 60).await.unwrap();
+        assert!(due.iter().any(|d| d.0 == delivery_id && d.5 == 0));
+
+        assert!(db::webhook_endpoint_delete(&ctx.pool, &owner, endpoint_id).await.unwrap());
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_concurrent_dispatchers_claim_disjoint_deliveries() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        let url = "http://127.0.0.1:9/hook";
+        let endpoint_id =
+            db::webhook_endpoint_insert(&ctx.pool, Some(&owner), url, "sealed", &[], &owner)
+                .await
+                .unwrap();
+        let payload = serde_json::json!({ "type": "deposit" });
+        let mut ids = std::collections::HashSet::new();
+        for _ in 0..40 {
+            let event_id = uuid::Uuid::new_v4();
+            db::webhook_delivery_enqueue(
+                &ctx.pool,
+                endpoint_id,
+                url,
+                Some(event_id),
+                "deposit",
+                &payload,
+            )
+            .await
+            .unwrap();
+            ids.insert(event_id);
+        }
+
+        let ours = |rows: Result<Vec<db::WebhookDeliveryRow>, sqlx::Error>| -> Vec<uuid::Uuid> {
+            rows.unwrap()
+                .into_iter()
+                .filter_map(|d| d.3)
+                .filter(|id| ids.contains(id))
+                .collect()
+        };
+
+        // Each delivery goes to exactly one of the racing dispatchers
+        let claims = futures::future::join_all(
+            (0..4).map(|_| db::webhook_deliveries_claim(&ctx.pool, 15, 60)),
+        )
+        .await;
+        let mut claimed = Vec::new();
+        for batch in claims {
+            claimed.extend(ours(batch));
+        }
+        let unique: std::collections::HashSet<_> = claimed.iter().collect();
+        assert_eq!(unique.len(), claimed.len(), "a delivery was claimed twice");
+        let rest = ours(db::webhook_deliveries_claim(&ctx.pool, 1000, 60).await);
+        assert_eq!(claimed.len() + rest.len(), ids.len());
+
+        // Claimed rows are not handed out again until their lease expires
+        let again = ours(db::webhook_deliveries_claim(&ctx.pool, 1000, 60).await);
+        assert!(again.is_empty());
+        sqlx::query(
+            "UPDATE webhook_deliveries SET locked_until = NOW() - INTERVAL '1 second'
+             WHERE endpoint_id = $1",
+        )
+        .bind(endpoint_id)
+        .execute(&ctx.pool)
+        .await
+        .unwrap();
+        let expired = ours(db::webhook_deliveries_claim(&ctx.pool, 1000, 60).await);
+        assert_eq!(expired.len(), ids.len());
+
+        let deleted = db::webhook_endpoint_delete(&ctx.pool, &owner, endpoint_id).await;
+        assert!(deleted.unwrap());
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_signer_notifications_use_registered_endpoints() {
+        let ctx = TestContext::new().await;
+        let signer = TestContext::generate_test_owner();
+        let other = TestContext::generate_test_owner();
+        let mut ids = Vec::new();
+        let url = "https://signer.example.com/hook";
+        for (owner, event_types) in [
+            (&signer, vec!["ms_request_signature".to_string()]),
+            (&signer, vec!["deposit".to_string()]),
+            (&signer, vec![]),
+            (&other, vec!["ms_partial_tx_ready".to_string()]),
+        ] {
+            let id = db::webhook_endpoint_insert(
+                &ctx.pool,
+                Some(owner),
+                url,
+                "sealed",
+                &event_types,
+                owner,
+            )
+            .await
+            .unwrap();
+            ids.push(id);
+        }
+
+        // Only the signer's endpoints that accept the notification, filtered or catch-all
+        let signers = vec![signer.clone(), other.clone()];
+        let found = db::webhook_endpoints_for_owners(&ctx.pool, &signers, "ms_request_signature")
+            .await
+            .unwrap();
+        let found: Vec<i64> = found.into_iter().map(|(id, ..)| id).collect();
+        assert_eq!(found, vec![ids[0], ids[2]]);
+
+        // Deliveries always reference an endpoint, so they are signed with its secret
+        let endpoint_less = sqlx::query(
+            "INSERT INTO webhook_deliveries (url, event_type, payload)
+             VALUES ('https://signer.example.com/hook', 'ms_request_signature', '{}')",
+        )
+        .execute(&ctx.pool)
+        .await;
+        assert!(endpoint_less.is_err());
+
+        for (owner, id) in [&signer, &signer, &signer, &other].into_iter().zip(ids) {
+            let deleted = db::webhook_endpoint_delete(&ctx.pool, owner, id).await;
+            assert!(deleted.unwrap());
+        }
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_fanout_lock_admits_one_dispatcher() {
+        let ctx = TestContext::new().await;
+        let mut first = ctx.pool.begin().await.unwrap();
+        let mut second = ctx.pool.begin().await.unwrap();
+        assert!(db::webhook_fanout_try_lock(&mut *first).await.unwrap());
+        assert!(!db::webhook_fanout_try_lock(&mut *second).await.unwrap());
+
+        // Released with the transaction
+        first.commit().await.unwrap();
+        assert!(db::webhook_fanout_try_lock(&mut *second).await.unwrap());
+        second.rollback().await.unwrap();
+    }
+}
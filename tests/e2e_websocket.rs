@@ -5,7 +5,32 @@ mod test_utils;
 #[cfg(test)]
 mod tests {
     use crate::test_utils::*;
+    use axum::{
+        body::{Body, BodyDataStream},
+        http::{header, Request, StatusCode},
+    };
+    use cvmsback::config::SlowConsumerPolicy;
+    use cvmsback::db;
+    use cvmsback::events::{
+        BalanceUpdate, EventEnvelope, Severity, Topic, TvlUpdate, TxEvent, VaultEvent,
+    };
     use cvmsback::notify::Notifier;
+    use futures::{SinkExt, StreamExt};
+    use serde_json::json;
+    use solana_sdk::signature::{Keypair, Signer};
+    use std::time::Duration;
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+    use tower::ServiceExt;
+
+    type WsClient = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    fn tx(amount: u64, signature: &str) -> TxEvent {
+        TxEvent {
+            amount: Some(amount),
+            signature: signature.to_string(),
+        }
+    }
 
     #[tokio::test]
     #[ignore] // Requires WebSocket server
@@ -15,17 +40,23 @@ mod tests {
         
         let notifier = Notifier::new(1024);
         
-        // Test that notifier channels work
-        let _ = notifier.deposit_tx.send("test_deposit".to_string());
-        let _ = notifier.withdraw_tx.send("test_withdraw".to_string());
-        let _ = notifier.lock_tx.send("test_lock".to_string());
-        let _ = notifier.unlock_tx.send("test_unlock".to_string());
-        let _ = notifier.vault_balance_tx.send("test_balance".to_string());
-        let _ = notifier.tvl_tx.send("test_tvl".to_string());
-        let _ = notifier.security_tx.send("test_security".to_string());
-        
-        // Verify channels are working (messages are sent)
-        // In real test, you'd subscribe and verify messages are received
+        // Test that notifier routes each event to its topic
+        let mut deposits = notifier.subscribe(Topic::DepositEvent);
+        let mut security = notifier.subscribe(Topic::SecurityAlert);
+        notifier.publish_for("owner", VaultEvent::Deposit(tx(1, "test_deposit")));
+        notifier.publish_for("owner", VaultEvent::Withdraw(tx(1, "test_withdraw")));
+        notifier.publish_for("owner", VaultEvent::Lock(tx(1, "test_lock")));
+        notifier.publish_for("owner", VaultEvent::Unlock(tx(1, "test_unlock")));
+        notifier.publish(EventEnvelope::new(VaultEvent::Tvl(TvlUpdate { tvl: 1 })));
+        notifier.publish_for(
+            "owner",
+            VaultEvent::security("test_security", Severity::Info, serde_json::json!({})),
+        );
+
+        let deposit = deposits.recv().await.unwrap();
+        assert_eq!(deposit.topic(), Topic::DepositEvent);
+        assert!(matches!(security.recv().await.unwrap().event, VaultEvent::SecurityAlert(_)));
+        assert!(deposits.try_recv().is_err());
     }
 
     #[tokio::test]
@@ -35,14 +66,14 @@ mod tests {
         let owner = TestContext::generate_test_owner();
         
         // Simulate balance update notification
-        let payload = serde_json::json!({
-            "owner": owner,
-            "balance": 50000,
-            "previous_balance": 40000,
-            "delta": 10000,
-        });
-        
-        let _ = ctx.state.notifier.vault_balance_tx.send(payload.to_string());
+        ctx.state.notifier.publish_for(
+            &owner,
+            VaultEvent::BalanceUpdate(BalanceUpdate {
+                balance: 50000,
+                previous_balance: Some(40000),
+                delta: Some(10000),
+            }),
+        );
         
         // In a real test, you'd connect via WebSocket and verify the message is received
     }
@@ -54,20 +85,14 @@ mod tests {
         let owner = TestContext::generate_test_owner();
         
         // Simulate deposit notification
-        let deposit_payload = serde_json::json!({
-            "owner": owner,
-            "amount": 10000,
-            "signature": "test_sig_deposit"
-        });
-        let _ = ctx.state.notifier.deposit_tx.send(deposit_payload.to_string());
+        ctx.state
+            .notifier
+            .publish_for(&owner, VaultEvent::Deposit(tx(10000, "test_sig_deposit")));
         
         // Simulate withdrawal notification
-        let withdraw_payload = serde_json::json!({
-            "owner": owner,
-            "amount": 5000,
-            "signature": "test_sig_withdraw"
-        });
-        let _ = ctx.state.notifier.withdraw_tx.send(withdraw_payload.to_string());
+        ctx.state
+            .notifier
+            .publish_for(&owner, VaultEvent::Withdraw(tx(5000, "test_sig_withdraw")));
         
         // In real test, verify WebSocket clients receive these messages
     }
@@ -79,20 +104,14 @@ mod tests {
         let owner = TestContext::generate_test_owner();
         
         // Simulate lock notification
-        let lock_payload = serde_json::json!({
-            "owner": owner,
-            "amount": 5000,
-            "signature": "test_sig_lock"
-        });
-        let _ = ctx.state.notifier.lock_tx.send(lock_payload.to_string());
+        ctx.state
+            .notifier
+            .publish_for(&owner, VaultEvent::Lock(tx(5000, "test_sig_lock")));
         
         // Simulate unlock notification
-        let unlock_payload = serde_json::json!({
-            "owner": owner,
-            "amount": 5000,
-            "signature": "test_sig_unlock"
-        });
-        let _ = ctx.state.notifier.unlock_tx.send(unlock_payload.to_string());
+        ctx.state
+            .notifier
+            .publish_for(&owner, VaultEvent::Unlock(tx(5000, "test_sig_unlock")));
     }
 
     #[tokio::test]
@@ -101,10 +120,9 @@ mod tests {
         let ctx = TestContext::new().await;
         
         // Simulate TVL update
-        let tvl_payload = serde_json::json!({
-            "tvl": 1000000
-        });
-        let _ = ctx.state.notifier.tvl_tx.send(tvl_payload.to_string());
+        ctx.state
+            .notifier
+            .publish(EventEnvelope::new(VaultEvent::Tvl(TvlUpdate { tvl: 1000000 })));
     }
 
     #[tokio::test]
@@ -114,11 +132,514 @@ mod tests {
         let owner = TestContext::generate_test_owner();
         
         // Simulate security alert
-        let alert_payload = serde_json::json!({
-            "type": "unusual_activity",
-            "owner": owner,
-            "count": 15
-        });
-        let _ = ctx.state.notifier.security_tx.send(alert_payload.to_string());
+        ctx.state.notifier.publish_for(
+            &owner,
+            VaultEvent::security(
+                "unusual_activity",
+                Severity::Warning,
+                serde_json::json!({ "count": 15 }),
+            ),
+        );
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_outbox_replay_from_cursor() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        let dropped = ctx.state.metrics.event_outbox_dropped.clone();
+        let notifier = Notifier::with_outbox(16, ctx.pool.clone(), dropped);
+
+        // Live subscriber sees the cursor each event was persisted under
+        let mut live = notifier.subscribe(Topic::DepositEvent);
+        for amount in 1..=3 {
+            notifier.publish_for(&owner, VaultEvent::Deposit(tx(amount, "outbox_sig")));
+        }
+        let mut cursors = Vec::new();
+        while cursors.len() < 3 {
+            let envelope = live.recv().await.unwrap();
+            if envelope.owner.as_deref() == Some(owner.as_str()) {
+                cursors.push(envelope.cursor.expect("persisted event has a cursor"));
+            }
+        }
+        assert!(cursors.windows(2).all(|w| w[0] < w[1]));
+
+        // A client that disconnected after the first event gets the rest replayed, then live ones
+        let mut resumed =
+            notifier.subscribe_from(&[Topic::DepositEvent], Some(owner.clone()), Some(cursors[0]));
+        assert_eq!(resumed.next().await.unwrap().cursor, Some(cursors[1]));
+        assert_eq!(resumed.next().await.unwrap().cursor, Some(cursors[2]));
+        notifier.publish_for(&owner, VaultEvent::Deposit(tx(4, "outbox_sig")));
+        let envelope = resumed.next().await.unwrap();
+        assert!(envelope.cursor.unwrap() > cursors[2]);
+
+        let replayed = notifier
+            .replay(&[Topic::DepositEvent], Some(&owner), 0, 10)
+            .await
+            .unwrap();
+        assert_eq!(replayed.len(), 4);
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_outbox_replay_keeps_global_topics_under_owner_filter() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        let other = TestContext::generate_test_owner();
+        let dropped = ctx.state.metrics.event_outbox_dropped.clone();
+        let notifier = Notifier::with_outbox(16, ctx.pool.clone(), dropped);
+        let topics = [Topic::DepositEvent, Topic::TvlUpdate];
+
+        let mut live = notifier.subscribe_from(&topics, None, None);
+        notifier.publish_for(&other, VaultEvent::Deposit(tx(1, "global_sig")));
+        notifier.publish(EventEnvelope::new(VaultEvent::Tvl(TvlUpdate { tvl: 7 })));
+        notifier.publish_for(&owner, VaultEvent::Deposit(tx(2, "global_sig")));
+        let first = live.next().await.unwrap().cursor.unwrap();
+        let last = loop {
+            let envelope = live.next().await.unwrap();
+            if envelope.owner.as_deref() == Some(owner.as_str()) {
+                break envelope.cursor.unwrap();
+            }
+        };
+
+        // The owner filter drops other owners' deposits but keeps the owner-less TVL update
+        let replayed = notifier
+            .replay(&topics, Some(&owner), first - 1, 100)
+            .await
+            .unwrap();
+        let replayed: Vec<_> = replayed
+            .into_iter()
+            .filter(|e| e.cursor.unwrap() <= last)
+            .collect();
+        assert_eq!(replayed.len(), 2);
+        assert_eq!(replayed[0].topic(), Topic::TvlUpdate);
+        assert_eq!(replayed[1].owner.as_deref(), Some(owner.as_str()));
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_outbox_cursor_never_skips_concurrent_writes() {
+        let ctx = TestContext::new().await;
+        let owner = TestContext::generate_test_owner();
+        let mut cursor = db::outbox_max_id(&ctx.pool).await.unwrap();
+
+        // Writers on separate connections, as several instances would be
+        for i in 0..40 {
+            let pool = ctx.pool.clone();
+            let owner = owner.clone();
+            tokio::spawn(async move {
+                let topic = Topic::DepositEvent.as_str();
+                let payload = json!({ "i": i });
+                let event_id = uuid::Uuid::new_v4();
+                db::outbox_insert(&pool, event_id, topic, Some(&owner), "deposit", &payload)
+                    .await
+                    .unwrap();
+            });
+        }
+
+        // A reader that moves its cursor to the last id it saw still sees every row
+        let mut seen = std::collections::HashSet::new();
+        let read_all = async {
+            while seen.len() < 40 {
+                let rows = db::outbox_after_all(&ctx.pool, cursor, 1000).await.unwrap();
+                for (id, _, row_owner, ..) in rows {
+                    cursor = id;
+                    if row_owner.as_deref() == Some(owner.as_str()) {
+                        seen.insert(id);
+                    }
+                }
+                tokio::task::yield_now().await;
+            }
+        };
+        let read = tokio::time::timeout(Duration::from_secs(10), read_all).await;
+        assert!(read.is_ok(), "cursor skipped a row committed late");
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    async fn test_http_event_feeds_require_a_session() {
+        let ctx = TestContext::in_memory();
+        let owner = TestContext::generate_test_owner();
+
+        // Owner-scoped topics are refused to anonymous requests, with or without an owner
+        for uri in [
+            "/events?topic=security_alert".to_string(),
+            format!("/events?topic=deposit_event&owner={owner}"),
+            "/events/stream?topics=tvl_update,security_alert".to_string(),
+            format!("/events/stream?topics=withdraw_event&owner={owner}"),
+        ] {
+            let (status, _) = get_json(&ctx, &uri).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{uri}");
+        }
+        let (status, _) = get_json(&ctx, "/events?topic=tvl_update").await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = admin_get(&ctx, "/events?topic=security_alert").await;
+        assert_eq!(status, StatusCode::OK);
+        let request = Request::get("/events?topic=security_alert")
+            .header(header::AUTHORIZATION, "Bearer not-a-token")
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send(&ctx, request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    /// Body of a `/events/session` request signing a fresh `ws_auth` nonce.
+    async fn signed_ws_auth(ctx: &TestContext, keypair: &Keypair) -> serde_json::Value {
+        let owner = keypair.pubkey().to_string();
+        let nonce = generate_test_signature();
+        cvmsback::db::insert_nonce(&ctx.pool, &nonce, &owner, "ws_auth", 300)
+            .await
+            .expect("Failed to insert nonce");
+        let signature = keypair.sign_message(format!("ws_auth:{owner}:{nonce}").as_bytes());
+        json!({ "owner": owner, "nonce": nonce, "signature": signature.to_string() })
+    }
+
+    /// Opens an event request and returns its status and body stream.
+    async fn open_events(
+        ctx: &TestContext,
+        uri: &str,
+        headers: &[(&str, &str)],
+    ) -> (StatusCode, BodyDataStream) {
+        let mut request = Request::get(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = cvmsback::api::router(ctx.state.clone())
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .expect("request failed");
+        (response.status(), response.into_body().into_data_stream())
+    }
+
+    /// Next SSE event on `stream` as its `id` and parsed `data`, skipping heartbeats.
+    async fn next_sse_event(
+        stream: &mut BodyDataStream,
+        buf: &mut String,
+    ) -> (i64, serde_json::Value) {
+        loop {
+            if let Some(end) = buf.find("\n\n") {
+                let block: String = buf.drain(..end + 2).collect();
+                let field = |name: &str| {
+                    block
+                        .lines()
+                        .find_map(|line| line.strip_prefix(name))
+                        .map(str::trim)
+                        .map(str::to_string)
+                };
+                if let (Some(id), Some(data)) = (field("id:"), field("data:")) {
+                    return (id.parse().unwrap(), serde_json::from_str(&data).unwrap());
+                }
+                continue;
+            }
+            let chunk = tokio::time::timeout(Duration::from_secs(10), stream.next())
+                .await
+                .expect("Timed out waiting for an event")
+                .expect("Stream ended")
+                .unwrap();
+            buf.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_http_event_feeds_accept_wallet_sessions() {
+        let ctx = TestContext::new().await;
+        let keypair = Keypair::new();
+        let owner = keypair.pubkey().to_string();
+        let body = signed_ws_auth(&ctx, &keypair).await;
+
+        let request = Request::post("/events/session")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = cvmsback::api::router(ctx.state.clone())
+            .oneshot(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let cookie = response.headers()[header::SET_COOKIE]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(cookie.starts_with("events_session="));
+        assert!(cookie.contains("HttpOnly"));
+        let session: serde_json::Value = serde_json::from_slice(
+            &axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(session["owner"], owner);
+        let token = session["token"].as_str().unwrap();
+
+        // The session is reusable, unlike the nonce it was exchanged for
+        let uri = format!("/events?topic=security_alert&owner={owner}");
+        let bearer = format!("Bearer {token}");
+        for _ in 0..2 {
+            let request = Request::get(&uri)
+                .header(header::AUTHORIZATION, &bearer)
+                .body(Body::empty())
+                .unwrap();
+            let (status, body) = send(&ctx, request).await;
+            assert_eq!(status, StatusCode::OK);
+            assert!(body["events"].is_array());
+        }
+        let request = Request::post("/events/session")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let (status, _) = send(&ctx, request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        // It only covers its own owner, and a tampered token is refused
+        let other = TestContext::generate_test_owner();
+        let request = Request::get(format!("/events?topic=security_alert&owner={other}"))
+            .header(header::AUTHORIZATION, &bearer)
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send(&ctx, request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let request = Request::get(&uri)
+            .header(header::COOKIE, format!("events_session={token}x"))
+            .body(Body::empty())
+            .unwrap();
+        let (status, _) = send(&ctx, request).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        ctx.cleanup().await;
+    }
+
+    #[tokio::test]
+    #[ignore] // Requires database
+    async fn test_sse_reconnects_with_last_event_id_on_owner_topic() {
+        let mut ctx = TestContext::new().await;
+        let dropped = ctx.state.metrics.event_outbox_dropped.clone();
+        ctx.state.notifier = Notifier::with_outbox(64, ctx.pool.clone(), dropped);
+        let keypair = Keypair::new();
+        let owner = keypair.pubkey().to_string();
+        let other = TestContext::generate_test_owner();
+
+        let body = signed_ws_auth(&ctx, &keypair).await;
+        let request = Request::post("/events/session")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let (status, session) = send(&ctx, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let cookie = format!("events_session={}", session["token"].as_str().unwrap());
+        let uri = "/events/stream?topics=deposit_event,tvl_update";
+
+        let (status, mut stream) = open_events(&ctx, uri, &[("cookie", &cookie)]).await;
+        assert_eq!(status, StatusCode::OK);
+        let notifier = &ctx.state.notifier;
+        notifier.publish_for(&owner, VaultEvent::Deposit(tx(1, "sse_sig")));
+        notifier.publish_for(&other, VaultEvent::Deposit(tx(2, "sse_sig")));
+        notifier.publish(EventEnvelope::new(VaultEvent::Tvl(TvlUpdate { tvl: 3 })));
+        notifier.publish_for(&owner, VaultEvent::Deposit(tx(4, "sse_sig")));
+        let (first, event) = next_sse_event(&mut stream, &mut String::new()).await;
+        assert_eq!(event["data"]["amount"], 1);
+        drop(stream);
+
+        // The browser reconnects with the same cookie and resumes after the last event it saw
+        let last_event_id = first.to_string();
+        let headers = [
+            ("cookie", cookie.as_str()),
+            ("last-event-id", &last_event_id),
+        ];
+        let (status, mut stream) = open_events(&ctx, uri, &headers).await;
+        assert_eq!(status, StatusCode::OK);
+        let mut buf = String::new();
+        let (tvl_id, event) = next_sse_event(&mut stream, &mut buf).await;
+        assert_eq!(event["type"], "tvl");
+        let (deposit_id, event) = next_sse_event(&mut stream, &mut buf).await;
+        assert_eq!(event["owner"], owner);
+        assert_eq!(event["data"]["amount"], 4);
+        assert!(first < tvl_id && tvl_id < deposit_id);
+
+        ctx.cleanup().await;
+    }
+
+    /// Serves the API router on an ephemeral port and opens a `/ws` connection, consuming
+    /// the `connected` greeting.
+    async fn connect_ws(ctx: &TestContext) -> WsClient {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = cvmsback::api::router(ctx.state.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/ws"))
+            .await
+            .expect("Failed to connect");
+        assert_eq!(recv(&mut ws).await.unwrap()["method"], "connected");
+        ws
+    }
+
+    /// Next text frame, or `None` once the server has closed the connection.
+    async fn recv(ws: &mut WsClient) -> Option<serde_json::Value> {
+        loop {
+            let msg = tokio::time::timeout(Duration::from_secs(10), ws.next())
+                .await
+                .expect("Timed out waiting for a frame");
+            match msg {
+                Some(Ok(Message::Text(text))) => return Some(serde_json::from_str(&text).unwrap()),
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return None,
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+
+    async fn call(
+        ws: &mut WsClient,
+        id: u64,
+        method: &str,
+        params: serde_json::Value,
+    ) -> serde_json::Value {
+        let request = json!({ "id": id, "method": method, "params": params });
+        ws.send(Message::Text(request.to_string())).await.unwrap();
+        let reply = recv(ws).await.expect("Connection closed");
+        assert_eq!(reply["id"], id);
+        reply
+    }
+
+    fn tvl(tvl: i64) -> EventEnvelope {
+        EventEnvelope::new(VaultEvent::Tvl(TvlUpdate { tvl }))
+    }
+
+    /// Publishes `count` admin-visible alerts of about 64KB each, far more than the socket
+    /// buffers hold, to a client that is not reading.
+    async fn flood_slow_consumer(ctx: &TestContext, count: usize) -> WsClient {
+        let mut ws = connect_ws(ctx).await;
+        let token = generate_admin_token(ctx);
+        call(&mut ws, 1, "auth", json!({ "token": token })).await;
+        let reply = call(
+            &mut ws,
+            2,
+            "subscribe",
+            json!({ "topic": "security_alert" }),
+        )
+        .await;
+        assert_eq!(reply["result"]["subscription"], "s1");
+
+        let padding = "x".repeat(64 * 1024);
+        for seq in 0..count {
+            let details = json!({ "seq": seq, "padding": padding });
+            ctx.state.notifier.publish_for(
+                &TestContext::generate_test_owner(),
+                VaultEvent::security("flood", Severity::Info, details),
+            );
+        }
+        ws
+    }
+
+    #[tokio::test]
+    async fn test_ws_subscribe_and_unsubscribe_acks() {
+        let ctx = TestContext::in_memory();
+        let mut ws = connect_ws(&ctx).await;
+
+        let reply = call(&mut ws, 1, "subscribe", json!({ "topic": "tvl_update" })).await;
+        assert_eq!(reply["result"]["subscription"], "s1");
+        ctx.state.notifier.publish(tvl(42));
+        let event = recv(&mut ws).await.unwrap();
+        assert_eq!(event["method"], "event");
+        assert_eq!(event["params"]["subscription"], "s1");
+        assert_eq!(event["params"]["event"]["data"]["tvl"], 42);
+
+        let reply = call(&mut ws, 2, "unsubscribe", json!({ "subscription": "s1" })).await;
+        assert_eq!(reply["result"], true);
+        let reply = call(&mut ws, 3, "unsubscribe", json!({ "subscription": "s1" })).await;
+        assert_eq!(reply["error"]["code"], 4004);
+
+        // Owner-scoped topics need a session, and unknown topics are invalid params
+        let reply = call(&mut ws, 4, "subscribe", json!({ "topic": "deposit_event" })).await;
+        assert_eq!(reply["error"]["code"], 4001);
+        let reply = call(&mut ws, 5, "subscribe", json!({ "topic": "nope" })).await;
+        assert_eq!(reply["error"]["code"], -32602);
+
+        // Nothing is delivered for the dropped subscription
+        ctx.state.notifier.publish(tvl(43));
+        let reply = call(&mut ws, 6, "ping", serde_json::Value::Null).await;
+        assert_eq!(reply["result"], "pong");
+    }
+
+    #[tokio::test]
+    async fn test_ws_subscription_limit() {
+        let mut ctx = TestContext::in_memory();
+        ctx.state.cfg.ws_max_subscriptions = 2;
+        let mut ws = connect_ws(&ctx).await;
+        let topic = json!({ "topic": "tvl_update" });
+
+        for id in 1..=2 {
+            let reply = call(&mut ws, id, "subscribe", topic.clone()).await;
+            assert_eq!(reply["result"]["subscription"], format!("s{id}"));
+        }
+        let reply = call(&mut ws, 3, "subscribe", topic.clone()).await;
+        assert_eq!(reply["error"]["code"], 4029);
+        let pubkey = TestContext::generate_test_owner();
+        let reply = call(&mut ws, 4, "account_subscribe", json!({ "pubkey": pubkey })).await;
+        assert_eq!(reply["error"]["code"], 4029);
+
+        // Unsubscribing frees a slot
+        call(&mut ws, 5, "unsubscribe", json!({ "subscription": "s1" })).await;
+        let reply = call(&mut ws, 6, "subscribe", topic).await;
+        assert_eq!(reply["result"]["subscription"], "s3");
+    }
+
+    #[tokio::test]
+    async fn test_ws_disconnect_cancels_subscriptions() {
+        let ctx = TestContext::in_memory();
+        let mut ws = connect_ws(&ctx).await;
+        for id in 1..=3 {
+            call(&mut ws, id, "subscribe", json!({ "topic": "tvl_update" })).await;
+        }
+        assert_eq!(ctx.state.notifier.publish(tvl(1)), 3);
+
+        ws.close(None).await.unwrap();
+        drop(ws);
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while ctx.state.notifier.publish(tvl(2)) > 0 {
+            assert!(
+                tokio::time::Instant::now() < deadline,
+                "subscriptions outlived the socket"
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ws_slow_consumer_is_disconnected() {
+        let mut ctx = TestContext::in_memory();
+        ctx.state.cfg.ws_send_queue_capacity = 1;
+        ctx.state.cfg.ws_slow_consumer_policy = SlowConsumerPolicy::Disconnect;
+        let count = 512;
+        let mut ws = flood_slow_consumer(&ctx, count).await;
+
+        // Whatever was buffered before the queue overflowed arrives, then the socket closes
+        let mut delivered = 0;
+        while let Some(frame) = recv(&mut ws).await {
+            assert_eq!(frame["method"], "event");
+            delivered += 1;
+        }
+        assert!(delivered < count, "delivered {delivered} of {count}");
+        assert_eq!(ctx.state.notifier.publish(tvl(1)), 0);
+    }
+
+    #[tokio::test]
+    async fn test_ws_slow_consumer_waits() {
+        let mut ctx = TestContext::in_memory();
+        ctx.state.cfg.ws_send_queue_capacity = 1;
+        ctx.state.cfg.ws_slow_consumer_policy = SlowConsumerPolicy::Wait;
+        let count = 512;
+        let mut ws = flood_slow_consumer(&ctx, count).await;
+
+        // Backpressure holds the events back instead of dropping the connection
+        for seq in 0..count {
+            let frame = recv(&mut ws).await.expect("Connection closed");
+            assert_eq!(frame["params"]["event"]["data"]["details"]["seq"], seq);
+        }
+        let reply = call(&mut ws, 3, "ping", serde_json::Value::Null).await;
+        assert_eq!(reply["result"], "pong");
     }
 }